@@ -20,25 +20,95 @@
 //!   Typically, this type is an `enum` containing subcommand option types.
 //! * `help_flag` marks an option as a help flag. The field must be `bool` type.
 //!   Options named `help` will automatically receive this option.
-//! * `no_help_flag` prevents an option from being considered a help flag.
+//! * `help_all_flag` marks an option as a "help all" flag, which requests
+//!   usage for the program and all of its subcommands. The field must be
+//!   `bool` type. Options named `help-all` will automatically receive this option.
+//! * `version_flag` marks an option as a version flag, which requests
+//!   the program version, as given by the type level `version` item.
+//!   The field must be `bool` type. `bool` options named `version` will
+//!   automatically receive this option.
+//! * `no_help_flag` prevents an option from being considered a help flag,
+//!   a "help all" flag, or a version flag.
 //! * `count` marks a field as a counter value. The field will be incremented
 //!   each time the option appears in the arguments, i.e. `field += 1;`
 //! * `free` marks a field as a positional argument field. Non-option arguments
 //!   will be used to fill all `free` fields, in declared sequence.
 //!   If the final `free` field is of type `Vec<T>`, it will contain all
-//!   remaining free arguments.
+//!   remaining free arguments. One `free` field of type `Vec<T>` may instead
+//!   be followed by other `free` fields, e.g. `SOURCE... DEST`, in which case
+//!   each of the following fields receives one of the final arguments and
+//!   the `Vec<T>` field receives all others.
+//!   A `free` field of tuple or array type, e.g. `(u32, u32)` or `[u32; 2]`,
+//!   or an `Option` or `Vec` of such a type, consumes a value from as many
+//!   consecutive arguments.
+//!   A `free` field given a `long` or `short` name, e.g.
+//!   `#[options(free, long = "output")]`, may be given either as a positional
+//!   argument or as an option. If the option is given, the field's position
+//!   is skipped, so that following positional arguments fill the following
+//!   fields. Giving the option after a positional argument has filled the
+//!   field's position is an error.
+//!   Such a field may not be of type `Vec<T>`.
+//!   A `free` field of type `HashMap<K, V>` or `BTreeMap<K, V>` receives
+//!   consecutive arguments of the form `KEY=VALUE`, e.g. `CC=gcc`, parsing
+//!   each key and value using `FromStr`. An argument not of this form
+//!   produces an error unless the field is given `fall_through`, in which case
+//!   the argument and those following are given to the next `free` field.
+//!   A `free` field of type `OsString` or `PathBuf`, or an `Option` or `Vec`
+//!   of these, receives arguments which are not valid UTF-8 unchanged when
+//!   parsed by `Options::parse_args_os` or `Options::parse_args_or_exit`.
+//!   `free` fields may be used with a `command` field, provided that none is
+//!   of type `Vec<T>`. Free arguments are given to `free` fields until one
+//!   names a command or all `free` fields are filled; the following argument
+//!   names the command.
+//! * `index = N`, given with `free`, sets the position of a positional
+//!   argument field. If any `free` field has an `index`, all must have one,
+//!   and fields are filled in ascending order of `index`, rather than in
+//!   declared sequence.
+//!   A `required` `free` field may not follow one which is not required,
+//!   except following a `free` field of type `Vec<T>`.
 //! * `short = "?"` sets the short option name to the given character
 //! * `no_short` prevents a short option from being assigned to the field
 //! * `long = "..."` sets the long option name to the given string
 //! * `no_long` prevents a long option from being assigned to the field
 //! * `default` provides a default value for the option field.
-//!   The value of this field is parsed in the same way as argument values.
-//! * `default_expr` provides a default value for the option field.
-//!   The value of this field is parsed at compile time as a Rust expression
-//!   and is evaluated before any argument values are processed.  
-//!   The `default_expr` feature must be enabled to use this attribute.
+//!   A string value is parsed in the same way as argument values.
+//!   An integer, float, boolean, or character literal, e.g. `default = 3`,
+//!   is instead assigned to the field as a constant and must be of the
+//!   field type.
+//! * `default_expr = ...` provides a default value for the option field,
+//!   given as a Rust expression, e.g. `default_expr = foo() + 1`, which is
+//!   evaluated before any argument values are processed. The expression
+//!   may also be given as a string, e.g. `default_expr = "foo() + 1"`.
+//! * `default_fn = "..."` names a function, taking no arguments, which is
+//!   called to produce a default value for the option field each time
+//!   arguments are parsed, e.g. `default_fn = "num_cpus::get"`.
+//! * `default_expr_display = "..."` sets the default value displayed in
+//!   usage text, e.g. for a field having a `default_expr` or `default_fn` value.
+//! * `hide_default` omits the default value from usage text, while still
+//!   applying it to the field; this is equivalent to `show_default = false`.
+//! * `show_default = true|false` controls whether the default value appears
+//!   in usage text. When `true`, the source of a `default_expr` value is shown
+//!   if no `default_expr_display` is given.
 //! * `required` will cause an error if the option is not present,
 //!   unless at least one `help_flag` option is also present.
+//!   Required options are marked `(required)` in usage text.
+//! * `env = "..."` reads the option value from the named environment variable
+//!   when the option is not present in arguments. The variable name is shown
+//!   in usage text following the option's help, e.g. `(env: MY_VAR)`.
+//!   An option set by its environment variable satisfies `required`.
+//! * `prompt_secret` reads the option value from the terminal, with echo
+//!   disabled, when the option is not present in arguments or its
+//!   environment variable, e.g. for a password. The prompt is the long name of
+//!   the option followed by `: `; `prompt_secret = "..."` gives another prompt.
+//!   The value is read by `Options::parse_args_or_exit` once all arguments
+//!   are parsed without error; other parsing methods do not read from the
+//!   terminal, see `Options::prompt_secrets`.
+//!   This requires the `prompt` feature of `gumdrop`.
+//! * `confirm = "..."`, given for a `bool` option, e.g. `--delete-all`, causes
+//!   `parse_args_or_exit` to ask the user to confirm the given prompt
+//!   when the option is present, exiting if it is not confirmed. An option
+//!   `--yes` is added, which declines all confirmation prompts, unless a
+//!   `bool` option of that name is declared.
 //! * `multi = "..."` will allow parsing an option multiple times,
 //!   adding each parsed value to the field using the named method.
 //!   This behavior is automatically applied to `Vec<T>` fields, unless the
@@ -48,8 +118,43 @@
 //! * `help = "..."` sets help text returned from the `Options::usage` method;
 //!   field doc comment may also be provided to set the help text.
 //!   If both are present, the `help` attribute value is used.
+//!   When a doc comment is used, only its first line is included.
+//!   The value may also be a macro which expands to a string literal, such as
+//!   `help = include_str!("help.txt")` or `help = concat!(...)`, for `struct`
+//!   fields, `enum` variants, and at the type level.
+//! * `long_help = "..."` sets help text returned from the `Options::long_usage`
+//!   method, which is displayed when help is requested using the long name
+//!   of a help flag, e.g. `--help`. If not present, the full text of a
+//!   field doc comment is used; lines are joined into paragraphs, which are
+//!   separated by blank lines.
+//! * `verbatim_doc_comment` preserves the line breaks and indentation of a
+//!   field doc comment when it is used as long help text, e.g. for tables
+//!   or examples.
 //! * `meta = "..."` sets the meta variable displayed in usage for options
 //!   which accept an argument
+//! * `possible_values = "..."` lists, separated by commas, the values accepted
+//!   by an option, e.g. those of an `enum` type implementing `FromStr`.
+//!   These are displayed in usage text, e.g. `[possible values: a, b, c]`.
+//!   If a value fails to parse, the error lists these values and suggests
+//!   the most similar one, rather than including the parse error.
+//! * `ignore_case` accepts any of the `possible_values` without regard to
+//!   ASCII case, e.g. `--format JSON` for the value `json`. The value is
+//!   parsed as listed in `possible_values`, which is shown in usage text.
+//! * `conflicts_with = "..."` names another option field which may not be
+//!   given in arguments together with this option.
+//! * `requires = "..."` names another option field which must be given
+//!   in arguments whenever this option is given.
+//!   Both `conflicts_with` and `requires` may be given more than once and are
+//!   displayed in usage text, e.g. `[conflicts with --json]`.
+//! * `heading = "..."` lists the option in usage under the given heading,
+//!   rather than under `Optional arguments:`. Options sharing a heading are
+//!   grouped together, and groups appear in the order of their first option.
+//!   A colon is appended to the heading, unless it already ends with one.
+//! * `display_order = N` sets the position of the option in usage text.
+//!   Options are listed in ascending order; those without a `display_order`
+//!   follow all those with one.
+//! * `preview` lists the option, returned by `Options::preview`, beneath the
+//!   name of the command in help text for a parent `enum` marked `preview`.
 //! * `parse(...)` uses a named function to parse a value from a string.
 //!   Valid parsing function types are:
 //!     * `parse(from_str = "...")` for `fn(&str) -> T`
@@ -57,10 +162,138 @@
 //!       `fn(&str) -> Result<T, E> where E: Display`
 //!     * `parse(from_str)` uses `std::convert::From::from`
 //!     * `parse(try_from_str)` uses `std::str::FromStr::from_str`
+//!     * `parse(with_parser = "...")` for
+//!       `fn(Opt<'a>, &mut Parser<'a, DynArg>) -> Result<T, gumdrop::Error>`,
+//!       which receives the option and the parser and may consume any number
+//!       of following arguments. `Parser::next_value` returns the first value,
+//!       including one attached to the option, as in `--foo=bar`.
+//!       This may not be used with `free`, `default`, `env`, or
+//!       `possible_values`.
+//!     * `parse(contextual = "...")` for
+//!       `fn(&str, &Self) -> Result<T, E> where E: Display`,
+//!       which also receives the options value as parsed so far, i.e.
+//!       having values of arguments preceding the one being parsed.
+//!       This may not be used with `default`.
+//!
+//!   An error `E` implementing `std::error::Error` is retained as the source
+//!   of the resulting `gumdrop::Error`, so that it may be retrieved using
+//!   `Error::downcast_ref`; otherwise, only its `Display` text is kept.
+//!   For a generic type, an error which may involve a type parameter,
+//!   e.g. that of parsing a field of type `T` or `Vec<T>`, or of a
+//!   `contextual` function, keeps only its `Display` text.
 //!
 //! Additionally, the following flags may be set at the type level to establish
 //! default values for all contained fields: `no_help_flag`, `no_long`,
-//! `no_short`, and `required`.
+//! `no_short`, and `required`. `auto_short = false` and `auto_help = false`
+//! are equivalent to `no_short` and `no_help_flag`, respectively.
+//!
+//! At the `struct` type level, `min_free = N` and `max_free = N` set the least
+//! and greatest number of free arguments which may be given. Parsing fails
+//! with an error of kind `InvalidFreeCount` if the number given is outside
+//! this range. These may not be used with a `command` field.
+//!
+//! The strictness of parsing may be relaxed at the `struct` type level:
+//!
+//! * `lenient_unknown_options` ignores unrecognized options, rather than
+//!   returning an error. An unrecognized option is skipped along with any
+//!   argument attached to it, as in `--foo=bar`, but a value given as a
+//!   separate argument is treated as a free argument.
+//! * `lenient_extra_free` ignores free arguments beyond those accepted by
+//!   `free` fields, rather than returning an error.
+//!
+//! Lenient types otherwise behave as any other: usage text, command lists,
+//! and the usage model are generated as usual.
+//!
+//! The following items are also supported at the `struct` and `enum` type level:
+//!
+//! * `after_help = "..."` sets text returned from the `Options::after_help`
+//!   method, which is displayed after all other sections of help text.
+//!   If `after_help` is given without a value, any doc comments following the
+//!   `#[options(...)]` attribute are used as this text instead.
+//!
+//! * `help_column_width = N` sets the minimum width of the column preceding
+//!   help text in usage, 8 by default.
+//! * `help_max_width = N` sets the maximum width of the column preceding
+//!   help text in usage, 30 by default. Help text for options whose names
+//!   exceed this width begins on the following line.
+//! * `next_line_help` places help text for every item on the line following
+//!   its name, indented to the column set by `help_column_width`.
+//! * `command_heading = "..."` replaces the heading `Available commands:`
+//!   in help text. A `struct` containing a `command` field uses the heading
+//!   of its command type, unless one is given.
+//! * `error_usage = "line"` or `error_usage = "full"` causes
+//!   `Options::parse_args_or_exit` to print, following an error message,
+//!   a usage line or the help text for the command in which the error occurred.
+//! * `markdown = "..."` controls the treatment of Markdown syntax in doc
+//!   comments. `"strip"` removes emphasis, code span, and escape syntax,
+//!   replacing each link with its text followed by its destination in
+//!   parentheses; `"render"` additionally marks emphasized text and code
+//!   spans to be styled, e.g. in bold or underlined, by `Options::usage_styled`.
+//!   `"verbatim"`, the default, leaves doc comments unchanged.
+//!   Help text given by `help = "..."` is never changed.
+//! * `program_name = "..."` sets the program name displayed in help text and
+//!   error messages printed by `Options::parse_args_or_exit`, in place of
+//!   the name by which the program was invoked.
+//! * `localize = "..."` names a function of type `fn(&str) -> Option<String>`,
+//!   e.g. a wrapper around `gettext`, through which `Options::localized_usage`
+//!   translates help text, value names, and section headings.
+//! * `to_args` generates an implementation of `gumdrop::ToArgs`, which
+//!   converts a value back into arguments that parse to an equal value.
+//!   Option values and free arguments are converted using `ToString`.
+//!   `ToArgs::to_map` converts option values into a map keyed by
+//!   long option name.
+//! * `arbitrary` generates an implementation of `arbitrary::Arbitrary`,
+//!   in addition to `ToArgs`, for use in fuzzing. This requires the `arbitrary`
+//!   feature of `gumdrop` and is not supported for generic types.
+//!
+//! When the `strip_help` feature of `gumdrop` is enabled, usage and help
+//! text, including `after_help` text and examples, are omitted from generated
+//! code: `Options::usage` and related methods return empty strings, and help
+//! text given by `options_info`, `commands_info`, and `usage_model` is `None`.
+//! Parsing and error messages are unaffected. This reduces the size of
+//! binaries in which help text is not needed.
+//!
+//! At the `struct` type level, `version = "..."` sets the version printed by
+//! `Options::parse_args_or_exit` when a version flag is given. If a version
+//! flag is present but no `version` is given, the `CARGO_PKG_VERSION` of the
+//! crate containing the type is used.
+//!
+//! At the `struct` type level, `base = "..."` gives an expression, evaluated
+//! each time arguments are parsed, producing a value of the type from which
+//! fields take their initial values, e.g. `base = "Self::load_config()"`.
+//! Only fields given in arguments are changed, while fields having a `default`
+//! or similar item take that default instead. Values of `free` fields of type
+//! `Vec<T>` are appended to those of the base value.
+//!
+//! At the `struct` type level, `example = "..."` adds an example invocation
+//! to an `Examples:` section, which follows option help text in the usage
+//! string. This item may be given more than once.
+//!
+//! Default values are displayed in usage text as `(default: ...)`.
+//! The `struct` type level item `default_format = "..."` replaces this format,
+//! in which `{}` is replaced with the default value, while `show_default = false`
+//! hides default values of all options which do not set `show_default`.
+//!
+//! Required options and free arguments are marked `(required)` in usage text.
+//! The `struct` type level item `required_marker = "..."` replaces this marker;
+//! an empty string removes it.
+//!
+//! The `struct` type level item `sort = "..."` controls the order of options
+//! having equal `display_order` values. `sort = "declaration"`, the default,
+//! lists options in the order in which fields are declared, while
+//! `sort = "alpha"` lists options alphabetically by name.
+//!
+//! Long option names are derived from field names by replacing underscores
+//! with hyphens. The `struct` type level item `rename_all = "..."` selects
+//! another convention: `"kebab-case"`, the default, `"snake_case"`,
+//! `"SCREAMING_SNAKE_CASE"`, `"SCREAMING-KEBAB-CASE"`, `"lowercase"`,
+//! which joins words without a separator, or `"verbatim"`, which uses the
+//! field name unchanged. A `long = "..."` item on a field overrides the
+//! derived name.
+//!
+//! The headings `Positional arguments:`, `Optional arguments:`, and `Examples:`
+//! may be replaced using the `struct` type level items `positional_heading = "..."`,
+//! `optional_heading = "..."`, and `examples_heading = "..."`, respectively.
 //!
 //! Supported items for `enum` variants are:
 //!
@@ -69,10 +302,61 @@
 //! * `help = "..."` sets the help string for the command;
 //!   variant doc comment may also be provided to set the help text.
 //!   If both are present, the `help` attribute value is used.
+//! * `catch_all` marks a variant of the form `Other(String, Vec<String>)`,
+//!   which receives an unrecognized command name and all following arguments,
+//!   rather than returning an error. At most one variant may be marked
+//!   `catch_all`; it is not listed in usage text.
+//!
+//! Supported items at the `enum` type level are:
+//!
+//! * `help_command` adds a `help [COMMAND...]` command, which is listed in
+//!   usage text after all other commands. When it is invoked,
+//!   `Options::parse_args_or_exit` prints usage for the named command
+//!   (or the program itself, if none is named) and exits.
+//!   Other parsing methods parse the enclosing struct as if its help flag
+//!   were given; the named command path is returned by
+//!   `Parser::help_command_path`.
+//! * `dispatch = "..."` generates a trait with the given name, having
+//!   an associated type `Output` and one method for each variant.
+//!   Method names are generated from variant names, e.g. `FooBar` becomes
+//!   `foo_bar`, and each method receives the variant's options value.
+//!   A method `dispatch(self, handler: &mut H) -> H::Output` is also
+//!   generated for the `enum` type, which calls the method corresponding
+//!   to the selected command.
+//! * `rename_all = "..."` selects the convention by which command names are
+//!   generated from variant names, with the same values as the `struct` item
+//!   of the same name. By default, `"kebab-case"` makes `FooBar` into
+//!   `foo-bar`; `"lowercase"` makes it `foobar` and `"verbatim"` leaves it
+//!   unchanged.
+//! * `fallback = "..."` names a function of type
+//!   `fn(&str, &mut Parser<DynArg>) -> Result<Self, gumdrop::Error>`,
+//!   which is called with an unrecognized command name and the parser,
+//!   rather than returning an error. It may consume following arguments
+//!   and return any variant, e.g. treating `foo` as an implicit `run foo`.
+//! * `ignore_case` matches command names without regard to ASCII case,
+//!   so that `INSTALL` and `Install` each select the `install` command.
+//!   Command names are listed in usage text and errors as declared.
+//! * `localize = "..."` is accepted as for a `struct` type, translating
+//!   the help text of each command in `Options::localized_usage`.
+//! * `markdown = "..."` is accepted as for a `struct` type, applying to
+//!   doc comments on variants.
+//! * `sort = "..."` controls the order in which commands are listed in usage
+//!   text. `sort = "declaration"`, the default, lists commands in the order
+//!   in which variants are declared, while `sort = "alpha"` lists commands
+//!   alphabetically by name.
+//! * `pin = "..."` lists, separated by commas, the names of commands which
+//!   precede all others in usage text, in the order given, regardless of
+//!   `sort`, e.g. `pin = "help, version"`. The command generated by
+//!   `help_command` may be named.
+//! * `preview` lists, in help text, the options marked `preview` of each
+//!   command beneath its name, giving an overview of the most important
+//!   options of every command. Usage text returned by `Options::usage`
+//!   is unchanged.
 //!
 //! The `help` attribute (or a type-level doc comment) can be used to provide
 //! some introductory text which will precede option help text in the usage
-//! string.
+//! string. Leading whitespace on each line of a type-level doc comment is
+//! removed, unless the `verbatim_doc_comment` item is present at the type level.
 
 #![recursion_limit = "1024"]
 
@@ -80,20 +364,21 @@ extern crate proc_macro;
 
 use std::iter::repeat;
 
-use quote::quote;
+use quote::{quote, ToTokens};
 
 use proc_macro::TokenStream;
-use proc_macro2::{Span, TokenStream as TokenStream2};
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
 
 use syn::{
     parse::Error, spanned::Spanned,
     Attribute, AttrStyle, Data, DataEnum, DataStruct, DeriveInput, Fields,
-    GenericArgument, Ident, Lit, Meta, NestedMeta, Path, PathArguments, Type,
+    GenericArgument, Generics, Ident, Lit, Macro, Meta, NestedMeta, Path, PathArguments, Type,
     parse_str,
 };
 
-#[cfg(feature = "default_expr")]
-use syn::Expr;
+use syn::{Expr, ExprLit, Token, parse::{Parse, ParseStream}, punctuated::Punctuated};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Derives the `gumdrop::Options` trait for `struct` and `enum` items.
 ///
@@ -135,9 +420,43 @@ fn derive_options_enum(ast: &DeriveInput, data: &DataEnum)
     let name = &ast.ident;
     let mut commands = Vec::new();
     let mut var_ty = Vec::new();
+    let mut catch_all = None;
+
+    let enum_opts = EnumOpts::parse(&ast.attrs)?;
+    enum_opts.column_width.check(name.span())?;
+    let after_help = help_tokens(enum_opts.after_help.as_ref());
+    let error_usage_impl = error_usage_impl(&enum_opts.error_usage);
+    let program_name_impl = program_name_impl(&enum_opts.program_name);
+    let localize_impl = localize_impl(&enum_opts.localize);
+    let command_heading = option_str(&enum_opts.command_heading);
 
     for var in &data.variants {
         let span = var.ident.span();
+        let opts = CmdOpts::parse(&var.attrs)?;
+
+        if opts.catch_all {
+            if catch_all.is_some() {
+                return Err(Error::new(span,
+                    "only one variant may be marked `catch_all`"));
+            }
+            if enum_opts.fallback.is_some() {
+                return Err(Error::new(span,
+                    "`catch_all` and `fallback` are mutually exclusive"));
+            }
+            if opts.name.is_some() {
+                return Err(Error::new(span,
+                    "`catch_all` and `name` are mutually exclusive"));
+            }
+
+            match &var.fields {
+                Fields::Unnamed(fields) if fields.unnamed.len() == 2 => (),
+                _ => return Err(Error::new(span,
+                    "`catch_all` variant must be of the form `Other(String, Vec<String>)`"))
+            }
+
+            catch_all = Some(&var.ident);
+            continue;
+        }
 
         let ty = match &var.fields {
             Fields::Unit | Fields::Named(_) =>
@@ -150,121 +469,457 @@ fn derive_options_enum(ast: &DeriveInput, data: &DataEnum)
                 &fields.unnamed.first().unwrap().ty,
         };
 
-        let opts = CmdOpts::parse(&var.attrs)?;
-
         let var_name = &var.ident;
+        let cmd_name = opts.name.unwrap_or_else(
+            || make_command_name(&var_name.to_string(), enum_opts.rename_all));
+
+        if enum_opts.help_command && cmd_name == "help" {
+            return Err(Error::new(span,
+                "command `help` conflicts with `help_command`"));
+        }
 
         var_ty.push(ty);
 
         commands.push(Cmd{
-            name: opts.name.unwrap_or_else(
-                || make_command_name(&var_name.to_string())),
-            help: opts.help.or(opts.doc),
+            name: cmd_name,
+            help: opts.help.or(opts.doc.map(|doc| markdown(&doc, enum_opts.markdown))),
             variant_name: var_name,
-            ty: ty,
+            ty,
         });
     }
 
+    if let Some(span) = enum_opts.pin_span {
+        for pin in &enum_opts.pin {
+            let is_help = enum_opts.help_command && pin == HELP_COMMAND.0;
+
+            if !is_help && !commands.iter().any(|cmd| cmd.name == *pin) {
+                return Err(Error::new(span,
+                    format!("`pin` names unknown command `{}`", pin)));
+            }
+        }
+    }
+
+    let command_info = commands.iter().map(|cmd| cmd.info_tokens()).collect::<Vec<_>>();
     let mut command = Vec::new();
     let mut handle_cmd = Vec::new();
     let mut help_req_impl = Vec::new();
+    let mut help_all_req_impl = Vec::new();
     let mut variant = Vec::new();
-    let usage = make_cmd_usage(&commands);
+    let usage_model = make_cmd_usage_model(&commands, &enum_opts);
+    let usage_markup = make_cmd_usage(&commands, &enum_opts);
+    let (command_preview, command_preview_markup) = if enum_opts.preview {
+        (make_cmd_preview(&commands, &enum_opts, false),
+            make_cmd_preview(&commands, &enum_opts, true))
+    } else {
+        (quote!{ ::std::option::Option::None }, quote!{ ::std::option::Option::None })
+    };
+    let usage = usage_tokens(&strip_markup(&usage_markup));
+    let usage_markup = usage_tokens(&usage_markup);
 
-    for cmd in commands {
-        command.push(cmd.name);
+    let to_args_impl = if enum_opts.to_args || enum_opts.arbitrary {
+        enum_to_args_impl(ast, &commands, catch_all)
+    } else {
+        quote!{ }
+    };
 
+    let arbitrary_impl = if enum_opts.arbitrary {
+        enum_arbitrary_impl(ast, &commands)?
+    } else {
+        quote!{ }
+    };
+
+    for cmd in commands {
         let var_name = cmd.variant_name;
         let ty = &cmd.ty;
+        let cmd_name = &cmd.name;
 
         variant.push(var_name);
 
         handle_cmd.push(quote!{
-            #name::#var_name(<#ty as ::gumdrop::Options>::parse(_parser)?)
+            let cmd = <#ty as ::gumdrop::Options>::parse_dyn(_parser)
+                .map_err(|e| ::gumdrop::Error::in_command(e, #cmd_name))?;
+            _parser.help_command_in(#cmd_name);
+            #name::#var_name(cmd)
         });
 
+        command.push(cmd.name);
+
         help_req_impl.push(quote!{
             #name::#var_name(cmd) => { ::gumdrop::Options::help_requested(cmd) }
         });
+
+        help_all_req_impl.push(quote!{
+            #name::#var_name(cmd) => { ::gumdrop::Options::help_all_requested(cmd) }
+        });
     }
 
     // Borrow re-used items
     let command = &command;
 
+    // Match arm for the `catch_all` variant, if any, evaluating to `value`
+    let catch_all_arm = |value: TokenStream2| match catch_all {
+        Some(var_name) => quote!{ #name::#var_name(..) => #value, },
+        None => quote!{ }
+    };
+
+    let help_cmd_name = if enum_opts.help_command {
+        quote!{ "help" }
+    } else {
+        quote!{ }
+    };
+
+    let handle_help_cmd = if enum_opts.help_command {
+        quote!{
+            "help" => {
+                let mut _path = ::std::vec::Vec::new();
+
+                while let ::std::option::Option::Some(_arg) = _parser.next_arg() {
+                    _path.push(::std::string::ToString::to_string(_arg));
+                }
+
+                return ::std::result::Result::Err(
+                    ::gumdrop::Error::help_command(_path));
+            }
+        }
+    } else {
+        quote!{ }
+    };
+
+    // With `ignore_case`, a name given by the user is replaced by the name
+    // of the command it selects before matching
+    let (find_command_impl, select_name, select_first) = if enum_opts.ignore_case {
+        (quote!{
+            fn find_command(name: &str) -> ::std::option::Option<&'static str> {
+                ::std::iter::Iterator::find(
+                    &mut ::std::iter::Iterator::copied(
+                        <Self as ::gumdrop::Options>::command_names().iter()),
+                    |cmd| cmd.eq_ignore_ascii_case(name))
+            }
+        }, quote!{
+            let name = <Self as ::gumdrop::Options>::find_command(name).unwrap_or(name);
+        }, quote!{ {
+            let first = ::std::convert::AsRef::<str>::as_ref(first);
+            <Self as ::gumdrop::Options>::find_command(first).unwrap_or(first)
+        } })
+    } else {
+        (quote!{ }, quote!{ }, quote!{
+            ::std::convert::AsRef::<str>::as_ref(first)
+        })
+    };
+
+    let handle_unknown_cmd = match (&enum_opts.fallback, catch_all) {
+        (Some(fun), _) => quote!{
+            _ => #fun(name, _parser)?
+        },
+        (None, Some(var_name)) => quote!{
+            _ => {
+                let mut _args = ::std::vec::Vec::new();
+
+                while let ::std::option::Option::Some(_arg) = _parser.next_arg() {
+                    _args.push(::std::string::ToString::to_string(_arg));
+                }
+
+                #name::#var_name(::std::string::ToString::to_string(name), _args)
+            }
+        },
+        (None, None) => quote!{
+            _ => return ::std::result::Result::Err(
+                ::gumdrop::Error::with_commands(
+                    ::gumdrop::Error::unrecognized_command(name),
+                    <Self as ::gumdrop::Options>::command_names()))
+        }
+    };
+
+    let usage_markup_impl = if cfg!(feature = "style") {
+        quote!{
+            fn usage_markup() -> &'static str {
+                #usage_markup
+            }
+
+            fn command_path_markup<__S: ::std::convert::AsRef<str>>(path: &[__S])
+                    -> ::std::option::Option<::gumdrop::HelpInfo> {
+                match path.split_first() {
+                    ::std::option::Option::None => ::std::option::Option::Some(
+                        ::gumdrop::HelpInfo{
+                            command_list: ::std::option::Option::Some(
+                                <Self as ::gumdrop::Options>::usage_markup()),
+                            command_preview: #command_preview_markup,
+                            .. <Self as ::gumdrop::Options>::help_info()
+                        }),
+                    ::std::option::Option::Some((first, rest)) => {
+                        match #select_first {
+                            #( #command => <#var_ty as ::gumdrop::Options>::command_path_markup(rest), )*
+                            _ => ::std::option::Option::None
+                        }
+                    }
+                }
+            }
+        }
+    } else {
+        quote!{ }
+    };
+
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
+    let dispatch_impl = match &enum_opts.dispatch {
+        Some(trait_name) => {
+            if !ast.generics.params.is_empty() {
+                return Err(Error::new(ast.generics.span(),
+                    "`dispatch` is not supported for generic types"));
+            }
+
+            let vis = &ast.vis;
+            let name_str = name.to_string();
+            let trait_doc = format!("Handles commands of `{}`, \
+                as passed by `{}::dispatch`", name_str, name_str);
+            let dispatch_doc = format!("Passes the options of the selected command \
+                to the corresponding method of a `{}` implementation.", trait_name);
+            let method = variant.iter()
+                .map(|var| make_method_name(&var.to_string()))
+                .collect::<Vec<_>>();
+            let method_doc = command.iter()
+                .map(|cmd| format!("Handles the `{}` command.", cmd));
+            let enum_name = repeat(name);
+
+            let (other_method, other_arm) = match catch_all {
+                Some(var_name) => {
+                    let method = make_method_name(&var_name.to_string());
+
+                    (quote!{
+                        /// Handles an unrecognized command, receiving its name
+                        /// and the arguments following it.
+                        fn #method(&mut self, name: ::std::string::String,
+                            args: ::std::vec::Vec<::std::string::String>) -> Self::Output;
+                    }, quote!{
+                        #name::#var_name(name, args) => handler.#method(name, args),
+                    })
+                }
+                None => (quote!{ }, quote!{ })
+            };
+
+            quote!{
+                #[doc = #trait_doc]
+                #vis trait #trait_name {
+                    /// Value returned from each command handler
+                    type Output;
+
+                    #(
+                        #[doc = #method_doc]
+                        fn #method(&mut self, opts: #var_ty) -> Self::Output;
+                    )*
+
+                    #other_method
+                }
+
+                impl #impl_generics #name #ty_generics #where_clause {
+                    #[doc = #dispatch_doc]
+                    #vis fn dispatch<__H: #trait_name>(self, handler: &mut __H)
+                            -> <__H as #trait_name>::Output {
+                        match self {
+                            #( #enum_name::#variant(opts) => handler.#method(opts), )*
+                            #other_arm
+                        }
+                    }
+                }
+            }
+        }
+        None => quote!{ }
+    };
+
     let command_impl = {
         let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::option::Option::None });
 
         quote!{
             match self {
                 #( #name::#variant(cmd) => ::gumdrop::Options::command(cmd), )*
+                #other_arm
+            }
+        }
+    };
+
+    let command_mut_impl = {
+        let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::option::Option::None });
+
+        quote!{
+            match self {
+                #( #name::#variant(cmd) => ::gumdrop::Options::command_mut(cmd), )*
+                #other_arm
             }
         }
     };
 
     let command_name_impl = {
         let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::option::Option::None });
 
         quote!{
             match self {
                 #( #name::#variant(_) => ::std::option::Option::Some(#command), )*
+                #other_arm
+            }
+        }
+    };
+
+    let command_path_impl = {
+        let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::vec::Vec::new() });
+
+        quote!{
+            match self {
+                #( #name::#variant(sub) => {
+                    let mut path = ::std::vec![#command];
+                    path.extend(::gumdrop::Options::command_path(sub));
+                    path
+                } )*
+                #other_arm
             }
         }
     };
 
     let self_usage_impl = {
         let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ <Self as ::gumdrop::Options>::usage() });
 
         quote!{
             match self {
                 #( #name::#variant(sub) => ::gumdrop::Options::self_usage(sub), )*
+                #other_arm
             }
         }
     };
 
     let self_command_list_impl = {
         let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::option::Option::None });
 
         quote!{
             match self {
                 #( #name::#variant(sub) => ::gumdrop::Options::self_command_list(sub), )*
+                #other_arm
+            }
+        }
+    };
+
+    let not_requested_arm = catch_all_arm(quote!{ false });
+
+    let accept_impl = {
+        let other_arm = match catch_all {
+            Some(var_name) => quote!{
+                #name::#var_name(name, args) => visitor.visit_unknown(name, args),
+            },
+            None => quote!{ }
+        };
+        let name = repeat(name);
+
+        quote!{
+            match self {
+                #( #name::#variant(sub) => {
+                    visitor.visit_command(#command, sub);
+                    ::gumdrop::Options::accept(sub, visitor);
+                } )*
+                #other_arm
+            }
+        }
+    };
+
+    let prompt_secrets_impl = {
+        let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::result::Result::Ok(()) });
+
+        quote!{
+            match self {
+                #( #name::#variant(sub) => ::gumdrop::Options::prompt_secrets(sub, _parser), )*
+                #other_arm
+            }
+        }
+    };
+
+    let confirmations_impl = {
+        let name = repeat(name);
+        let other_arm = catch_all_arm(quote!{ ::std::vec::Vec::new() });
+
+        quote!{
+            match self {
+                #( #name::#variant(sub) => ::gumdrop::Options::confirmations(sub), )*
+                #other_arm
             }
         }
     };
 
     Ok(quote!{
+        #dispatch_impl
+
+        #to_args_impl
+
+        #arbitrary_impl
+
         impl #impl_generics ::gumdrop::Options for #name #ty_generics #where_clause {
             fn parse<__S: ::std::convert::AsRef<str>>(
                     _parser: &mut ::gumdrop::Parser<__S>)
                     -> ::std::result::Result<Self, ::gumdrop::Error> {
+                _parser.with_dyn(<Self as ::gumdrop::Options>::parse_dyn)
+            }
+
+            fn parse_dyn(_parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>)
+                    -> ::std::result::Result<Self, ::gumdrop::Error> {
                 let _arg = _parser.next_arg()
-                    .ok_or_else(::gumdrop::Error::missing_command)?;
+                    .ok_or_else(|| ::gumdrop::Error::with_commands(
+                        ::gumdrop::Error::missing_command(),
+                        <Self as ::gumdrop::Options>::command_names()))?;
 
-                Self::parse_command(_arg, _parser)
+                Self::parse_command_dyn(_arg, _parser)
             }
 
             fn command(&self) -> ::std::option::Option<&dyn ::gumdrop::Options> {
                 #command_impl
             }
 
+            fn command_mut(&mut self) -> ::std::option::Option<&mut dyn ::gumdrop::Options> {
+                #command_mut_impl
+            }
+
             fn command_name(&self) -> ::std::option::Option<&'static str> {
                 #command_name_impl
             }
 
+            fn command_path(&self) -> ::std::vec::Vec<&'static str> {
+                #command_path_impl
+            }
+
             fn help_requested(&self) -> bool {
                 match self {
                     #( #help_req_impl )*
+                    #not_requested_arm
+                }
+            }
+
+            fn help_all_requested(&self) -> bool {
+                match self {
+                    #( #help_all_req_impl )*
+                    #not_requested_arm
                 }
             }
 
             fn parse_command<__S: ::std::convert::AsRef<str>>(name: &str,
                     _parser: &mut ::gumdrop::Parser<__S>)
                     -> ::std::result::Result<Self, ::gumdrop::Error> {
+                _parser.with_dyn(|_parser| Self::parse_command_dyn(name, _parser))
+            }
+
+            fn parse_command_dyn(name: &str,
+                    _parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>)
+                    -> ::std::result::Result<Self, ::gumdrop::Error> {
+                #select_name
+
                 let cmd = match name {
-                    #( #command => { #handle_cmd } )*
-                    _ => return ::std::result::Result::Err(
-                        ::gumdrop::Error::unrecognized_command(name))
+                    #( #command => {
+                        _parser.notify(::gumdrop::ParseEvent::Command(name));
+                        #handle_cmd
+                    } )*
+                    #handle_help_cmd
+                    #handle_unknown_cmd
                 };
 
                 ::std::result::Result::Ok(cmd)
@@ -274,6 +929,35 @@ fn derive_options_enum(ast: &DeriveInput, data: &DataEnum)
                 #usage
             }
 
+            #usage_markup_impl
+
+            fn usage_model() -> ::gumdrop::UsageModel {
+                const MODEL: ::gumdrop::UsageModel = #usage_model;
+                MODEL
+            }
+
+            fn localized_usage() -> ::std::string::String {
+                let model = <Self as ::gumdrop::Options>::usage_model();
+
+                model.sections[0].render_rows(model.help_column, model.next_line_help,
+                    &<Self as ::gumdrop::Options>::localize)
+            }
+
+            fn usage_all(command: &str) -> ::std::string::String {
+                let mut res = ::std::string::String::new();
+
+                #( {
+                    if !res.is_empty() {
+                        res.push_str("\n\n");
+                    }
+
+                    res.push_str(&<#var_ty as ::gumdrop::Options>::usage_all(
+                        &::std::format!("{} {}", command, #command)));
+                } )*
+
+                res
+            }
+
             fn self_usage(&self) -> &'static str {
                 #self_usage_impl
             }
@@ -282,23 +966,107 @@ fn derive_options_enum(ast: &DeriveInput, data: &DataEnum)
                 ::std::option::Option::Some(<Self as ::gumdrop::Options>::usage())
             }
 
+            fn after_help() -> ::std::option::Option<&'static str> {
+                #after_help
+            }
+
+            #error_usage_impl
+            #program_name_impl
+            #localize_impl
+
+            fn help_info() -> ::gumdrop::HelpInfo {
+                ::gumdrop::HelpInfo{
+                    usage: "",
+                    long_usage: "",
+                    command_list: <Self as ::gumdrop::Options>::command_list(),
+                    command_preview: #command_preview,
+                    after_help: <Self as ::gumdrop::Options>::after_help(),
+                    command_heading: #command_heading,
+                }
+            }
+
             fn self_command_list(&self) -> ::std::option::Option<&'static str> {
                 #self_command_list_impl
             }
 
+            fn confirmations(&self) -> ::std::vec::Vec<&'static str> {
+                #confirmations_impl
+            }
+
+            fn prompt_secrets<__S: ::std::convert::AsRef<str>>(&mut self,
+                    _parser: &::gumdrop::Parser<__S>)
+                    -> ::std::result::Result<(), ::gumdrop::Error> {
+                #prompt_secrets_impl
+            }
+
+            fn accept(&self, visitor: &mut dyn ::gumdrop::CommandVisitor) {
+                #accept_impl
+            }
+
+            fn command_names() -> &'static [&'static str] {
+                &[ #( #command , )* #help_cmd_name ]
+            }
+
+            #find_command_impl
+
+            fn commands_info() -> &'static [::gumdrop::CommandInfo] {
+                const INFO: &[::gumdrop::CommandInfo] = &[ #( #command_info ),* ];
+                INFO
+            }
+
+            fn scan_help(_parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>,
+                    _path: &mut ::std::vec::Vec<&'static str>)
+                    -> ::std::option::Option<::gumdrop::HelpFlag> {
+                let _arg = _parser.next_arg()?;
+                Self::scan_help_command(_arg, _parser, _path)
+            }
+
+            fn scan_help_command(name: &str,
+                    _parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>,
+                    _path: &mut ::std::vec::Vec<&'static str>)
+                    -> ::std::option::Option<::gumdrop::HelpFlag> {
+                #select_name
+
+                match name {
+                    #( #command => {
+                        _path.push(#command);
+                        <#var_ty as ::gumdrop::Options>::scan_help(_parser, _path)
+                    } )*
+                    _ => ::std::option::Option::None
+                }
+            }
+
             fn command_usage(name: &str) -> ::std::option::Option<&'static str> {
+                #select_name
+
                 match name {
                     #( #command => ::std::option::Option::Some(
                         <#var_ty as ::gumdrop::Options>::usage()), )*
                     _ => ::std::option::Option::None
                 }
             }
+
+            fn command_path_usage<__S: ::std::convert::AsRef<str>>(path: &[__S])
+                    -> ::std::option::Option<::gumdrop::HelpInfo> {
+                match path.split_first() {
+                    ::std::option::Option::None => ::std::option::Option::Some(
+                        <Self as ::gumdrop::Options>::help_info()),
+                    ::std::option::Option::Some((first, rest)) => {
+                        match #select_first {
+                            #( #command => <#var_ty as ::gumdrop::Options>::command_path_usage(rest), )*
+                            _ => ::std::option::Option::None
+                        }
+                    }
+                }
+            }
         }
     })
 }
 
 fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         -> Result<TokenStream2, Error> {
+    // Names the `--yes` option added to types having `confirm` options
+    let yes_ident = Ident::new("yes", Span::call_site());
     let mut pattern = Vec::new();
     let mut handle_opt = Vec::new();
     let mut short_names = Vec::new();
@@ -310,12 +1078,15 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
     let mut command_ty = None;
     let mut command_required = false;
     let mut help_flag = Vec::new();
+    let mut help_all_flag = Vec::new();
+    let mut version_flag = Vec::new();
     let mut options = Vec::new();
     let mut field_name = Vec::new();
     let mut default = Vec::new();
 
     let default_expr = quote!{ ::std::default::Default::default() };
     let default_opts = DefaultOpts::parse(&ast.attrs)?;
+    default_opts.column_width.check(ast.ident.span())?;
 
     for field in fields {
         let span = field.ident.as_ref().unwrap().span();
@@ -325,24 +1096,22 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
 
         let ident = field.ident.as_ref().unwrap();
 
-        field_name.push(ident);
-
-        if let Some(expr) = &opts.default {
-            default.push(opts.parse.as_ref()
+        let field_default = if let Some(lit) = &opts.default_lit {
+            Some(quote!{ #lit })
+        } else if let Some(fun) = &opts.default_fn {
+            Some(quote!{ #fun() })
+        } else if let Some(expr) = &opts.default {
+            Some(opts.parse.as_ref()
                 .unwrap_or(&ParseFn::Default)
-                .make_parse_default_action(ident, &expr));
+                .make_parse_default_action(ident, expr))
         } else {
-            #[cfg(not(feature = "default_expr"))]
-            default.push(default_expr.clone());
+            opts.default_expr.as_ref().map(|expr| quote!{ #expr })
+        };
 
-            #[cfg(feature = "default_expr")]
-            {
-                if let Some(expr) = &opts.default_expr {
-                    default.push(quote!{ #expr });
-                } else {
-                    default.push(default_expr.clone());
-                }
-            }
+        // Fields without a default of their own are taken from the base value
+        if field_default.is_some() || default_opts.base.is_none() {
+            field_name.push(ident);
+            default.push(field_default.unwrap_or_else(|| default_expr.clone()));
         }
 
         if opts.command {
@@ -350,56 +1119,95 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 return Err(Error::new(span,
                     "duplicate declaration of `command` field"));
             }
-            if !free.is_empty() {
-                return Err(Error::new(span,
-                    "`command` and `free` options are mutually exclusive"));
-            }
-
             command = Some(ident);
             command_ty = Some(first_ty_param(&field.ty).unwrap_or(&field.ty));
             command_required = opts.required;
 
             if opts.required {
+                let ty = command_ty.unwrap();
+
                 required.push(ident);
                 required_err.push(quote!{
-                    ::gumdrop::Error::missing_required_command() });
+                    ::gumdrop::Error::with_commands(
+                        ::gumdrop::Error::missing_required_command(),
+                        <#ty as ::gumdrop::Options>::command_names()) });
             }
 
             continue;
         }
 
         if opts.free {
-            if command.is_some() {
-                return Err(Error::new(span,
-                    "`command` and `free` options are mutually exclusive"));
-            }
+            let action = FreeAction::infer(&field.ty, &opts);
+            let arity = match action {
+                FreeAction::SetField => FreeArity::infer(&field.ty),
+                FreeAction::Insert => None,
+                _ => first_ty_param(&field.ty).and_then(FreeArity::infer)
+            };
+            let os = opts.parse.is_none() && arity.is_none() && match action {
+                FreeAction::SetField => is_os_type(&field.ty),
+                FreeAction::Insert => false,
+                _ => first_ty_param(&field.ty).is_some_and(is_os_type)
+            };
 
-            if let Some(last) = free.last() {
-                if last.action.is_push() {
+            if action.is_insert() {
+                if opts.parse.is_some() {
                     return Err(Error::new(span,
-                        "only the final `free` option may be of type `Vec<T>`"));
+                        "`parse` may not be given for a `free` field of map type"));
                 }
+            } else if opts.fall_through {
+                return Err(Error::new(span,
+                    "`fall_through` requires a `free` field of map type"));
             }
 
             if opts.required {
                 required.push(ident);
-                required_err.push(quote!{
-                    ::gumdrop::Error::missing_required_free() });
+                required_err.push(match arity {
+                    Some(arity) => {
+                        let n = arity.len();
+                        quote!{ ::gumdrop::Error::missing_required_free_count(#n, 0) }
+                    }
+                    None => quote!{ ::gumdrop::Error::missing_required_free() }
+                });
+            }
+
+            // A `free` field given a `long` or `short` name may also be
+            // given as an option, which takes precedence
+            let named = opts.long.is_some() || opts.short.is_some();
+
+            if named && action.is_push() {
+                return Err(Error::new(span,
+                    "`long` and `short` may not be given for a `free` field of type `Vec<T>`"));
+            }
+
+            if named && action.is_insert() {
+                return Err(Error::new(span,
+                    "`long` and `short` may not be given for a `free` field of map type"));
             }
 
+            let long_help = opts.long_help();
+
             free.push(FreeOpt{
                 field: ident,
-                action: FreeAction::infer(&field.ty, &opts),
-                parse: opts.parse.unwrap_or_default(),
+                index: opts.index,
+                action,
+                arity,
+                parse: opts.parse.clone().unwrap_or_default(),
+                generic_error: parse_error_is_generic(&field.ty, &opts, &ast.generics),
                 required: opts.required,
-                help: opts.help.or(opts.doc),
+                named,
+                fall_through: opts.fall_through,
+                os,
+                long_help,
+                help: opts.help.clone().or_else(|| opts.doc.clone()),
             });
 
-            continue;
+            if !named {
+                continue;
+            }
         }
 
         if opts.long.is_none() && !opts.no_long {
-            opts.long = Some(make_long_name(&ident.to_string()));
+            opts.long = Some(make_long_name(&ident.to_string(), default_opts.rename_all));
         }
 
         if let Some(long) = &opts.long {
@@ -417,12 +1225,23 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
             help_flag.push(ident);
         }
 
+        if opts.help_all_flag || (!opts.no_help_flag &&
+                opts.long.as_ref().map(|s| &s[..]) == Some("help-all")) {
+            help_all_flag.push(ident);
+        }
+
         let action = if opts.count {
             Action::Count
         } else {
-            Action::infer(&field.ty, &opts)
+            Action::infer(&field.ty, &opts,
+                parse_error_is_generic(&field.ty, &opts, &ast.generics))
         };
 
+        if opts.version_flag || (!opts.no_help_flag && matches!(action, Action::Switch) &&
+                opts.long.as_ref().map(|s| &s[..]) == Some("version")) {
+            version_flag.push(ident);
+        }
+
         if action.takes_arg() {
             if opts.meta.is_none() {
                 opts.meta = Some(make_meta(&ident.to_string(), &action));
@@ -432,19 +1251,104 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 "`meta` value is invalid for this field"));
         }
 
+        if opts.env.is_some() && (!action.takes_arg() || action.tuple_len().is_some()) {
+            return Err(Error::new(span,
+                "`env` value is invalid for this field"));
+        }
+
+        if !opts.possible_values.is_empty() && !action.takes_arg() {
+            return Err(Error::new(span,
+                "`possible_values` value is invalid for this field"));
+        }
+
+        if opts.confirm.is_some() && !matches!(action, Action::Switch) {
+            return Err(Error::new(span,
+                "`confirm` is invalid for this field"));
+        }
+
+        let prompt_secret = match opts.prompt_secret.take() {
+            Some(_) if !matches!(action, Action::SetField(_) | Action::SetOption(_)) ||
+                    action.tuple_len().is_some() =>
+                return Err(Error::new(span,
+                    "`prompt_secret` is invalid for this field")),
+            Some(prompt) if prompt.is_empty() => {
+                let name = opts.long.as_ref().map_or_else(|| ident.to_string(), |s| s.clone());
+                Some(format!("{}: ", name))
+            }
+            prompt => prompt
+        };
+
+        let long_help = opts.long_help();
+        let default = opts.default_display();
+
         options.push(Opt{
             field: ident,
-            action: action,
+            action,
             long: opts.long,
             short: opts.short,
             no_short: opts.no_short,
-            required: opts.required,
+            required: opts.required && !opts.free,
+            named_free: opts.free.then_some(opts.required),
+            free_slot: None,
             meta: opts.meta,
+            long_help,
             help: opts.help.or(opts.doc),
-            default: opts.default,
+            default,
+            heading: opts.heading,
+            display_order: opts.display_order,
+            preview: opts.preview,
+            tracked: (opts.required && !opts.free) || opts.env.is_some() ||
+                prompt_secret.is_some(),
+            env: opts.env,
+            prompt_secret,
+            confirm: opts.confirm,
+            assume_yes: false,
+            synthetic: false,
+            conflicts_with: opts.conflicts_with,
+            requires: opts.requires,
+            possible_values: opts.possible_values,
         });
     }
 
+    // Confirmation of options marked `confirm` may be declined using `--yes`,
+    // which is added unless an option of that name is declared
+    if options.iter().any(|opt| opt.confirm.is_some()) {
+        match options.iter_mut().find(|opt| opt.long.as_deref() == Some("yes")) {
+            Some(opt) if matches!(opt.action, Action::Switch) => opt.assume_yes = true,
+            Some(opt) => return Err(Error::new(opt.field.span(),
+                "option `--yes` must be a `bool` field when `confirm` is used")),
+            None => {
+                long_names.push("yes".to_owned());
+                options.push(Opt{
+                    field: &yes_ident,
+                    action: Action::Switch,
+                    long: Some("yes".to_owned()),
+                    short: None,
+                    no_short: true,
+                    required: false,
+                    named_free: None,
+                    free_slot: None,
+                    help: Some("assume yes in answer to confirmation prompts".to_owned()),
+                    meta: None,
+                    long_help: None,
+                    default: None,
+                    heading: None,
+                    display_order: None,
+                    preview: false,
+                    env: None,
+                    prompt_secret: None,
+                    confirm: None,
+                    assume_yes: true,
+                    synthetic: true,
+                    conflicts_with: Vec::new(),
+                    requires: Vec::new(),
+                    possible_values: Vec::new(),
+                    tracked: false,
+                });
+            }
+        }
+    }
+
     // Assign short names after checking all options.
     // Thus, manual short names will take priority over automatic ones.
     for opt in &mut options {
@@ -459,12 +1363,115 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     }
 
+    // Resolve fields named by `conflicts_with` and `requires`
+    // after all option names have been assigned.
+    let mut relations = Vec::new();
+
+    for (i, opt) in options.iter().enumerate() {
+        let names = opt.conflicts_with.iter().map(|name| (name, true))
+            .chain(opt.requires.iter().map(|name| (name, false)));
+
+        for (name, conflict) in names {
+            match options.iter().position(|other| other.field == name) {
+                Some(j) if j != i => relations.push((i, j, conflict)),
+                _ => return Err(Error::new(opt.field.span(),
+                    format!("`{}` does not name another option field", name)))
+            }
+        }
+    }
+
+    for opt in &mut options {
+        opt.conflicts_with.clear();
+        opt.requires.clear();
+    }
+
+    let mut relation_check = Vec::new();
+
+    for (i, j, conflict) in relations {
+        let (field, other) = (options[i].field, options[j].field);
+        let (name, other_name) = (options[i].display_form(), options[j].display_form());
+        let (err_name, err_other) = (options[i].error_form(), options[j].error_form());
+
+        options[i].tracked = true;
+        options[j].tracked = true;
+
+        if conflict {
+            if !options[i].conflicts_with.contains(&other_name) {
+                options[i].conflicts_with.push(other_name.clone());
+            }
+            if !options[j].conflicts_with.contains(&name) {
+                options[j].conflicts_with.push(name.clone());
+            }
+
+            relation_check.push(quote!{
+                if _used.#field && _used.#other {
+                    _parser.report_error(
+                        ::gumdrop::Error::conflicting_options_static(#err_name, #err_other))?;
+                }
+            });
+        } else {
+            options[i].requires.push(other_name.clone());
+
+            relation_check.push(quote!{
+                if _used.#field && !_used.#other {
+                    _parser.report_error(
+                        ::gumdrop::Error::missing_requirement_static(#err_name, #err_other))?;
+                }
+            });
+        }
+    }
+
+    let handle_unknown = if default_opts.lenient_unknown_options {
+        quote!{ }
+    } else {
+        let long_names = options.iter()
+            .filter_map(|opt| opt.long.as_ref());
+
+        quote!{
+            return ::std::result::Result::Err(
+                ::gumdrop::Error::with_options(
+                    ::gumdrop::Error::unrecognized_option(_opt),
+                    &[ #( #long_names ),* ]));
+        }
+    };
+
+    sort_free(&mut free)?;
+
+    // Fields following a `Vec<T>` field are filled once all arguments are
+    // read; others are filled in order, so that the option form of such a
+    // field conflicts with a positional argument already given in its place
+    let in_order = match free.iter().position(|opt| opt.action.is_push()) {
+        Some(i) if i + 1 < free.len() => i + 1,
+        _ => free.len()
+    };
+
+    for opt in &mut options {
+        if opt.named_free.is_some() {
+            opt.free_slot = free[..in_order].iter().position(|free| free.field == opt.field);
+        }
+    }
+
+    let mut tracked = Vec::new();
+    let mut env_action = Vec::new();
+
     for opt in &options {
+        if opt.tracked && !opt.required && !required.contains(&opt.field) {
+            tracked.push(opt.field);
+        }
+
+        if let Some(action) = opt.make_env_action() {
+            env_action.push(action);
+        }
+
+        if let Some(action) = opt.make_prompt_request(&ast.ident) {
+            env_action.push(action);
+        }
+
         if opt.required {
             required.push(opt.field);
-            let display = opt.display_form();
+            let display = opt.error_form();
             required_err.push(quote!{
-                ::gumdrop::Error::missing_required(#display) });
+                ::gumdrop::Error::missing_required_static(#display) });
         }
 
         let pat = match (&opt.long, opt.short) {
@@ -483,8 +1490,36 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
             }
         };
 
-        pattern.push(pat);
-        handle_opt.push(opt.make_action());
+        // A help flag given by its long name requests long help text
+        match (&opt.long, opt.short) {
+            (Some(long), short) if help_flag.contains(&opt.field) => {
+                let action = opt.make_action();
+
+                pattern.push(quote!{ ::gumdrop::Opt::Long(#long) });
+                handle_opt.push(quote!{
+                    _parser.notify(::gumdrop::ParseEvent::Option(_opt));
+                    _parser.request_long_help();
+                    #action
+                });
+
+                if let Some(short) = short {
+                    pattern.push(quote!{ ::gumdrop::Opt::Short(#short) });
+                    handle_opt.push(quote!{
+                        _parser.notify(::gumdrop::ParseEvent::Option(_opt));
+                        #action
+                    });
+                }
+            }
+            _ => {
+                let action = opt.make_action();
+
+                pattern.push(pat);
+                handle_opt.push(quote!{
+                    _parser.notify(::gumdrop::ParseEvent::Option(_opt));
+                    #action
+                });
+            }
+        }
 
         if let Some(long) = &opt.long {
             let (pat, handle) = if let Some(n) = opt.action.tuple_len() {
@@ -492,8 +1527,13 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                     quote!{ return ::std::result::Result::Err(
                         ::gumdrop::Error::unexpected_single_argument(_opt, #n)) })
             } else if opt.action.takes_arg() {
+                let action = opt.make_action_arg();
+
                 (quote!{ ::gumdrop::Opt::LongWithArg(#long, _arg) },
-                    opt.make_action_arg())
+                    quote!{
+                        _parser.notify(::gumdrop::ParseEvent::Option(_opt));
+                        #action
+                    })
             } else {
                 (quote!{ ::gumdrop::Opt::LongWithArg(#long, _) },
                     quote!{ return ::std::result::Result::Err(
@@ -505,12 +1545,207 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     }
 
+    // Help and version flags are found by `scan_help` while skipping
+    // the values of other options
+    let mut scan_pattern = Vec::new();
+    let mut scan_action = Vec::new();
+
+    for opt in &options {
+        let long = opt.long.iter().map(|long| quote!{ ::gumdrop::Opt::Long(#long) });
+        let short = opt.short.iter().map(|short| quote!{ ::gumdrop::Opt::Short(#short) });
+
+        let flag = if help_flag.contains(&opt.field) {
+            quote!{ Help }
+        } else if help_all_flag.contains(&opt.field) {
+            quote!{ HelpAll }
+        } else if version_flag.contains(&opt.field) {
+            quote!{ Version }
+        } else {
+            let n = match opt.action.tuple_len() {
+                Some(n) => n,
+                None if opt.action.takes_arg() => 1,
+                None => 0
+            };
+
+            if n != 0 {
+                let skip = (0..n).map(|_| quote!{ _parser.next_arg(); });
+
+                scan_pattern.push(quote!{ #( #long )|* #( | #short )* });
+                scan_action.push(quote!{ #( #skip )* });
+            }
+            continue;
+        };
+
+        if let Some(long) = &opt.long {
+            let request_long = if help_flag.contains(&opt.field) {
+                quote!{ _parser.request_long_help(); }
+            } else {
+                quote!{ }
+            };
+
+            scan_pattern.push(quote!{ ::gumdrop::Opt::Long(#long) });
+            scan_action.push(quote!{
+                #request_long
+                return ::std::option::Option::Some(::gumdrop::HelpFlag::#flag);
+            });
+        }
+
+        if let Some(short) = opt.short {
+            scan_pattern.push(quote!{ ::gumdrop::Opt::Short(#short) });
+            scan_action.push(quote!{
+                return ::std::option::Option::Some(::gumdrop::HelpFlag::#flag);
+            });
+        }
+    }
+
+    let scan_free = match command_ty {
+        Some(ty) => quote!{
+            return <#ty as ::gumdrop::Options>::scan_help_command(_free, _parser, _path);
+        },
+        None => quote!{ }
+    };
+
+    // Free arguments are given to `free` fields until one names a command
+    // or all `free` fields are filled
+    if let (Some(_), Some(opt)) = (command, free.iter().find(|opt| opt.action.is_push())) {
+        return Err(Error::new(opt.field.span(),
+            "a `free` field of type `Vec<T>` may not be used with a `command` field"));
+    }
+
+    let (free_count_decl, count_free, free_count_check) =
+            match (default_opts.min_free, default_opts.max_free) {
+        (None, None) => (quote!{ }, quote!{ }, quote!{ }),
+        (min, max) => {
+            if command.is_some() {
+                return Err(Error::new(ast.ident.span(),
+                    "`min_free` and `max_free` may not be used with a `command` field"));
+            }
+
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(Error::new(ast.ident.span(),
+                        "`min_free` must not be greater than `max_free`"));
+                }
+            }
+
+            let check = match (min, max) {
+                (Some(min), Some(max)) => quote!{ !(#min..=#max).contains(&_free_total) },
+                (Some(min), None) => quote!{ _free_total < #min },
+                (None, max) => quote!{ _free_total > #max },
+            };
+            let min = min.unwrap_or(0);
+            let max = match max {
+                Some(max) => quote!{ ::std::option::Option::Some(#max) },
+                None => quote!{ ::std::option::Option::None }
+            };
+
+            (quote!{ let mut _free_total = 0usize; },
+                quote!{ _free_total += 1; },
+                quote!{
+                    if #check {
+                        _parser.report_error(::gumdrop::Error::invalid_free_count(
+                            #min, #max, _free_total))?;
+                    }
+                })
+        }
+    };
+
     let name = &ast.ident;
-    let opts_help = default_opts.help.or(default_opts.doc);
-    let usage = make_usage(&opts_help, &free, &options);
+    let base = default_opts.base.as_ref().map(|base| quote!{ ..#base });
+    let after_help = help_tokens(default_opts.after_help.as_ref());
+    let error_usage_impl = error_usage_impl(&default_opts.error_usage);
+    let program_name_impl = program_name_impl(&default_opts.program_name);
+    let localize_impl = localize_impl(&default_opts.localize);
+    let command_heading = match (&default_opts.command_heading, command_ty) {
+        (Some(_), _) | (None, None) => option_str(&default_opts.command_heading),
+        (None, Some(ty)) => quote!{
+            <#ty as ::gumdrop::Options>::help_info().command_heading
+        }
+    };
+    let usage_model = make_usage_model(&default_opts, &free, &options);
+    let usage_markup = make_usage(&default_opts, &free, &options, false);
+    let usage = usage_tokens(&strip_markup(&usage_markup));
+    let usage_markup = usage_tokens(&usage_markup);
+    let long_usage_markup = make_usage(&default_opts, &free, &options, true);
+    let long_usage = usage_tokens(&strip_markup(&long_usage_markup));
+    let long_usage_markup = usage_tokens(&long_usage_markup);
+    let preview_markup = make_preview(&default_opts, &options);
+    let preview = usage_tokens(&strip_markup(&preview_markup));
+    let preview_markup = usage_tokens(&preview_markup);
+    let has_preview = options.iter().any(|opt| opt.preview);
+
+    let preview_impl = if has_preview {
+        quote!{
+            fn preview() -> &'static str {
+                #preview
+            }
+        }
+    } else {
+        quote!{ }
+    };
+
+    let (command_preview, command_preview_markup) = match command_ty {
+        Some(ty) => (
+            quote!{ <#ty as ::gumdrop::Options>::help_info().command_preview },
+            quote!{ <#ty as ::gumdrop::Options>::command_path_markup::<&str>(&[])
+                .and_then(|info| info.command_preview) },
+        ),
+        None => (
+            quote!{ ::std::option::Option::None },
+            quote!{ ::std::option::Option::None },
+        ),
+    };
+
+    let to_args_impl = if default_opts.to_args || default_opts.arbitrary {
+        struct_to_args_impl(ast, &options, &free, command)
+    } else {
+        quote!{ }
+    };
+
+    let mut free_rest_decl = quote!{ };
+    let mut free_rest_action = quote!{ };
+
+    let handle_command = command.map(|ident| {
+        let mark_used = if command_required {
+            quote!{ _used.#ident = true; }
+        } else {
+            quote!{ }
+        };
+
+        quote!{
+            #mark_used
+            match ::gumdrop::Options::parse_command_dyn(_free, _parser) {
+                ::std::result::Result::Ok(cmd) =>
+                    _result.#ident = ::std::option::Option::Some(cmd),
+                ::std::result::Result::Err(e) => _parser.report_error(e)?,
+            }
+
+            // A `help` command is treated as a help flag
+            if ::std::option::Option::is_some(&_parser.help_command_path()) {
+                #( _result.#help_flag = true; )*
+            }
+
+            return ::std::result::Result::Ok(true);
+        }
+    });
 
     let handle_free = if !free.is_empty() {
-        let catch_all = if free.last().unwrap().action.is_push() {
+        let variadic = free.iter().position(|opt| opt.action.is_push());
+
+        let catch_all = if let Some(i) = variadic.filter(|&i| i + 1 < free.len()) {
+            // Arguments are divided between the `Vec` field and those following
+            // it once all have been read
+            let trailing = free.split_off(i + 1);
+            let rest = free.pop().unwrap();
+
+            free_rest_decl = quote!{ let mut _free_rest = ::std::vec::Vec::new(); };
+            free_rest_action = make_free_rest_action(&rest, &trailing);
+
+            quote!{
+                _parser.notify(::gumdrop::ParseEvent::Free(_free));
+                _free_rest.push((_free, _free_pos, _free_ordinal));
+            }
+        } else if variadic.is_some() {
             let last = free.pop().unwrap();
 
             let free = last.field;
@@ -520,14 +1755,18 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 _ => unreachable!()
             };
 
-            let parse = last.parse.make_parse_action(Some(&name[..]));
+            let value = last.make_parse_value(&name);
             let mark_used = last.mark_used();
 
             quote!{
+                _parser.notify(::gumdrop::ParseEvent::Free(_free));
                 #mark_used
-                let _arg = _free;
-                _result.#free.#meth(#parse);
+                _result.#free.#meth(#value);
             }
+        } else if let Some(handle_command) = &handle_command {
+            handle_command.clone()
+        } else if default_opts.lenient_extra_free {
+            quote!{ }
         } else {
             quote!{
                 return ::std::result::Result::Err(
@@ -535,57 +1774,71 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
             }
         };
 
+        let match_command = match (&handle_command, command_ty) {
+            (Some(handle_command), Some(ty)) => quote!{
+                if <#ty as ::gumdrop::Options>::find_command(_free).is_some() {
+                    #handle_command
+                }
+            },
+            _ => quote!{ }
+        };
+
         let num = 0..free.len();
         let action = free.iter().map(|free| {
-            let field = free.field;
-            let name = field.to_string();
-
-            let mark_used = free.mark_used();
-            let parse = free.parse.make_parse_action(Some(&name[..]));
+            let assign = free.make_assign();
 
-            let assign = match &free.action {
-                FreeAction::Push(meth) => quote!{
-                    let _arg = _free;
-                    _result.#field.#meth(#parse);
-                },
-                FreeAction::SetField => quote!{
-                    let _arg = _free;
-                    _result.#field = #parse;
-                },
-                FreeAction::SetOption => quote!{
-                    let _arg = _free;
-                    _result.#field = ::std::option::Option::Some(#parse);
-                },
+            // A map field receives arguments until one is not `KEY=VALUE`
+            let advance = if free.action.is_insert() {
+                quote!{ }
+            } else {
+                quote!{ _free_counter += 1; }
             };
 
             quote!{
-                #mark_used
+                #advance
+                _parser.notify(::gumdrop::ParseEvent::Free(_free));
                 #assign
             }
         }).collect::<Vec<_>>();
 
+        // A map field passes an argument not of the form `KEY=VALUE`
+        // to the next field; a field given as an option is skipped
+        let skip = free.iter().enumerate().filter_map(|(i, opt)| {
+            let field = opt.field;
+
+            if opt.fall_through {
+                Some(quote!{
+                    if _free_counter == #i && !_free.contains('=') {
+                        _free_counter += 1;
+                    }
+                })
+            } else if opt.named {
+                Some(quote!{
+                    if _free_counter == #i && _named.#field {
+                        _free_counter += 1;
+                    }
+                })
+            } else {
+                None
+            }
+        });
+
         quote!{
+            #match_command
+
+            #( #skip )*
+
             match _free_counter {
                 #( #num => {
-                    _free_counter += 1;
                     #action
                 } )*
                 _ => { #catch_all }
             }
         }
-    } else if let Some(ident) = command {
-        let mark_used = if command_required {
-            quote!{ _used.#ident = true; }
-        } else {
-            quote!{ }
-        };
-
-        quote!{
-            #mark_used
-            _result.#ident = ::std::option::Option::Some(
-                ::gumdrop::Options::parse_command(_free, _parser)?);
-            break;
-        }
+    } else if let Some(handle_command) = handle_command {
+        handle_command
+    } else if default_opts.lenient_extra_free {
+        quote!{ }
     } else {
         quote!{
             return ::std::result::Result::Err(
@@ -602,6 +1855,15 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     };
 
+    let command_mut_impl = match &command {
+        None => quote!{ ::std::option::Option::None },
+        Some(field) => quote!{
+            ::std::option::Option::map(
+                ::std::option::Option::as_mut(&mut self.#field),
+                |sub| sub as _)
+        }
+    };
+
     let command_name_impl = match &command {
         None => quote!{ ::std::option::Option::None },
         Some(field) => quote!{
@@ -611,6 +1873,16 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     };
 
+    let command_path_impl = match &command {
+        None => quote!{ ::std::vec::Vec::new() },
+        Some(field) => quote!{
+            ::std::option::Option::map_or_else(
+                ::std::option::Option::as_ref(&self.#field),
+                ::std::vec::Vec::new,
+                ::gumdrop::Options::command_path)
+        }
+    };
+
     let command_list = match command_ty {
         Some(ty) => quote!{
             ::std::option::Option::Some(
@@ -621,6 +1893,33 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     };
 
+    let command_names = match command_ty {
+        Some(ty) => quote!{
+            <#ty as ::gumdrop::Options>::command_names()
+        },
+        None => quote!{
+            &[]
+        }
+    };
+
+    let find_command_impl = match command_ty {
+        Some(ty) => quote!{
+            fn find_command(name: &str) -> ::std::option::Option<&'static str> {
+                <#ty as ::gumdrop::Options>::find_command(name)
+            }
+        },
+        None => quote!{ }
+    };
+
+    let commands_info = match command_ty {
+        Some(ty) => quote!{
+            <#ty as ::gumdrop::Options>::commands_info()
+        },
+        None => quote!{
+            &[]
+        }
+    };
+
     let command_usage = match command_ty {
         Some(ty) => quote!{
             <#ty as ::gumdrop::Options>::command_usage(_name)
@@ -630,6 +1929,70 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     };
 
+    let command_path_usage = match command_ty {
+        Some(ty) => quote!{
+            <#ty as ::gumdrop::Options>::command_path_usage(path)
+        },
+        None => quote!{
+            ::std::option::Option::None
+        }
+    };
+
+    let usage_markup_impl = if cfg!(feature = "style") {
+        let preview_markup_impl = if has_preview {
+            quote!{
+                fn preview_markup() -> &'static str {
+                    #preview_markup
+                }
+            }
+        } else {
+            quote!{ }
+        };
+
+        let (command_list, command_path) = match command_ty {
+            Some(ty) => (
+                quote!{ ::std::option::Option::Some(
+                    <#ty as ::gumdrop::Options>::usage_markup()) },
+                quote!{ <#ty as ::gumdrop::Options>::command_path_markup(path) },
+            ),
+            None => (
+                quote!{ ::std::option::Option::None },
+                quote!{ ::std::option::Option::None },
+            ),
+        };
+
+        quote!{
+            fn usage_markup() -> &'static str {
+                #usage_markup
+            }
+
+            fn long_usage_markup() -> &'static str {
+                #long_usage_markup
+            }
+
+            #preview_markup_impl
+
+            fn command_path_markup<__S: ::std::convert::AsRef<str>>(path: &[__S])
+                    -> ::std::option::Option<::gumdrop::HelpInfo> {
+                if path.is_empty() {
+                    ::std::option::Option::Some(::gumdrop::HelpInfo{
+                        usage: <Self as ::gumdrop::Options>::usage_markup(),
+                        long_usage: <Self as ::gumdrop::Options>::long_usage_markup(),
+                        command_list: #command_list,
+                        command_preview: #command_preview_markup,
+                        .. <Self as ::gumdrop::Options>::help_info()
+                    })
+                } else {
+                    #command_path
+                }
+            }
+        }
+    } else {
+        quote!{ }
+    };
+
+    let option_info = options.iter().map(|opt| opt.info_tokens()).collect::<Vec<_>>();
+
     let help_requested_impl = match (&help_flag, &command) {
         (flags, None) => quote!{
             fn help_requested(&self) -> bool {
@@ -646,21 +2009,77 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
         }
     };
 
-    let self_usage_impl = match &command {
-        None => quote!{ <Self as ::gumdrop::Options>::usage() },
-        Some(field) => quote!{
-            ::std::option::Option::map_or_else(
-                ::std::option::Option::as_ref(&self.#field),
-                <Self as ::gumdrop::Options>::usage,
-                ::gumdrop::Options::self_usage)
+    let help_all_requested_impl = match &command {
+        None => quote!{
+            fn help_all_requested(&self) -> bool {
+                false #( || self.#help_all_flag )*
+            }
+        },
+        Some(cmd) => quote!{
+            fn help_all_requested(&self) -> bool {
+                #( self.#help_all_flag || )*
+                ::std::option::Option::map_or(
+                    ::std::option::Option::as_ref(&self.#cmd),
+                    false, ::gumdrop::Options::help_all_requested)
+            }
         }
     };
 
-    let self_command_list_impl = match &command {
-        None => quote!{ <Self as ::gumdrop::Options>::command_list() },
-        Some(field) => quote!{
-            ::std::option::Option::map_or_else(
-                ::std::option::Option::as_ref(&self.#field),
+    let version_requested_impl = if version_flag.is_empty() {
+        quote!{ }
+    } else {
+        quote!{
+            fn version_requested(&self) -> bool {
+                false #( || self.#version_flag )*
+            }
+        }
+    };
+
+    let version_impl = match (&default_opts.version, version_flag.is_empty()) {
+        (Some(version), _) => quote!{
+            #version_requested_impl
+
+            fn version() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#version)
+            }
+        },
+        (None, false) => quote!{
+            #version_requested_impl
+
+            fn version() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(::std::env!("CARGO_PKG_VERSION"))
+            }
+        },
+        (None, true) => quote!{ }
+    };
+
+    let usage_all_impl = match command_ty {
+        Some(ty) => quote!{
+            let sub = <#ty as ::gumdrop::Options>::usage_all(command);
+
+            if !sub.is_empty() {
+                res.push_str("\n\n");
+                res.push_str(&sub);
+            }
+        },
+        None => quote!{ }
+    };
+
+    let self_usage_impl = match &command {
+        None => quote!{ <Self as ::gumdrop::Options>::usage() },
+        Some(field) => quote!{
+            ::std::option::Option::map_or_else(
+                ::std::option::Option::as_ref(&self.#field),
+                <Self as ::gumdrop::Options>::usage,
+                ::gumdrop::Options::self_usage)
+        }
+    };
+
+    let self_command_list_impl = match &command {
+        None => quote!{ <Self as ::gumdrop::Options>::command_list() },
+        Some(field) => quote!{
+            ::std::option::Option::map_or_else(
+                ::std::option::Option::as_ref(&self.#field),
                 <Self as ::gumdrop::Options>::command_list,
                 ::gumdrop::Options::self_command_list)
         }
@@ -668,40 +2087,151 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
 
     let required = &required;
 
+    let accept_impl = command.map(|field| quote!{
+        fn accept(&self, visitor: &mut dyn ::gumdrop::CommandVisitor) {
+            if let ::std::option::Option::Some(sub) = &self.#field {
+                ::gumdrop::Options::accept(sub, visitor);
+            }
+        }
+    });
+
+    let confirmations_impl = {
+        let (field, prompt): (Vec<_>, Vec<_>) = options.iter()
+            .filter_map(|opt| Some((opt.field, opt.confirm.as_ref()?)))
+            .unzip();
+        let command = command.map(|field| quote!{
+            if let ::std::option::Option::Some(sub) = &self.#field {
+                _res.extend(::gumdrop::Options::confirmations(sub));
+            }
+        });
+
+        if field.is_empty() && command.is_none() {
+            quote!{ }
+        } else {
+            quote!{
+                fn confirmations(&self) -> ::std::vec::Vec<&'static str> {
+                    let mut _res = ::std::vec::Vec::new();
+                    #( if self.#field { _res.push(#prompt); } )*
+                    #command
+                    _res
+                }
+            }
+        }
+    };
+
+    let prompt_secrets_impl = {
+        let prompt = options.iter()
+            .filter_map(|opt| opt.make_prompt_action(&ast.ident))
+            .collect::<Vec<_>>();
+        let command = command.map(|field| quote!{
+            if let ::std::option::Option::Some(sub) = &mut _result.#field {
+                ::gumdrop::Options::prompt_secrets(sub, _parser)?;
+            }
+        });
+
+        if prompt.is_empty() && command.is_none() {
+            quote!{ }
+        } else {
+            quote!{
+                fn prompt_secrets<__S: ::std::convert::AsRef<str>>(&mut self,
+                        _parser: &::gumdrop::Parser<__S>)
+                        -> ::std::result::Result<(), ::gumdrop::Error> {
+                    let _result = self;
+                    #command
+                    #( #prompt )*
+                    ::std::result::Result::Ok(())
+                }
+            }
+        }
+    };
+
+    let named = options.iter()
+        .filter(|opt| opt.named_free.is_some())
+        .map(|opt| opt.field)
+        .collect::<Vec<_>>();
+    let named_decl = if named.is_empty() {
+        quote!{ }
+    } else {
+        quote!{
+            #[derive(Default)]
+            struct _Named {
+                #( #named: bool , )*
+            }
+
+            let mut _named = _Named::default();
+        }
+    };
+
+    let arbitrary_impl = if default_opts.arbitrary {
+        struct_arbitrary_impl(ast, fields, &options)?
+    } else {
+        quote!{ }
+    };
+
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
     Ok(quote!{
+        #to_args_impl
+
+        #arbitrary_impl
+
         impl #impl_generics ::gumdrop::Options for #name #ty_generics #where_clause {
             fn parse<__S: ::std::convert::AsRef<str>>(
                     _parser: &mut ::gumdrop::Parser<__S>)
                     -> ::std::result::Result<Self, ::gumdrop::Error> {
+                _parser.with_dyn(<Self as ::gumdrop::Options>::parse_dyn)
+            }
+
+            #[allow(unreachable_code)]
+            fn parse_dyn(_parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>)
+                    -> ::std::result::Result<Self, ::gumdrop::Error> {
                 #[derive(Default)]
                 struct _Used {
                     #( #required: bool , )*
+                    #( #tracked: bool , )*
                 }
 
                 let mut _result = #name{
-                    #( #field_name: #default ),*
+                    #( #field_name: #default , )*
+                    #base
                 };
                 let mut _free_counter = 0usize;
+                let mut _free_ordinal = 0usize;
+                #free_rest_decl
+                #free_count_decl
                 let mut _used = _Used::default();
+                #named_decl
 
-                while let ::std::option::Option::Some(_opt) = _parser.next_opt() {
+                // Returns whether parsing should stop; an error is reported
+                // to the parser, which may collect it and continue parsing.
+                _parser.parse_opts(|_parser, _opt| {
                     match _opt {
                         #( #pattern => { #handle_opt } )*
                         ::gumdrop::Opt::Free(_free) => {
+                            let _free_pos = _parser.position();
+                            _free_ordinal += 1;
+                            #count_free
                             #handle_free
                         }
-                        _ => {
-                            return ::std::result::Result::Err(
-                                ::gumdrop::Error::unrecognized_option(_opt));
-                        }
+                        _ => { #handle_unknown }
                     }
-                }
 
-                if true #( && !_result.#help_flag )* {
+                    ::std::result::Result::Ok(false)
+                })?;
+
+                #free_rest_action
+
+                if true #( && !_result.#help_flag )* #( && !_result.#help_all_flag )*
+                        #( && !_result.#version_flag )* &&
+                        ::std::option::Option::is_none(&_parser.help_command_path()) {
+                    #( #relation_check )*
+
+                    #( #env_action )*
+
+                    #free_count_check
+
                     #( if !_used.#required {
-                        return ::std::result::Result::Err(#required_err);
+                        _parser.report_error(#required_err)?;
                     } )*
                 }
 
@@ -712,12 +2242,49 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 #command_impl
             }
 
+            fn command_mut(&mut self) -> ::std::option::Option<&mut dyn ::gumdrop::Options> {
+                #command_mut_impl
+            }
+
             fn command_name(&self) -> ::std::option::Option<&'static str> {
                 #command_name_impl
             }
 
+            fn command_path(&self) -> ::std::vec::Vec<&'static str> {
+                #command_path_impl
+            }
+
             #help_requested_impl
 
+            #help_all_requested_impl
+
+            #confirmations_impl
+
+            #prompt_secrets_impl
+
+            #accept_impl
+
+            fn options_info() -> &'static [::gumdrop::OptionInfo] {
+                const INFO: &[::gumdrop::OptionInfo] = &[ #( #option_info ),* ];
+                INFO
+            }
+
+            fn scan_help(_parser: &mut ::gumdrop::Parser<::gumdrop::DynArg>,
+                    _path: &mut ::std::vec::Vec<&'static str>)
+                    -> ::std::option::Option<::gumdrop::HelpFlag> {
+                while let ::std::option::Option::Some(_opt) = _parser.next_opt() {
+                    match _opt {
+                        #( #scan_pattern => { #scan_action } )*
+                        ::gumdrop::Opt::Free(_free) => { #scan_free }
+                        _ => ()
+                    }
+                }
+
+                ::std::option::Option::None
+            }
+
+            #version_impl
+
             fn parse_command<__S: ::std::convert::AsRef<str>>(name: &str,
                     _parser: &mut ::gumdrop::Parser<__S>)
                     -> ::std::result::Result<Self, ::gumdrop::Error> {
@@ -729,6 +2296,28 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 #usage
             }
 
+            fn long_usage() -> &'static str {
+                #long_usage
+            }
+
+            #preview_impl
+
+            #usage_markup_impl
+
+            fn usage_model() -> ::gumdrop::UsageModel {
+                const MODEL: ::gumdrop::UsageModel = #usage_model;
+                MODEL
+            }
+
+            fn usage_all(command: &str) -> ::std::string::String {
+                let mut res = ::gumdrop::HelpInfo::format(
+                    &<Self as ::gumdrop::Options>::help_info(), command);
+
+                #usage_all_impl
+
+                res
+            }
+
             fn self_usage(&self) -> &'static str {
                 #self_usage_impl
             }
@@ -737,10 +2326,49 @@ fn derive_options_struct(ast: &DeriveInput, fields: &Fields)
                 #command_list
             }
 
+            fn after_help() -> ::std::option::Option<&'static str> {
+                #after_help
+            }
+
+            #error_usage_impl
+            #program_name_impl
+            #localize_impl
+
+            fn help_info() -> ::gumdrop::HelpInfo {
+                ::gumdrop::HelpInfo{
+                    usage: <Self as ::gumdrop::Options>::usage(),
+                    long_usage: <Self as ::gumdrop::Options>::long_usage(),
+                    command_list: <Self as ::gumdrop::Options>::command_list(),
+                    command_preview: #command_preview,
+                    after_help: <Self as ::gumdrop::Options>::after_help(),
+                    command_heading: #command_heading,
+                }
+            }
+
+            fn command_names() -> &'static [&'static str] {
+                #command_names
+            }
+
+            #find_command_impl
+
+            fn commands_info() -> &'static [::gumdrop::CommandInfo] {
+                #commands_info
+            }
+
             fn command_usage(_name: &str) -> ::std::option::Option<&'static str> {
                 #command_usage
             }
 
+            fn command_path_usage<__S: ::std::convert::AsRef<str>>(path: &[__S])
+                    -> ::std::option::Option<::gumdrop::HelpInfo> {
+                if path.is_empty() {
+                    ::std::option::Option::Some(
+                        <Self as ::gumdrop::Options>::help_info())
+                } else {
+                    #command_path_usage
+                }
+            }
+
             fn self_command_list(&self) -> ::std::option::Option<&'static str> {
                 #self_command_list_impl
             }
@@ -767,8 +2395,11 @@ struct AttrOpts {
     short: Option<char>,
     multi: Option<Ident>,
     free: bool,
+    fall_through: bool,
     count: bool,
     help_flag: bool,
+    help_all_flag: bool,
+    version_flag: bool,
     no_help_flag: bool,
     no_short: bool,
     no_long: bool,
@@ -776,12 +2407,31 @@ struct AttrOpts {
     required: bool,
     not_required: bool,
     doc: Option<String>,
+    long_doc: Option<String>,
+    verbatim_doc_comment: bool,
     help: Option<String>,
+    long_help: Option<String>,
     meta: Option<String>,
     parse: Option<ParseFn>,
     default: Option<String>,
-    #[cfg(feature = "default_expr")]
+    default_lit: Option<Lit>,
+    default_fn: Option<Path>,
     default_expr: Option<Expr>,
+    default_expr_src: Option<String>,
+    default_expr_display: Option<String>,
+    show_default: Option<bool>,
+    hide_default: bool,
+    conflicts_with: Vec<String>,
+    requires: Vec<String>,
+    possible_values: Vec<String>,
+    ignore_case: bool,
+    heading: Option<String>,
+    display_order: Option<i32>,
+    preview: bool,
+    index: Option<usize>,
+    env: Option<String>,
+    prompt_secret: Option<String>,
+    confirm: Option<String>,
 
     command: bool,
 }
@@ -793,36 +2443,254 @@ struct Cmd<'a> {
     ty: &'a Type,
 }
 
+impl<'a> Cmd<'a> {
+    /// Returns an expression of type `gumdrop::CommandInfo` describing the command
+    fn info_tokens(&self) -> TokenStream2 {
+        let name = &self.name;
+        let ty = self.ty;
+        let help = help_tokens(self.help.as_ref());
+
+        quote!{
+            ::gumdrop::CommandInfo{
+                name: #name,
+                help: #help,
+                options: <#ty as ::gumdrop::Options>::options_info,
+                commands: <#ty as ::gumdrop::Options>::commands_info,
+                usage_model: <#ty as ::gumdrop::Options>::usage_model,
+            }
+        }
+    }
+
+}
+
 #[derive(Default)]
 struct CmdOpts {
+    catch_all: bool,
     name: Option<String>,
     doc: Option<String>,
     help: Option<String>,
 }
 
+#[derive(Default)]
+struct EnumOpts {
+    help_command: bool,
+    dispatch: Option<Ident>,
+    after_help: Option<String>,
+    after_help_doc: bool,
+    command_heading: Option<String>,
+    column_width: ColumnWidth,
+    error_usage: Option<Ident>,
+    program_name: Option<String>,
+    to_args: bool,
+    arbitrary: bool,
+    rename_all: RenameRule,
+    ignore_case: bool,
+    fallback: Option<Path>,
+    localize: Option<Path>,
+    next_line_help: bool,
+    sort: SortOrder,
+    pin: Vec<String>,
+    pin_span: Option<Span>,
+    markdown: Markdown,
+    preview: bool,
+}
+
 #[derive(Default)]
 struct DefaultOpts {
     no_help_flag: bool,
+    localize: Option<Path>,
+    next_line_help: bool,
     no_long: bool,
     no_multi: bool,
     no_short: bool,
     required: bool,
     doc: Option<String>,
     help: Option<String>,
+    after_help: Option<String>,
+    after_help_doc: bool,
+    verbatim_doc_comment: bool,
+    examples: Vec<String>,
+    positional_heading: Option<String>,
+    optional_heading: Option<String>,
+    examples_heading: Option<String>,
+    command_heading: Option<String>,
+    required_marker: Option<String>,
+    show_default: Option<bool>,
+    default_format: Option<String>,
+    error_usage: Option<Ident>,
+    program_name: Option<String>,
+    version: Option<String>,
+    to_args: bool,
+    arbitrary: bool,
+    lenient_unknown_options: bool,
+    lenient_extra_free: bool,
+    rename_all: RenameRule,
+    base: Option<TokenStream2>,
+    min_free: Option<usize>,
+    max_free: Option<usize>,
+    sort: SortOrder,
+    markdown: Markdown,
+    column_width: ColumnWidth,
+}
+
+/// Bounds of the column at which help text begins in usage text
+#[derive(Copy, Clone)]
+struct ColumnWidth {
+    min: usize,
+    max: usize,
+}
+
+/// Order in which options are listed in usage text
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+enum SortOrder {
+    #[default]
+    Declaration,
+    Alpha,
+}
+
+/// Treatment of Markdown syntax in doc comments
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+enum Markdown {
+    #[default]
+    Verbatim,
+    Strip,
+    Render,
+}
+
+impl Markdown {
+    fn parse(lit: &Lit) -> Result<Markdown, Error> {
+        match &lit_str(lit)?[..] {
+            "verbatim" => Ok(Markdown::Verbatim),
+            "strip" => Ok(Markdown::Strip),
+            "render" => Ok(Markdown::Render),
+            _ => Err(Error::new(lit.span(),
+                "expected `\"verbatim\"`, `\"strip\"`, or `\"render\"`"))
+        }
+    }
+}
+
+/// Case convention by which names are derived from field or variant names
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+enum RenameRule {
+    #[default]
+    KebabCase,
+    SnakeCase,
+    ScreamingSnakeCase,
+    ScreamingKebabCase,
+    Lowercase,
+    Verbatim,
+}
+
+impl RenameRule {
+    fn parse(lit: &Lit) -> Result<RenameRule, Error> {
+        match &lit_str(lit)?[..] {
+            "kebab-case" => Ok(RenameRule::KebabCase),
+            "snake_case" => Ok(RenameRule::SnakeCase),
+            "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnakeCase),
+            "SCREAMING-KEBAB-CASE" => Ok(RenameRule::ScreamingKebabCase),
+            "lowercase" => Ok(RenameRule::Lowercase),
+            "verbatim" => Ok(RenameRule::Verbatim),
+            _ => Err(Error::new(lit.span(),
+                "expected `\"kebab-case\"`, `\"snake_case\"`, \
+                 `\"SCREAMING_SNAKE_CASE\"`, `\"SCREAMING-KEBAB-CASE\"`, \
+                 `\"lowercase\"`, or `\"verbatim\"`"))
+        }
+    }
+
+    /// Joins the words of a name according to the rule
+    fn rename(self, name: &str, words: &[String]) -> String {
+        match self {
+            RenameRule::Verbatim => name.to_owned(),
+            RenameRule::KebabCase => words.join("-"),
+            RenameRule::SnakeCase => words.join("_"),
+            RenameRule::ScreamingSnakeCase => words.join("_").to_uppercase(),
+            RenameRule::ScreamingKebabCase => words.join("-").to_uppercase(),
+            RenameRule::Lowercase => words.concat(),
+        }
+    }
+}
+
+/// Item of an `#[options(...)]` attribute, which may contain an item with
+/// an expression value, e.g. `default_expr = ...` or `help = include_str!(...)`
+enum OptionsItem {
+    /// Item whose value is an expression other than a literal
+    Expr(Path, Box<Expr>),
+    Meta(NestedMeta),
+}
+
+impl Parse for OptionsItem {
+    fn parse(input: ParseStream) -> syn::Result<OptionsItem> {
+        if input.peek(Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+            let fork = input.fork();
+
+            fork.parse::<Ident>()?;
+            fork.parse::<Token![=]>()?;
+
+            if !fork.peek(Lit) {
+                let ident = input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                let expr = input.parse()?;
+                return Ok(OptionsItem::Expr(ident.into(), Box::new(expr)));
+            }
+        }
+
+        input.parse().map(OptionsItem::Meta)
+    }
+}
+
+/// Parses the items of an `#[options(...)]` attribute
+fn options_items(attr: &Attribute) -> Result<Vec<OptionsItem>, Error> {
+    match attr.parse_meta() {
+        Ok(Meta::Path(path)) =>
+            Err(Error::new(path.span(), "`#[options]` is not a valid attribute")),
+        Ok(Meta::NameValue(nv)) =>
+            Err(Error::new(nv.path.span(), "`#[options = ...]` is not a valid attribute")),
+        Ok(Meta::List(list)) =>
+            Ok(list.nested.into_iter().map(OptionsItem::Meta).collect()),
+        // Expression values are not valid in the syntax of `Meta`
+        Err(_) => {
+            let items = attr.parse_args_with(
+                Punctuated::<OptionsItem, Token![,]>::parse_terminated)?;
+
+            Ok(items.into_iter().collect())
+        }
+    }
 }
 
 enum FreeAction {
     Push(Ident),
+    /// Insert a key and value given as `KEY=VALUE` into a map
+    Insert,
     SetField,
     SetOption,
 }
 
+/// Number of consecutive arguments making up each value of a `free` field
+/// of tuple or array type
+#[derive(Copy, Clone)]
+enum FreeArity {
+    Tuple(usize),
+    Array(usize),
+}
+
 struct FreeOpt<'a> {
     field: &'a Ident,
+    index: Option<usize>,
     action: FreeAction,
+    arity: Option<FreeArity>,
     parse: ParseFn,
+    /// Whether the parse error type may involve type parameters
+    generic_error: bool,
     required: bool,
+    /// Whether the field may also be given as an option
+    named: bool,
+    /// Whether an argument not of the form `KEY=VALUE` is given to the
+    /// following field, rather than producing an error
+    fall_through: bool,
+    /// Whether values are taken from the original form of arguments
+    os: bool,
     help: Option<String>,
+    long_help: Option<String>,
 }
 
 struct Opt<'a> {
@@ -832,27 +2700,66 @@ struct Opt<'a> {
     short: Option<char>,
     no_short: bool,
     required: bool,
+    /// For the option form of a `free` field, whether the field is required
+    named_free: Option<bool>,
+    /// For the option form of a `free` field filled as arguments are read,
+    /// the index of the field among `free` fields
+    free_slot: Option<usize>,
     help: Option<String>,
     meta: Option<String>,
+    long_help: Option<String>,
     default: Option<String>,
+    heading: Option<String>,
+    display_order: Option<i32>,
+    /// Whether the option is listed in a preview of the command
+    preview: bool,
+    env: Option<String>,
+    /// Prompt written when reading the value from the terminal
+    prompt_secret: Option<String>,
+    /// Prompt asking the user to confirm the option, if given
+    confirm: Option<String>,
+    /// Whether the option is `--yes`, declining confirmation prompts
+    assume_yes: bool,
+    /// Whether the option is added by the derive rather than declared as a field
+    synthetic: bool,
+    conflicts_with: Vec<String>,
+    requires: Vec<String>,
+    possible_values: Vec<String>,
+    /// Whether the presence of the option is recorded in `_used`
+    tracked: bool,
     // NOTE: `default_expr` is not contained here
     // because it is not displayed to the user in usage text
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 enum ParseFn {
+    #[default]
     Default,
     FromStr(Option<Path>),
     TryFromStr(Path),
+    WithParser(Path),
+    Contextual(Path),
 }
 
 struct ParseMethod {
     parse_fn: ParseFn,
+    /// Whether the parse error type may involve type parameters
+    generic_error: bool,
     tuple_len: Option<usize>,
+    possible_values: Vec<String>,
+    /// Whether an argument matching a possible value without regard to case
+    /// is replaced by that value
+    ignore_case: bool,
 }
 
 impl Action {
-    fn infer(ty: &Type, opts: &AttrOpts) -> Action {
+    fn infer(ty: &Type, opts: &AttrOpts, generic_error: bool) -> Action {
+        // A value parsed with a parser is not read as a tuple of arguments
+        let tuple_len = |ty: &Type| match &opts.parse {
+            Some(ParseFn::WithParser(_)) => None,
+            _ => tuple_len(ty)
+        };
+
         match ty {
             Type::Path(path) => {
                 let path = path.path.segments.last().unwrap();
@@ -867,7 +2774,10 @@ impl Action {
                             Ident::new("push", Span::call_site()),
                             ParseMethod{
                                 parse_fn: opts.parse.clone().unwrap_or_default(),
+                                generic_error,
                                 tuple_len,
+                                possible_values: opts.possible_values.clone(),
+                                ignore_case: opts.ignore_case,
                             })
                     }
                     "Option" if param.is_some() => {
@@ -875,7 +2785,10 @@ impl Action {
 
                         Action::SetOption(ParseMethod{
                             parse_fn: opts.parse.clone().unwrap_or_default(),
+                            generic_error,
                             tuple_len,
+                            possible_values: opts.possible_values.clone(),
+                            ignore_case: opts.ignore_case,
                         })
                     }
                     _ => {
@@ -886,12 +2799,18 @@ impl Action {
                                 meth.clone(),
                                 ParseMethod{
                                     parse_fn: opts.parse.clone().unwrap_or_default(),
+                                    generic_error,
                                     tuple_len,
+                                    possible_values: opts.possible_values.clone(),
+                                    ignore_case: opts.ignore_case,
                                 })
                         } else {
                             Action::SetField(ParseMethod{
                                 parse_fn: opts.parse.clone().unwrap_or_default(),
+                                generic_error,
                                 tuple_len: tuple_len(ty),
+                                possible_values: opts.possible_values.clone(),
+                                ignore_case: opts.ignore_case,
                             })
                         }
                     }
@@ -902,7 +2821,10 @@ impl Action {
 
                 Action::SetField(ParseMethod{
                     parse_fn: opts.parse.clone().unwrap_or_default(),
+                    generic_error,
                     tuple_len,
+                    possible_values: opts.possible_values.clone(),
+                    ignore_case: opts.ignore_case,
                 })
             }
         }
@@ -942,29 +2864,73 @@ impl AttrOpts {
         if self.command {
             if self.free { err!("`command` and `free` are mutually exclusive"); }
             if self.default.is_some() { err!("`command` and `default` are mutually exclusive"); }
+            if self.default_fn.is_some() { err!("`command` and `default_fn` are mutually exclusive"); }
             if self.multi.is_some() { err!("`command` and `multi` are mutually exclusive"); }
             if self.long.is_some() { err!("`command` and `long` are mutually exclusive"); }
             if self.short.is_some() { err!("`command` and `short` are mutually exclusive"); }
             if self.count { err!("`command` and `count` are mutually exclusive"); }
             if self.help_flag { err!("`command` and `help_flag` are mutually exclusive"); }
+            if self.help_all_flag { err!("`command` and `help_all_flag` are mutually exclusive"); }
+            if self.version_flag { err!("`command` and `version_flag` are mutually exclusive"); }
             if self.no_help_flag { err!("`command` and `no_help_flag` are mutually exclusive"); }
             if self.no_short { err!("`command` and `no_short` are mutually exclusive"); }
             if self.no_long { err!("`command` and `no_long` are mutually exclusive"); }
             if self.no_multi { err!("`command` and `no_multi` are mutually exclusive"); }
             if self.help.is_some() { err!("`command` and `help` are mutually exclusive"); }
+            if self.long_help.is_some() { err!("`command` and `long_help` are mutually exclusive"); }
+            if self.preview { err!("`command` and `preview` are mutually exclusive"); }
             if self.meta.is_some() { err!("`command` and `meta` are mutually exclusive"); }
+            if self.heading.is_some() { err!("`command` and `heading` are mutually exclusive"); }
+            if self.display_order.is_some() { err!("`command` and `display_order` are mutually exclusive"); }
+            if self.env.is_some() { err!("`command` and `env` are mutually exclusive"); }
+            if self.default_expr_display.is_some() { err!("`command` and `default_expr_display` are mutually exclusive"); }
+            if self.show_default.is_some() { err!("`command` and `show_default` are mutually exclusive"); }
+            if self.hide_default { err!("`command` and `hide_default` are mutually exclusive"); }
+            if !self.conflicts_with.is_empty() { err!("`command` and `conflicts_with` are mutually exclusive"); }
+            if !self.requires.is_empty() { err!("`command` and `requires` are mutually exclusive"); }
+            if !self.possible_values.is_empty() { err!("`command` and `possible_values` are mutually exclusive"); }
         }
 
         if self.free {
             if self.default.is_some() { err!("`free` and `default` are mutually exclusive"); }
-            if self.long.is_some() { err!("`free` and `long` are mutually exclusive"); }
-            if self.short.is_some() { err!("`free` and `short` are mutually exclusive"); }
+            if self.default_fn.is_some() { err!("`free` and `default_fn` are mutually exclusive"); }
             if self.count { err!("`free` and `count` are mutually exclusive"); }
             if self.help_flag { err!("`free` and `help_flag` are mutually exclusive"); }
+            if self.help_all_flag { err!("`free` and `help_all_flag` are mutually exclusive"); }
+            if self.version_flag { err!("`free` and `version_flag` are mutually exclusive"); }
             if self.no_help_flag { err!("`free` and `no_help_flag` are mutually exclusive"); }
-            if self.no_short { err!("`free` and `no_short` are mutually exclusive"); }
             if self.no_long { err!("`free` and `no_long` are mutually exclusive"); }
-            if self.meta.is_some() { err!("`free` and `meta` are mutually exclusive"); }
+
+            // Items describing the option form of a named `free` field
+            if self.long.is_none() && self.short.is_none() {
+                if self.no_short { err!("`free` and `no_short` are mutually exclusive"); }
+                if self.meta.is_some() { err!("`free` and `meta` are mutually exclusive"); }
+                if self.heading.is_some() { err!("`free` and `heading` are mutually exclusive"); }
+                if self.preview { err!("`free` and `preview` are mutually exclusive"); }
+                if self.display_order.is_some() {
+                    err!("`free` and `display_order` are mutually exclusive");
+                }
+            }
+            if self.env.is_some() { err!("`free` and `env` are mutually exclusive"); }
+            if self.confirm.is_some() { err!("`free` and `confirm` are mutually exclusive"); }
+            if self.default_expr_display.is_some() { err!("`free` and `default_expr_display` are mutually exclusive"); }
+            if self.show_default.is_some() { err!("`free` and `show_default` are mutually exclusive"); }
+            if self.hide_default { err!("`free` and `hide_default` are mutually exclusive"); }
+            if !self.conflicts_with.is_empty() { err!("`free` and `conflicts_with` are mutually exclusive"); }
+            if !self.requires.is_empty() { err!("`free` and `requires` are mutually exclusive"); }
+            if !self.possible_values.is_empty() { err!("`free` and `possible_values` are mutually exclusive"); }
+        }
+
+        if self.ignore_case && self.possible_values.is_empty() {
+            err!("`ignore_case` requires `possible_values`");
+        }
+
+        if self.index.is_some() && !self.free {
+            err!("`index` requires `free`");
+        }
+
+        if self.fall_through && !self.free {
+            err!("`fall_through` requires `free`");
         }
 
         if self.multi.is_some() && self.no_multi {
@@ -975,6 +2941,22 @@ impl AttrOpts {
             err!("`help_flag` and `no_help_flag` are mutually exclusive");
         }
 
+        if self.help_all_flag && self.no_help_flag {
+            err!("`help_all_flag` and `no_help_flag` are mutually exclusive");
+        }
+
+        if self.help_flag && self.help_all_flag {
+            err!("`help_flag` and `help_all_flag` are mutually exclusive");
+        }
+
+        if self.version_flag && self.no_help_flag {
+            err!("`version_flag` and `no_help_flag` are mutually exclusive");
+        }
+
+        if self.version_flag && (self.help_flag || self.help_all_flag) {
+            err!("`version_flag` and `help_flag` are mutually exclusive");
+        }
+
         if self.no_short && self.short.is_some() {
             err!("`no_short` and `short` are mutually exclusive");
         }
@@ -987,17 +2969,58 @@ impl AttrOpts {
             err!("`required` and `not_required` are mutually exclusive");
         }
 
-        if self.parse.is_some() {
-            if self.count { err!("`count` and `parse` are mutually exclusive"); }
+        if let Some(ParseFn::WithParser(_)) = &self.parse {
+            if self.free { err!("`free` and `parse(with_parser)` are mutually exclusive"); }
+            if self.default.is_some() { err!("`default` and `parse(with_parser)` are mutually exclusive"); }
+            if self.env.is_some() { err!("`env` and `parse(with_parser)` are mutually exclusive"); }
+            if !self.possible_values.is_empty() {
+                err!("`possible_values` and `parse(with_parser)` are mutually exclusive");
+            }
+        }
+
+        if let Some(ParseFn::Contextual(_)) = &self.parse {
+            if self.default.is_some() { err!("`default` and `parse(contextual)` are mutually exclusive"); }
+        }
+
+        if self.parse.is_some() && self.count {
+            err!("`count` and `parse` are mutually exclusive");
         }
 
-        #[cfg(feature = "default_expr")]
-        {
-            if self.default.is_some() && self.default_expr.is_some() {
-                err!("`default` and `default_expr` are mutually exclusive");
+        if self.env.is_some() && self.count {
+            err!("`count` and `env` are mutually exclusive");
+        }
+
+        if self.prompt_secret.is_some() {
+            if !cfg!(feature = "prompt") {
+                err!("`prompt_secret` requires the `prompt` feature of `gumdrop`");
+            }
+            if self.free { err!("`free` and `prompt_secret` are mutually exclusive"); }
+            if self.count { err!("`count` and `prompt_secret` are mutually exclusive"); }
+            if self.default.is_some() || self.default_lit.is_some() {
+                err!("`default` and `prompt_secret` are mutually exclusive");
+            }
+            if self.default_fn.is_some() { err!("`default_fn` and `prompt_secret` are mutually exclusive"); }
+            if self.default_expr.is_some() {
+                err!("`default_expr` and `prompt_secret` are mutually exclusive");
             }
         }
 
+        if self.hide_default && self.show_default.is_some() {
+            err!("`hide_default` and `show_default` are mutually exclusive");
+        }
+
+        if self.default.is_some() && self.default_fn.is_some() {
+            err!("`default` and `default_fn` are mutually exclusive");
+        }
+
+        if self.default.is_some() && self.default_expr.is_some() {
+            err!("`default` and `default_expr` are mutually exclusive");
+        }
+
+        if self.default_fn.is_some() && self.default_expr.is_some() {
+            err!("`default_fn` and `default_expr` are mutually exclusive");
+        }
+
         Ok(())
     }
 
@@ -1015,21 +3038,20 @@ impl AttrOpts {
                         if opts.doc.is_none() {
                             opts.doc = Some(doc.trim_start().to_owned());
                         }
+
+                        push_doc_line(&mut opts.long_doc, &doc);
                     }
                 } else if path_eq(&attr.path, "options") {
-                    let meta = attr.parse_meta()?;
-
-                    match meta {
-                        Meta::Path(path) =>
-                            return Err(Error::new(path.span(),
-                                "`#[options]` is not a valid attribute")),
-                        Meta::NameValue(nv) =>
-                            return Err(Error::new(nv.path.span(),
-                                "`#[options = ...]` is not a valid attribute")),
-                        Meta::List(items) => {
-                            for item in &items.nested {
-                                opts.parse_item(item)?;
-                            }
+                    for item in options_items(attr)? {
+                        match item {
+                            OptionsItem::Expr(path, expr) => match path.get_ident() {
+                                Some(ident) if ident == "default_expr" =>
+                                    opts.set_default_expr(*expr)?,
+                                Some(ident) if ident == "help" =>
+                                    opts.help = Some(help_expr(*expr)?),
+                                _ => return Err(unknown_item(&path, &path, FIELD_ITEMS))
+                            },
+                            OptionsItem::Meta(item) => opts.parse_item(&item)?,
                         }
                     }
                 }
@@ -1050,57 +3072,83 @@ impl AttrOpts {
                     Meta::Path(path) => match path.get_ident() {
                         Some(ident) => match ident.to_string().as_str() {
                             "free" => self.free = true,
+                            "fall_through" => self.fall_through = true,
+                            // The prompt is made from the option name
+                            "prompt_secret" => self.prompt_secret = Some(String::new()),
+                            "verbatim_doc_comment" => self.verbatim_doc_comment = true,
                             "command" => self.command = true,
                             "count" => self.count = true,
                             "help_flag" => self.help_flag = true,
+                            "help_all_flag" => self.help_all_flag = true,
+                            "version_flag" => self.version_flag = true,
                             "no_help_flag" => self.no_help_flag = true,
                             "no_short" => self.no_short = true,
                             "no_long" => self.no_long = true,
                             "no_multi" => self.no_multi = true,
                             "required" => self.required = true,
                             "not_required" => self.not_required = true,
-                            _ => return Err(unexpected_meta_item(path.span()))
+                            "hide_default" => self.hide_default = true,
+                            "preview" => self.preview = true,
+                            "ignore_case" => self.ignore_case = true,
+                            _ => return Err(unknown_item(path, path, FIELD_ITEMS))
                         }
-                        None => return Err(unexpected_meta_item(path.span()))
+                        None => return Err(unknown_item(path, path, FIELD_ITEMS))
                     },
                     Meta::List(list) => {
                         match list.path.get_ident() {
-                            Some(ident) if ident.to_string() == "parse" => {
+                            Some(ident) if ident == "parse" => {
                                 if list.nested.len() != 1 {
                                     return Err(unexpected_meta_item(list.path.span()));
                                 }
 
                                 self.parse = Some(ParseFn::parse(&list.nested[0])?);
                             }
-                            _ => return Err(unexpected_meta_item(list.path.span()))
+                            _ => return Err(unknown_item(list, &list.path, FIELD_ITEMS))
                         }
                     }
                     Meta::NameValue(nv) => {
                         match nv.path.get_ident() {
                             Some(ident) => match ident.to_string().as_str() {
-                                "default" => self.default = Some(lit_str(&nv.lit)?),
-                                #[cfg(feature = "default_expr")]
-                                "default_expr" => {
-                                    let expr = parse_str(&lit_str(&nv.lit)?)?;
-                                    self.default_expr = Some(expr);
+                                "default" => self.parse_default(&nv.lit)?,
+                                "default_fn" => {
+                                    let path = parse_str(&lit_str(&nv.lit)?)?;
+                                    self.default_fn = Some(path);
                                 }
-                                #[cfg(not(feature = "default_expr"))]
                                 "default_expr" => {
-                                    return Err(Error::new(nv.path.span(),
-                                    "compile gumdrop with the `default_expr` \
-                                    feature to enable this attribute"));
+                                    let src = lit_str(&nv.lit)?;
+                                    self.default_expr = Some(parse_str(&src)?);
+                                    self.default_expr_src = Some(src);
                                 }
                                 "long" => self.long = Some(lit_str(&nv.lit)?),
                                 "short" => self.short = Some(lit_char(&nv.lit)?),
                                 "help" => self.help = Some(lit_str(&nv.lit)?),
+                                "long_help" => self.long_help = Some(lit_str(&nv.lit)?),
                                 "meta" => self.meta = Some(lit_str(&nv.lit)?),
+                                "heading" => self.heading = Some(lit_str(&nv.lit)?),
+                                "env" => self.env = Some(lit_str(&nv.lit)?),
+                                "prompt_secret" => self.prompt_secret = Some(lit_str(&nv.lit)?),
+                                "confirm" => self.confirm = Some(lit_str(&nv.lit)?),
+                                "default_expr_display" =>
+                                    self.default_expr_display = Some(lit_str(&nv.lit)?),
+                                "show_default" => self.show_default = Some(lit_bool(&nv.lit)?),
+                                "conflicts_with" => self.conflicts_with.push(lit_str(&nv.lit)?),
+                                "requires" => self.requires.push(lit_str(&nv.lit)?),
+                                "possible_values" => {
+                                    self.possible_values = lit_str(&nv.lit)?
+                                        .split(',')
+                                        .map(|s| s.trim().to_owned())
+                                        .filter(|s| !s.is_empty())
+                                        .collect();
+                                }
+                                "display_order" => self.display_order = Some(lit_int(&nv.lit)?),
+                                "index" => self.index = Some(lit_int(&nv.lit)?),
                                 "multi" => {
                                     let name = parse_str(&lit_str(&nv.lit)?)?;
                                     self.multi = Some(name);
                                 }
-                                _ => return Err(unexpected_meta_item(nv.path.span()))
+                                _ => return Err(unknown_item(nv, &nv.path, FIELD_ITEMS))
                             }
-                            None => return Err(unexpected_meta_item(nv.path.span()))
+                            None => return Err(unknown_item(nv, &nv.path, FIELD_ITEMS))
                         }
                     }
                 }
@@ -1110,8 +3158,19 @@ impl AttrOpts {
         Ok(())
     }
 
+    /// Returns help text for long usage, if distinct from short help text
+    fn long_help(&self) -> Option<String> {
+        self.long_help.clone().or_else(|| self.long_doc.as_ref()
+            .map(|doc| if self.verbatim_doc_comment {
+                doc.trim_end().to_owned()
+            } else {
+                paragraphs(doc)
+            })
+            .filter(|doc| Some(doc) != self.doc.as_ref()))
+    }
+
     fn set_defaults(&mut self, defaults: &DefaultOpts) {
-        if !self.help_flag && defaults.no_help_flag {
+        if !self.help_flag && !self.help_all_flag && !self.version_flag && defaults.no_help_flag {
             self.no_help_flag = true;
         }
         if self.short.is_none() && defaults.no_short {
@@ -1129,6 +3188,63 @@ impl AttrOpts {
         } else if defaults.required {
             self.required = true;
         }
+
+        if self.hide_default {
+            self.show_default = Some(false);
+        } else if self.show_default.is_none() {
+            self.show_default = defaults.show_default;
+        }
+
+        if defaults.markdown != Markdown::Verbatim {
+            self.doc = self.doc.as_ref().map(|doc| markdown(doc, defaults.markdown));
+            self.long_doc = self.long_doc.as_ref().map(|doc| markdown(doc, defaults.markdown));
+        }
+    }
+
+    /// Sets a `default_expr` value given as an expression; a string literal
+    /// is parsed as an expression
+    fn set_default_expr(&mut self, expr: Expr) -> Result<(), Error> {
+        if let Expr::Lit(ExprLit{lit: Lit::Str(s), ..}) = &expr {
+            let src = s.value();
+            self.default_expr = Some(parse_str(&src)?);
+            self.default_expr_src = Some(src);
+        } else {
+            self.default_expr_src = Some(quote!{ #expr }.to_string());
+            self.default_expr = Some(expr);
+        }
+
+        Ok(())
+    }
+
+    /// Parses a `default` value, which is either a string to be parsed
+    /// at runtime or a literal of the field type
+    fn parse_default(&mut self, lit: &Lit) -> Result<(), Error> {
+        let display = match lit {
+            Lit::Str(s) => {
+                self.default = Some(s.value());
+                return Ok(());
+            }
+            Lit::Int(i) => i.base10_digits().to_owned(),
+            Lit::Float(f) => f.base10_digits().to_owned(),
+            Lit::Bool(b) => b.value.to_string(),
+            Lit::Char(ch) => ch.value().to_string(),
+            _ => return Err(Error::new(lit.span(),
+                "expected string, integer, float, boolean, or character literal"))
+        };
+
+        self.default = Some(display);
+        self.default_lit = Some(lit.clone());
+        Ok(())
+    }
+
+    /// Returns the default value displayed in usage text, if any
+    fn default_display(&self) -> Option<String> {
+        match self.show_default {
+            Some(false) => None,
+            Some(true) if self.default_expr_display.is_none() =>
+                self.default.clone().or_else(|| self.default_expr_src.clone()),
+            _ => self.default_expr_display.clone().or_else(|| self.default.clone())
+        }
     }
 }
 
@@ -1149,19 +3265,13 @@ impl CmdOpts {
                         }
                     }
                 } else if path_eq(&attr.path, "options") {
-                    let meta = attr.parse_meta()?;
-
-                    match meta {
-                        Meta::Path(path) =>
-                            return Err(Error::new(path.span(),
-                                "`#[options]` is not a valid attribute")),
-                        Meta::NameValue(nv) =>
-                            return Err(Error::new(nv.path.span(),
-                                "`#[options = ...]` is not a valid attribute")),
-                        Meta::List(items) => {
-                            for item in &items.nested {
-                                opts.parse_item(item)?;
-                            }
+                    for item in options_items(attr)? {
+                        match item {
+                            OptionsItem::Expr(path, expr) if path.is_ident("help") =>
+                                opts.help = Some(help_expr(*expr)?),
+                            OptionsItem::Expr(path, _) =>
+                                return Err(unknown_item(&path, &path, VARIANT_ITEMS)),
+                            OptionsItem::Meta(item) => opts.parse_item(&item)?,
                         }
                     }
                 }
@@ -1177,18 +3287,20 @@ impl CmdOpts {
                 return Err(unexpected_meta_item(lit.span())),
             NestedMeta::Meta(item) => {
                 match item {
+                    Meta::Path(path) if path.is_ident("catch_all") =>
+                        self.catch_all = true,
                     Meta::Path(path) =>
-                        return Err(unexpected_meta_item(path.span())),
+                        return Err(unknown_item(path, path, VARIANT_ITEMS)),
                     Meta::List(list) =>
-                        return Err(unexpected_meta_item(list.path.span())),
+                        return Err(unknown_item(list, &list.path, VARIANT_ITEMS)),
                     Meta::NameValue(nv) => {
                         match nv.path.get_ident() {
                             Some(ident) => match ident.to_string().as_str() {
                                 "name" => self.name = Some(lit_str(&nv.lit)?),
                                 "help" => self.help = Some(lit_str(&nv.lit)?),
-                                _ => return Err(unexpected_meta_item(nv.path.span()))
+                                _ => return Err(unknown_item(nv, &nv.path, VARIANT_ITEMS))
                             }
-                            None => return Err(unexpected_meta_item(nv.path.span()))
+                            None => return Err(unknown_item(nv, &nv.path, VARIANT_ITEMS))
                         }
                     }
                 }
@@ -1200,6 +3312,15 @@ impl CmdOpts {
 }
 
 impl DefaultOpts {
+    /// Returns the marker displayed following help text of required items
+    fn required_marker(&self) -> Option<&str> {
+        match self.required_marker.as_deref() {
+            Some("") => None,
+            Some(marker) => Some(marker),
+            None => Some("(required)"),
+        }
+    }
+
     fn parse(attrs: &[Attribute]) -> Result<DefaultOpts, Error> {
         let mut opts = DefaultOpts::default();
 
@@ -1211,33 +3332,44 @@ impl DefaultOpts {
                     if let Meta::NameValue(nv) = meta {
                         let doc = lit_str(&nv.lit)?;
 
-                        if let Some(text) = opts.doc.as_mut() {
-                            text.push('\n');
-                            text.push_str(doc.trim_start());
+                        if opts.after_help_doc {
+                            push_doc_line(&mut opts.after_help, &doc);
                         } else {
-                            opts.doc = Some(doc.trim_start().to_owned());
+                            push_doc_line(&mut opts.doc, &doc);
                         }
                     }
                 } else if path_eq(&attr.path, "options") {
-                    let meta = attr.parse_meta()?;
-
-                    match meta {
-                        Meta::Path(path) =>
-                            return Err(Error::new(path.span(),
-                                "`#[options]` is not a valid attribute")),
-                        Meta::NameValue(nv) =>
-                            return Err(Error::new(nv.path.span(),
-                                "`#[options = ...]` is not a valid attribute")),
-                        Meta::List(items) => {
-                            for item in &items.nested {
-                                opts.parse_item(item)?;
-                            }
+                    for item in options_items(attr)? {
+                        match item {
+                            OptionsItem::Expr(path, expr) if path.is_ident("help") =>
+                                opts.help = Some(help_expr(*expr)?),
+                            OptionsItem::Expr(path, _) =>
+                                return Err(unknown_item(&path, &path, STRUCT_ITEMS)),
+                            OptionsItem::Meta(item) => opts.parse_item(&item)?,
                         }
                     }
                 }
             }
         }
 
+        if !opts.verbatim_doc_comment {
+            opts.doc = opts.doc.map(|doc| trim_doc(&doc));
+
+            if opts.after_help_doc {
+                opts.after_help = opts.after_help.map(|doc| trim_doc(&doc));
+            }
+        }
+
+        let mode = opts.markdown;
+
+        if mode != Markdown::Verbatim {
+            opts.doc = opts.doc.map(|doc| markdown(&doc, mode));
+
+            if opts.after_help_doc {
+                opts.after_help = opts.after_help.map(|doc| markdown(&doc, mode));
+            }
+        }
+
         Ok(opts)
     }
 
@@ -1249,23 +3381,201 @@ impl DefaultOpts {
                 match item {
                     Meta::Path(path) => match path.get_ident() {
                         Some(ident) => match ident.to_string().as_str() {
+                            "verbatim_doc_comment" => self.verbatim_doc_comment = true,
                             "no_help_flag" => self.no_help_flag = true,
                             "no_short" => self.no_short = true,
                             "no_long" => self.no_long = true,
                             "no_multi" => self.no_multi = true,
                             "required" => self.required = true,
-                            _ => return Err(unexpected_meta_item(ident.span()))
+                            "next_line_help" => self.next_line_help = true,
+                            "after_help" => self.after_help_doc = true,
+                            "to_args" => self.to_args = true,
+                            "arbitrary" => self.arbitrary = true,
+                            "lenient_unknown_options" => self.lenient_unknown_options = true,
+                            "lenient_extra_free" => self.lenient_extra_free = true,
+                            _ => return Err(unknown_item(path, path, STRUCT_ITEMS))
                         }
-                        None => return Err(unexpected_meta_item(path.span()))
+                        None => return Err(unknown_item(path, path, STRUCT_ITEMS))
                     },
                     Meta::NameValue(nv) => {
                         match nv.path.get_ident() {
-                           Some(ident) if ident.to_string() == "help" => self.help = Some(lit_str(&nv.lit)?),
-                            _ => return Err(unexpected_meta_item(nv.path.span()))
-                        }
+                            Some(ident) if ident == "help" => self.help = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "after_help" =>
+                                self.after_help = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "example" =>
+                                self.examples.push(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "positional_heading" =>
+                                self.positional_heading = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "optional_heading" =>
+                                self.optional_heading = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "examples_heading" =>
+                                self.examples_heading = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "command_heading" =>
+                                self.command_heading = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "error_usage" =>
+                                self.error_usage = Some(error_usage(&nv.lit)?),
+                            Some(ident) if ident == "program_name" =>
+                                self.program_name = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "version" =>
+                                self.version = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "required_marker" =>
+                                self.required_marker = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "show_default" =>
+                                self.show_default = Some(lit_bool(&nv.lit)?),
+                            Some(ident) if ident == "auto_short" =>
+                                self.no_short = !lit_bool(&nv.lit)?,
+                            Some(ident) if ident == "auto_help" =>
+                                self.no_help_flag = !lit_bool(&nv.lit)?,
+                            Some(ident) if ident == "rename_all" =>
+                                self.rename_all = RenameRule::parse(&nv.lit)?,
+                            Some(ident) if ident == "min_free" =>
+                                self.min_free = Some(lit_int(&nv.lit)?),
+                            Some(ident) if ident == "max_free" =>
+                                self.max_free = Some(lit_int(&nv.lit)?),
+                            Some(ident) if ident == "base" =>
+                                self.base = Some(parse_str(&lit_str(&nv.lit)?)?),
+                            Some(ident) if ident == "localize" =>
+                                self.localize = Some(parse_str(&lit_str(&nv.lit)?)?),
+                            Some(ident) if ident == "default_format" => {
+                                let format = lit_str(&nv.lit)?;
+
+                                if !format.contains("{}") {
+                                    return Err(Error::new(nv.lit.span(),
+                                        "`default_format` must contain `{}`"));
+                                }
+
+                                self.default_format = Some(format);
+                            }
+                            Some(ident) if ident == "help_column_width" =>
+                                self.column_width.min = lit_int(&nv.lit)?,
+                            Some(ident) if ident == "help_max_width" =>
+                                self.column_width.max = lit_int(&nv.lit)?,
+                            Some(ident) if ident == "sort" =>
+                                self.sort = sort_order(&nv.lit)?,
+                            Some(ident) if ident == "markdown" =>
+                                self.markdown = Markdown::parse(&nv.lit)?,
+                            _ => return Err(unknown_item(nv, &nv.path, STRUCT_ITEMS))
+                        }
+                    }
+                    Meta::List(list) =>
+                        return Err(unknown_item(list, &list.path, STRUCT_ITEMS))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl EnumOpts {
+    fn parse(attrs: &[Attribute]) -> Result<EnumOpts, Error> {
+        let mut opts = EnumOpts::default();
+
+        for attr in attrs {
+            if !is_outer(attr.style) {
+                continue;
+            }
+
+            if path_eq(&attr.path, "doc") {
+                if opts.after_help_doc {
+                    if let Meta::NameValue(nv) = attr.parse_meta()? {
+                        push_doc_line(&mut opts.after_help, &lit_str(&nv.lit)?);
+                    }
+                }
+            } else if path_eq(&attr.path, "options") {
+                let meta = attr.parse_meta()?;
+
+                match meta {
+                    Meta::Path(path) =>
+                        return Err(Error::new(path.span(),
+                            "`#[options]` is not a valid attribute")),
+                    Meta::NameValue(nv) =>
+                        return Err(Error::new(nv.path.span(),
+                            "`#[options = ...]` is not a valid attribute")),
+                    Meta::List(items) => {
+                        for item in &items.nested {
+                            opts.parse_item(item)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        if opts.after_help_doc {
+            opts.after_help = opts.after_help.map(|doc| trim_doc(&doc));
+
+            let mode = opts.markdown;
+
+            if mode != Markdown::Verbatim {
+                opts.after_help = opts.after_help.map(|doc| markdown(&doc, mode));
+            }
+        }
+
+        Ok(opts)
+    }
+
+    fn parse_item(&mut self, item: &NestedMeta) -> Result<(), Error> {
+        match item {
+            NestedMeta::Lit(lit) =>
+                return Err(unexpected_meta_item(lit.span())),
+            NestedMeta::Meta(item) => {
+                match item {
+                    Meta::Path(path) => match path.get_ident() {
+                        Some(ident) => match ident.to_string().as_str() {
+                            "help_command" => self.help_command = true,
+                            "preview" => self.preview = true,
+                            "after_help" => self.after_help_doc = true,
+                            "to_args" => self.to_args = true,
+                            "arbitrary" => self.arbitrary = true,
+                            "ignore_case" => self.ignore_case = true,
+                            "next_line_help" => self.next_line_help = true,
+                            _ => return Err(unknown_item(path, path, ENUM_ITEMS))
+                        }
+                        None => return Err(unknown_item(path, path, ENUM_ITEMS))
+                    },
+                    Meta::NameValue(nv) => {
+                        match nv.path.get_ident() {
+                            Some(ident) if ident == "dispatch" => {
+                                let name = parse_str(&lit_str(&nv.lit)?)?;
+                                self.dispatch = Some(name);
+                            }
+                            Some(ident) if ident == "fallback" => {
+                                let path = parse_str(&lit_str(&nv.lit)?)?;
+                                self.fallback = Some(path);
+                            }
+                            Some(ident) if ident == "localize" =>
+                                self.localize = Some(parse_str(&lit_str(&nv.lit)?)?),
+                            Some(ident) if ident == "sort" =>
+                                self.sort = sort_order(&nv.lit)?,
+                            Some(ident) if ident == "markdown" =>
+                                self.markdown = Markdown::parse(&nv.lit)?,
+                            Some(ident) if ident == "pin" => {
+                                self.pin = lit_str(&nv.lit)?
+                                    .split(',')
+                                    .map(|s| s.trim().to_owned())
+                                    .filter(|s| !s.is_empty())
+                                    .collect();
+                                self.pin_span = Some(nv.lit.span());
+                            }
+                            Some(ident) if ident == "after_help" =>
+                                self.after_help = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "command_heading" =>
+                                self.command_heading = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "error_usage" =>
+                                self.error_usage = Some(error_usage(&nv.lit)?),
+                            Some(ident) if ident == "program_name" =>
+                                self.program_name = Some(lit_str(&nv.lit)?),
+                            Some(ident) if ident == "help_column_width" =>
+                                self.column_width.min = lit_int(&nv.lit)?,
+                            Some(ident) if ident == "help_max_width" =>
+                                self.column_width.max = lit_int(&nv.lit)?,
+                            Some(ident) if ident == "rename_all" =>
+                                self.rename_all = RenameRule::parse(&nv.lit)?,
+                            _ => return Err(unknown_item(nv, &nv.path, ENUM_ITEMS))
+                        }
                     }
                     Meta::List(list) =>
-                        return Err(unexpected_meta_item(list.path.span()))
+                        return Err(unknown_item(list, &list.path, ENUM_ITEMS))
                 }
             }
         }
@@ -1282,6 +3592,7 @@ impl FreeAction {
 
                 match &path.ident.to_string()[..] {
                     "Option" => FreeAction::SetOption,
+                    "BTreeMap" | "HashMap" => FreeAction::Insert,
                     "Vec" if !opts.no_multi =>
                         FreeAction::Push(Ident::new("push", Span::call_site())),
                     _ => {
@@ -1298,14 +3609,137 @@ impl FreeAction {
     }
 
     fn is_push(&self) -> bool {
+        matches!(self, FreeAction::Push(_))
+    }
+
+    fn is_insert(&self) -> bool {
+        matches!(self, FreeAction::Insert)
+    }
+}
+
+impl FreeArity {
+    fn infer(ty: &Type) -> Option<FreeArity> {
+        match ty {
+            Type::Tuple(tup) if !tup.elems.is_empty() =>
+                Some(FreeArity::Tuple(tup.elems.len())),
+            Type::Array(arr) => match &arr.len {
+                Expr::Lit(ExprLit{lit: Lit::Int(n), ..}) =>
+                    n.base10_parse().ok().filter(|&n| n != 0).map(FreeArity::Array),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn len(self) -> usize {
         match self {
-            FreeAction::Push(_) => true,
-            _ => false
+            FreeArity::Tuple(n) | FreeArity::Array(n) => n
         }
     }
 }
 
 impl<'a> FreeOpt<'a> {
+    /// Returns an expression parsing a value from `_free` and, for a field
+    /// of tuple or array type, the arguments immediately following it
+    fn make_parse_value(&self, name: &str) -> TokenStream2 {
+        let parse = self.parse.make_parse_free_action(name, quote!{ _arg },
+            self.generic_error);
+        let first = if self.os {
+            quote!{ {
+                let _arg = _free;
+
+                match _parser.os_arg(_free_pos) {
+                    ::std::option::Option::Some(_os) => ::std::convert::From::from(_os),
+                    ::std::option::Option::None => #parse
+                }
+            } }
+        } else {
+            quote!{ {
+                let _arg = _free;
+                #parse
+            } }
+        };
+
+        let arity = match self.arity {
+            Some(arity) => arity,
+            None => return first
+        };
+
+        let n = arity.len();
+        let rest = (1..n).map(|i| quote!{ {
+            let _arg = _parser.next_arg()
+                .ok_or_else(|| ::gumdrop::Error::missing_required_free_count(#n, #i))?;
+            _parser.notify(::gumdrop::ParseEvent::Free(_arg));
+            _free_ordinal += 1;
+            #parse
+        } });
+
+        match arity {
+            FreeArity::Tuple(_) => quote!{ ( #first , #( #rest , )* ) },
+            FreeArity::Array(_) => quote!{ [ #first , #( #rest , )* ] },
+        }
+    }
+
+    /// Returns code assigning a value parsed from `_free` to the field.
+    /// A value is not assigned to a named field which was given as an option.
+    fn make_assign(&self) -> TokenStream2 {
+        let field = self.field;
+        let value = self.make_parse_value(&field.to_string());
+        let mark_used = self.mark_used();
+
+        if self.action.is_insert() {
+            return self.make_insert(&field.to_string());
+        }
+
+        let assign = match &self.action {
+            FreeAction::Push(meth) => quote!{
+                _result.#field.#meth(_value);
+            },
+            FreeAction::SetField => quote!{
+                _result.#field = _value;
+            },
+            FreeAction::SetOption => quote!{
+                _result.#field = ::std::option::Option::Some(_value);
+            },
+            FreeAction::Insert => unreachable!()
+        };
+
+        let assign = if self.named {
+            quote!{ if !_named.#field { #assign } }
+        } else {
+            assign
+        };
+
+        quote!{
+            #mark_used
+            let _value = #value;
+            #assign
+        }
+    }
+
+    /// Returns code inserting a key and value parsed from `_free`,
+    /// given as `KEY=VALUE`, into the field
+    fn make_insert(&self, name: &str) -> TokenStream2 {
+        let field = self.field;
+        let parse = self.parse.make_parse_free_action(name, quote!{ _free },
+            self.generic_error);
+        let mark_used = self.mark_used();
+
+        quote!{
+            let (_key, _value) = match _free.split_once('=') {
+                ::std::option::Option::Some(_pair) => _pair,
+                ::std::option::Option::None => return ::std::result::Result::Err(
+                    ::gumdrop::Error::failed_parse_free(#name, _free_ordinal, _free,
+                        "expected `KEY=VALUE`"))
+            };
+
+            #mark_used
+            let _key = { let _arg = _key; #parse };
+            let _value = { let _arg = _value; #parse };
+            _result.#field.insert(_key, _value);
+        }
+    }
+
     fn mark_used(&self) -> TokenStream2 {
         if self.required {
             let field = self.field;
@@ -1316,11 +3750,66 @@ impl<'a> FreeOpt<'a> {
     }
 
     fn width(&self) -> usize {
-        2 + self.field.to_string().len() + 2 // name + spaces before and after
+        2 + self.field.to_string().width() + 2 // name + spaces before and after
+    }
+}
+
+impl ColumnWidth {
+    fn check(&self, span: Span) -> Result<(), Error> {
+        if self.min > self.max {
+            Err(Error::new(span,
+                "`help_column_width` must not exceed `help_max_width`"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for ColumnWidth {
+    fn default() -> ColumnWidth {
+        ColumnWidth{
+            min: 8,
+            max: 30,
+        }
     }
 }
 
 impl<'a> Opt<'a> {
+    /// Returns an expression of type `gumdrop::OptionInfo` describing the option
+    fn info_tokens(&self) -> TokenStream2 {
+        let long = option_str(&self.long);
+        let short = match self.short {
+            Some(short) => quote!{ ::std::option::Option::Some(#short) },
+            None => quote!{ ::std::option::Option::None }
+        };
+        let meta = option_str(&self.meta);
+        let help = help_tokens(self.help.as_ref());
+        let required = self.required;
+        let takes_value = self.action.takes_arg();
+        let default = option_str(&self.default);
+
+        quote!{
+            ::gumdrop::OptionInfo{
+                long: #long,
+                short: #short,
+                meta: #meta,
+                help: #help,
+                required: #required,
+                takes_value: #takes_value,
+                default: #default,
+            }
+        }
+    }
+
+    /// Returns the name by which options are sorted with `sort = "alpha"`
+    fn sort_name(&self) -> String {
+        match (&self.long, self.short) {
+            (Some(long), _) => long.to_lowercase(),
+            (None, Some(short)) => short.to_lowercase().collect(),
+            (None, None) => self.field.to_string(),
+        }
+    }
+
     fn display_form(&self) -> String {
         if let Some(long) = &self.long {
             format!("--{}", long)
@@ -1329,20 +3818,156 @@ impl<'a> Opt<'a> {
         }
     }
 
-    fn mark_used(&self) -> TokenStream2 {
-        if self.required {
-            let field = self.field;
-            quote!{ _used.#field = true; }
+    /// Returns the name of the option in errors not arising from an argument,
+    /// e.g. a missing required option, which lists both short and long forms
+    fn error_form(&self) -> String {
+        if self.short.is_some() && self.long.is_some() {
+            self.names()
         } else {
-            quote!{ }
+            self.display_form()
+        }
+    }
+
+    fn mark_used(&self) -> TokenStream2 {
+        let field = self.field;
+
+        match self.named_free {
+            Some(required) => {
+                let check = self.free_slot.map(|i| {
+                    let display = self.display_form();
+                    let name = field.to_string();
+
+                    quote!{
+                        if _free_counter > #i && !_named.#field {
+                            return ::std::result::Result::Err(
+                                ::gumdrop::Error::conflicting_options_static(#display, #name));
+                        }
+                    }
+                });
+                let used = if required {
+                    quote!{ _used.#field = true; }
+                } else {
+                    quote!{ }
+                };
+
+                quote!{
+                    #check
+                    #used
+                    _named.#field = true;
+                }
+            }
+            None if self.tracked => quote!{ _used.#field = true; },
+            None => quote!{ }
         }
     }
 
+    /// Returns an action which sets the option from its environment variable,
+    /// if the option was not given in arguments
+    fn make_env_action(&self) -> Option<TokenStream2> {
+        use self::Action::*;
+
+        let var = self.env.as_ref()?;
+        let field = self.field;
+        let parse = match &self.action {
+            Push(_, parse) | SetField(parse) | SetOption(parse) =>
+                parse.parse_fn.make_parse_action_map_err(quote!{
+                    |e| ::gumdrop::Error::failed_parse_env(#var,
+                        ::std::string::ToString::to_string(&e))
+                }),
+            _ => unreachable!()
+        };
+
+        let action = match &self.action {
+            Push(meth, _) => quote!{ _result.#field.#meth(#parse); },
+            SetField(_) => quote!{ _result.#field = #parse; },
+            SetOption(_) => quote!{
+                _result.#field = ::std::option::Option::Some(#parse);
+            },
+            _ => unreachable!()
+        };
+
+        Some(quote!{
+            if !_used.#field {
+                if let ::std::result::Result::Ok(_value) = ::std::env::var(#var) {
+                    let _arg: &str = &_value;
+                    _used.#field = true;
+
+                    #[allow(clippy::redundant_closure_call)]
+                    let _step = (|| -> ::std::result::Result<(), ::gumdrop::Error> {
+                        #action
+                        ::std::result::Result::Ok(())
+                    })();
+
+                    if let ::std::result::Result::Err(e) = _step {
+                        _parser.report_error(e)?;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns an action which reads the option value from the terminal,
+    /// if the option was not otherwise given
+    /// Returns a unique key identifying an option marked `prompt_secret`
+    fn prompt_key(&self, name: &Ident) -> TokenStream2 {
+        let key = format!("{}.{}", name, self.field);
+
+        quote!{ ::std::concat!(::std::module_path!(), "::", #key) }
+    }
+
+    /// Returns code recording, during parsing, that the value of an option
+    /// marked `prompt_secret` must be read from the terminal
+    fn make_prompt_request(&self, name: &Ident) -> Option<TokenStream2> {
+        self.prompt_secret.as_ref()?;
+        let field = self.field;
+        let key = self.prompt_key(name);
+
+        Some(quote!{
+            if !_used.#field {
+                _used.#field = true;
+                _parser.request_prompt(#key);
+            }
+        })
+    }
+
+    /// Returns code reading the value of an option marked `prompt_secret`,
+    /// as part of `Options::prompt_secrets`
+    fn make_prompt_action(&self, name: &Ident) -> Option<TokenStream2> {
+        let prompt = self.prompt_secret.as_ref()?;
+        let key = self.prompt_key(name);
+        let field = self.field;
+        let display = self.display_form();
+        let parse = match &self.action {
+            Action::SetField(parse) | Action::SetOption(parse) =>
+                parse.parse_fn.make_parse_action_map_err(quote!{
+                    |e| ::gumdrop::Error::failed_parse_with_name(
+                        ::std::string::ToString::to_string(#display),
+                        ::std::string::ToString::to_string(&e))
+                }),
+            _ => unreachable!()
+        };
+
+        let action = match &self.action {
+            Action::SetOption(_) => quote!{
+                _result.#field = ::std::option::Option::Some(#parse);
+            },
+            _ => quote!{ _result.#field = #parse; }
+        };
+
+        Some(quote!{
+            if _parser.prompt_requested(#key) {
+                let _value = ::gumdrop::prompt_secret(#display, #prompt)?;
+                let _arg: &str = &_value;
+                #action
+            }
+        })
+    }
+
     fn width(&self) -> usize {
-        let short = self.short.map_or(0, |_| 1 + 1); // '-' + char
-        let long = self.long.as_ref().map_or(0, |s| s.len() + 2); // "--" + str
+        let short = self.short.map_or(0, |ch| 1 + ch.width().unwrap_or(0)); // '-' + char
+        let long = self.long.as_ref().map_or(0, |s| s.width() + 2); // "--" + str
         let sep = if short == 0 || long == 0 { 0 } else { 2 }; // ", "
-        let meta = self.meta.as_ref().map_or(0, |s| s.len() + 1); // ' ' + meta
+        let meta = self.meta.as_ref().map_or(0, |s| s.width() + 1); // ' ' + meta
 
         2 + short + long + sep + meta + 2 // total + spaces before and after
     }
@@ -1350,6 +3975,16 @@ impl<'a> Opt<'a> {
     fn make_action(&self) -> TokenStream2 {
         use self::Action::*;
 
+        let assume_yes = if self.assume_yes {
+            quote!{ _parser.set_assume_yes(); }
+        } else {
+            quote!{ }
+        };
+
+        if self.synthetic {
+            return assume_yes;
+        }
+
         let field = self.field;
         let mark_used = self.mark_used();
 
@@ -1385,6 +4020,7 @@ impl<'a> Opt<'a> {
 
         quote!{
             #mark_used
+            #assume_yes
             #action
         }
     }
@@ -1426,49 +4062,102 @@ impl<'a> Opt<'a> {
         }
     }
 
-    fn usage(&self, col_width: usize) -> String {
+    fn usage(&self, col_width: usize, long: bool, type_opts: &DefaultOpts) -> String {
         let mut res = String::from("  ");
 
+        res.push_str(&styled(STYLE_OPTION, &self.names()));
+
+        if let Some(meta) = &self.meta {
+            res.push(' ');
+            res.push_str(&styled(STYLE_META, meta));
+        }
+
+        let help = if long { self.long_help.as_ref().or(self.help.as_ref()) }
+            else { self.help.as_ref() };
+
+        let notes = self.notes(type_opts);
+
+        if help.is_some() || !notes.is_empty() {
+            push_help_padding(&mut res, col_width, type_opts.next_line_help);
+        }
+
+        if let Some(help) = help {
+            push_help_text(&mut res, help, col_width);
+        }
+
+        push_help_notes(&mut res, help.is_some(), &notes);
+
+        res
+    }
+
+    /// Returns the option names displayed in usage text, e.g. `-f, --foo`
+    fn names(&self) -> String {
+        let mut names = String::new();
+
         if let Some(short) = self.short {
-            res.push('-');
-            res.push(short);
+            names.push('-');
+            names.push(short);
         }
 
         if self.short.is_some() && self.long.is_some() {
-            res.push_str(", ");
+            names.push_str(", ");
         }
 
         if let Some(long) = &self.long {
-            res.push_str("--");
-            res.push_str(long);
+            names.push_str("--");
+            names.push_str(long);
         }
 
-        if let Some(meta) = &self.meta {
-            res.push(' ');
-            res.push_str(meta);
-        }
+        names
+    }
 
-        if self.help.is_some() || self.default.is_some() {
-            if res.len() < col_width {
-                let n = col_width - res.len();
-                res.extend(repeat(' ').take(n));
-            } else {
-                res.push('\n');
-                res.extend(repeat(' ').take(col_width));
-            }
+    /// Returns notes displayed following help text, e.g. `(default: 1)`
+    fn notes(&self, type_opts: &DefaultOpts) -> Vec<String> {
+        let mut notes = Vec::new();
+
+        if let Some(marker) = type_opts.required_marker().filter(|_| self.required) {
+            notes.push(marker.to_owned());
         }
 
-        if let Some(help) = &self.help {
-            res.push_str(help);
+        if let Some(env) = &self.env {
+            notes.push(format!("(env: {})", env));
         }
 
         if let Some(default) = &self.default {
-            res.push_str(" (default: ");
-            res.push_str(default);
-            res.push_str(")");
+            notes.push(type_opts.default_format.as_deref()
+                .unwrap_or("(default: {})").replace("{}", default));
         }
 
-        res
+        if !self.possible_values.is_empty() {
+            notes.push(format!("[possible values: {}]", self.possible_values.join(", ")));
+        }
+
+        if !self.conflicts_with.is_empty() {
+            notes.push(format!("[conflicts with {}]", self.conflicts_with.join(", ")));
+        }
+
+        if !self.requires.is_empty() {
+            notes.push(format!("[requires {}]", self.requires.join(", ")));
+        }
+
+        notes
+    }
+
+    /// Returns an expression of type `gumdrop::UsageRow` describing the option
+    fn row_tokens(&self, type_opts: &DefaultOpts) -> TokenStream2 {
+        let name = self.names();
+        let meta = option_str(&self.meta);
+        let help = help_tokens(self.help.as_ref());
+        let notes = self.notes(type_opts);
+
+        quote!{
+            ::gumdrop::UsageRow{
+                name: #name,
+                meta: #meta,
+                help: #help,
+                notes: &[ #( #notes ),* ],
+            }
+        }
     }
 }
 
@@ -1480,9 +4169,9 @@ impl ParseFn {
                     Some(ident) => match ident.to_string().as_str() {
                         "from_str" => ParseFn::FromStr(None),
                         "try_from_str" => ParseFn::Default,
-                        _ => return Err(unexpected_meta_item(ident.span()))
+                        _ => return Err(unknown_item(path, path, PARSE_ITEMS))
                     }
-                    None => return Err(unexpected_meta_item(path.span()))
+                    None => return Err(unknown_item(path, path, PARSE_ITEMS))
                 }
             }
             NestedMeta::Meta(Meta::NameValue(nv)) => {
@@ -1496,9 +4185,17 @@ impl ParseFn {
                             let path = parse_str(&lit_str(&nv.lit)?)?;
                             ParseFn::TryFromStr(path)
                         }
-                        _ => return Err(unexpected_meta_item(nv.path.span()))
+                        "with_parser" => {
+                            let path = parse_str(&lit_str(&nv.lit)?)?;
+                            ParseFn::WithParser(path)
+                        }
+                        "contextual" => {
+                            let path = parse_str(&lit_str(&nv.lit)?)?;
+                            ParseFn::Contextual(path)
+                        }
+                        _ => return Err(unknown_item(nv, &nv.path, PARSE_ITEMS))
                     }
-                    None => return Err(unexpected_meta_item(nv.path.span()))
+                    None => return Err(unknown_item(nv, &nv.path, PARSE_ITEMS))
                 }
             }
             NestedMeta::Lit(_) |
@@ -1509,18 +4206,41 @@ impl ParseFn {
         Ok(result)
     }
 
-    fn make_parse_action(&self, name: Option<&str>) -> TokenStream2 {
-        let name = if let Some(name) = name {
-            quote!{ ::std::string::ToString::to_string(#name) }
-        } else {
-            quote!{ ::gumdrop::Opt::to_string(&_opt) }
-        };
+    fn make_parse_action(&self, generic_error: bool) -> TokenStream2 {
+        let source_traits = source_traits(generic_error);
+
+        self.make_parse_action_map_err(quote!{
+            |e| {
+                #source_traits
+                ::gumdrop::Error::failed_parse_with_source(
+                    ::gumdrop::Opt::to_string(&_opt),
+                    (&::gumdrop::ParseErrorSource::new(e)).take_source())
+            }
+        })
+    }
+
+    /// Returns an expression parsing `_arg` for the `free` field `name`,
+    /// reporting `value` as the free argument numbered `_free_ordinal`
+    /// in case of error
+    fn make_parse_free_action(&self, name: &str, value: TokenStream2,
+            generic_error: bool) -> TokenStream2 {
+        let source_traits = source_traits(generic_error);
+
+        self.make_parse_action_map_err(quote!{
+            |e| {
+                #source_traits
+                ::gumdrop::Error::failed_parse_free(#name, _free_ordinal, #value,
+                    (&::gumdrop::ParseErrorSource::new(e)).take_source())
+            }
+        })
+    }
 
-        let res = match self {
+    /// Returns an expression parsing `_arg`, which uses the given function
+    /// to convert a parse error into `gumdrop::Error`
+    fn make_parse_action_map_err(&self, map_err: TokenStream2) -> TokenStream2 {
+        match self {
             ParseFn::Default => quote!{
-                ::std::str::FromStr::from_str(_arg)
-                    .map_err(|e| ::gumdrop::Error::failed_parse_with_name(
-                        #name, ::std::string::ToString::to_string(&e)))?
+                ::std::str::FromStr::from_str(_arg).map_err(#map_err)?
             },
             ParseFn::FromStr(None) => quote!{
                 ::std::convert::From::from(_arg)
@@ -1529,17 +4249,18 @@ impl ParseFn {
                 #fun(_arg)
             },
             ParseFn::TryFromStr(fun) => quote!{
-                #fun(_arg)
-                    .map_err(|e| ::gumdrop::Error::failed_parse_with_name(
-                        #name, ::std::string::ToString::to_string(&e)))?
-            }
-        };
-
-        res
+                #fun(_arg).map_err(#map_err)?
+            },
+            ParseFn::Contextual(fun) => quote!{
+                #fun(_arg, &_result).map_err(#map_err)?
+            },
+            // Options using `with_parser` do not parse a single argument
+            ParseFn::WithParser(_) => unreachable!()
+        }
     }
 
     fn make_parse_default_action(&self, ident: &Ident, expr: &str) -> TokenStream2 {
-        let res = match self {
+        match self {
             ParseFn::Default => quote!{
                 ::std::str::FromStr::from_str(#expr)
                     .map_err(|e| ::gumdrop::Error::failed_parse_default(
@@ -1557,27 +4278,51 @@ impl ParseFn {
                     .map_err(|e| ::gumdrop::Error::failed_parse_default(
                         stringify!(#ident), #expr,
                         ::std::string::ToString::to_string(&e)))?
-            }
-        };
-
-        res
+            },
+            // Defaults are parsed before any options value exists
+            ParseFn::WithParser(_) | ParseFn::Contextual(_) => unreachable!()
+        }
     }
 }
 
-impl Default for ParseFn {
-    fn default() -> ParseFn {
-        ParseFn::Default
+impl ParseMethod {
+    /// Returns an expression parsing `_arg`; if the option lists its
+    /// possible values, a failure to parse produces an error listing them
+    fn make_parse(&self) -> TokenStream2 {
+        if self.possible_values.is_empty() {
+            self.parse_fn.make_parse_action(self.generic_error)
+        } else {
+            let values = &self.possible_values;
+
+            let parse = self.parse_fn.make_parse_action_map_err(quote!{
+                |_| ::gumdrop::Error::invalid_value(
+                    ::gumdrop::Opt::to_string(&_opt), _arg, &[ #( #values ),* ])
+            });
+
+            if self.ignore_case {
+                quote!{ {
+                    let _arg: &str = [ #( #values ),* ].iter().copied()
+                        .find(|value| value.eq_ignore_ascii_case(_arg))
+                        .unwrap_or(_arg);
+
+                    #parse
+                } }
+            } else {
+                parse
+            }
+        }
     }
-}
 
-impl ParseMethod {
     fn make_action_type(&self) -> TokenStream2 {
-        let parse = self.parse_fn.make_parse_action(None);
+        if let ParseFn::WithParser(fun) = &self.parse_fn {
+            return quote!{ #fun(_opt, _parser)? };
+        }
+
+        let parse = self.make_parse();
 
         match self.tuple_len {
             None => quote!{ {
-                let _arg = _parser.next_arg()
-                    .ok_or_else(|| ::gumdrop::Error::missing_argument(_opt))?;
+                let _arg = _parser.next_value(_opt)?;
 
                 #parse
             } },
@@ -1588,10 +4333,7 @@ impl ParseMethod {
 
                 quote!{
                     ( #( {
-                        let _found = #num;
-                        let _arg = _parser.next_arg()
-                            .ok_or_else(|| ::gumdrop::Error::insufficient_arguments(
-                                _opt, #n, _found))?;
+                        let _arg = _parser.next_tuple_value(_opt, #n, #num)?;
 
                         #parse
                     } , )* )
@@ -1601,48 +4343,701 @@ impl ParseMethod {
     }
 
     fn make_action_type_arg(&self) -> TokenStream2 {
+        if let ParseFn::WithParser(fun) = &self.parse_fn {
+            return quote!{ #fun(_opt, _parser)? };
+        }
+
+        let parse = self.make_parse();
+
         match self.tuple_len {
-            None => self.parse_fn.make_parse_action(None),
+            None => quote!{ {
+                _parser.notify(::gumdrop::ParseEvent::Value(_arg));
+
+                #parse
+            } },
             Some(_) => unreachable!()
         }
     }
-    fn takes_arg(&self) -> bool {
-        match self.tuple_len {
-            Some(0) => false,
-            _ => true
-        }
+    fn takes_arg(&self) -> bool {
+        !matches!(self.tuple_len, Some(0))
+    }
+}
+
+/// Returns whether a type is `OsString` or `PathBuf`, which may be
+/// constructed from arguments which are not valid UTF-8
+fn is_os_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => {
+            let ident = &path.path.segments.last().unwrap().ident;
+            ident == "OsString" || ident == "PathBuf"
+        }
+        _ => false
+    }
+}
+
+/// Sorts `free` fields by `index`, if given, and checks that required fields
+/// precede optional ones and that only one field collects remaining arguments
+fn sort_free(free: &mut [FreeOpt]) -> Result<(), Error> {
+    if free.iter().any(|opt| opt.index.is_some()) {
+        if let Some(opt) = free.iter().find(|opt| opt.index.is_none()) {
+            return Err(Error::new(opt.field.span(),
+                "`index` must be given for all `free` fields if given for any"));
+        }
+
+        free.sort_by_key(|opt| opt.index);
+
+        for pair in free.windows(2) {
+            if pair[0].index == pair[1].index {
+                return Err(Error::new(pair[1].field.span(),
+                    "duplicate `index` of `free` field"));
+            }
+        }
+    }
+
+    let mut variadic = free.iter().enumerate().filter(|(_, opt)| opt.action.is_push());
+    let first_variadic = variadic.clone().next().map(|(i, _)| i);
+
+    // Fields following a `Vec<T>` field are filled before it, so only those
+    // up to and including it must be ordered.
+    let leading = match first_variadic {
+        Some(i) => &free[..=i],
+        None => &free[..]
+    };
+
+    for pair in leading.windows(2) {
+        if pair[1].required && !pair[0].required {
+            return Err(Error::new(pair[1].field.span(),
+                format!("required `free` field `{}` may not follow \
+                    optional `free` field `{}`; an argument would be \
+                    given to `{}` first", pair[1].field, pair[0].field,
+                    pair[0].field)));
+        }
+    }
+
+    if let Some((i, _)) = variadic.next() {
+        if let Some((_, opt)) = variadic.next() {
+            return Err(Error::new(opt.field.span(),
+                "only one `free` option may be of type `Vec<T>`"));
+        }
+
+        if i + 1 < free.len() {
+            if let Some(opt) = free[i..].iter()
+                    .find(|opt| opt.arity.is_some() || opt.action.is_insert()) {
+                return Err(Error::new(opt.field.span(),
+                    "`free` options of tuple, array, or map type may not be used \
+                     with a `free` option of type `Vec<T>` which is not final"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns code assigning arguments collected in `_free_rest` to a `free`
+/// field of type `Vec<T>` and the `free` fields which follow it, giving
+/// one argument to each of the following fields and the remainder to the
+/// `Vec<T>` field
+fn make_free_rest_action(rest: &FreeOpt, trailing: &[FreeOpt]) -> TokenStream2 {
+    let field = rest.field;
+    let meth = match &rest.action {
+        FreeAction::Push(meth) => meth,
+        _ => unreachable!()
+    };
+    let value = rest.make_parse_value(&field.to_string());
+    let mark_used = rest.mark_used();
+
+    let index = 0..trailing.len();
+    let assign = trailing.iter().map(|opt| opt.make_assign());
+
+    // Fields given as options are skipped
+    let open = trailing.iter().map(|opt| {
+        let field = opt.field;
+
+        if opt.named {
+            quote!{ !_named.#field }
+        } else {
+            quote!{ true }
+        }
+    });
+
+    quote!{
+        let _free_open = [ #( #open ),* ];
+        let _free_split = _free_rest.len().saturating_sub(
+            _free_open.iter().filter(|&&open| open).count());
+
+        for (_i, &(_free, _free_pos, _free_ordinal)) in _free_rest.iter().enumerate() {
+            #[allow(clippy::redundant_closure_call)]
+            let _step = (|| -> ::std::result::Result<(), ::gumdrop::Error> {
+                if _i < _free_split {
+                    #mark_used
+                    _result.#field.#meth(#value);
+                } else {
+                    let _slot = _free_open.iter().enumerate()
+                        .filter(|&(_, &open)| open)
+                        .nth(_i - _free_split);
+
+                    match _slot {
+                        #( ::std::option::Option::Some((#index, _)) => { #assign } )*
+                        _ => ()
+                    }
+                }
+
+                ::std::result::Result::Ok(())
+            })();
+
+            if let ::std::result::Result::Err(e) = _step {
+                _parser.report_error(e)?;
+            }
+        }
+    }
+}
+
+fn first_ty_param(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Path(path) => {
+            let path = path.path.segments.last().unwrap();
+
+            match &path.arguments {
+                PathArguments::AngleBracketed(data) =>
+                    data.args.iter().filter_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None
+                    }).next(),
+                _ => None
+            }
+        }
+        _ => None
+    }
+}
+
+fn is_outer(style: AttrStyle) -> bool {
+    matches!(style, AttrStyle::Outer)
+}
+
+// Help text given by a macro, e.g. `help = include_str!("help.txt")`, is
+// represented in usage text by its source surrounded by these markers.
+// Such usage text is emitted as an invocation of `concat!`.
+
+/// Begins the source of a help text macro
+const EXPR_START: char = '\u{3}';
+/// Ends the source of a help text macro
+const EXPR_END: char = '\u{4}';
+
+/// Parses a `help` value given as an expression, returning a placeholder for
+/// use in usage text
+fn help_expr(expr: Expr) -> Result<String, Error> {
+    match expr {
+        Expr::Macro(expr) => {
+            let mac = expr.mac;
+            Ok(format!("{}{}{}", EXPR_START, quote!{ #mac }, EXPR_END))
+        }
+        expr => Err(Error::new_spanned(expr,
+            "`help` must be a string or a macro which expands to a string literal"))
+    }
+}
+
+/// Returns an expression of type `&'static str` for the given usage text,
+/// expanding any placeholders of help text given by a macro
+fn usage_tokens(usage: &str) -> TokenStream2 {
+    if cfg!(feature = "strip_help") {
+        return quote!{ "" };
+    }
+
+    if !usage.contains(EXPR_START) {
+        return quote!{ #usage };
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = usage;
+
+    while let Some(start) = rest.find(EXPR_START) {
+        let lit = &rest[..start];
+        rest = &rest[start + EXPR_START.len_utf8()..];
+
+        let end = rest.find(EXPR_END).unwrap_or(rest.len());
+        let mac: Macro = parse_str(&rest[..end])
+            .expect("invalid help macro placeholder");
+        rest = rest.get(end + EXPR_END.len_utf8()..).unwrap_or("");
+
+        if !lit.is_empty() {
+            parts.push(quote!{ #lit });
+        }
+        parts.push(quote!{ #mac });
+    }
+
+    if !rest.is_empty() {
+        parts.push(quote!{ #rest });
+    }
+
+    quote!{ ::std::concat!( #( #parts ),* ) }
+}
+
+/// Returns an expression of type `Option<&'static str>` for help text,
+/// expanding any placeholders of help text given by a macro
+fn help_tokens<S: AsRef<str>>(help: Option<S>) -> TokenStream2 {
+    if cfg!(feature = "strip_help") {
+        return quote!{ ::std::option::Option::None };
+    }
+
+    match help {
+        Some(help) => {
+            let help = usage_tokens(&strip_markup(help.as_ref()));
+            quote!{ ::std::option::Option::Some(#help) }
+        }
+        None => quote!{ ::std::option::Option::None }
+    }
+}
+
+/// Returns an expression of type `Option<&'static str>`
+fn option_str(s: &Option<String>) -> TokenStream2 {
+    match s {
+        Some(s) => quote!{ ::std::option::Option::Some(#s) },
+        None => quote!{ ::std::option::Option::None }
+    }
+}
+
+fn lit_str(lit: &Lit) -> Result<String, Error> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(Error::new(lit.span(), "expected string literal"))
+    }
+}
+
+fn lit_int<N>(lit: &Lit) -> Result<N, Error>
+        where N: std::str::FromStr, N::Err: std::fmt::Display {
+    match lit {
+        Lit::Int(i) => i.base10_parse(),
+        _ => Err(Error::new(lit.span(), "expected integer literal"))
+    }
+}
+
+fn error_usage(lit: &Lit) -> Result<Ident, Error> {
+    match &lit_str(lit)?[..] {
+        "line" => Ok(Ident::new("Line", Span::call_site())),
+        "full" => Ok(Ident::new("Full", Span::call_site())),
+        _ => Err(Error::new(lit.span(), "expected `\"line\"` or `\"full\"`"))
+    }
+}
+
+fn error_usage_impl(usage: &Option<Ident>) -> TokenStream2 {
+    match usage {
+        Some(variant) => quote!{
+            fn error_usage() -> ::gumdrop::ErrorUsage {
+                ::gumdrop::ErrorUsage::#variant
+            }
+        },
+        None => quote!{ }
+    }
+}
+
+/// Returns an implementation of `gumdrop::ToArgs` for a `struct` type
+fn struct_to_args_impl(ast: &DeriveInput, options: &[Opt], free: &[FreeOpt],
+        command: Option<&Ident>) -> TokenStream2 {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    // Named `free` fields are written as positional arguments
+    let push_opts = options.iter()
+            .filter(|opt| opt.named_free.is_none() && !opt.synthetic).map(|opt| {
+        let field = opt.field;
+        let flag = match (&opt.long, opt.short) {
+            (Some(long), _) => format!("--{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => unreachable!("option has no long or short flags"),
+        };
+
+        let push_value = |value: TokenStream2, tuple_len: Option<usize>| {
+            let values = match tuple_len {
+                None => vec![value],
+                Some(n) => (0..n).map(|i| {
+                    let i = syn::Index::from(i);
+                    quote!{ &(#value).#i }
+                }).collect(),
+            };
+
+            quote!{
+                _args.push(::std::string::ToString::to_string(#flag));
+                #( _args.push(::std::string::ToString::to_string(#values)); )*
+            }
+        };
+
+        match &opt.action {
+            Action::Count => quote!{
+                for _ in 0..self.#field {
+                    _args.push(::std::string::ToString::to_string(#flag));
+                }
+            },
+            Action::Switch => quote!{
+                if self.#field {
+                    _args.push(::std::string::ToString::to_string(#flag));
+                }
+            },
+            Action::Push(_, parse) => {
+                let push = push_value(quote!{ _value }, parse.tuple_len);
+
+                quote!{
+                    for _value in &self.#field {
+                        #push
+                    }
+                }
+            }
+            Action::SetField(parse) => push_value(quote!{ &self.#field }, parse.tuple_len),
+            Action::SetOption(parse) => {
+                let push = push_value(quote!{ _value }, parse.tuple_len);
+
+                quote!{
+                    if let ::std::option::Option::Some(_value) = &self.#field {
+                        #push
+                    }
+                }
+            }
+        }
+    });
+
+    let push_free = free.iter().map(|opt| {
+        let field = opt.field;
+        let push_value = match opt.arity {
+            None => quote!{
+                _free.push(::std::string::ToString::to_string(_value));
+            },
+            Some(FreeArity::Tuple(n)) => {
+                let index = (0..n).map(syn::Index::from);
+
+                quote!{
+                    #( _free.push(::std::string::ToString::to_string(&_value.#index)); )*
+                }
+            }
+            Some(FreeArity::Array(_)) => quote!{
+                for _elem in _value {
+                    _free.push(::std::string::ToString::to_string(_elem));
+                }
+            },
+        };
+
+        match &opt.action {
+            FreeAction::Push(_) => quote!{
+                for _value in &self.#field {
+                    #push_value
+                }
+            },
+            FreeAction::Insert => quote!{
+                for (_key, _value) in &self.#field {
+                    _free.push(::std::format!("{}={}", _key, _value));
+                }
+            },
+            FreeAction::SetField => quote!{
+                let _value = &self.#field;
+                #push_value
+            },
+            // A later free field cannot be given unless this one is
+            FreeAction::SetOption => quote!{
+                if let ::std::option::Option::Some(_value) = &self.#field {
+                    #push_value
+                }
+            },
+        }
+    });
+
+    let push_command = command.map(|field| quote!{
+        if let ::std::option::Option::Some(_cmd) = &self.#field {
+            _args.extend(::gumdrop::ToArgs::to_args(_cmd));
+        }
+    });
+
+    // Arguments following `--` could not name a command
+    let push_terminator = if command.is_some() {
+        quote!{ }
+    } else {
+        quote!{ _args.push(::std::string::ToString::to_string("--")); }
+    };
+
+    let to_map_impl = struct_to_map_impl(options);
+
+    quote!{
+        impl #impl_generics ::gumdrop::ToArgs for #name #ty_generics #where_clause {
+            fn to_args(&self) -> ::std::vec::Vec<::std::string::String> {
+                let mut _args = ::std::vec::Vec::new();
+                #[allow(unused_mut)]
+                let mut _free: ::std::vec::Vec<::std::string::String> = ::std::vec::Vec::new();
+
+                #( #push_opts )*
+                #( #push_free )*
+
+                if !_free.is_empty() {
+                    #push_terminator
+                    _args.extend(_free);
+                }
+
+                #push_command
+
+                _args
+            }
+
+            #to_map_impl
+        }
+    }
+}
+
+/// Returns an implementation of `ToArgs::to_map` for a `struct` type
+fn struct_to_map_impl(options: &[Opt]) -> TokenStream2 {
+    let insert = options.iter().filter(|opt| !opt.synthetic).filter_map(|opt| {
+        let field = opt.field;
+        let long = opt.long.as_ref()?;
+
+        let make_value = |value: TokenStream2, tuple_len: Option<usize>| {
+            match tuple_len {
+                None => quote!{
+                    ::gumdrop::Value::String(::std::string::ToString::to_string(#value))
+                },
+                Some(n) => {
+                    let i = (0..n).map(syn::Index::from);
+
+                    quote!{
+                        ::gumdrop::Value::Tuple(::std::vec![
+                            #( ::std::string::ToString::to_string(&(#value).#i), )*
+                        ])
+                    }
+                }
+            }
+        };
+
+        Some(match &opt.action {
+            Action::Count => quote!{
+                _map.insert(::std::string::ToString::to_string(#long),
+                    ::gumdrop::Value::Count(self.#field as usize));
+            },
+            Action::Switch => quote!{
+                _map.insert(::std::string::ToString::to_string(#long),
+                    ::gumdrop::Value::Bool(self.#field));
+            },
+            Action::Push(_, parse) => {
+                let value = make_value(quote!{ _value }, parse.tuple_len);
+
+                quote!{
+                    _map.insert(::std::string::ToString::to_string(#long),
+                        ::gumdrop::Value::List(self.#field.iter()
+                            .map(|_value| #value).collect()));
+                }
+            }
+            Action::SetField(parse) => {
+                let value = make_value(quote!{ &self.#field }, parse.tuple_len);
+
+                quote!{
+                    _map.insert(::std::string::ToString::to_string(#long), #value);
+                }
+            }
+            Action::SetOption(parse) => {
+                let value = make_value(quote!{ _value }, parse.tuple_len);
+
+                quote!{
+                    if let ::std::option::Option::Some(_value) = &self.#field {
+                        _map.insert(::std::string::ToString::to_string(#long), #value);
+                    }
+                }
+            }
+        })
+    });
+
+    quote!{
+        fn to_map(&self) -> ::std::collections::BTreeMap<
+                ::std::string::String, ::gumdrop::Value> {
+            #[allow(unused_mut)]
+            let mut _map = ::std::collections::BTreeMap::new();
+            #( #insert )*
+            _map
+        }
+    }
+}
+
+/// Returns an implementation of `gumdrop::ToArgs` for an `enum` type
+fn enum_to_args_impl(ast: &DeriveInput, commands: &[Cmd], catch_all: Option<&Ident>)
+        -> TokenStream2 {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let variant = commands.iter().map(|cmd| cmd.variant_name);
+    let cmd_name = commands.iter().map(|cmd| &cmd.name);
+
+    let other_arm = match catch_all {
+        Some(var_name) => quote!{
+            #name::#var_name(_name, _rest) => {
+                let mut _args = ::std::vec![::std::clone::Clone::clone(_name)];
+                _args.extend(::std::iter::Iterator::cloned(_rest.iter()));
+                _args
+            }
+        },
+        None => quote!{ }
+    };
+
+    quote!{
+        impl #impl_generics ::gumdrop::ToArgs for #name #ty_generics #where_clause {
+            fn to_args(&self) -> ::std::vec::Vec<::std::string::String> {
+                match self {
+                    #( #name::#variant(_cmd) => {
+                        let mut _args = ::std::vec![::std::string::ToString::to_string(#cmd_name)];
+                        _args.extend(::gumdrop::ToArgs::to_args(_cmd));
+                        _args
+                    } )*
+                    #other_arm
+                }
+            }
+
+            // Commands are not included in the map
+            fn to_map(&self) -> ::std::collections::BTreeMap<
+                    ::std::string::String, ::gumdrop::Value> {
+                ::std::collections::BTreeMap::new()
+            }
+        }
+    }
+}
+
+/// Maximum count generated for a `count` field by `arbitrary`
+const ARBITRARY_MAX_COUNT: u8 = 8;
+
+/// Returns an implementation of `arbitrary::Arbitrary` for a `struct` type
+fn struct_arbitrary_impl(ast: &DeriveInput, fields: &Fields, options: &[Opt])
+        -> Result<TokenStream2, Error> {
+    let name = &ast.ident;
+
+    if ast.generics.params.iter().next().is_some() {
+        return Err(Error::new(name.span(),
+            "`arbitrary` is not supported for generic types"));
+    }
+
+    let values = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let is_count = options.iter()
+            .any(|opt| opt.field == ident && matches!(opt.action, Action::Count));
+
+        if is_count {
+            quote!{ #ident: _u.int_in_range(0..=#ARBITRARY_MAX_COUNT)?.into() }
+        } else {
+            quote!{ #ident: _u.arbitrary()? }
+        }
+    });
+
+    Ok(quote!{
+        impl<'__a> ::gumdrop::arbitrary::Arbitrary<'__a> for #name {
+            fn arbitrary(_u: &mut ::gumdrop::arbitrary::Unstructured<'__a>)
+                    -> ::gumdrop::arbitrary::Result<Self> {
+                ::std::result::Result::Ok(#name{
+                    #( #values , )*
+                })
+            }
+        }
+    })
+}
+
+/// Returns an implementation of `arbitrary::Arbitrary` for an `enum` type
+fn enum_arbitrary_impl(ast: &DeriveInput, commands: &[Cmd]) -> Result<TokenStream2, Error> {
+    let name = &ast.ident;
+
+    if ast.generics.params.iter().next().is_some() {
+        return Err(Error::new(name.span(),
+            "`arbitrary` is not supported for generic types"));
+    }
+
+    let n = commands.len();
+    let index = 0..n;
+    let variant = commands.iter().map(|cmd| cmd.variant_name);
+
+    Ok(quote!{
+        impl<'__a> ::gumdrop::arbitrary::Arbitrary<'__a> for #name {
+            fn arbitrary(_u: &mut ::gumdrop::arbitrary::Unstructured<'__a>)
+                    -> ::gumdrop::arbitrary::Result<Self> {
+                ::std::result::Result::Ok(match _u.choose_index(#n)? {
+                    #( #index => #name::#variant(_u.arbitrary()?), )*
+                    _ => ::std::unreachable!(),
+                })
+            }
+        }
+    })
+}
+
+/// Returns an import of the traits used to convert parse errors into
+/// error sources, using `gumdrop::ParseErrorSource`.
+///
+/// Selecting between the traits by autoref requires that the error type be
+/// known. When the error type may involve type parameters, only its `Display`
+/// implementation is used.
+fn source_traits(generic_error: bool) -> TokenStream2 {
+    if generic_error {
+        quote!{
+            #[allow(unused_imports)]
+            use ::gumdrop::SourceFromDisplay as _;
+        }
+    } else {
+        quote!{
+            #[allow(unused_imports)]
+            use ::gumdrop::{SourceFromDisplay as _, SourceFromError as _};
+        }
+    }
+}
+
+/// Returns whether the error type of parsing a field may involve type
+/// parameters of the options type, i.e. whether the field type or its
+/// parse function names one, or the function receives the options value
+fn parse_error_is_generic(ty: &Type, opts: &AttrOpts, generics: &Generics) -> bool {
+    let params = generics.type_params().map(|p| &p.ident).collect::<Vec<_>>();
+
+    if params.is_empty() {
+        return false;
+    }
+
+    let mut tokens = ty.to_token_stream();
+
+    match &opts.parse {
+        Some(ParseFn::Contextual(_)) => return true,
+        Some(ParseFn::FromStr(Some(path))) |
+        Some(ParseFn::TryFromStr(path)) |
+        Some(ParseFn::WithParser(path)) => path.to_tokens(&mut tokens),
+        _ => ()
     }
+
+    names_ident(tokens, &params)
 }
 
-fn first_ty_param(ty: &Type) -> Option<&Type> {
-    match ty {
-        Type::Path(path) => {
-            let path = path.path.segments.last().unwrap();
+fn names_ident(tokens: TokenStream2, idents: &[&Ident]) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => idents.iter().any(|&id| *id == ident),
+        TokenTree::Group(group) => names_ident(group.stream(), idents),
+        _ => false
+    })
+}
 
-            match &path.arguments {
-                PathArguments::AngleBracketed(data) =>
-                    data.args.iter().filter_map(|arg| match arg {
-                        GenericArgument::Type(ty) => Some(ty),
-                        _ => None
-                    }).next(),
-                _ => None
+fn localize_impl(lookup: &Option<Path>) -> TokenStream2 {
+    match lookup {
+        Some(lookup) => quote!{
+            fn localize(text: &str) -> ::std::option::Option<::std::string::String> {
+                #lookup(text)
             }
-        }
-        _ => None
+        },
+        None => quote!{ }
     }
 }
 
-fn is_outer(style: AttrStyle) -> bool {
-    match style {
-        AttrStyle::Outer => true,
-        _ => false
+fn program_name_impl(name: &Option<String>) -> TokenStream2 {
+    match name {
+        Some(name) => quote!{
+            fn program_name() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#name)
+            }
+        },
+        None => quote!{ }
     }
 }
 
-fn lit_str(lit: &Lit) -> Result<String, Error> {
+fn sort_order(lit: &Lit) -> Result<SortOrder, Error> {
+    match &lit_str(lit)?[..] {
+        "alpha" => Ok(SortOrder::Alpha),
+        "declaration" => Ok(SortOrder::Declaration),
+        _ => Err(Error::new(lit.span(), "expected `\"alpha\"` or `\"declaration\"`"))
+    }
+}
+
+fn lit_bool(lit: &Lit) -> Result<bool, Error> {
     match lit {
-        Lit::Str(s) => Ok(s.value()),
-        _ => Err(Error::new(lit.span(), "expected string literal"))
+        Lit::Bool(b) => Ok(b.value),
+        _ => Err(Error::new(lit.span(), "expected boolean literal"))
     }
 }
 
@@ -1685,7 +5080,20 @@ fn tuple_len(ty: &Type) -> Option<usize> {
     }
 }
 
-fn make_command_name(name: &str) -> String {
+fn make_command_name(name: &str, rule: RenameRule) -> String {
+    let mut words = Vec::<String>::new();
+
+    for ch in name.chars() {
+        match words.last_mut() {
+            Some(word) if ch.is_lowercase() => word.push(ch),
+            _ => words.push(ch.to_lowercase().collect())
+        }
+    }
+
+    rule.rename(name, &words)
+}
+
+fn make_method_name(name: &str) -> Ident {
     let mut res = String::with_capacity(name.len());
 
     for ch in name.chars() {
@@ -1693,18 +5101,22 @@ fn make_command_name(name: &str) -> String {
             res.push(ch);
         } else {
             if !res.is_empty() {
-                res.push('-');
+                res.push('_');
             }
 
             res.extend(ch.to_lowercase());
         }
     }
 
-    res
+    // Names such as `type` or `move` must be given as raw identifiers
+    parse_str::<Ident>(&res)
+        .unwrap_or_else(|_| Ident::new_raw(&res, Span::call_site()))
 }
 
-fn make_long_name(name: &str) -> String {
-    name.replace('_', "-")
+fn make_long_name(name: &str, rule: RenameRule) -> String {
+    let words = name.split('_').map(str::to_owned).collect::<Vec<_>>();
+
+    rule.rename(name, &words)
 }
 
 fn make_short_name(name: &str, short: &[char]) -> Option<char> {
@@ -1751,6 +5163,47 @@ fn validate_short_name(span: Span, ch: char, names: &[char])
     }
 }
 
+/// Joins lines of doc comment text into paragraphs, separated by newlines
+fn paragraphs(doc: &str) -> String {
+    let mut res = String::new();
+    let mut new_para = false;
+
+    for line in doc.lines().map(str::trim) {
+        if line.is_empty() {
+            new_para = !res.is_empty();
+        } else {
+            if new_para {
+                res.push('\n');
+                new_para = false;
+            } else if !res.is_empty() {
+                res.push(' ');
+            }
+
+            res.push_str(line);
+        }
+    }
+
+    res
+}
+
+/// Appends a line of doc comment text to `text`, removing only the
+/// single space which conventionally follows `///`
+fn push_doc_line(text: &mut Option<String>, doc: &str) {
+    let doc = doc.strip_prefix(' ').unwrap_or(doc).trim_end();
+
+    if let Some(text) = text.as_mut() {
+        text.push('\n');
+        text.push_str(doc);
+    } else {
+        *text = Some(doc.to_owned());
+    }
+}
+
+/// Removes leading whitespace from each line of doc comment text
+fn trim_doc(doc: &str) -> String {
+    doc.lines().map(str::trim_start).collect::<Vec<_>>().join("\n")
+}
+
 fn make_meta(name: &str, action: &Action) -> String {
     use std::fmt::Write;
 
@@ -1772,55 +5225,77 @@ fn make_meta(name: &str, action: &Action) -> String {
     name
 }
 
-fn make_usage(help: &Option<String>, free: &[FreeOpt], opts: &[Opt]) -> String {
+/// Returns usage text for a `struct`; if `long` is `true`, option help text
+/// is taken from `long_help`, where available.
+fn make_usage(type_opts: &DefaultOpts, free: &[FreeOpt], opts: &[Opt], long: bool) -> String {
     let mut res = String::new();
 
-    if let Some(help) = help {
+    if let Some(help) = type_opts.help.as_ref().or(type_opts.doc.as_ref()) {
         res.push_str(help);
         res.push('\n');
     }
 
-    let width = max_width(free, |opt| opt.width())
-        .max(max_width(opts, |opt| opt.width()));
+    let width = usage_width(type_opts, free, opts);
 
     if !free.is_empty() {
         if !res.is_empty() {
             res.push('\n');
         }
 
-        res.push_str("Positional arguments:\n");
+        res.push_str(&styled(STYLE_HEADING, positional_heading(type_opts)));
+        res.push('\n');
 
         for opt in free {
             let mut line = String::from("  ");
 
-            line.push_str(&opt.field.to_string());
+            line.push_str(&styled(STYLE_POSITIONAL, &opt.field.to_string()));
 
-            if let Some(help) = &opt.help {
-                if line.len() < width {
-                    let n = width - line.len();
-                    line.extend(repeat(' ').take(n));
-                } else {
-                    line.push('\n');
-                    line.extend(repeat(' ').take(width));
-                }
+            let help = if long { opt.long_help.as_ref().or(opt.help.as_ref()) }
+                else { opt.help.as_ref() };
+
+            let notes = type_opts.required_marker().filter(|_| opt.required)
+                .into_iter().collect::<Vec<_>>();
+
+            if help.is_some() || !notes.is_empty() {
+                push_help_padding(&mut line, width, type_opts.next_line_help);
+            }
 
-                line.push_str(help);
+            if let Some(help) = help {
+                push_help_text(&mut line, help, width);
             }
 
+            push_help_notes(&mut line, help.is_some(), &notes);
+
             res.push_str(&line);
             res.push('\n');
         }
     }
 
-    if !opts.is_empty() {
+    for (heading, group) in option_groups(type_opts, opts) {
+        if !res.is_empty() {
+            res.push('\n');
+        }
+
+        res.push_str(&styled(STYLE_HEADING, &heading));
+        res.push('\n');
+
+        for opt in group {
+            res.push_str(&opt.usage(width, long, type_opts));
+            res.push('\n');
+        }
+    }
+
+    if !type_opts.examples.is_empty() {
         if !res.is_empty() {
             res.push('\n');
         }
 
-        res.push_str("Optional arguments:\n");
+        res.push_str(&styled(STYLE_HEADING, examples_heading(type_opts)));
+        res.push('\n');
 
-        for opt in opts {
-            res.push_str(&opt.usage(width));
+        for example in &type_opts.examples {
+            res.push_str("  ");
+            res.push_str(example);
             res.push('\n');
         }
     }
@@ -1831,54 +5306,545 @@ fn make_usage(help: &Option<String>, free: &[FreeOpt], opts: &[Opt]) -> String {
     res
 }
 
-fn max_width<T, F>(items: &[T], f: F) -> usize
-        where F: Fn(&T) -> usize {
-    const MIN_WIDTH: usize = 8;
-    const MAX_WIDTH: usize = 30;
+/// Returns usage text listing the options marked `preview`,
+/// in the order in which they are listed by `make_usage`
+fn make_preview(type_opts: &DefaultOpts, opts: &[Opt]) -> String {
+    let preview = option_groups(type_opts, opts).into_iter()
+        .flat_map(|(_, group)| group)
+        .filter(|opt| opt.preview)
+        .collect::<Vec<_>>();
+
+    let width = if type_opts.next_line_help {
+        type_opts.column_width.min
+    } else {
+        max_width(&preview, type_opts.column_width, |opt| opt.width())
+    };
+
+    preview.iter()
+        .map(|opt| opt.usage(width, false, type_opts))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns an expression of type `gumdrop::UsageModel` describing
+/// the sections of usage text produced by `make_usage`
+fn make_usage_model(type_opts: &DefaultOpts, free: &[FreeOpt], opts: &[Opt]) -> TokenStream2 {
+    let description = help_tokens(type_opts.help.as_ref().or(type_opts.doc.as_ref()));
+    let width = usage_width(type_opts, free, opts);
+    let next_line_help = type_opts.next_line_help;
+    let mut sections = Vec::new();
+
+    if !free.is_empty() {
+        let heading = positional_heading(type_opts);
+        let rows = free.iter().map(|opt| {
+            let name = opt.field.to_string();
+            let help = help_tokens(opt.help.as_ref());
+            let notes = type_opts.required_marker().filter(|_| opt.required)
+                .into_iter().collect::<Vec<_>>();
+
+            quote!{
+                ::gumdrop::UsageRow{
+                    name: #name,
+                    meta: ::std::option::Option::None,
+                    help: #help,
+                    notes: &[ #( #notes ),* ],
+                }
+            }
+        });
+
+        sections.push(quote!{
+            ::gumdrop::UsageSection{
+                kind: ::gumdrop::SectionKind::Positional,
+                heading: #heading,
+                rows: &[ #( #rows ),* ],
+            }
+        });
+    }
+
+    for (heading, group) in option_groups(type_opts, opts) {
+        let rows = group.iter().map(|opt| opt.row_tokens(type_opts));
+
+        sections.push(quote!{
+            ::gumdrop::UsageSection{
+                kind: ::gumdrop::SectionKind::Optional,
+                heading: #heading,
+                rows: &[ #( #rows ),* ],
+            }
+        });
+    }
+
+    if !type_opts.examples.is_empty() && !cfg!(feature = "strip_help") {
+        let heading = examples_heading(type_opts);
+        let examples = &type_opts.examples;
+
+        sections.push(quote!{
+            ::gumdrop::UsageSection{
+                kind: ::gumdrop::SectionKind::Examples,
+                heading: #heading,
+                rows: &[ #( ::gumdrop::UsageRow{
+                    name: #examples,
+                    meta: ::std::option::Option::None,
+                    help: ::std::option::Option::None,
+                    notes: &[],
+                } ),* ],
+            }
+        });
+    }
+
+    quote!{
+        ::gumdrop::UsageModel{
+            description: #description,
+            help_column: #width,
+            next_line_help: #next_line_help,
+            sections: &[ #( #sections ),* ],
+        }
+    }
+}
+
+/// Returns the column at which help text begins in usage text
+fn usage_width(type_opts: &DefaultOpts, free: &[FreeOpt], opts: &[Opt]) -> usize {
+    if type_opts.next_line_help {
+        return type_opts.column_width.min;
+    }
+
+    max_width(free, type_opts.column_width, |opt| opt.width())
+        .max(max_width(opts, type_opts.column_width, |opt| opt.width()))
+}
+
+fn positional_heading(type_opts: &DefaultOpts) -> &str {
+    type_opts.positional_heading.as_deref().unwrap_or("Positional arguments:")
+}
+
+fn examples_heading(type_opts: &DefaultOpts) -> &str {
+    type_opts.examples_heading.as_deref().unwrap_or("Examples:")
+}
+
+/// Returns groups of options in the order displayed in usage text,
+/// each with the heading preceding the group
+fn option_groups<'o, 'a>(type_opts: &DefaultOpts, opts: &'o [Opt<'a>])
+        -> Vec<(String, Vec<&'o Opt<'a>>)> {
+    let mut sorted = opts.iter().collect::<Vec<_>>();
+
+    // Stable sort, so that equal options remain in declaration order.
+    // Options without a `display_order` follow all those with one.
+    sorted.sort_by(|a, b| {
+        let order = |opt: &Opt| (opt.display_order.is_none(), opt.display_order);
+
+        order(a).cmp(&order(b)).then_with(|| match type_opts.sort {
+            SortOrder::Declaration => std::cmp::Ordering::Equal,
+            SortOrder::Alpha => a.sort_name().cmp(&b.sort_name()),
+        })
+    });
+
+    // Options without a `heading` are listed first, followed by each
+    // group of options in order of the first appearance of its heading.
+    let mut groups: Vec<(Option<&str>, Vec<&Opt>)> = Vec::new();
+
+    for opt in sorted {
+        let heading = opt.heading.as_deref();
+
+        match groups.iter_mut().find(|(h, _)| *h == heading) {
+            Some((_, group)) => group.push(opt),
+            None if heading.is_none() => groups.insert(0, (None, vec![opt])),
+            None => groups.push((heading, vec![opt])),
+        }
+    }
+
+    groups.into_iter().map(|(heading, group)| {
+        let heading = match heading {
+            Some(h) if h.ends_with(':') => h.to_owned(),
+            Some(h) => format!("{}:", h),
+            None => type_opts.optional_heading.as_deref()
+                .unwrap_or("Optional arguments:").to_owned(),
+        };
 
+        (heading, group)
+    }).collect()
+}
+
+fn max_width<T, F>(items: &[T], bounds: ColumnWidth, f: F) -> usize
+        where F: Fn(&T) -> usize {
     let width = items.iter().filter_map(|item| {
         let w = f(item);
 
-        if w > MAX_WIDTH {
+        if w > bounds.max {
             None
         } else {
             Some(w)
         }
     }).max().unwrap_or(0);
 
-    width.max(MIN_WIDTH).min(MAX_WIDTH)
+    width.clamp(bounds.min, bounds.max)
 }
 
-fn make_cmd_usage(cmds: &[Cmd]) -> String {
-    let mut res = String::new();
+const HELP_COMMAND: (&str, &str) = ("help", "show help for a command");
 
-    let width = max_width(cmds,
+/// Returns the name and help text of each command listed in usage text
+fn cmd_names<'a>(cmds: &'a [Cmd], enum_opts: &EnumOpts) -> Vec<(&'a str, Option<&'a str>)> {
+    let mut names = cmds.iter()
+        .map(|cmd| (&cmd.name[..], cmd.help.as_ref().map(|s| &s[..])))
+        .collect::<Vec<_>>();
+
+    if enum_opts.help_command {
+        names.push((HELP_COMMAND.0, Some(HELP_COMMAND.1)));
+    }
+
+    if enum_opts.sort == SortOrder::Alpha {
+        names.sort_by_key(|&(name, _)| name);
+    }
+
+    // Pinned commands precede all others, in the order given by `pin`
+    names.sort_by_key(|&(name, _)| enum_opts.pin.iter()
+        .position(|pin| pin == name)
+        .unwrap_or(enum_opts.pin.len()));
+
+    names
+}
+
+fn cmd_usage_width(names: &[(&str, Option<&str>)], enum_opts: &EnumOpts) -> usize {
+    if enum_opts.next_line_help {
+        return enum_opts.column_width.min;
+    }
+
+    max_width(names, enum_opts.column_width,
         // Two spaces each, before and after
-        |cmd| cmd.name.len() + 4);
+        |&(name, _)| name.width() + 4)
+}
+
+/// Returns an expression of type `gumdrop::UsageModel` describing
+/// the command list produced by `make_cmd_usage`
+fn make_cmd_usage_model(cmds: &[Cmd], enum_opts: &EnumOpts) -> TokenStream2 {
+    let names = cmd_names(cmds, enum_opts);
+    let width = cmd_usage_width(&names, enum_opts);
+    let next_line_help = enum_opts.next_line_help;
+    let heading = enum_opts.command_heading.as_deref().unwrap_or("Available commands:");
+
+    let rows = names.iter().map(|&(name, help)| {
+        let help = help_tokens(help);
+
+        quote!{
+            ::gumdrop::UsageRow{
+                name: #name,
+                meta: ::std::option::Option::None,
+                help: #help,
+                notes: &[],
+            }
+        }
+    });
+
+    quote!{
+        ::gumdrop::UsageModel{
+            description: ::std::option::Option::None,
+            help_column: #width,
+            next_line_help: #next_line_help,
+            sections: &[ ::gumdrop::UsageSection{
+                kind: ::gumdrop::SectionKind::Commands,
+                heading: #heading,
+                rows: &[ #( #rows ),* ],
+            } ],
+        }
+    }
+}
 
-    for cmd in cmds {
+fn make_cmd_usage(cmds: &[Cmd], enum_opts: &EnumOpts) -> String {
+    cmd_usage_lines(cmds, enum_opts).into_iter()
+        .map(|(line, _)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the line of usage text for each command, in the order listed,
+/// with the name of the command
+fn cmd_usage_lines<'a>(cmds: &'a [Cmd], enum_opts: &EnumOpts) -> Vec<(String, &'a str)> {
+    let names = cmd_names(cmds, enum_opts);
+    let width = cmd_usage_width(&names, enum_opts);
+
+    names.into_iter().map(|(name, help)| {
         let mut line = String::from("  ");
 
-        line.push_str(&cmd.name);
+        line.push_str(&styled(STYLE_COMMAND, name));
+
+        if let Some(help) = help {
+            push_help_padding(&mut line, width, enum_opts.next_line_help);
+            line.push_str(help);
+        }
+
+        (line, name)
+    }).collect()
+}
 
-        if let Some(help) = &cmd.help {
-            if line.len() < width {
-                let n = width - line.len();
-                line.extend(repeat(' ').take(n));
+/// Returns an expression of type `Option<fn() -> String>` producing the
+/// command list followed, beneath each command, by the options given by
+/// `Options::preview` for the command type
+fn make_cmd_preview(cmds: &[Cmd], enum_opts: &EnumOpts, markup: bool) -> TokenStream2 {
+    let lines = cmd_usage_lines(cmds, enum_opts).into_iter().map(|(line, name)| {
+        let line = if markup { line } else { strip_markup(&line) };
+        let line = usage_tokens(&line);
+        let preview = cmds.iter().find(|cmd| cmd.name == name).map(|cmd| {
+            let ty = cmd.ty;
+
+            if markup {
+                quote!{ <#ty as ::gumdrop::Options>::preview_markup() }
             } else {
-                line.push('\n');
-                line.extend(repeat(' ').take(width));
+                quote!{ <#ty as ::gumdrop::Options>::preview() }
             }
+        });
 
-            line.push_str(help);
+        match preview {
+            Some(preview) => quote!{
+                res.push_str(#line);
+
+                for line in #preview.lines() {
+                    res.push_str(if line.is_empty() { "\n" } else { "\n    " });
+                    res.push_str(line);
+                }
+
+                res.push('\n');
+            },
+            None => quote!{
+                res.push_str(#line);
+                res.push('\n');
+            }
         }
+    });
+
+    quote!{
+        ::std::option::Option::Some(|| {
+            let mut res = ::std::string::String::new();
+            #( #lines )*
+            res.pop();
+            res
+        })
+    }
+}
 
-        res.push_str(&line);
-        res.push('\n');
+/// Pads a line of usage text to the column at which help text begins,
+/// moving to the next line if the column has already been passed
+/// or `next_line` is `true`.
+fn push_help_padding(line: &mut String, col_width: usize, next_line: bool) {
+    let len = visible_len(line);
+
+    if len < col_width && !next_line {
+        line.push_str(&" ".repeat(col_width - len));
+    } else {
+        line.push('\n');
+        line.push_str(&" ".repeat(col_width));
     }
+}
 
-    // Pop the last newline
-    res.pop();
+/// Appends help text to a line of usage text, indenting any
+/// subsequent lines to the column at which help text begins.
+fn push_help_text(line: &mut String, help: &str, col_width: usize) {
+    for (i, text) in help.split('\n').enumerate() {
+        if i != 0 {
+            line.push('\n');
+
+            if !text.is_empty() {
+                line.push_str(&" ".repeat(col_width));
+            }
+        }
+
+        line.push_str(text);
+    }
+}
+
+/// Appends notes, such as `(default: ...)`, following help text
+/// in a line of usage text
+fn push_help_notes<S: AsRef<str>>(line: &mut String, has_help: bool, notes: &[S]) {
+    for (i, note) in notes.iter().enumerate() {
+        if has_help || i != 0 {
+            line.push(' ');
+        }
+
+        line.push_str(&styled(STYLE_DEFAULT, note.as_ref()));
+    }
+}
+
+// Usage text is first generated with markers surrounding spans of text
+// which may be styled by `gumdrop` at runtime, e.g. `"\u{1}hHeading:\u{2}"`.
+// These must agree with the definitions in `gumdrop`.
+
+/// Begins a styled span; followed by one of the `STYLE_*` codes below
+const STYLE_START: char = '\u{1}';
+/// Ends a styled span
+const STYLE_END: char = '\u{2}';
+
+const STYLE_HEADING: char = 'h';
+const STYLE_OPTION: char = 'o';
+const STYLE_META: char = 'm';
+const STYLE_DEFAULT: char = 'd';
+const STYLE_POSITIONAL: char = 'p';
+const STYLE_COMMAND: char = 'c';
+const STYLE_STRONG: char = 's';
+const STYLE_EMPHASIS: char = 'e';
+const STYLE_CODE: char = 'l';
+
+fn styled(style: char, text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 3);
+
+    res.push(STYLE_START);
+    res.push(style);
+    res.push_str(text);
+    res.push(STYLE_END);
+    res
+}
+
+/// Returns the display width of the final line of text, excluding style markers
+fn visible_len(text: &str) -> usize {
+    let line = text.rsplit('\n').next().unwrap_or(text);
+
+    strip_markup(line).width()
+}
+
+/// Converts Markdown syntax in doc comment text: strong emphasis, emphasis,
+/// code spans, links, and backslash escapes.
+///
+/// With `Markdown::Render`, emphasized text and code spans are marked with
+/// style codes; otherwise, only their text is retained. A link is replaced
+/// by its text followed by its destination in parentheses.
+fn markdown(text: &str, mode: Markdown) -> String {
+    let span = |res: &mut String, style: char, text: &str| {
+        if mode == Markdown::Render {
+            res.push_str(&styled(style, text));
+        } else {
+            res.push_str(text);
+        }
+    };
+
+    if mode == Markdown::Verbatim {
+        return text.to_owned();
+    }
+
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        let after = &rest[ch.len_utf8()..];
+
+        match ch {
+            '\\' => {
+                if let Some(esc) = after.chars().next().filter(char::is_ascii_punctuation) {
+                    res.push(esc);
+                    rest = &after[1..];
+                    continue;
+                }
+            }
+            '`' => {
+                let ticks = &rest[..rest.len() - rest.trim_start_matches('`').len()];
+                let inner = &rest[ticks.len()..];
+
+                if let Some(end) = inner.find(ticks) {
+                    span(&mut res, STYLE_CODE, inner[..end].trim());
+                    rest = &inner[end + ticks.len()..];
+                } else {
+                    res.push_str(ticks);
+                    rest = inner;
+                }
+                continue;
+            }
+            '*' | '_' => {
+                let delim = if after.starts_with(ch) { &rest[..2] } else { &rest[..1] };
+                let inner = &rest[delim.len()..];
+                // `_` does not begin emphasis within a word, e.g. `snake_case`
+                let intraword = ch == '_' &&
+                    res.chars().next_back().is_some_and(char::is_alphanumeric);
+
+                if !intraword && inner.starts_with(|c: char| !c.is_whitespace()) {
+                    if let Some(end) = closing_delim(inner, delim) {
+                        let style = if delim.len() == 2 { STYLE_STRONG } else { STYLE_EMPHASIS };
+
+                        span(&mut res, style, &markdown(&inner[..end], Markdown::Strip));
+                        rest = &inner[end + delim.len()..];
+                        continue;
+                    }
+                }
+
+                res.push_str(delim);
+                rest = inner;
+                continue;
+            }
+            '[' => {
+                if let Some(link) = parse_link(rest) {
+                    let (label, dest, len) = link;
+
+                    res.push_str(&markdown(label, mode));
+
+                    if label != dest {
+                        res.push_str(" (");
+                        res.push_str(dest);
+                        res.push(')');
+                    }
+
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+            '<' => {
+                if let Some(end) = after.find('>') {
+                    let url = &after[..end];
+
+                    if url.contains("://") && !url.contains(char::is_whitespace) {
+                        res.push_str(url);
+                        rest = &after[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            _ => ()
+        }
+
+        res.push(ch);
+        rest = after;
+    }
+
+    res
+}
+
+/// Returns the offset within `text` of a delimiter closing emphasis,
+/// which must follow a non-whitespace character
+fn closing_delim(text: &str, delim: &str) -> Option<usize> {
+    let ch = delim.chars().next()?;
+
+    text.match_indices(delim)
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let before = text[..i].chars().next_back();
+            let next = text[i + delim.len()..].chars().next();
+
+            i != 0 && before.is_some_and(|c| !c.is_whitespace() && c != ch) &&
+                next != Some(ch) &&
+                !(ch == '_' && next.is_some_and(char::is_alphanumeric))
+        })
+}
+
+/// Parses a link of the form `[label](destination)` at the start of `text`,
+/// returning the label, destination, and length of the link
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find(']')?;
+    let label = &text[1..close];
+    let rest = text[close + 1..].strip_prefix('(')?;
+    let end = rest.find(')')?;
+    let dest = &rest[..end];
+
+    if label.is_empty() || label.contains(['[', '\n']) || dest.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((label, dest, close + 2 + end + 1))
+}
+
+/// Removes all style markers from usage text
+fn strip_markup(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            STYLE_START => { chars.next(); }
+            STYLE_END => (),
+            ch => res.push(ch)
+        }
+    }
 
     res
 }
@@ -1886,3 +5852,99 @@ fn make_cmd_usage(cmds: &[Cmd]) -> String {
 fn unexpected_meta_item(span: Span) -> Error {
     Error::new(span, "unexpected meta item")
 }
+
+/// Items accepted by `#[options(...)]` on `struct` fields
+const FIELD_ITEMS: &[&str] = &[
+    "command", "confirm", "conflicts_with", "count", "default", "default_expr",
+    "default_expr_display", "default_fn", "display_order", "env",
+    "fall_through", "free", "heading", "help", "help_all_flag", "help_flag",
+    "hide_default", "ignore_case", "index", "long", "long_help", "meta", "multi", "no_help_flag",
+    "no_long", "no_multi", "no_short", "not_required", "parse",
+    "possible_values", "preview", "prompt_secret", "required", "requires", "short", "show_default",
+    "verbatim_doc_comment", "version_flag",
+];
+
+/// Items accepted by `#[options(...)]` on `enum` variants
+const VARIANT_ITEMS: &[&str] = &["catch_all", "help", "name"];
+
+/// Items accepted by `#[options(...)]` on a `struct` type
+const STRUCT_ITEMS: &[&str] = &[
+    "after_help", "arbitrary", "auto_help", "auto_short", "base",
+    "command_heading", "default_format", "error_usage", "example",
+    "examples_heading", "help", "help_column_width",
+    "help_max_width", "lenient_extra_free", "lenient_unknown_options",
+    "localize", "markdown", "max_free", "min_free", "next_line_help", "no_help_flag", "no_long", "no_multi",
+    "no_short", "optional_heading",
+    "positional_heading", "program_name", "rename_all", "required",
+    "required_marker", "show_default", "sort", "to_args",
+    "verbatim_doc_comment", "version",
+];
+
+/// Items accepted by `#[options(...)]` on an `enum` type
+const ENUM_ITEMS: &[&str] = &[
+    "after_help", "arbitrary", "command_heading", "dispatch", "error_usage",
+    "fallback", "help_column_width", "help_command", "help_max_width",
+    "ignore_case", "localize", "markdown", "next_line_help", "pin", "preview",
+    "program_name", "rename_all",
+    "sort", "to_args",
+];
+
+/// Items accepted by `parse(...)`
+const PARSE_ITEMS: &[&str] = &["contextual", "from_str", "try_from_str", "with_parser"];
+
+/// Returns an error for an unrecognized item, spanning the whole item,
+/// which suggests the most similar of the valid item names
+fn unknown_item<T: ToTokens>(item: T, path: &Path, valid: &[&'static str]) -> Error {
+    let name = match path.get_ident() {
+        Some(ident) => ident.to_string(),
+        None => quote!{ #path }.to_string()
+    };
+
+    let msg = if valid.contains(&&name[..]) {
+        format!("invalid use of item `{}`", name)
+    } else if let Some(similar) = suggest(&name, valid) {
+        format!("unknown item `{}`; did you mean `{}`?", name, similar)
+    } else {
+        format!("unknown item `{}`", name)
+    };
+
+    Error::new_spanned(item, msg)
+}
+
+/// Returns the candidate most similar to `name`, if any is similar enough
+/// to suggest that the user may have meant it.
+fn suggest(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let len = name.chars().count();
+    let max_dist = (len / 3).max(1);
+
+    candidates.iter()
+        .map(|&cand| (edit_distance(name, cand), cand))
+        .filter(|&(dist, _)| dist <= max_dist && dist < len)
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, cand)| cand)
+}
+
+/// Returns the Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, &cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+
+            row[j + 1] = if ca == cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
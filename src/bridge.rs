@@ -0,0 +1,106 @@
+//! Conversion of option metadata into a `clap::Command`
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::{CommandInfo, OptionInfo, Options, SectionKind, UsageModel};
+
+/// Builds a `clap::Command` describing the options and commands of `T`.
+///
+/// The returned command is intended for use with tools built upon `clap`,
+/// such as generators of manual pages or shell completions, rather than for
+/// parsing arguments. Options are described as given by
+/// [`Options::options_info`](trait.Options.html#method.options_info),
+/// positional arguments by
+/// [`Options::usage_model`](trait.Options.html#method.usage_model),
+/// and subcommands by
+/// [`Options::commands_info`](trait.Options.html#method.commands_info).
+///
+/// `clap` does not add its own help and version flags to the command;
+/// any such flags defined by `T` are included as ordinary options.
+///
+/// This function is available only when the `clap` feature is enabled.
+pub fn clap_command<T: Options>(name: &'static str) -> Command {
+    build_command(name, T::usage_model(), T::options_info(), T::commands_info())
+}
+
+fn build_command(name: &'static str, usage: UsageModel,
+        options: &'static [OptionInfo], commands: &'static [CommandInfo]) -> Command {
+    let mut cmd = Command::new(name)
+        .disable_help_flag(true)
+        .disable_version_flag(true);
+
+    if let Some(desc) = usage.description {
+        cmd = cmd.about(desc);
+    }
+
+    for opt in options {
+        cmd = cmd.arg(option_arg(opt));
+    }
+
+    let positional = usage.sections.iter()
+        .filter(|section| section.kind == SectionKind::Positional)
+        .flat_map(|section| section.rows);
+
+    for (i, row) in positional.enumerate() {
+        let mut arg = Arg::new(row.name)
+            .index(i + 1)
+            .value_name(row.name.to_uppercase());
+
+        if let Some(help) = row.help {
+            arg = arg.help(help);
+        }
+
+        cmd = cmd.arg(arg);
+    }
+
+    for sub in commands {
+        let mut sub_cmd = build_command(sub.name,
+            (sub.usage_model)(), (sub.options)(), (sub.commands)());
+
+        if let Some(help) = sub.help {
+            sub_cmd = sub_cmd.about(help);
+        }
+
+        cmd = cmd.subcommand(sub_cmd);
+    }
+
+    cmd
+}
+
+fn option_arg(opt: &OptionInfo) -> Arg {
+    let id = match (opt.long, opt.short) {
+        (Some(long), _) => long.to_owned(),
+        (None, Some(short)) => short.to_string(),
+        (None, None) => String::new(),
+    };
+
+    let mut arg = Arg::new(id).required(opt.required);
+
+    if let Some(long) = opt.long {
+        arg = arg.long(long);
+    }
+
+    if let Some(short) = opt.short {
+        arg = arg.short(short);
+    }
+
+    if let Some(help) = opt.help {
+        arg = arg.help(help);
+    }
+
+    if opt.takes_value {
+        arg = arg.action(ArgAction::Set);
+
+        if let Some(meta) = opt.meta {
+            arg = arg.value_name(meta);
+        }
+
+        if let Some(default) = opt.default {
+            arg = arg.default_value(default);
+        }
+    } else {
+        arg = arg.action(ArgAction::SetTrue);
+    }
+
+    arg
+}
@@ -0,0 +1,148 @@
+//! Generation of configuration file templates from option metadata
+
+use crate::{CommandInfo, OptionInfo, Options};
+
+/// Returns a documented TOML template for a configuration file mirroring
+/// the options and commands of `T`.
+///
+/// Each option with a long name is written as a commented-out key of the
+/// same name, preceded by its help text. Options taking a value are given
+/// their default value, if any, or otherwise their `meta` name as a string;
+/// flags are given the value `false`. Required options are marked
+/// `(required)`. Options of each subcommand are written to a commented-out
+/// table of the command's name, with nested commands using dotted names.
+///
+/// Options are described as given by
+/// [`Options::options_info`](trait.Options.html#method.options_info)
+/// and subcommands by
+/// [`Options::commands_info`](trait.Options.html#method.commands_info).
+///
+/// # Examples
+///
+/// ```
+/// use gumdrop::Options;
+///
+/// #[derive(Options)]
+/// struct MyOptions {
+///     #[options(help = "be verbose")]
+///     verbose: bool,
+///     #[options(help = "number of jobs", default = "4")]
+///     jobs: u32,
+///     #[options(help = "output file", meta = "PATH")]
+///     output: Option<String>,
+/// }
+///
+/// # if cfg!(feature = "strip_help") { return; }
+/// assert_eq!(gumdrop::config_template::<MyOptions>(), "\
+/// ## be verbose
+/// #verbose = false
+///
+/// ## number of jobs
+/// #jobs = 4
+///
+/// ## output file
+/// #output = \"PATH\"
+/// ");
+/// ```
+pub fn config_template<T: Options>() -> String {
+    let mut res = String::new();
+
+    write_options(&mut res, T::options_info());
+    write_commands(&mut res, "", T::commands_info());
+
+    res
+}
+
+fn write_options(res: &mut String, options: &[OptionInfo]) {
+    for opt in options {
+        let long = match opt.long {
+            Some(long) => long,
+            None => continue
+        };
+
+        if !res.is_empty() {
+            res.push('\n');
+        }
+
+        if let Some(help) = opt.help {
+            for line in help.lines() {
+                res.push_str("# ");
+                res.push_str(line);
+                res.push('\n');
+            }
+        }
+
+        if opt.required {
+            res.push_str("# (required)\n");
+        }
+
+        res.push('#');
+        res.push_str(long);
+        res.push_str(" = ");
+
+        if !opt.takes_value {
+            res.push_str("false");
+        } else if let Some(default) = opt.default {
+            push_value(res, default);
+        } else {
+            push_string(res, opt.meta.unwrap_or("VALUE"));
+        }
+
+        res.push('\n');
+    }
+}
+
+fn write_commands(res: &mut String, path: &str, commands: &[CommandInfo]) {
+    for cmd in commands {
+        let path = if path.is_empty() {
+            cmd.name.to_owned()
+        } else {
+            format!("{}.{}", path, cmd.name)
+        };
+
+        if !res.is_empty() {
+            res.push('\n');
+        }
+
+        if let Some(help) = cmd.help {
+            for line in help.lines() {
+                res.push_str("# ");
+                res.push_str(line);
+                res.push('\n');
+            }
+        }
+
+        res.push_str("#[");
+        res.push_str(&path);
+        res.push_str("]\n");
+
+        write_options(res, (cmd.options)());
+        write_commands(res, &path, (cmd.commands)());
+    }
+}
+
+/// Writes a default value, which is quoted unless it is a TOML
+/// boolean or number
+fn push_value(res: &mut String, value: &str) {
+    if value == "true" || value == "false" || value.parse::<f64>().is_ok() {
+        res.push_str(value);
+    } else {
+        push_string(res, value);
+    }
+}
+
+fn push_string(res: &mut String, value: &str) {
+    res.push('"');
+
+    for ch in value.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            _ => res.push(ch)
+        }
+    }
+
+    res.push('"');
+}
@@ -91,28 +91,23 @@
 //! // Each variant of a command enum should be a unary tuple variant with only
 //! // one field. This field must implement `Options` and is used to parse arguments
 //! // that are given after the command name.
+//! //
+//! // The `help_command` attribute adds a `help [COMMAND...]` command,
+//! // which `parse_args_or_exit` handles by printing usage for the named command.
 //! #[derive(Debug, Options)]
+//! #[options(help_command)]
 //! enum Command {
 //!     // Command names are generated from variant names.
 //!     // By default, a CamelCase name will be converted into a lowercase,
 //!     // hyphen-separated name; e.g. `FooBar` becomes `foo-bar`.
 //!     //
 //!     // Names can be explicitly specified using `#[options(name = "...")]`
-//!     #[options(help = "show help for a command")]
-//!     Help(HelpOpts),
 //!     #[options(help = "make stuff")]
 //!     Make(MakeOpts),
 //!     #[options(help = "install stuff")]
 //!     Install(InstallOpts),
 //! }
 //!
-//! // Options accepted for the `help` command
-//! #[derive(Debug, Options)]
-//! struct HelpOpts {
-//!     #[options(free)]
-//!     free: Vec<String>,
-//! }
-//!
 //! // Options accepted for the `make` command
 //! #[derive(Debug, Options)]
 //! struct MakeOpts {
@@ -170,49 +165,250 @@
 
 pub use gumdrop_derive::*;
 
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+
+#[cfg(feature = "clap")]
+mod bridge;
+mod config;
+mod message;
+#[cfg(feature = "prompt")]
+mod prompt;
+mod repl;
+mod style;
+mod wrap;
+
+pub use crate::config::config_template;
+pub use crate::message::{DefaultErrorFormatter, ErrorFormatter, ErrorMessage};
+pub use crate::repl::{repl, repl_with, split_line};
+pub use crate::wrap::{terminal_width, wrap_help};
+
+#[cfg(feature = "clap")]
+pub use crate::bridge::clap_command;
+
+#[cfg(feature = "prompt")]
+pub use crate::prompt::prompt_secret;
+
+#[cfg(feature = "style")]
+pub use crate::style::{AnsiStyle, HelpStyle, PlainStyle};
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::error::Error as StdError;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
-use std::slice::Iter;
-use std::str::Chars;
+use std::io::{self, Write};
 
 /// Represents an error encountered during argument parsing
 #[derive(Debug)]
 pub struct Error {
-    kind: ErrorKind,
+    repr: Repr,
+    /// Index and value of the argument at which the error occurred
+    position: Option<(usize, String)>,
+    /// Names of commands within which the error occurred
+    command_path: Vec<String>,
+}
+
+/// Category of an [`Error`](struct.Error.html), returned by `Error::kind`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Two options were given which may not be used together
+    ConflictingOptions,
+    /// An error created by `Error::custom`
+    Custom,
+    /// An option value failed to parse
+    FailedParse,
+    /// The default value of an option failed to parse
+    FailedParseDefault,
+    /// An option value taken from an environment variable failed to parse
+    FailedParseEnv,
+    /// Help was requested using a generated `help` command
+    HelpCommand,
+    /// An option expecting two or more arguments received too few
+    InsufficientArguments,
+    /// Fewer or more free arguments were given than are accepted
+    InvalidFreeCount,
+    /// An option value was not one of its possible values
+    InvalidValue,
+    /// An option expecting an argument received none
+    MissingArgument,
+    /// A command name was expected, but none was given
+    MissingCommand,
+    /// A required option was not given
+    MissingRequired,
+    /// A required command was not given
+    MissingRequiredCommand,
+    /// A required free argument was not given
+    MissingRequiredFree,
+    /// An option was given without another option which it requires
+    MissingRequirement,
+    /// An option which does not accept an argument received one
+    UnexpectedArgument,
+    /// An option expecting two or more arguments received only one,
+    /// e.g. `--option=value`
+    UnexpectedSingleArgument,
+    /// A free argument was given, but none are accepted
+    UnexpectedFree,
+    /// A command name was not recognized
+    UnrecognizedCommand,
+    /// An option name was not recognized
+    UnrecognizedOption,
 }
 
 #[derive(Debug)]
-enum ErrorKind {
-    FailedParse(String, String),
+enum Repr {
+    ConflictingOptions(Cow<'static, str>, Cow<'static, str>),
+    Custom(Box<dyn StdError + Send + Sync>),
+    FailedParse(String, Box<dyn StdError + Send + Sync>),
+    FailedParseFree{
+        name: &'static str,
+        ordinal: usize,
+        value: Box<str>,
+        err: Box<dyn StdError + Send + Sync>,
+    },
     FailedParseDefault{
         option: &'static str,
         value: &'static str,
         err: String,
     },
+    FailedParseEnv{
+        var: &'static str,
+        err: String,
+    },
+    HelpCommand(Vec<String>),
     InsufficientArguments{
         option: String,
         expected: usize,
         found: usize,
     },
+    InvalidFreeCount{
+        min: usize,
+        max: Option<usize>,
+        found: usize,
+    },
+    InvalidValue{
+        option: String,
+        value: String,
+        possible: &'static [&'static str],
+    },
     MissingArgument(String),
-    MissingCommand,
-    MissingRequired(String),
-    MissingRequiredCommand,
+    MissingCommand(&'static [&'static str]),
+    MissingRequired(Cow<'static, str>),
+    MissingRequiredCommand(&'static [&'static str]),
     MissingRequiredFree,
+    MissingRequiredFreeCount{
+        expected: usize,
+        found: usize,
+    },
+    MissingRequirement(Cow<'static, str>, Cow<'static, str>),
     UnexpectedArgument(String),
     UnexpectedSingleArgument(String, usize),
     UnexpectedFree(String),
-    UnrecognizedCommand(String),
-    UnrecognizedLongOption(String),
+    UnrecognizedCommand(String, &'static [&'static str]),
+    UnrecognizedLongOption(String, &'static [&'static str]),
     UnrecognizedShortOption(char),
 }
 
 /// Parses options from a series of `&str`-like values.
+///
+/// Parsing with `derive(Options)` implementations does not allocate unless
+/// an error is encountered, other than to store parsed values which are
+/// themselves allocated, e.g. `String` or `Vec<T>` fields.
 pub struct Parser<'a, S: 'a> {
-    args: Iter<'a, S>,
-    cur: Option<Chars<'a>>,
+    args: Args<'a, S>,
+    /// Number of arguments consumed
+    pos: usize,
+    /// Byte offset of remaining short options within the current argument
+    cur: Option<usize>,
     style: ParsingStyle,
     terminated: bool,
+    long_help: bool,
+    /// Options whose values are to be read by `Options::prompt_secrets`
+    prompts: Vec<&'static str>,
+    /// Path of commands for which help was requested by a `help` command
+    help_command: Option<Vec<String>>,
+    errors: Option<Vec<Error>>,
+    observer: Option<Observer<'a>>,
+    /// Arguments in their original form, if given by `set_os_args`
+    os_args: Option<&'a [&'a OsStr]>,
+    assume_yes: bool,
+}
+
+type Observer<'a> = Box<dyn FnMut(ParseEvent) + 'a>;
+
+/// Arguments of a `Parser`, either borrowed directly or through `ArgList`,
+/// which allows parsing code to be compiled once for all argument types
+enum Args<'a, S: 'a> {
+    Slice(&'a [S]),
+    Dyn(&'a dyn ArgList),
+}
+
+impl<'a, S: 'a> Clone for Args<'a, S> {
+    fn clone(&self) -> Args<'a, S> {
+        *self
+    }
+}
+
+impl<'a, S: 'a> Copy for Args<'a, S> {}
+
+impl<'a, S: 'a + AsRef<str>> Args<'a, S> {
+    fn get(&self, index: usize) -> Option<&'a str> {
+        match *self {
+            Args::Slice(args) => args.get(index).map(|s| s.as_ref()),
+            Args::Dyn(args) => args.get(index),
+        }
+    }
+}
+
+trait ArgList {
+    fn get(&self, index: usize) -> Option<&str>;
+}
+
+impl<S: AsRef<str>> ArgList for &[S] {
+    fn get(&self, index: usize) -> Option<&str> {
+        <[S]>::get(self, index).map(|s| s.as_ref())
+    }
+}
+
+/// Argument type of a parser passed to `Options::parse_dyn`
+///
+/// A `Parser<DynArg>` is created by `Parser::with_dyn` and may refer to
+/// arguments of any type implementing `AsRef<str>`.
+/// No value of this type can be constructed.
+#[derive(Copy, Clone, Debug)]
+pub enum DynArg {}
+
+impl AsRef<str> for DynArg {
+    fn as_ref(&self) -> &str {
+        match *self {}
+    }
+}
+
+/// Event in parsing, reported to the observer installed by
+/// [`Parser::set_observer`](struct.Parser.html#method.set_observer)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseEvent<'a> {
+    /// An option was recognized
+    Option(Opt<'a>),
+    /// An argument was taken as the value of the preceding option
+    Value(&'a str),
+    /// A free argument was accepted
+    Free(&'a str),
+    /// A command was recognized; subsequent events belong to the command
+    Command(&'a str),
+}
+
+impl fmt::Display for ParseEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseEvent::Option(opt) => write!(f, "option {}", opt.to_string()),
+            ParseEvent::Value(arg) => write!(f, "value {}", arg),
+            ParseEvent::Free(arg) => write!(f, "free {}", arg),
+            ParseEvent::Command(name) => write!(f, "command {}", name),
+        }
+    }
 }
 
 /// Represents an option parsed from a `Parser`
@@ -236,6 +432,17 @@ pub trait Options {
     /// an error is encountered.
     fn parse<S: AsRef<str>>(parser: &mut Parser<S>) -> Result<Self, Error> where Self: Sized;
 
+    /// Parses arguments using a parser which may refer to arguments of any type.
+    ///
+    /// `derive(Options)` implements parsing in this method, which is compiled
+    /// once for each options type, and implements `parse` by calling it
+    /// through `Parser::with_dyn`.
+    ///
+    /// The default implementation calls `parse`.
+    fn parse_dyn(parser: &mut Parser<DynArg>) -> Result<Self, Error> where Self: Sized {
+        Self::parse(parser)
+    }
+
     /// Returns the subcommand instance, if present.
     ///
     /// This method **must never** return `self` or otherwise return a `&dyn Options` instance
@@ -243,6 +450,14 @@ pub trait Options {
     /// to loop infinitely or overflow the runtime stack.
     fn command(&self) -> Option<&dyn Options>;
 
+    /// Returns a mutable reference to the subcommand instance, if present,
+    /// e.g. to adjust the options of a nested command after parsing.
+    ///
+    /// As with `command`, this method **must never** return `self`.
+    ///
+    /// The default implementation returns `None`.
+    fn command_mut(&mut self) -> Option<&mut dyn Options> { None }
+
     /// Returns the name of a parsed command, if present.
     ///
     /// This is implemented by `derive(Options)` in one of two ways:
@@ -253,71 +468,334 @@ pub trait Options {
     /// * For `enum` types, the name corresponding to the variant is returned.
     fn command_name(&self) -> Option<&'static str> { None }
 
+    /// Returns the full sequence of parsed command names, from the outermost
+    /// command to the innermost selected subcommand.
+    ///
+    /// This is implemented by `derive(Options)` in one of two ways:
+    ///
+    /// * For `struct` types, if the type contains a field marked
+    ///   `#[options(command)]`, this method is called on that value.
+    ///   Otherwise, an empty `Vec` is returned.
+    /// * For `enum` types, the name corresponding to the variant is followed
+    ///   by the command path of the variant's value.
+    ///
+    /// The default implementation returns an empty `Vec`.
+    fn command_path(&self) -> Vec<&'static str> { Vec::new() }
+
     /// Returns whether the user supplied a "help" option to request
     /// usage information about the program or any contained subcommands.
     ///
     /// The default implementation returns `false`.
     fn help_requested(&self) -> bool { false }
 
+    /// Returns whether the user supplied a "help all" option to request
+    /// usage information about the program and all of its subcommands.
+    ///
+    /// The default implementation returns `false`.
+    fn help_all_requested(&self) -> bool { false }
+
+    /// Returns whether the user supplied a version option, e.g. `--version`.
+    ///
+    /// The default implementation returns `false`.
+    fn version_requested(&self) -> bool { false }
+
+    /// Returns the confirmation prompts of options marked `confirm` which
+    /// were supplied by the user, including those of any contained
+    /// subcommands.
+    ///
+    /// `parse_args_or_exit` asks the user to confirm each of these before
+    /// returning parsed options, unless `--yes` was given.
+    ///
+    /// The default implementation returns an empty `Vec`.
+    fn confirmations(&self) -> Vec<&'static str> { Vec::new() }
+
+    /// Reads from the terminal the value of each option marked
+    /// `prompt_secret`, including those of any contained subcommands,
+    /// which was not supplied in arguments parsed by `parser`.
+    ///
+    /// `parse_args_or_exit` calls this once arguments are parsed without
+    /// error. Other parsing methods do not read from the terminal; a program
+    /// using them may call this method with the parser used.
+    ///
+    /// The default implementation returns `Ok(())`.
+    fn prompt_secrets<S: AsRef<str>>(&mut self, parser: &Parser<S>) -> Result<(), Error>
+            where Self: Sized {
+        let _ = parser;
+        Ok(())
+    }
+
+    /// Passes each command selected in these options to the given visitor,
+    /// beginning with the outermost.
+    ///
+    /// For `enum` types with `derive(Options)`, the selected command is
+    /// visited, followed by any contained subcommands.
+    ///
+    /// For `struct` types containing a field marked `#[options(command)]`,
+    /// `accept` is called on the command, if one was given.
+    ///
+    /// The default implementation visits nothing.
+    fn accept(&self, visitor: &mut dyn CommandVisitor) {
+        let _ = visitor;
+    }
+
+    /// Returns the program version printed by `parse_args_or_exit`
+    /// when a version option is supplied.
+    ///
+    /// The default implementation returns `None`.
+    fn version() -> Option<&'static str> where Self: Sized { None }
+
+    /// Returns a description of each option, in the order declared.
+    ///
+    /// Positional arguments and commands are not included.
+    ///
+    /// The default implementation returns an empty slice.
+    fn options_info() -> &'static [OptionInfo] where Self: Sized { &[] }
+
+    /// Returns a description of each available command, in the order declared.
+    ///
+    /// For `struct` types containing a field marked `#[options(command)]`,
+    /// `commands_info` is called on the command type.
+    /// The `help` command added by `help_command` is not included.
+    ///
+    /// The default implementation returns an empty slice.
+    fn commands_info() -> &'static [CommandInfo] where Self: Sized { &[] }
+
+    /// Returns a description of the options and commands accepted,
+    /// as given by `options_info` and `commands_info`, e.g. for use in tests
+    /// asserting that the command line interface of a program has not changed.
+    fn cli_spec() -> CliSpec where Self: Sized {
+        CliSpec{
+            options: Self::options_info(),
+            commands: Self::commands_info(),
+        }
+    }
+
+    /// Returns the sections and rows of usage text, as returned by `usage`,
+    /// so that usage text may be displayed in another form.
+    ///
+    /// The default implementation returns a model containing no sections.
+    fn usage_model() -> UsageModel where Self: Sized { UsageModel::default() }
+
+    /// Returns the translation of a string of usage text, e.g. help text
+    /// or a section heading, if any.
+    ///
+    /// For types having `#[options(localize = "...")]`, the named function
+    /// is called.
+    ///
+    /// The default implementation returns `None`.
+    fn localize(text: &str) -> Option<String> where Self: Sized {
+        let _ = text;
+        None
+    }
+
+    /// Returns usage text, as by `usage`, in which each description,
+    /// heading, value name, help text, and note is replaced by its
+    /// translation returned by `localize`, if any.
+    ///
+    /// The returned string does **not** end with a newline.
+    fn localized_usage() -> String where Self: Sized {
+        Self::usage_model().render_localized(&Self::localize)
+    }
+
+    /// Parses options from values keyed by long option name,
+    /// as returned by [`ToArgs::to_map`](trait.ToArgs.html#tymethod.to_map).
+    ///
+    /// Values are converted into arguments, which are parsed as if given on
+    /// the command line; options not present in the map take their default
+    /// values. An error is returned if a key does not name an option or if
+    /// a value is of the wrong kind for its option.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use gumdrop::{Options, Value};
+    ///
+    /// #[derive(Debug, Options)]
+    /// struct MyOptions {
+    ///     verbose: bool,
+    ///     jobs: Option<u32>,
+    /// }
+    ///
+    /// let mut map = BTreeMap::new();
+    ///
+    /// map.insert("verbose".to_owned(), Value::Bool(true));
+    /// map.insert("jobs".to_owned(), Value::String("4".to_owned()));
+    ///
+    /// let opts = MyOptions::from_map(&map).unwrap();
+    ///
+    /// assert!(opts.verbose);
+    /// assert_eq!(opts.jobs, Some(4));
+    /// ```
+    fn from_map(map: &BTreeMap<String, Value>) -> Result<Self, Error> where Self: Sized {
+        let options = Self::options_info();
+        let mut args = Vec::new();
+
+        for (key, value) in map {
+            let opt = options.iter().find(|opt| opt.long == Some(&key[..]))
+                .ok_or_else(|| Error::unrecognized_long(key))?;
+
+            map_args(&mut args, opt, key, value)?;
+        }
+
+        Self::parse_args_default(args)
+    }
+
     /// Parses arguments received from the command line.
     ///
+    /// Arguments may be given as any iterable of string values,
+    /// e.g. a slice, an array, a `Vec`, or an iterator adapter.
+    ///
     /// The first argument (the program name) should be omitted.
-    fn parse_args<S: AsRef<str>>(args: &[S], style: ParsingStyle) -> Result<Self, Error>
-            where Self: Sized {
-        Self::parse(&mut Parser::new(args, style))
+    fn parse_args<I>(args: I, style: ParsingStyle) -> Result<Self, Error>
+            where Self: Sized, I: IntoIterator, I::Item: AsRef<str> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let mut parser = Parser::new(&args, style);
+
+        Self::parse(&mut parser).map_err(|e| e.at_position(&parser))
+    }
+
+    /// Parses arguments received from the command line, which may not be
+    /// valid UTF-8.
+    ///
+    /// Values of `free` fields of type `OsString` or `PathBuf`, or an
+    /// `Option` or `Vec` of these, are taken from the arguments unchanged.
+    /// Other values are parsed from the arguments converted to strings,
+    /// with invalid UTF-8 replaced as in `OsStr::to_string_lossy`.
+    ///
+    /// The first argument (the program name) should be omitted.
+    fn parse_args_os<I>(args: I, style: ParsingStyle) -> Result<Self, Error>
+            where Self: Sized, I: IntoIterator, I::Item: AsRef<OsStr> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let os_args = args.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        let str_args = os_args.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>();
+        let mut parser = Parser::new(&str_args, style);
+        parser.set_os_args(&os_args);
+
+        Self::parse(&mut parser).map_err(|e| e.at_position(&parser))
+    }
+
+    /// Parses arguments received from the command line, continuing after
+    /// an error is encountered, so that all errors may be reported at once.
+    ///
+    /// If any errors are encountered, e.g. invalid option values, unrecognized
+    /// options, or missing required options, all are returned.
+    ///
+    /// The first argument (the program name) should be omitted.
+    fn parse_args_all_errors<I>(args: I, style: ParsingStyle) -> Result<Self, Vec<Error>>
+            where Self: Sized, I: IntoIterator, I::Item: AsRef<str> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let mut parser = Parser::new(&args, style);
+        parser.collect_errors();
+
+        let res = Self::parse(&mut parser);
+        let mut errors = parser.take_errors();
+
+        match res {
+            Ok(opts) if errors.is_empty() => Ok(opts),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e.at_position(&parser));
+                Err(errors)
+            }
+        }
     }
 
     /// Parses arguments from the environment.
     ///
     /// If an error is encountered, the error is printed to `stderr` and the
-    /// process will exit with status code `2`.
+    /// process will exit with status code `2`. The error is followed by usage
+    /// text, as given by [`error_usage`](#method.error_usage).
     ///
     /// If the user supplies a help option, option usage will be printed to
     /// `stderr` and the process will exit with status code `0`.
+    /// Long help text, from `long_usage`, is printed if the long name of the
+    /// help option was used, e.g. `--help`, or if help was requested using
+    /// a `help` command.
+    /// If the user supplies a "help all" option, the output of `usage_all`
+    /// is printed instead. Help text is wrapped to the width given by
+    /// [`terminal_width`](fn.terminal_width.html), if any.
+    ///
+    /// If the user supplies a version option, the program name and
+    /// [`version`](#method.version) are printed to `stderr` and the process
+    /// will exit with status code `0`.
+    ///
+    /// Help and version options take effect even if other arguments are
+    /// invalid, e.g. `prog --help --number notanumber` prints help text
+    /// rather than an error.
+    ///
+    /// The program name displayed in messages is given by
+    /// [`program_name`](#method.program_name).
     ///
     /// Otherwise, the parsed options are returned.
     fn parse_args_or_exit(style: ParsingStyle) -> Self where Self: Sized {
-        use std::env::args;
-        use std::process::exit;
-
-        let args = args().collect::<Vec<_>>();
-
-        let opts = Self::parse_args(&args[1..], style).unwrap_or_else(|e| {
-            eprintln!("{}: {}", args[0], e);
-            exit(2);
-        });
-
-        if opts.help_requested() {
-            let mut command = &opts as &dyn Options;
-            let mut command_str = String::new();
-
-            loop {
-                if let Some(new_command) = command.command() {
-                    command = new_command;
+        Self::parse_args_or_exit_using(style, &mut StandardExit::new())
+    }
 
-                    if let Some(name) = new_command.command_name() {
-                        command_str.push(' ');
-                        command_str.push_str(name);
-                    }
-                } else {
-                    break;
-                }
-            }
+    /// Parses arguments from the environment as with `parse_args_or_exit`,
+    /// using the given [`ExitStrategy`](trait.ExitStrategy.html) to print
+    /// messages and exit the process.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use gumdrop::{Options, ParsingStyle, StandardExit};
+    ///
+    /// #[derive(Options)]
+    /// struct MyOptions {
+    ///     #[options(help = "print help message")]
+    ///     help: bool,
+    /// }
+    ///
+    /// // Print help to `stdout` in the form expected by `help2man`
+    /// let opts = MyOptions::parse_args_or_exit_using(ParsingStyle::default(),
+    ///     &mut StandardExit::new().help2man());
+    /// ```
+    fn parse_args_or_exit_using(style: ParsingStyle, strategy: &mut dyn ExitStrategy) -> Self
+            where Self: Sized {
+        let os_args = std::env::args_os().collect::<Vec<_>>();
+        let os_args = os_args.iter().map(|s| &s[..]).collect::<Vec<_>>();
+        let mut args = os_args.iter()
+            .map(|s| s.to_string_lossy().into_owned()).collect::<Vec<_>>();
 
-            eprintln!("Usage: {}{} [OPTIONS]", args[0], command_str);
-            eprintln!();
-            eprintln!("{}", command.self_usage());
+        args[0] = match Self::program_name() {
+            Some(name) => name.to_owned(),
+            None => base_name(&args[0]).to_owned()
+        };
 
-            if let Some(cmds) = command.self_command_list() {
-                eprintln!();
-                eprintln!("Available commands:");
-                eprintln!("{}", cmds);
-            }
+        parse_or_exit(&args, Some(&os_args[1..]), style, strategy)
+    }
 
-            exit(0);
-        }
+    /// Parses arguments as with `parse_args_or_exit`, using the given
+    /// [`ExitStrategy`](trait.ExitStrategy.html) to print messages
+    /// and exit the process.
+    ///
+    /// Unlike other parsing methods, `args` includes the program name
+    /// as its first element.
+    ///
+    /// # Panics
+    ///
+    /// If `args` is empty.
+    fn parse_args_or_exit_with(args: &[String], style: ParsingStyle,
+            strategy: &mut dyn ExitStrategy) -> Self where Self: Sized {
+        parse_or_exit(args, None, style, strategy)
+    }
 
-        opts
+    /// Parses arguments, returning the help text which `parse_args_or_exit`
+    /// would print rather than printing it and exiting the process.
+    ///
+    /// Unlike other parsing methods, `args` includes the program name
+    /// as its first element.
+    ///
+    /// Help text is not styled, but is wrapped to the width given by
+    /// [`terminal_width`](fn.terminal_width.html), if any.
+    ///
+    /// # Panics
+    ///
+    /// If `args` is empty.
+    fn parse_args_or_help<S: AsRef<str>>(args: &[S], style: ParsingStyle)
+            -> ParseResult<Self> where Self: Sized {
+        parse_or_help(args, None, style, false, false, &mut false)
     }
 
     /// Parses arguments from the environment, using the default
@@ -334,23 +812,183 @@ pub trait Options {
         Self::parse_args_or_exit(ParsingStyle::default())
     }
 
+    /// Parses arguments from the environment, using the default
+    /// [parsing style](enum.ParsingStyle.html).
+    ///
+    /// This is equivalent to `parse_args_default_or_exit`, provided for
+    /// compatibility with `structopt`.
+    fn from_args() -> Self where Self: Sized {
+        Self::parse_args_default_or_exit()
+    }
+
+    /// Parses arguments from an iterator as with `parse_args_or_exit`,
+    /// using the default [parsing style](enum.ParsingStyle.html).
+    ///
+    /// As with `std::env::args`, the first item is the program name.
+    /// This is provided for compatibility with `structopt`.
+    ///
+    /// # Panics
+    ///
+    /// If the iterator is empty.
+    fn from_iter<I>(iter: I) -> Self
+            where Self: Sized, I: IntoIterator, I::Item: AsRef<str> {
+        let args = iter.into_iter().map(|s| s.as_ref().to_owned()).collect::<Vec<_>>();
+
+        Self::parse_args_or_exit_with(&args, ParsingStyle::default(),
+            &mut StandardExit::new())
+    }
+
     /// Parses arguments received from the command line,
     /// using the default [parsing style](enum.ParsingStyle.html).
     ///
     /// The first argument (the program name) should be omitted.
-    fn parse_args_default<S: AsRef<str>>(args: &[S]) -> Result<Self, Error> where Self: Sized {
-        Self::parse(&mut Parser::new(args, ParsingStyle::default()))
+    fn parse_args_default<I>(args: I) -> Result<Self, Error>
+            where Self: Sized, I: IntoIterator, I::Item: AsRef<str> {
+        Self::parse_args(args, ParsingStyle::default())
     }
 
     /// Parses options for the named command.
     fn parse_command<S: AsRef<str>>(name: &str, parser: &mut Parser<S>) -> Result<Self, Error> where Self: Sized;
 
+    /// Parses options for the named command, using a parser which may refer
+    /// to arguments of any type.
+    ///
+    /// This is to `parse_command` as `parse_dyn` is to `parse`.
+    ///
+    /// The default implementation calls `parse_command`.
+    fn parse_command_dyn(name: &str, parser: &mut Parser<DynArg>) -> Result<Self, Error>
+            where Self: Sized {
+        Self::parse_command(name, parser)
+    }
+
     /// Returns a string showing usage and help for each supported option.
     ///
     /// Option descriptions are separated by newlines. The returned string
     /// should **not** end with a newline.
     fn usage() -> &'static str where Self: Sized;
 
+    /// Returns a string showing usage and long help for each supported option.
+    ///
+    /// This is displayed by `parse_args_or_exit` when help is requested using
+    /// the long name of a help flag, e.g. `--help`, while `usage` is displayed
+    /// for the short name, e.g. `-h`.
+    ///
+    /// The default implementation returns `usage`.
+    fn long_usage() -> &'static str where Self: Sized {
+        Self::usage()
+    }
+
+    /// Returns usage text as from `usage`, styled for display on a terminal.
+    ///
+    /// If the `style` feature is enabled, option names, meta variables,
+    /// and section headings are colored using ANSI escape sequences,
+    /// provided that `stdout` is a terminal and the `NO_COLOR` environment
+    /// variable is unset. Otherwise, the text of `usage` is returned unchanged.
+    fn usage_styled() -> String where Self: Sized {
+        #[cfg(feature = "style")]
+        {
+            if style::color_enabled(&std::io::stdout()) {
+                return Self::usage_with_style(&AnsiStyle);
+            }
+        }
+
+        Self::usage().to_owned()
+    }
+
+    /// Returns usage text as from `usage`, rendered using the given style.
+    #[cfg(feature = "style")]
+    fn usage_with_style(style: &dyn HelpStyle) -> String where Self: Sized {
+        style::render(Self::usage_markup(), style)
+    }
+
+    /// Returns usage text containing style markers.
+    ///
+    /// This is implemented by `derive(Options)` when the `style` feature is
+    /// enabled. The default implementation returns `usage`.
+    #[doc(hidden)]
+    fn usage_markup() -> &'static str where Self: Sized {
+        Self::usage()
+    }
+
+    /// Returns usage text as from `long_usage`, containing style markers.
+    ///
+    /// This is implemented by `derive(Options)` when the `style` feature is
+    /// enabled. The default implementation returns `long_usage`.
+    #[doc(hidden)]
+    fn long_usage_markup() -> &'static str where Self: Sized {
+        Self::long_usage()
+    }
+
+    /// Returns usage text for the options of this type marked `preview`,
+    /// displayed beneath the name of the command in help text for a parent
+    /// command marked `#[options(preview)]`.
+    ///
+    /// Option descriptions are separated by newlines. The returned string
+    /// should **not** end with a newline.
+    ///
+    /// The default implementation returns an empty string.
+    fn preview() -> &'static str where Self: Sized { "" }
+
+    /// Returns usage text as from `preview`, containing style markers.
+    ///
+    /// This is implemented by `derive(Options)` when the `style` feature is
+    /// enabled. The default implementation returns `preview`.
+    #[doc(hidden)]
+    fn preview_markup() -> &'static str where Self: Sized {
+        Self::preview()
+    }
+
+    /// Returns help text for this type and, recursively, for every subcommand.
+    ///
+    /// Each command's help is formatted as with [`format_help`](fn.format_help.html),
+    /// and separated from the next by an empty line. `command` is the name used
+    /// in the `Usage:` line for this type, typically the program name.
+    ///
+    /// The default implementation returns help for this type only.
+    ///
+    /// The returned string should **not** end with a newline.
+    fn usage_all(command: &str) -> String where Self: Sized {
+        Self::help_info().format(command)
+    }
+
+    /// Returns the program name displayed by `parse_args_or_exit`.
+    ///
+    /// If `None`, the file name of the path by which the program was invoked
+    /// is used, e.g. `prog` rather than `target/debug/prog`.
+    ///
+    /// The default implementation returns `None`.
+    fn program_name() -> Option<&'static str> where Self: Sized { None }
+
+    /// Returns what usage text `parse_args_or_exit` prints following
+    /// an error message.
+    ///
+    /// For types with `derive(Options)`, this is given by the `error_usage`
+    /// attribute.
+    ///
+    /// The default implementation returns `ErrorUsage::Omit`.
+    fn error_usage() -> ErrorUsage where Self: Sized { ErrorUsage::Omit }
+
+    /// Returns text to be displayed after all other sections of help text,
+    /// such as examples, links, or license information.
+    ///
+    /// For types with `derive(Options)`, this is given by the `after_help`
+    /// attribute.
+    ///
+    /// The default implementation returns `None`.
+    fn after_help() -> Option<&'static str> where Self: Sized { None }
+
+    /// Returns each section of help text for this type.
+    fn help_info() -> HelpInfo where Self: Sized {
+        HelpInfo{
+            usage: Self::usage(),
+            long_usage: Self::long_usage(),
+            command_list: Self::command_list(),
+            command_preview: None,
+            after_help: Self::after_help(),
+            command_heading: None,
+        }
+    }
+
     /// Returns a string showing usage and help for this options instance.
     ///
     /// In contrast to `usage`, this method will return usage for a subcommand,
@@ -360,6 +998,32 @@ pub trait Options {
     /// should **not** end with a newline.
     fn self_usage(&self) -> &'static str;
 
+    /// Returns the names of all available commands.
+    ///
+    /// For `enum` types with `derive(Options)`, this is the name of each variant.
+    ///
+    /// For `struct` types containing a field marked `#[options(command)]`,
+    /// `command_names` is called on the command type.
+    /// Otherwise, an empty slice is returned.
+    ///
+    /// The default implementation returns an empty slice.
+    fn command_names() -> &'static [&'static str] where Self: Sized { &[] }
+
+    /// Returns the name, as listed by `command_names`, of the command
+    /// which the user may select by the given name, if any.
+    ///
+    /// For `enum` types marked `#[options(ignore_case)]`, names are matched
+    /// without regard to ASCII case.
+    ///
+    /// For `struct` types containing a field marked `#[options(command)]`,
+    /// `find_command` is called on the command type.
+    ///
+    /// The default implementation returns the name in `command_names`
+    /// which is equal to `name`.
+    fn find_command(name: &str) -> Option<&'static str> where Self: Sized {
+        Self::command_names().iter().copied().find(|&cmd| cmd == name)
+    }
+
     /// Returns a usage string for the named command.
     ///
     /// If the named command does not exist, `None` is returned.
@@ -368,18 +1032,65 @@ pub trait Options {
     /// should **not** end with a newline.
     fn command_usage(command: &str) -> Option<&'static str> where Self: Sized;
 
-    /// Returns a string listing available commands and help text.
+    /// Returns sections of help text for the command named by a sequence
+    /// of command names, e.g. `["alpha", "foo"]` for the subcommand `foo`
+    /// of the command `alpha`.
     ///
-    /// Commands are separated by newlines. The string should **not** end with
-    /// a newline.
+    /// An empty path refers to this type itself.
+    /// If any name in the path is not a recognized command, `None` is returned.
     ///
-    /// For `enum` types with `derive(Options)`, this is the same as `usage`.
+    /// The default implementation recognizes only the empty path.
+    fn command_path_usage<S: AsRef<str>>(path: &[S]) -> Option<HelpInfo> where Self: Sized {
+        if path.is_empty() {
+            Some(Self::help_info())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of `command_path_usage`, containing style markers.
     ///
-    /// For `struct` types containing a field marked `#[options(command)]`,
-    /// `usage` is called on the command type.
-    fn command_list() -> Option<&'static str> where Self: Sized;
+    /// This is implemented by `derive(Options)` when the `style` feature is
+    /// enabled. The default implementation returns `command_path_usage`.
+    #[doc(hidden)]
+    fn command_path_markup<S: AsRef<str>>(path: &[S]) -> Option<HelpInfo> where Self: Sized {
+        Self::command_path_usage(path)
+    }
 
-    /// Returns a listing of available commands and help text.
+    /// Scans remaining arguments for a help or version flag, without parsing
+    /// option values, pushing to `path` the name of each command entered.
+    ///
+    /// This is implemented by `derive(Options)` and used by
+    /// `parse_args_or_exit` to let such a flag take precedence over errors
+    /// in other arguments. The default implementation returns `None`.
+    #[doc(hidden)]
+    fn scan_help(parser: &mut Parser<DynArg>, path: &mut Vec<&'static str>)
+            -> Option<HelpFlag> where Self: Sized {
+        let _ = (parser, path);
+        None
+    }
+
+    /// Scans remaining arguments, as by `scan_help`, following the name
+    /// of a command.
+    #[doc(hidden)]
+    fn scan_help_command(name: &str, parser: &mut Parser<DynArg>,
+            path: &mut Vec<&'static str>) -> Option<HelpFlag> where Self: Sized {
+        let _ = (name, parser, path);
+        None
+    }
+
+    /// Returns a string listing available commands and help text.
+    ///
+    /// Commands are separated by newlines. The string should **not** end with
+    /// a newline.
+    ///
+    /// For `enum` types with `derive(Options)`, this is the same as `usage`.
+    ///
+    /// For `struct` types containing a field marked `#[options(command)]`,
+    /// `usage` is called on the command type.
+    fn command_list() -> Option<&'static str> where Self: Sized;
+
+    /// Returns a listing of available commands and help text.
     ///
     /// In contrast to `usage`, this method will return command list for a subcommand,
     /// if one is selected.
@@ -387,6 +1098,76 @@ pub trait Options {
     /// Commands are separated by newlines. The string should **not** end with
     /// a newline.
     fn self_command_list(&self) -> Option<&'static str>;
+
+    /// Writes help text for this options instance, followed by a newline,
+    /// as printed by `parse_args_or_exit` when help is requested.
+    ///
+    /// Help text includes the usage line and, if a subcommand is selected,
+    /// describes that subcommand. `program` is the program name displayed
+    /// in the usage line.
+    fn print_help<W: Write + ?Sized>(&self, program: &str, w: &mut W) -> io::Result<()>
+            where Self: Sized {
+        writeln!(w, "{}", wrap_to_terminal(self.self_usage_with(program)))
+    }
+
+    /// Writes help text for this type, followed by a newline,
+    /// as printed by `parse_args_or_exit` when help is requested.
+    ///
+    /// `program` is the program name displayed in the usage line.
+    fn print_usage<W: Write + ?Sized>(program: &str, w: &mut W) -> io::Result<()>
+            where Self: Sized {
+        writeln!(w, "{}", wrap_to_terminal(Self::usage_with(program)))
+    }
+
+    /// Returns help text for this type, beginning with a usage line
+    /// naming `program`, e.g. `Usage: prog [OPTIONS]`, followed by
+    /// the text of `usage` and any available commands.
+    ///
+    /// This is the text written by `print_usage`, without wrapping to the
+    /// width of the terminal. The string does **not** end with a newline.
+    fn usage_with(program: &str) -> String where Self: Sized {
+        match Self::command_path_usage::<&str>(&[]) {
+            Some(info) => write_help(program, &info, false, false),
+            None => format_help(program, Self::usage(), Self::command_list())
+        }
+    }
+
+    /// Returns help text for the command at the given path, as printed by
+    /// `parse_args_or_exit` for `help` followed by the command names in `path`,
+    /// without wrapping to the width of the terminal.
+    ///
+    /// The text includes a usage line naming the program and the commands in
+    /// `path`, a description of each option, and any nested commands.
+    /// The program name is that returned by `program_name` or, if `None`,
+    /// the file name of the path by which the program was invoked.
+    ///
+    /// If `path` does not name a valid command, `None` is returned.
+    fn help_for<S: AsRef<str>>(path: &[S]) -> Option<String> where Self: Sized {
+        let info = Self::command_path_usage(path)?;
+        let program = match Self::program_name() {
+            Some(name) => name.to_owned(),
+            None => std::env::args_os().next()
+                .map(|arg| base_name(&arg.to_string_lossy()).to_owned())
+                .unwrap_or_default()
+        };
+
+        Some(write_help(&command_str(&program, path), &info, false, false))
+    }
+
+    /// Returns help text for this options instance, beginning with a usage
+    /// line naming `program` and the selected subcommand, if any,
+    /// e.g. `Usage: prog remote add [OPTIONS]`.
+    ///
+    /// This is the text written by `print_help`, without wrapping to the
+    /// width of the terminal. The string does **not** end with a newline.
+    fn self_usage_with(&self, program: &str) -> String where Self: Sized {
+        let path = self.command_path();
+
+        match Self::command_path_usage(&path) {
+            Some(info) => write_help(&command_str(program, &path), &info, false, false),
+            None => format_help(program, self.self_usage(), self.self_command_list())
+        }
+    }
 }
 
 /// Controls behavior of free arguments in `Parser`
@@ -437,247 +1218,2216 @@ pub enum ParsingStyle {
     StopAtFirstFree,
 }
 
-impl Error {
-    /// Returns an error for a failed attempt at parsing an option value.
-    pub fn failed_parse(opt: Opt, err: String) -> Error {
-        Error{kind: ErrorKind::FailedParse(opt.to_string(), err)}
+/// Usage text printed by `Options::parse_args_or_exit` following an error
+///
+/// Returned by [`Options::error_usage`](trait.Options.html#method.error_usage).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ErrorUsage {
+    /// Print only the error message
+    #[default]
+    Omit,
+    /// Print a usage line for the command in which the error occurred,
+    /// e.g. `Usage: prog [OPTIONS]`
+    Line,
+    /// Print help text for the command in which the error occurred
+    Full,
+}
+
+/// Converts a parsed options value back into command line arguments
+///
+/// Implemented by `derive(Options)` when the type level item `to_args`
+/// or `arbitrary` is given. Parsing the returned arguments yields a value
+/// equal to the original, which is useful for round-trip testing and fuzzing.
+///
+/// # Examples
+///
+/// ```
+/// use gumdrop::{Options, ToArgs};
+///
+/// #[derive(Debug, PartialEq, Options)]
+/// #[options(to_args)]
+/// struct MyOptions {
+///     #[options(free)]
+///     files: Vec<String>,
+///     #[options(help = "be verbose")]
+///     verbose: bool,
+///     #[options(help = "number of jobs")]
+///     jobs: Option<u32>,
+/// }
+///
+/// let opts = MyOptions{
+///     files: vec!["a".to_owned()],
+///     verbose: true,
+///     jobs: Some(4),
+/// };
+///
+/// let args = opts.to_args();
+///
+/// assert_eq!(args, ["--verbose", "--jobs", "4", "--", "a"]);
+/// assert_eq!(MyOptions::parse_args_default(&args).unwrap(), opts);
+/// ```
+pub trait ToArgs {
+    /// Returns arguments which, when parsed, produce a value equal to `self`
+    fn to_args(&self) -> Vec<String>;
+
+    /// Returns the values of options, keyed by long option name.
+    ///
+    /// Flags are given as `Value::Bool`, counting flags as `Value::Count`,
+    /// and options taking a value as `Value::String`, or `Value::Tuple`
+    /// for those taking more than one value. Options which may be given
+    /// more than once are given as `Value::List`. Options of type `Option<T>`
+    /// which are `None` are omitted. Options without a long name,
+    /// free arguments, and commands are not included.
+    ///
+    /// Passing the result to
+    /// [`Options::from_map`](trait.Options.html#method.from_map)
+    /// yields a value with equal options.
+    fn to_map(&self) -> BTreeMap<String, Value>;
+}
+
+/// Receives the commands selected in parsed options
+///
+/// Passed to [`Options::accept`](trait.Options.html#method.accept), so that
+/// commands may be handled generically, e.g. for logging or permission checks,
+/// without matching on each command type.
+///
+/// ```
+/// use gumdrop::{CommandVisitor, Options};
+///
+/// #[derive(Options)]
+/// enum Command {
+///     Remote(RemoteOpts),
+/// }
+///
+/// #[derive(Options)]
+/// struct RemoteOpts {
+///     #[options(command)]
+///     command: Option<RemoteCommand>,
+/// }
+///
+/// #[derive(Options)]
+/// enum RemoteCommand {
+///     Add(AddOpts),
+/// }
+///
+/// #[derive(Options)]
+/// struct AddOpts {
+///     #[options(free)]
+///     name: String,
+/// }
+///
+/// struct Log(Vec<&'static str>);
+///
+/// impl CommandVisitor for Log {
+///     fn visit_command(&mut self, name: &'static str, _opts: &dyn Options) {
+///         self.0.push(name);
+///     }
+/// }
+///
+/// let cmd = Command::parse_args_default(&["remote", "add", "origin"]).unwrap();
+/// let mut log = Log(Vec::new());
+///
+/// cmd.accept(&mut log);
+/// assert_eq!(log.0, ["remote", "add"]);
+/// ```
+pub trait CommandVisitor {
+    /// Visits a selected command, given its name and options
+    fn visit_command(&mut self, name: &'static str, opts: &dyn Options);
+
+    /// Visits an unrecognized command received by a variant marked
+    /// `catch_all`, given its name and the arguments following it
+    ///
+    /// The default implementation does nothing.
+    fn visit_unknown(&mut self, name: &str, args: &[String]) {
+        let _ = (name, args);
     }
+}
 
-    /// Returns an error for a failed attempt at parsing an option's default value.
-    pub fn failed_parse_default(option: &'static str,
-            value: &'static str, err: String) -> Error {
-        Error{kind: ErrorKind::FailedParseDefault{option, value, err}}
+/// Result of [`Options::parse_args_or_help`](trait.Options.html#method.parse_args_or_help)
+#[derive(Debug)]
+pub enum ParseResult<T> {
+    /// Options were parsed successfully
+    Parsed(T),
+    /// Help was requested; contains the help text to be displayed
+    Help(String),
+    /// Version information was requested; contains the text to be displayed
+    Version(String),
+    /// An error was encountered
+    Err(Error),
+}
+
+/// Controls how `Options::parse_args_or_exit` reports help and errors
+///
+/// Each method has a default implementation matching the behavior of
+/// `parse_args_or_exit`: messages are printed to `stderr`, help exits with
+/// status code `0`, errors exit with status code `2`.
+/// Implementations may override any of these, e.g. to capture output in tests.
+///
+/// Used by [`Options::parse_args_or_exit_with`](trait.Options.html#method.parse_args_or_exit_with).
+pub trait ExitStrategy {
+    /// Prints help text requested by the user
+    ///
+    /// The text does **not** end with a newline.
+    fn print_help(&mut self, text: &str) {
+        eprintln!("{}", text);
     }
 
-    /// Returns an error for a failed attempt at parsing an option value.
-    pub fn failed_parse_with_name(name: String, err: String) -> Error {
-        Error{kind: ErrorKind::FailedParse(name, err)}
+    /// Prints a message describing a parsing error
+    ///
+    /// The text does **not** end with a newline.
+    fn print_error(&mut self, text: &str) {
+        eprintln!("{}", text);
     }
 
-    /// Returns an error for an option expecting two or more arguments not
-    /// receiving the expected number of arguments.
-    pub fn insufficient_arguments(opt: Opt, expected: usize, found: usize) -> Error {
-        Error{kind: ErrorKind::InsufficientArguments{
-            option: opt.to_string(),
-            expected: expected,
-            found: found,
-        }}
+    /// Returns whether help text should be styled using color.
+    ///
+    /// This has no effect unless the `style` feature is enabled.
+    fn help_color(&self) -> bool {
+        stderr_color()
     }
 
-    /// Returns an error for an option receiving an unexpected argument value,
-    /// e.g. `--option=value`.
-    pub fn unexpected_argument(opt: Opt) -> Error {
-        Error{kind: ErrorKind::UnexpectedArgument(opt.to_string())}
+    /// Returns whether error messages should be styled using color.
+    ///
+    /// This has no effect unless the `style` feature is enabled.
+    fn error_color(&self) -> bool {
+        stderr_color()
     }
 
-    /// Returns an error for an option expecting two or more argument values
-    /// receiving only one in the long form, e.g. `--option=value`.
+    /// Returns the status code used to exit after printing help
+    fn help_code(&self) -> i32 {
+        0
+    }
+
+    /// Returns the status code used to exit after the given error
+    fn error_code(&self, err: &Error) -> i32 {
+        let _ = err;
+        2
+    }
+
+    /// Returns the formatter used to render error messages,
+    /// e.g. to translate them
+    fn error_formatter(&self) -> &dyn ErrorFormatter {
+        &DefaultErrorFormatter
+    }
+
+    /// Asks the user to confirm an action requested by an option marked
+    /// `confirm`, returning whether the action was confirmed
     ///
-    /// These options must be passed as, e.g. `--option value second-value [...]`.
-    pub fn unexpected_single_argument(opt: Opt, n: usize) -> Error {
-        Error{kind: ErrorKind::UnexpectedSingleArgument(opt.to_string(), n)}
+    /// The default implementation prints the prompt to `stderr` and reads
+    /// a line from `stdin`, accepting `y` or `yes` in any case.
+    /// If `false` is returned, `parse_args_or_exit` exits with status code `1`.
+    fn confirm(&mut self, prompt: &str) -> bool {
+        eprint!("{} [y/N] ", prompt);
+        let _ = io::stderr().flush();
+
+        read_confirmation(&mut io::stdin().lock()).unwrap_or(false)
+    }
+
+    /// Offers to correct an error whose message suggests a similar name,
+    /// e.g. ``unrecognized option `--verbos`; did you mean `--verbose`?``,
+    /// returning whether the correction was accepted
+    ///
+    /// If accepted, arguments are parsed again with the offending argument
+    /// replaced by its [correction](struct.Error.html#method.correction).
+    ///
+    /// The default implementation returns `false`.
+    /// `StandardExit` asks the user when enabled by
+    /// [`offer_corrections`](struct.StandardExit.html#method.offer_corrections).
+    fn accept_suggestion(&mut self, prompt: &str) -> bool {
+        let _ = prompt;
+        false
+    }
+
+    /// Exits the process with the given status code
+    fn exit(&mut self, code: i32) -> ! {
+        std::process::exit(code)
+    }
+}
+
+/// The standard [`ExitStrategy`](trait.ExitStrategy.html),
+/// used by `Options::parse_args_or_exit`
+///
+/// By default, all messages are printed to `stderr`.
+/// Exit codes may be assigned to particular kinds of errors
+/// using `exit_code` or `sysexits`.
+#[derive(Clone, Debug)]
+pub struct StandardExit {
+    help_to_stdout: bool,
+    help2man: bool,
+    pager: bool,
+    offer_corrections: bool,
+    error_code: i32,
+    kind_codes: Vec<(ErrorKind, i32)>,
+}
+
+impl StandardExit {
+    /// Returns a `StandardExit` with the default behavior.
+    pub fn new() -> StandardExit {
+        StandardExit{
+            help_to_stdout: false,
+            help2man: false,
+            pager: false,
+            offer_corrections: false,
+            error_code: 2,
+            kind_codes: Vec::new(),
+        }
+    }
+
+    /// Sets whether help text is printed to `stdout` rather than `stderr`.
+    pub fn help_to_stdout(mut self, stdout: bool) -> StandardExit {
+        self.help_to_stdout = stdout;
+        self
+    }
+
+    /// Prints help and version text in the form expected by `help2man`,
+    /// which generates manual pages from the output of a program's
+    /// `--help` and `--version` options.
+    ///
+    /// Help is printed to `stdout` without color, and options taking an
+    /// argument are listed in the form `--foo=FOO`.
+    pub fn help2man(mut self) -> StandardExit {
+        self.help_to_stdout = true;
+        self.help2man = true;
+        self
+    }
+
+    /// Sets whether help text is displayed using a pager.
+    ///
+    /// When enabled, help text is printed to `stdout`. If `stdout` is a
+    /// terminal, the text is piped to the program named by the `PAGER`
+    /// environment variable or, if it is unset, to `less -FR`.
+    /// If the pager cannot be run, the text is printed directly.
+    /// Setting `PAGER` to an empty value disables the pager.
+    pub fn pager(mut self, pager: bool) -> StandardExit {
+        self.pager = pager;
+        if pager {
+            self.help_to_stdout = true;
+        }
+        self
+    }
+
+    /// Sets whether the user is offered to correct an error whose message
+    /// suggests a similar name.
+    ///
+    /// When enabled, the user is asked, as by `confirm`, to accept the
+    /// suggestion, only when both `stdin` and `stderr` are terminals.
+    /// This is disabled by default.
+    pub fn offer_corrections(mut self, offer: bool) -> StandardExit {
+        self.offer_corrections = offer;
+        self
+    }
+
+    /// Sets the status code used to exit after an error.
+    ///
+    /// This code is used for any kind of error not assigned a code
+    /// by `exit_code`.
+    pub fn default_exit_code(mut self, code: i32) -> StandardExit {
+        self.error_code = code;
+        self
+    }
+
+    /// Sets the status code used to exit after an error of the given kind.
+    pub fn exit_code(mut self, kind: ErrorKind, code: i32) -> StandardExit {
+        self.kind_codes.retain(|&(k, _)| k != kind);
+        self.kind_codes.push((kind, code));
+        self
+    }
+
+    /// Assigns exit codes following the BSD `sysexits.h` convention.
+    ///
+    /// Errors in configuration from environment variables exit with
+    /// `EX_CONFIG` (`78`); invalid `default` values, which indicate a bug in
+    /// the program, exit with `EX_SOFTWARE` (`70`); all other errors exit
+    /// with `EX_USAGE` (`64`).
+    pub fn sysexits(self) -> StandardExit {
+        self.default_exit_code(64)
+            .exit_code(ErrorKind::FailedParseEnv, 78)
+            .exit_code(ErrorKind::FailedParseDefault, 70)
+    }
+}
+
+impl Default for StandardExit {
+    fn default() -> StandardExit {
+        StandardExit::new()
+    }
+}
+
+impl ExitStrategy for StandardExit {
+    fn print_help(&mut self, text: &str) {
+        let text = if self.help2man {
+            help2man_options(text)
+        } else {
+            text.to_owned()
+        };
+
+        if self.pager && page(&text) {
+            return;
+        }
+
+        if self.help_to_stdout {
+            println!("{}", text);
+        } else {
+            eprintln!("{}", text);
+        }
+    }
+
+    fn help_color(&self) -> bool {
+        if self.help2man {
+            false
+        } else if self.help_to_stdout {
+            stdout_color()
+        } else {
+            stderr_color()
+        }
+    }
+
+    fn accept_suggestion(&mut self, prompt: &str) -> bool {
+        use std::io::IsTerminal;
+
+        self.offer_corrections && io::stdin().is_terminal() &&
+            io::stderr().is_terminal() && self.confirm(prompt)
+    }
+
+    fn error_code(&self, err: &Error) -> i32 {
+        let kind = err.kind();
+
+        self.kind_codes.iter()
+            .find(|&&(k, _)| k == kind)
+            .map_or(self.error_code, |&(_, code)| code)
+    }
+}
+
+#[cfg(feature = "style")]
+fn stdout_color() -> bool {
+    style::color_enabled(&std::io::stdout())
+}
+
+#[cfg(not(feature = "style"))]
+fn stdout_color() -> bool {
+    false
+}
+
+#[cfg(feature = "style")]
+fn stderr_color() -> bool {
+    style::color_enabled(&std::io::stderr())
+}
+
+#[cfg(not(feature = "style"))]
+fn stderr_color() -> bool {
+    false
+}
+
+/// Describes a single option
+///
+/// Returned by [`Options::options_info`](trait.Options.html#method.options_info).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct OptionInfo {
+    /// Long name of the option, without the leading `--`
+    pub long: Option<&'static str>,
+    /// Short name of the option
+    pub short: Option<char>,
+    /// Name of the value taken by the option, e.g. `FOO`
+    pub meta: Option<&'static str>,
+    /// Help text for the option
+    pub help: Option<&'static str>,
+    /// Whether the option is required
+    pub required: bool,
+    /// Whether the option takes an argument
+    pub takes_value: bool,
+    /// Default value of the option, as displayed in usage text
+    pub default: Option<&'static str>,
+}
+
+/// Value of an option, as returned by
+/// [`ToArgs::to_map`](trait.ToArgs.html#tymethod.to_map)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    /// Whether a flag is given
+    Bool(bool),
+    /// Number of times a counting flag is given
+    Count(usize),
+    /// Value of an option taking a single value
+    String(String),
+    /// Values of an option taking more than one value
+    Tuple(Vec<String>),
+    /// Values of an option which may be given more than once
+    List(Vec<Value>),
+}
+
+/// Describes a single command
+///
+/// Returned by [`Options::commands_info`](trait.Options.html#method.commands_info).
+#[derive(Copy, Clone, Debug)]
+pub struct CommandInfo {
+    /// Name of the command
+    pub name: &'static str,
+    /// Help text for the command
+    pub help: Option<&'static str>,
+    /// Returns a description of each option of the command,
+    /// as given by `Options::options_info`
+    pub options: fn() -> &'static [OptionInfo],
+    /// Returns a description of each subcommand of the command,
+    /// as given by `Options::commands_info`
+    pub commands: fn() -> &'static [CommandInfo],
+    /// Returns the structure of usage text for the command,
+    /// as given by `Options::usage_model`
+    pub usage_model: fn() -> UsageModel,
+}
+
+/// Describes the options and commands of a type implementing `Options`
+///
+/// Returned by [`Options::cli_spec`](trait.Options.html#method.cli_spec).
+///
+/// # Examples
+///
+/// ```
+/// use gumdrop::Options;
+///
+/// #[derive(Options)]
+/// struct MyOptions {
+///     #[options(help = "print help message")]
+///     help: bool,
+///     #[options(command)]
+///     command: Option<Command>,
+/// }
+///
+/// #[derive(Options)]
+/// enum Command {
+///     #[options(help = "make stuff")]
+///     Make(MakeOptions),
+/// }
+///
+/// #[derive(Options)]
+/// struct MakeOptions {
+///     #[options(help = "number of jobs")]
+///     jobs: u32,
+/// }
+///
+/// let spec = MyOptions::cli_spec();
+///
+/// assert_eq!(spec.options[0].long, Some("help"));
+/// assert_eq!(spec.commands[0].name, "make");
+/// assert_eq!((spec.commands[0].options)()[0].long, Some("jobs"));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct CliSpec {
+    /// Options accepted by the type, as given by `Options::options_info`
+    pub options: &'static [OptionInfo],
+    /// Commands accepted by the type, as given by `Options::commands_info`
+    pub commands: &'static [CommandInfo],
+}
+
+/// Structured form of usage text
+///
+/// Returned by [`Options::usage_model`](trait.Options.html#method.usage_model).
+///
+/// For `struct` types, the model describes the text returned by
+/// `Options::usage`, which is produced by `render`. For `enum` types,
+/// the model contains a single section listing available commands.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct UsageModel {
+    /// Description of the program or command, preceding all sections
+    pub description: Option<&'static str>,
+    /// Column at which help text begins in each row
+    pub help_column: usize,
+    /// Whether help text begins on the line following the name of each
+    /// item, rather than on the same line
+    pub next_line_help: bool,
+    /// Sections of usage text, in the order displayed
+    pub sections: &'static [UsageSection],
+}
+
+/// A section of usage text, e.g. `Optional arguments:`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UsageSection {
+    /// Kind of items listed in the section
+    pub kind: SectionKind,
+    /// Heading of the section
+    pub heading: &'static str,
+    /// Items listed in the section
+    pub rows: &'static [UsageRow],
+}
+
+/// Kind of items listed in a [`UsageSection`](struct.UsageSection.html)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SectionKind {
+    /// Positional arguments
+    Positional,
+    /// Options, e.g. `--foo`
+    Optional,
+    /// Commands
+    Commands,
+    /// Example invocations
+    Examples,
+}
+
+/// A single item within a [`UsageSection`](struct.UsageSection.html)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UsageRow {
+    /// Name of the item, e.g. `-f, --foo` for an option
+    pub name: &'static str,
+    /// Name of the value taken by an option, e.g. `FOO`
+    pub meta: Option<&'static str>,
+    /// Help text for the item
+    pub help: Option<&'static str>,
+    /// Notes following help text, e.g. `(default: 1)`
+    pub notes: &'static [&'static str],
+}
+
+impl UsageModel {
+    /// Renders the model as usage text.
+    ///
+    /// For `struct` types, this is the same text as returned by `Options::usage`.
+    /// The returned string does **not** end with a newline.
+    pub fn render(&self) -> String {
+        self.render_localized(&|_| None)
+    }
+
+    /// Renders the model as usage text, replacing the description, headings,
+    /// and the value names, help text, and notes of each row with the text
+    /// returned by `lookup`, if any.
+    ///
+    /// Names of options and commands are not replaced, and help text
+    /// begins at the same column as in untranslated text.
+    /// The returned string does **not** end with a newline.
+    ///
+    /// ```
+    /// use gumdrop::Options;
+    ///
+    /// #[derive(Options)]
+    /// struct MyOptions {
+    ///     #[options(help = "print help message")]
+    ///     help: bool,
+    /// }
+    ///
+    /// let usage = MyOptions::usage_model().render_localized(&|text| match text {
+    ///     "Optional arguments:" => Some("Optionen:".to_owned()),
+    ///     "print help message" => Some("Hilfe anzeigen".to_owned()),
+    ///     _ => None
+    /// });
+    ///
+    /// # #[cfg(not(feature = "strip_help"))]
+    /// assert_eq!(usage, "Optionen:\n  -h, --help  Hilfe anzeigen");
+    /// ```
+    pub fn render_localized(&self, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+        let mut res = String::new();
+
+        if let Some(desc) = self.description {
+            res.push_str(&localize(desc, lookup));
+            res.push('\n');
+        }
+
+        for section in self.sections {
+            if !res.is_empty() {
+                res.push('\n');
+            }
+
+            res.push_str(&localize(section.heading, lookup));
+            res.push('\n');
+
+            let rows = section.render_rows(self.help_column, self.next_line_help, lookup);
+
+            if !rows.is_empty() {
+                res.push_str(&rows);
+                res.push('\n');
+            }
+        }
+
+        res.pop();
+        res
+    }
+}
+
+impl UsageSection {
+    /// Renders the rows of the section, without its heading, as lines of
+    /// usage text, replacing text as by `UsageModel::render_localized`.
+    ///
+    /// For `enum` types, rendering the single section of the model
+    /// produces the text returned by `Options::usage`.
+    /// The returned string does **not** end with a newline.
+    pub fn render_rows(&self, help_column: usize, next_line_help: bool,
+            lookup: &dyn Fn(&str) -> Option<String>) -> String {
+        self.rows.iter()
+            .map(|row| row.render_localized(help_column, next_line_help, lookup))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl UsageRow {
+    /// Renders the row as a line of usage text, with help text beginning
+    /// at the given column.
+    pub fn render(&self, help_column: usize) -> String {
+        self.render_localized(help_column, false, &|_| None)
     }
 
-    /// Returns an error for a missing required argument.
-    pub fn missing_argument(opt: Opt) -> Error {
-        Error{kind: ErrorKind::MissingArgument(opt.to_string())}
+    /// Renders the row, beginning help text on the following line
+    /// if `next_line` is `true` and replacing text returned by `lookup`
+    fn render_localized(&self, help_column: usize, next_line: bool,
+            lookup: &dyn Fn(&str) -> Option<String>) -> String {
+        use unicode_width::UnicodeWidthStr;
+
+        let mut line = String::from("  ");
+
+        line.push_str(self.name);
+
+        if let Some(meta) = self.meta {
+            line.push(' ');
+            line.push_str(&localize(meta, lookup));
+        }
+
+        if self.help.is_some() || !self.notes.is_empty() {
+            let len = line.width();
+
+            if len < help_column && !next_line {
+                line.push_str(&" ".repeat(help_column - len));
+            } else {
+                line.push('\n');
+                line.push_str(&" ".repeat(help_column));
+            }
+        }
+
+        if let Some(help) = self.help {
+            for (i, text) in localize(help, lookup).split('\n').enumerate() {
+                if i != 0 {
+                    line.push('\n');
+
+                    if !text.is_empty() {
+                        line.push_str(&" ".repeat(help_column));
+                    }
+                }
+
+                line.push_str(text);
+            }
+        }
+
+        for (i, note) in self.notes.iter().enumerate() {
+            if self.help.is_some() || i != 0 {
+                line.push(' ');
+            }
+
+            line.push_str(&localize(note, lookup));
+        }
+
+        line
+    }
+}
+
+/// Returns the replacement of `text` given by `lookup`, if any
+fn localize<'a>(text: &'a str, lookup: &dyn Fn(&str) -> Option<String>) -> Cow<'a, str> {
+    match lookup(text) {
+        Some(s) => Cow::Owned(s),
+        None => Cow::Borrowed(text)
+    }
+}
+
+/// Sections of help text for a single command
+///
+/// Returned by [`Options::help_info`](trait.Options.html#method.help_info)
+/// and [`Options::command_path_usage`](trait.Options.html#method.command_path_usage).
+// `command_preview` is compared by address, which suffices for comparing
+// values returned by the same implementation
+#[allow(unknown_lints, unpredictable_function_pointer_comparisons)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct HelpInfo {
+    /// Option usage, as returned by `Options::usage`
+    pub usage: &'static str,
+    /// Option usage with long help text, as returned by `Options::long_usage`
+    pub long_usage: &'static str,
+    /// Command list, as returned by `Options::command_list`
+    pub command_list: Option<&'static str>,
+    /// Returns the command list with a preview of the options of each
+    /// command, as given by `Options::preview`, which is displayed in place
+    /// of `command_list`.
+    ///
+    /// For `enum` types with `derive(Options)`, this is given when
+    /// the `preview` attribute is present.
+    pub command_preview: Option<fn() -> String>,
+    /// Trailing text, as returned by `Options::after_help`
+    pub after_help: Option<&'static str>,
+    /// Heading preceding the command list.
+    /// If `None`, the heading `Available commands:` is used.
+    pub command_heading: Option<&'static str>,
+}
+
+impl HelpInfo {
+    /// Formats help text in the form printed by
+    /// [`parse_args_or_exit`](trait.Options.html#method.parse_args_or_exit).
+    ///
+    /// `command` is the program name, followed by the names of any selected
+    /// subcommands, separated by spaces.
+    ///
+    /// The returned string does **not** end with a newline.
+    pub fn format(&self, command: &str) -> String {
+        write_help(command, self, false, false)
+    }
+
+    /// Formats help text as with `format`, using long help text for options.
+    pub fn format_long(&self, command: &str) -> String {
+        write_help(command, self, true, false)
+    }
+}
+
+impl Error {
+    const fn new(repr: Repr) -> Error {
+        Error{repr, position: None, command_path: Vec::new()}
+    }
+
+    /// Returns an error for two options which may not be given together.
+    pub fn conflicting_options(opt: &str, other: &str) -> Error {
+        Error::new(Repr::ConflictingOptions(
+            Cow::Owned(opt.to_owned()), Cow::Owned(other.to_owned())))
+    }
+
+    /// Returns an error for two options which may not be given together,
+    /// without allocating.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub const fn conflicting_options_static(opt: &'static str, other: &'static str) -> Error {
+        Error::new(Repr::ConflictingOptions(Cow::Borrowed(opt), Cow::Borrowed(other)))
+    }
+
+    /// Returns an error with an arbitrary payload, e.g. an error type
+    /// returned from a `parse(try_from_str = "...")` function or from
+    /// validation performed by the application.
+    ///
+    /// The payload may be retrieved using `Error::get_ref` or
+    /// `Error::downcast_ref`.
+    pub fn custom<E>(err: E) -> Error
+            where E: Into<Box<dyn StdError + Send + Sync>> {
+        Error::new(Repr::Custom(err.into()))
+    }
+
+    /// Returns an error for a failed attempt at parsing an option value.
+    pub fn failed_parse(opt: Opt, err: String) -> Error {
+        Error::new(Repr::FailedParse(opt.to_string(), err.into()))
+    }
+
+    /// Returns an error for a failed attempt at parsing an option's default value.
+    pub fn failed_parse_default(option: &'static str,
+            value: &'static str, err: String) -> Error {
+        Error::new(Repr::FailedParseDefault{option, value, err})
+    }
+
+    /// Returns an error for a failed attempt at parsing an option value
+    /// taken from an environment variable.
+    pub fn failed_parse_env(var: &'static str, err: String) -> Error {
+        Error::new(Repr::FailedParseEnv{var, err})
+    }
+
+    /// Returns an error for a failed attempt at parsing an option value.
+    pub fn failed_parse_with_name(name: String, err: String) -> Error {
+        Error::new(Repr::FailedParse(name, err.into()))
+    }
+
+    /// Returns an error for a failed attempt at parsing an option value,
+    /// retaining the underlying error, which is returned by `Error::source`.
+    pub fn failed_parse_with_source<E>(name: String, err: E) -> Error
+            where E: Into<Box<dyn StdError + Send + Sync>> {
+        Error::new(Repr::FailedParse(name, err.into()))
+    }
+
+    /// Returns an error for a failed attempt at parsing a free argument,
+    /// `value`, given for the field `name`, retaining the underlying error,
+    /// which is returned by `Error::source`.
+    ///
+    /// `ordinal` is the position of the argument among free arguments,
+    /// beginning at `1`.
+    pub fn failed_parse_free<E>(name: &'static str, ordinal: usize, value: &str, err: E) -> Error
+            where E: Into<Box<dyn StdError + Send + Sync>> {
+        Error::new(Repr::FailedParseFree{
+            name,
+            ordinal,
+            value: value.into(),
+            err: err.into(),
+        })
+    }
+
+    /// Returns an error indicating that the user invoked a generated `help`
+    /// command, requesting help for the command named by the given path.
+    ///
+    /// An empty path requests help for the program itself.
+    ///
+    /// This error is returned only when a command enum is parsed directly.
+    /// When the enum is parsed as the command of a struct, the request is
+    /// instead recorded by [`Parser::report_error`](struct.Parser.html#method.report_error)
+    /// and the struct is parsed as if a help flag were given.
+    pub fn help_command(path: Vec<String>) -> Error {
+        Error::new(Repr::HelpCommand(path))
+    }
+
+    /// Prepends a command name to the path of commands within which the
+    /// error occurred and, for a `help_command` error, to the path of the
+    /// command for which help was requested.
+    #[doc(hidden)]
+    pub fn in_command(mut self, name: &str) -> Error {
+        if let Repr::HelpCommand(path) = &mut self.repr {
+            path.insert(0, name.to_owned());
+        }
+        self.command_path.insert(0, name.to_owned());
+        self
+    }
+
+    /// Returns the names of the commands within which this error occurred,
+    /// e.g. `["remote", "add"]` for an invalid option to `prog remote add`.
+    ///
+    /// An empty path indicates that the error occurred in options
+    /// of the program itself.
+    pub fn command_path(&self) -> &[String] {
+        &self.command_path
+    }
+
+    /// Returns an error for an option expecting two or more arguments not
+    /// receiving the expected number of arguments.
+    pub fn insufficient_arguments(opt: Opt, expected: usize, found: usize) -> Error {
+        Error::new(Repr::InsufficientArguments{
+            option: opt.to_string(),
+            expected,
+            found,
+        })
+    }
+
+    /// Returns an error for a number of free arguments, `found`, which is
+    /// less than `min` or greater than `max`.
+    pub const fn invalid_free_count(min: usize, max: Option<usize>, found: usize) -> Error {
+        Error::new(Repr::InvalidFreeCount{min, max, found})
+    }
+
+    /// Returns an error for an option value which failed to parse,
+    /// given the values which the option accepts.
+    pub fn invalid_value(name: String, value: &str,
+            possible: &'static [&'static str]) -> Error {
+        Error::new(Repr::InvalidValue{
+            option: name,
+            value: value.to_owned(),
+            possible,
+        })
+    }
+
+    /// Returns an error for an option receiving an unexpected argument value,
+    /// e.g. `--option=value`.
+    pub fn unexpected_argument(opt: Opt) -> Error {
+        Error::new(Repr::UnexpectedArgument(opt.to_string()))
+    }
+
+    /// Returns an error for an option expecting two or more argument values
+    /// receiving only one in the long form, e.g. `--option=value`.
+    ///
+    /// These options must be passed as, e.g. `--option value second-value [...]`.
+    pub fn unexpected_single_argument(opt: Opt, n: usize) -> Error {
+        Error::new(Repr::UnexpectedSingleArgument(opt.to_string(), n))
+    }
+
+    /// Returns an error for a missing required argument.
+    pub fn missing_argument(opt: Opt) -> Error {
+        Error::new(Repr::MissingArgument(opt.to_string()))
+    }
+
+    /// Returns an error for a missing command name.
+    pub const fn missing_command() -> Error {
+        Error::new(Repr::MissingCommand(&[]))
+    }
+
+    /// Returns an error for a missing required option.
+    pub fn missing_required(opt: &str) -> Error {
+        Error::new(Repr::MissingRequired(Cow::Owned(opt.to_owned())))
+    }
+
+    /// Returns an error for a missing required option, without allocating.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gumdrop::{Error, ErrorKind};
+    ///
+    /// const MISSING_FOO: Error = Error::missing_required_static("--foo");
+    ///
+    /// assert_eq!(MISSING_FOO.kind(), ErrorKind::MissingRequired);
+    /// assert_eq!(MISSING_FOO.to_string(), "missing required option `--foo`");
+    /// ```
+    pub const fn missing_required_static(opt: &'static str) -> Error {
+        Error::new(Repr::MissingRequired(Cow::Borrowed(opt)))
+    }
+
+    /// Returns an error for a missing required command.
+    pub const fn missing_required_command() -> Error {
+        Error::new(Repr::MissingRequiredCommand(&[]))
+    }
+
+    /// Returns an error for a missing required free argument.
+    pub const fn missing_required_free() -> Error {
+        Error::new(Repr::MissingRequiredFree)
+    }
+
+    /// Returns an error for a free argument made up of `expected` consecutive
+    /// arguments, of which only `found` were given.
+    ///
+    /// The error is of kind `MissingRequiredFree`.
+    pub const fn missing_required_free_count(expected: usize, found: usize) -> Error {
+        Error::new(Repr::MissingRequiredFreeCount{expected, found})
+    }
+
+    /// Returns an error for an option given without another option
+    /// which it requires.
+    pub fn missing_requirement(opt: &str, required: &str) -> Error {
+        Error::new(Repr::MissingRequirement(
+            Cow::Owned(opt.to_owned()), Cow::Owned(required.to_owned())))
+    }
+
+    /// Returns an error for an option given without another option
+    /// which it requires, without allocating.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub const fn missing_requirement_static(opt: &'static str, required: &'static str) -> Error {
+        Error::new(Repr::MissingRequirement(Cow::Borrowed(opt), Cow::Borrowed(required)))
+    }
+
+    /// Returns an error when a free argument was encountered, but the options
+    /// type does not support free arguments.
+    pub fn unexpected_free(arg: &str) -> Error {
+        Error::new(Repr::UnexpectedFree(arg.to_owned()))
+    }
+
+    /// Returns an error for an unrecognized command.
+    pub fn unrecognized_command(name: &str) -> Error {
+        Error::new(Repr::UnrecognizedCommand(name.to_owned(), &[]))
+    }
+
+    /// Returns an error for an unrecognized option.
+    pub fn unrecognized_option(opt: Opt) -> Error {
+        match opt {
+            Opt::Short(short) => Error::unrecognized_short(short),
+            Opt::Long(long) | Opt::LongWithArg(long, _) =>
+                Error::unrecognized_long(long),
+            Opt::Free(_) => panic!("`Error::unrecognized_option` called with `Opt::Free` value")
+        }
+    }
+
+    /// Returns an error for an unrecognized long option, e.g. `--option`.
+    pub fn unrecognized_long(opt: &str) -> Error {
+        Error::new(Repr::UnrecognizedLongOption(opt.to_owned(), &[]))
+    }
+
+    /// Returns an error for an unrecognized short option, e.g. `-o`.
+    pub const fn unrecognized_short(opt: char) -> Error {
+        Error::new(Repr::UnrecognizedShortOption(opt))
+    }
+
+    /// Attaches the names of available commands to an error for a missing
+    /// or unrecognized command.
+    ///
+    /// Errors of any other kind are returned unchanged.
+    pub fn with_commands(mut self, commands: &'static [&'static str]) -> Error {
+        match &mut self.repr {
+            Repr::MissingCommand(cmds) |
+            Repr::MissingRequiredCommand(cmds) |
+            Repr::UnrecognizedCommand(_, cmds) => *cmds = commands,
+            _ => ()
+        }
+        self
+    }
+
+    /// Attaches the names of available long options to an error for an
+    /// unrecognized long option, e.g. to suggest a similar option name.
+    ///
+    /// Errors of any other kind are returned unchanged.
+    pub fn with_options(mut self, options: &'static [&'static str]) -> Error {
+        if let Repr::UnrecognizedLongOption(_, opts) = &mut self.repr {
+            *opts = options;
+        }
+        self
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self.repr {
+            Repr::ConflictingOptions(..) => ErrorKind::ConflictingOptions,
+            Repr::Custom(_) => ErrorKind::Custom,
+            Repr::FailedParse(..) |
+            Repr::FailedParseFree{..} => ErrorKind::FailedParse,
+            Repr::FailedParseDefault{..} => ErrorKind::FailedParseDefault,
+            Repr::FailedParseEnv{..} => ErrorKind::FailedParseEnv,
+            Repr::HelpCommand(_) => ErrorKind::HelpCommand,
+            Repr::InsufficientArguments{..} => ErrorKind::InsufficientArguments,
+            Repr::InvalidFreeCount{..} => ErrorKind::InvalidFreeCount,
+            Repr::InvalidValue{..} => ErrorKind::InvalidValue,
+            Repr::MissingArgument(_) => ErrorKind::MissingArgument,
+            Repr::MissingCommand(_) => ErrorKind::MissingCommand,
+            Repr::MissingRequired(_) => ErrorKind::MissingRequired,
+            Repr::MissingRequiredCommand(_) => ErrorKind::MissingRequiredCommand,
+            Repr::MissingRequiredFree |
+            Repr::MissingRequiredFreeCount{..} => ErrorKind::MissingRequiredFree,
+            Repr::MissingRequirement(..) => ErrorKind::MissingRequirement,
+            Repr::UnexpectedArgument(_) => ErrorKind::UnexpectedArgument,
+            Repr::UnexpectedSingleArgument(..) => ErrorKind::UnexpectedSingleArgument,
+            Repr::UnexpectedFree(_) => ErrorKind::UnexpectedFree,
+            Repr::UnrecognizedCommand(..) => ErrorKind::UnrecognizedCommand,
+            Repr::UnrecognizedLongOption(..) |
+            Repr::UnrecognizedShortOption(_) => ErrorKind::UnrecognizedOption,
+        }
+    }
+
+    /// Returns the name of the option which caused this error, if any,
+    /// e.g. `--option` or `-o`.
+    ///
+    /// For an error resulting from an option's default value,
+    /// the name of its field is returned.
+    ///
+    /// For an error naming both forms of an option, e.g. a missing required
+    /// option `-o, --option`, the last form is returned.
+    pub fn option(&self) -> Option<String> {
+        match &self.repr {
+            Repr::ConflictingOptions(opt, _) |
+            Repr::MissingRequired(opt) |
+            Repr::MissingRequirement(opt, _) =>
+                opt.rsplit(", ").next().map(|opt| opt.to_owned()),
+            Repr::FailedParse(opt, _) |
+            Repr::InsufficientArguments{option: opt, ..} |
+            Repr::InvalidValue{option: opt, ..} |
+            Repr::MissingArgument(opt) |
+            Repr::UnexpectedArgument(opt) |
+            Repr::UnexpectedSingleArgument(opt, _) => Some(opt.clone()),
+            Repr::FailedParseDefault{option, ..} |
+            Repr::FailedParseFree{name: option, ..} => Some((*option).to_owned()),
+            Repr::UnrecognizedLongOption(opt, _) => Some(format!("--{}", opt)),
+            Repr::UnrecognizedShortOption(opt) => Some(format!("-{}", opt)),
+            _ => None
+        }
+    }
+
+    /// Returns the name of the unrecognized command which caused this error,
+    /// if any.
+    pub fn command(&self) -> Option<&str> {
+        match &self.repr {
+            Repr::UnrecognizedCommand(cmd, _) => Some(cmd),
+            _ => None
+        }
+    }
+
+    /// Returns the payload of an error created by `Error::custom` or the
+    /// underlying error of a failed attempt at parsing an option value.
+    ///
+    /// For options of a generic type, the underlying error of parsing a field
+    /// whose type involves a type parameter, e.g. `T` or `Vec<T>`, or which is
+    /// parsed by a `contextual` function, is not retained; only its message
+    /// is available.
+    pub fn get_ref(&self) -> Option<&(dyn StdError + Send + Sync + 'static)> {
+        match &self.repr {
+            Repr::Custom(err) |
+            Repr::FailedParse(_, err) |
+            Repr::FailedParseFree{err, ..} => Some(&**err),
+            _ => None
+        }
+    }
+
+    /// Returns the payload or underlying parse error of this error,
+    /// as with `Error::get_ref`, if it is of type `T`.
+    ///
+    /// If a parse function returned an `Error` created by `Error::custom`,
+    /// its payload is returned.
+    pub fn downcast_ref<T: StdError + 'static>(&self) -> Option<&T> {
+        let err = self.get_ref()?;
+
+        match err.downcast_ref::<Error>() {
+            Some(inner) => inner.downcast_ref(),
+            None => err.downcast_ref()
+        }
+    }
+
+    /// Returns the index of the argument at which this error occurred, if known.
+    ///
+    /// The index refers to the arguments given to the `Parser`, which do not
+    /// include the program name.
+    pub fn position(&self) -> Option<usize> {
+        self.position.as_ref().map(|(index, _)| *index)
+    }
+
+    /// Returns the argument at which this error occurred, if known.
+    pub fn argument(&self) -> Option<&str> {
+        self.position.as_ref().map(|(_, arg)| &arg[..])
+    }
+
+    /// Records the position of the argument most recently consumed by the
+    /// given parser, if this error concerns a particular argument.
+    ///
+    /// Errors which already have a position, or which do not concern
+    /// a particular argument, e.g. a missing required option,
+    /// are returned unchanged.
+    ///
+    /// This is called by `Options::parse_args` and similar methods.
+    pub fn at_position<S: AsRef<str>>(mut self, parser: &Parser<S>) -> Error {
+        let located = matches!(self.repr,
+            Repr::FailedParse(..) |
+            Repr::FailedParseFree{..} |
+            Repr::InsufficientArguments{..} |
+            Repr::InvalidValue{..} |
+            Repr::MissingArgument(_) |
+            Repr::UnexpectedArgument(_) |
+            Repr::UnexpectedSingleArgument(..) |
+            Repr::UnexpectedFree(_) |
+            Repr::UnrecognizedCommand(..) |
+            Repr::UnrecognizedLongOption(..) |
+            Repr::UnrecognizedShortOption(_));
+
+        if located && self.position.is_none() {
+            self.position = parser.current_arg()
+                .map(|(index, arg)| (index, arg.to_owned()));
+        }
+
+        self
+    }
+
+    /// For an error resulting from a missing or unrecognized command,
+    /// returns the names of the available commands, if they are known.
+    pub fn available_commands(&self) -> Option<&'static [&'static str]> {
+        match self.repr {
+            Repr::MissingCommand(cmds) |
+            Repr::MissingRequiredCommand(cmds) |
+            Repr::UnrecognizedCommand(_, cmds) if !cmds.is_empty() => Some(cmds),
+            _ => None
+        }
+    }
+
+    /// For an error resulting from an invalid option value,
+    /// returns the values which the option accepts.
+    pub fn possible_values(&self) -> Option<&'static [&'static str]> {
+        match self.repr {
+            Repr::InvalidValue{possible, ..} => Some(possible),
+            _ => None
+        }
+    }
+
+    /// For an error whose message suggests a similar option name, command
+    /// name, or value, returns the position of the offending argument and
+    /// the argument as it would read with the suggestion applied.
+    ///
+    /// ```
+    /// use gumdrop::Options;
+    ///
+    /// #[derive(Debug, Options)]
+    /// struct MyOptions {
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let err = MyOptions::parse_args_default(&["--verbos"]).unwrap_err();
+    ///
+    /// assert_eq!(err.correction(), Some((0, "--verbose".to_owned())));
+    /// ```
+    pub fn correction(&self) -> Option<(usize, String)> {
+        let (index, arg) = self.position.as_ref()?;
+
+        let corrected = match &self.repr {
+            Repr::UnrecognizedCommand(cmd, cmds) if arg == cmd =>
+                suggest(cmd, cmds)?.to_owned(),
+            Repr::UnrecognizedLongOption(opt, opts) => {
+                let rest = arg.strip_prefix("--")?.strip_prefix(opt.as_str())?;
+
+                if !rest.is_empty() && !rest.starts_with('=') {
+                    return None;
+                }
+
+                format!("--{}{}", suggest(opt, opts)?, rest)
+            }
+            Repr::InvalidValue{value, possible, ..} => {
+                let prefix = arg.strip_suffix(value.as_str())?;
+
+                format!("{}{}", prefix, suggest_value(value, possible)?)
+            }
+            _ => return None
+        };
+
+        Some((*index, corrected))
+    }
+
+    /// If this error was produced by a generated `help` command,
+    /// returns the path of command names for which help was requested.
+    pub fn help_command_path(&self) -> Option<&[String]> {
+        match &self.repr {
+            Repr::HelpCommand(path) => Some(path),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    /// Formats the error message.
+    ///
+    /// The alternate form, `{:#}`, prefixes the message with the position
+    /// of the offending argument, if known, e.g.
+    /// ``argument 3 (`--frob`): unrecognized option `--frob` ``.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            if let Some((index, arg)) = &self.position {
+                write!(f, "argument {} (`{}`): ", index + 1, arg)?;
+            }
+        }
+
+        fmt::Display::fmt(&self.message(), f)
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        "failed to parse arguments"
+    }
+
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match &self.repr {
+            Repr::Custom(err) => err.source(),
+            Repr::FailedParse(_, err) |
+            Repr::FailedParseFree{err, ..} => Some(&**err),
+            _ => None
+        }
+    }
+}
+
+/// Kind of flag found by `Options::scan_help`
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HelpFlag {
+    Help,
+    HelpAll,
+    Version,
+}
+
+// `derive(Options)` implementations convert parse errors into the source of
+// an `Error` using these items. An error type implementing `std::error::Error`
+// is retained as is, while any other type is converted using `Display`.
+// The method is selected by autoref: `(&ParseErrorSource::new(e)).take_source()`.
+
+#[doc(hidden)]
+pub struct ParseErrorSource<E>(Cell<Option<E>>);
+
+impl<E> ParseErrorSource<E> {
+    #[doc(hidden)]
+    pub fn new(err: E) -> ParseErrorSource<E> {
+        ParseErrorSource(Cell::new(Some(err)))
+    }
+
+    fn take(&self) -> E {
+        self.0.take().expect("parse error already taken")
+    }
+}
+
+#[doc(hidden)]
+pub trait SourceFromError {
+    fn take_source(&self) -> Box<dyn StdError + Send + Sync>;
+}
+
+impl<E: StdError + Send + Sync + 'static> SourceFromError for ParseErrorSource<E> {
+    fn take_source(&self) -> Box<dyn StdError + Send + Sync> {
+        Box::new(self.take())
+    }
+}
+
+#[doc(hidden)]
+pub trait SourceFromDisplay {
+    fn take_source(&self) -> Box<dyn StdError + Send + Sync>;
+}
+
+impl<E: fmt::Display> SourceFromDisplay for &ParseErrorSource<E> {
+    fn take_source(&self) -> Box<dyn StdError + Send + Sync> {
+        self.take().to_string().into()
+    }
+}
+
+impl<'a, S: 'a + AsRef<str>> Parser<'a, S> {
+    /// Returns a new parser for the given series of arguments.
+    ///
+    /// The given slice should **not** contain the program name as its first
+    /// element.
+    pub fn new(args: &'a [S], style: ParsingStyle) -> Parser<'a, S> {
+        Parser{
+            args: Args::Slice(args),
+            pos: 0,
+            cur: None,
+            style,
+            terminated: false,
+            long_help: false,
+            prompts: Vec::new(),
+            help_command: None,
+            errors: None,
+            observer: None,
+            os_args: None,
+            assume_yes: false,
+        }
+    }
+
+    /// Installs a function which is called for each event in parsing,
+    /// replacing any function previously installed.
+    ///
+    /// Events are reported by `derive(Options)` implementations as each
+    /// option, option value, free argument, and command is recognized.
+    ///
+    /// A clone of the parser has no observer installed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gumdrop::{Options, ParseEvent, Parser, ParsingStyle};
+    ///
+    /// #[derive(Options)]
+    /// struct MyOptions {
+    ///     #[options(free)]
+    ///     free: Vec<String>,
+    ///     verbose: bool,
+    ///     jobs: u32,
+    /// }
+    ///
+    /// let args = ["-v", "--jobs", "4", "foo"];
+    /// let mut events = Vec::new();
+    ///
+    /// let mut parser = Parser::new(&args, ParsingStyle::default());
+    /// parser.set_observer(|event| events.push(event.to_string()));
+    ///
+    /// MyOptions::parse(&mut parser).unwrap();
+    /// drop(parser);
+    ///
+    /// assert_eq!(events, ["option -v", "option --jobs", "value 4", "free foo"]);
+    /// ```
+    pub fn set_observer<F: FnMut(ParseEvent) + 'a>(&mut self, observer: F) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Reports an event to the observer installed by `set_observer`, if any.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn notify(&mut self, event: ParseEvent) {
+        if let Some(observer) = &mut self.observer {
+            observer(event);
+        }
+    }
+
+    /// Returns the next option or `None` if no options remain.
+    pub fn next_opt(&mut self) -> Option<Opt<'a>> {
+        if let Some(rest) = self.take_cur() {
+            if let Some(opt) = rest.chars().next() {
+                self.set_cur(rest, opt.len_utf8());
+                return Some(Opt::Short(opt));
+            }
+        }
+
+        if self.terminated {
+            return self.advance().map(Opt::Free);
+        }
+
+        match self.advance() {
+            Some(arg @ "-") => {
+                if self.style == ParsingStyle::StopAtFirstFree {
+                    self.terminated = true;
+                }
+                Some(Opt::Free(arg))
+            }
+            Some("--") => {
+                self.terminated = true;
+                self.advance().map(Opt::Free)
+            }
+            Some(long) if long.starts_with("--") => {
+                match long.find('=') {
+                    Some(pos) => Some(Opt::LongWithArg(
+                        &long[2..pos], &long[pos + 1..])),
+                    None => Some(Opt::Long(&long[2..]))
+                }
+            }
+            Some(short) if short.starts_with('-') => {
+                let res = short[1..].chars().next();
+
+                self.cur = Some(1 + res.map_or(0, char::len_utf8));
+                res.map(Opt::Short)
+            }
+            Some(free) => {
+                if self.style == ParsingStyle::StopAtFirstFree {
+                    self.terminated = true;
+                }
+                Some(Opt::Free(free))
+            }
+            None => None
+        }
+    }
+
+    /// Returns the next argument to an option or `None` if none remain.
+    pub fn next_arg(&mut self) -> Option<&'a str> {
+        if let Some(arg) = self.take_cur() {
+            if !arg.is_empty() {
+                return Some(arg);
+            }
+        }
+
+        self.advance()
+    }
+
+    /// Consumes and returns the next argument
+    fn advance(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.pos)?;
+        self.pos += 1;
+        Some(arg)
+    }
+
+    /// Returns the unconsumed remainder of a group of short options
+    fn take_cur(&mut self) -> Option<&'a str> {
+        let offset = self.cur.take()?;
+        let arg = self.args.get(self.pos - 1)?;
+        Some(&arg[offset..])
+    }
+
+    /// Records that `n` bytes of `rest`, as returned by `take_cur`,
+    /// have been consumed
+    fn set_cur(&mut self, rest: &str, n: usize) {
+        let len = self.args.get(self.pos - 1).map_or(0, str::len);
+        self.cur = Some(len - rest.len() + n);
+    }
+
+    /// Returns the next argument as the value of the option `opt`.
+    ///
+    /// If `opt` is a long option with an attached argument, as in `--foo=bar`,
+    /// that argument is returned instead.
+    /// If no arguments remain, a `missing_argument` error is returned.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn next_value(&mut self, opt: Opt<'a>) -> Result<&'a str, Error> {
+        if let Opt::LongWithArg(_, arg) = opt {
+            self.notify(ParseEvent::Value(arg));
+            return Ok(arg);
+        }
+
+        let arg = self.next_arg().ok_or_else(|| Error::missing_argument(opt))?;
+        self.notify(ParseEvent::Value(arg));
+        Ok(arg)
+    }
+
+    /// Returns the next argument as one of `expected` values of the option `opt`,
+    /// of which `found` have already been consumed.
+    ///
+    /// If no arguments remain, an `insufficient_arguments` error is returned.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn next_tuple_value(&mut self, opt: Opt, expected: usize, found: usize)
+            -> Result<&'a str, Error> {
+        let arg = self.next_arg()
+            .ok_or_else(|| Error::insufficient_arguments(opt, expected, found))?;
+        self.notify(ParseEvent::Value(arg));
+        Ok(arg)
+    }
+
+    /// Calls `step` with each remaining option, until no options remain
+    /// or `step` returns `Ok(true)`.
+    ///
+    /// An error returned by `step` is passed to `report_error`;
+    /// if the error is not collected, parsing stops and the error is returned.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn parse_opts<F>(&mut self, mut step: F) -> Result<(), Error>
+            where F: FnMut(&mut Parser<'a, S>, Opt<'a>) -> Result<bool, Error> {
+        while let Some(opt) = self.next_opt() {
+            match step(self, opt) {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(e) => self.report_error(e)?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with a parser of type `Parser<DynArg>` sharing the state
+    /// of this parser, so that `f` is compiled once for all argument types.
+    ///
+    /// This is called by `derive(Options)` implementations of `Options::parse`,
+    /// which parse arguments using `Options::parse_dyn`.
+    pub fn with_dyn<T, F>(&mut self, f: F) -> T
+            where F: FnOnce(&mut Parser<DynArg>) -> T {
+        let args: &dyn ArgList = match &self.args {
+            Args::Slice(args) => args,
+            Args::Dyn(args) => *args,
+        };
+
+        let mut observer = self.observer.take();
+
+        let mut parser = Parser{
+            args: Args::Dyn(args),
+            pos: self.pos,
+            cur: self.cur,
+            style: self.style,
+            terminated: self.terminated,
+            long_help: self.long_help,
+            prompts: std::mem::take(&mut self.prompts),
+            help_command: self.help_command.take(),
+            errors: self.errors.take(),
+            observer: observer.as_mut().map(|observer| {
+                Box::new(move |event: ParseEvent| observer(event)) as Observer
+            }),
+            os_args: self.os_args,
+            assume_yes: self.assume_yes,
+        };
+
+        let res = f(&mut parser);
+
+        self.pos = parser.pos;
+        self.cur = parser.cur;
+        self.terminated = parser.terminated;
+        self.long_help = parser.long_help;
+        self.prompts = std::mem::take(&mut parser.prompts);
+        self.help_command = parser.help_command.take();
+        self.assume_yes = parser.assume_yes;
+        self.errors = parser.errors.take();
+        drop(parser);
+        self.observer = observer;
+
+        res
+    }
+
+    /// Returns the index of the argument most recently consumed,
+    /// or `None` if no arguments have been consumed.
+    pub fn position(&self) -> Option<usize> {
+        self.pos.checked_sub(1)
+    }
+
+    /// Supplies the arguments of this parser in their original form,
+    /// which may not be valid UTF-8.
+    ///
+    /// Arguments given to the parser should be the lossy conversion of these,
+    /// e.g. using `OsStr::to_string_lossy`. Values of `free` fields of type
+    /// `OsString` or `PathBuf` are then taken from the original arguments.
+    ///
+    /// # Panics
+    ///
+    /// If `os_args` does not contain as many arguments as the parser.
+    pub fn set_os_args(&mut self, os_args: &'a [&'a OsStr]) {
+        assert!(self.args.get(os_args.len()).is_none() &&
+            (os_args.is_empty() || self.args.get(os_args.len() - 1).is_some()),
+            "`set_os_args` given a different number of arguments");
+        self.os_args = Some(os_args);
+    }
+
+    /// Returns the argument at the given index in its original form,
+    /// if arguments were supplied by `set_os_args`.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn os_arg(&self, index: Option<usize>) -> Option<&'a OsStr> {
+        self.os_args?.get(index?).copied()
+    }
+
+    fn current_arg(&self) -> Option<(usize, &'a str)> {
+        self.position().and_then(|index| Some((index, self.args.get(index)?)))
+    }
+
+    /// Causes errors reported to this parser to be collected, rather than
+    /// returned, so that parsing may continue after an error is encountered.
+    ///
+    /// Collected errors are returned by `take_errors`.
+    pub fn collect_errors(&mut self) {
+        if self.errors.is_none() {
+            self.errors = Some(Vec::new());
+        }
+    }
+
+    /// Returns all errors collected by this parser.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        self.errors.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Reports an error encountered in parsing.
+    ///
+    /// If this parser collects errors, the error is recorded and `Ok(())` is
+    /// returned, allowing parsing to continue. Otherwise, the error is returned.
+    /// A `help_command` error is not an error in parsing; its path is
+    /// recorded, as returned by `help_command_path`, and `Ok(())` is returned.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn report_error(&mut self, err: Error) -> Result<(), Error> {
+        if let Repr::HelpCommand(path) = err.repr {
+            self.help_command = Some(path);
+            return Ok(());
+        }
+
+        let err = err.at_position(self);
+
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(err);
+                Ok(())
+            }
+            None => Err(err)
+        }
+    }
+
+    /// Records that help was requested using the long name of a help flag,
+    /// e.g. `--help`, rather than its short name.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn request_long_help(&mut self) {
+        self.long_help = true;
+    }
+
+    /// Records that the value of an option marked `prompt_secret`, identified
+    /// by `key`, was not supplied and is to be read by `Options::prompt_secrets`.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn request_prompt(&mut self, key: &'static str) {
+        self.prompts.push(key);
+    }
+
+    /// Returns whether `request_prompt` was called with the given key.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn prompt_requested(&self, key: &str) -> bool {
+        self.prompts.contains(&key)
+    }
+
+    /// Records that the user supplied `--yes`, declining to be asked to
+    /// confirm the actions of options marked `confirm`.
+    ///
+    /// This is called by `derive(Options)` implementations.
+    pub fn set_assume_yes(&mut self) {
+        self.assume_yes = true;
+    }
+
+    /// Prepends a command name to the path recorded by a `help` command
+    /// invoked within that command.
+    #[doc(hidden)]
+    pub fn help_command_in(&mut self, name: &str) {
+        if let Some(path) = &mut self.help_command {
+            path.insert(0, name.to_owned());
+        }
+    }
+
+    /// Returns the path of command names for which help was requested,
+    /// if the user invoked a `help` command generated by `help_command`.
+    ///
+    /// An empty path requests help for the program itself.
+    pub fn help_command_path(&self) -> Option<&[String]> {
+        self.help_command.as_deref()
+    }
+
+    /// Returns whether the user supplied `--yes`.
+    ///
+    /// See [`Options::confirmations`](trait.Options.html#method.confirmations).
+    pub fn assume_yes(&self) -> bool {
+        self.assume_yes
+    }
+
+    /// Returns whether long help text was requested.
+    ///
+    /// See [`Options::long_usage`](trait.Options.html#method.long_usage).
+    pub fn long_help_requested(&self) -> bool {
+        self.long_help
+    }
+}
+
+impl<'a, S: 'a> Clone for Parser<'a, S> {
+    fn clone(&self) -> Parser<'a, S> {
+        Parser{
+            args: self.args,
+            pos: self.pos,
+            cur: self.cur,
+            style: self.style,
+            terminated: self.terminated,
+            long_help: self.long_help,
+            prompts: self.prompts.clone(),
+            help_command: self.help_command.clone(),
+            errors: self.errors.as_ref().map(|_| Vec::new()),
+            observer: None,
+            os_args: self.os_args,
+            assume_yes: self.assume_yes,
+        }
+    }
+}
+
+impl<'a> Opt<'a> {
+    #[doc(hidden)]
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(&self) -> String {
+        match *self {
+            Opt::Short(ch) => format!("-{}", ch),
+            Opt::Long(s) => format!("--{}", s),
+            Opt::LongWithArg(opt, _) => format!("--{}", opt),
+            Opt::Free(_) => "free".to_owned()
+        }
+    }
+}
+
+impl Default for ParsingStyle {
+    /// Returns the default parsing style, `AllOptions`.
+    fn default() -> ParsingStyle {
+        ParsingStyle::AllOptions
+    }
+}
+
+/// Formats help text for a command in the form printed by
+/// [`parse_args_or_exit`](trait.Options.html#method.parse_args_or_exit).
+///
+/// `command` is the program name, followed by the names of any selected
+/// subcommands, separated by spaces. `usage` and `command_list` are the values
+/// returned by the corresponding `Options` methods.
+///
+/// The returned string does **not** end with a newline.
+///
+/// To include all sections of help text, use [`HelpInfo::format`](struct.HelpInfo.html#method.format).
+pub fn format_help(command: &str, usage: &str, command_list: Option<&str>) -> String {
+    let mut res = String::new();
+    push_help_header(&mut res, command, usage, command_list, None, false);
+    res
+}
+
+fn write_help(command: &str, info: &HelpInfo, long: bool, markup: bool) -> String {
+    let mut res = String::new();
+    let usage = if long { info.long_usage } else { info.usage };
+    let preview = info.command_preview.map(|preview| preview());
+
+    push_help_header(&mut res, command, usage, preview.as_deref().or(info.command_list),
+        info.command_heading, markup);
+
+    if let Some(after) = info.after_help {
+        res.push_str("\n\n");
+        res.push_str(after);
+    }
+
+    res
+}
+
+fn push_help_header(res: &mut String, command: &str, usage: &str,
+        command_list: Option<&str>, command_heading: Option<&str>, markup: bool) {
+    let heading = |s: &str| if markup { style::heading(s) } else { s.to_owned() };
+
+    res.push_str(&heading("Usage:"));
+    res.push(' ');
+    res.push_str(command);
+    res.push_str(" [OPTIONS]");
+
+    if !usage.is_empty() {
+        res.push_str("\n\n");
+        res.push_str(usage);
+    }
+
+    if let Some(cmds) = command_list {
+        res.push_str("\n\n");
+        res.push_str(&heading(command_heading.unwrap_or("Available commands:")));
+        res.push('\n');
+        res.push_str(cmds);
+    }
+}
+
+/// Formats help text for the command at the given path, as with
+/// [`format_help`](fn.format_help.html), rendered using the given style.
+///
+/// `path` is a sequence of command names, as accepted by
+/// [`Options::command_path_usage`](trait.Options.html#method.command_path_usage).
+/// If the path does not name a valid command, `None` is returned.
+#[cfg(feature = "style")]
+pub fn format_help_with_style<T, S>(program: &str, path: &[S], style: &dyn HelpStyle)
+        -> Option<String> where T: Options, S: AsRef<str> {
+    let info = T::command_path_markup(path)?;
+    let help = write_help(&command_str(program, path), &info, false, true);
+
+    Some(style::render(&help, style))
+}
+
+/// Formats an error message, rendered using the given style.
+///
+/// If the position of the error is known, the command line is echoed
+/// beneath the message, with the offending argument marked by carets:
+///
+/// ```text
+/// error: unrecognized option `--frob`
+///   prog -f 1 --frob
+///             ^^^^^^
+/// ```
+///
+/// `args` are the arguments given to the parser, not including `program`.
+#[cfg(feature = "style")]
+pub fn format_error_with_style<S: AsRef<str>>(program: &str, args: &[S],
+        err: &Error, style: &dyn HelpStyle) -> String {
+    format_error_styled(program, args, err, style, &DefaultErrorFormatter)
+}
+
+#[cfg(feature = "style")]
+fn format_error_styled<S: AsRef<str>>(program: &str, args: &[S],
+        err: &Error, style: &dyn HelpStyle, formatter: &dyn ErrorFormatter) -> String {
+    use unicode_width::UnicodeWidthStr;
+
+    let mut res = String::new();
+
+    style.error("error:", &mut res);
+    res.push(' ');
+    formatter.message(&err.message(), &mut res);
+
+    let pos = match err.position() {
+        Some(pos) if pos < args.len() => pos,
+        _ => return res
+    };
+
+    let mut line = String::from("  ");
+    let mut offset = 2 + program.width();
+
+    line.push_str(program);
+
+    for (i, arg) in args.iter().enumerate() {
+        let arg = arg.as_ref();
+
+        line.push(' ');
+
+        if i < pos {
+            offset += 1 + arg.width();
+            line.push_str(arg);
+        } else if i == pos {
+            offset += 1;
+            style.error(arg, &mut line);
+        } else {
+            line.push_str(arg);
+        }
     }
 
-    /// Returns an error for a missing command name.
-    pub fn missing_command() -> Error {
-        Error{kind: ErrorKind::MissingCommand}
-    }
+    let marker = "^".repeat(args[pos].as_ref().width().max(1));
 
-    /// Returns an error for a missing required option.
-    pub fn missing_required(opt: &str) -> Error {
-        Error{kind: ErrorKind::MissingRequired(opt.to_owned())}
-    }
+    res.push('\n');
+    res.push_str(&line);
+    res.push('\n');
+    res.push_str(&" ".repeat(offset));
+    style.error(&marker, &mut res);
+    res
+}
 
-    /// Returns an error for a missing required command.
-    pub fn missing_required_command() -> Error {
-        Error{kind: ErrorKind::MissingRequiredCommand}
-    }
+/// Displays text using a pager, if `stdout` is a terminal.
+///
+/// Returns `false` if no pager was run.
+fn page(text: &str) -> bool {
+    use std::io::IsTerminal;
+    use std::process::{Command, Stdio};
 
-    /// Returns an error for a missing required free argument.
-    pub fn missing_required_free() -> Error {
-        Error{kind: ErrorKind::MissingRequiredFree}
+    if !std::io::stdout().is_terminal() {
+        return false;
     }
 
-    /// Returns an error when a free argument was encountered, but the options
-    /// type does not support free arguments.
-    pub fn unexpected_free(arg: &str) -> Error {
-        Error{kind: ErrorKind::UnexpectedFree(arg.to_owned())}
-    }
+    let (program, args) = match pager_command(std::env::var("PAGER").ok()) {
+        Some(cmd) => cmd,
+        None => return false
+    };
 
-    /// Returns an error for an unrecognized command.
-    pub fn unrecognized_command(name: &str) -> Error {
-        Error{kind: ErrorKind::UnrecognizedCommand(name.to_owned())}
-    }
+    let mut child = match Command::new(program).args(args).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(_) => return false
+    };
 
-    /// Returns an error for an unrecognized option.
-    pub fn unrecognized_option(opt: Opt) -> Error {
-        match opt {
-            Opt::Short(short) => Error::unrecognized_short(short),
-            Opt::Long(long) | Opt::LongWithArg(long, _) =>
-                Error::unrecognized_long(long),
-            Opt::Free(_) => panic!("`Error::unrecognized_option` called with `Opt::Free` value")
-        }
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may exit before reading all input; this is not an error.
+        let _ = writeln!(stdin, "{}", text);
     }
 
-    /// Returns an error for an unrecognized long option, e.g. `--option`.
-    pub fn unrecognized_long(opt: &str) -> Error {
-        Error{kind: ErrorKind::UnrecognizedLongOption(opt.to_owned())}
-    }
+    child.wait().is_ok()
+}
 
-    /// Returns an error for an unrecognized short option, e.g. `-o`.
-    pub fn unrecognized_short(opt: char) -> Error {
-        Error{kind: ErrorKind::UnrecognizedShortOption(opt)}
-    }
+/// Returns the pager program and its arguments, given the value of `PAGER`.
+fn pager_command(var: Option<String>) -> Option<(String, Vec<String>)> {
+    let var = var.unwrap_or_else(|| "less -FR".to_owned());
+    let mut words = var.split_whitespace().map(|s| s.to_owned());
+
+    let program = words.next()?;
+
+    Some((program, words.collect()))
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::ErrorKind::*;
-
-        match &self.kind {
-            FailedParse(opt, arg) => write!(f, "invalid argument to option `{}`: {}", opt, arg),
-            FailedParseDefault{option, value, err} => write!(f, "invalid default value for `{}` ({:?}): {}", option, value, err),
-            InsufficientArguments{option, expected, found} =>
-                write!(f, "insufficient arguments to option `{}`: expected {}; found {}",
-                    option, expected, found),
-            MissingArgument(opt) => write!(f, "missing argument to option `{}`", opt),
-            MissingCommand => f.write_str("missing command name"),
-            MissingRequired(opt) => write!(f, "missing required option `{}`", opt),
-            MissingRequiredCommand => f.write_str("missing required command"),
-            MissingRequiredFree => f.write_str("missing required free argument"),
-            UnexpectedArgument(opt) => write!(f, "option `{}` does not accept an argument", opt),
-            UnexpectedSingleArgument(opt, n) =>
-                write!(f, "option `{}` expects {} arguments; found 1", opt, n),
-            UnexpectedFree(arg) => write!(f, "unexpected free argument `{}`", arg),
-            UnrecognizedCommand(cmd) => write!(f, "unrecognized command `{}`", cmd),
-            UnrecognizedLongOption(opt) => write!(f, "unrecognized option `--{}`", opt),
-            UnrecognizedShortOption(opt) => write!(f, "unrecognized option `-{}`", opt),
+/// Joins long option names to their meta variables in lines of option help,
+/// e.g. `-f, --foo FOO` becomes `-f, --foo=FOO`, as `help2man` expects.
+fn help2man_options(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for (i, line) in text.lines().enumerate() {
+        if i != 0 {
+            res.push('\n');
         }
-    }
-}
 
-impl StdError for Error {
-    fn description(&self) -> &str {
-        "failed to parse arguments"
-    }
-}
+        let body = line.trim_start();
 
-impl<'a, S: 'a + AsRef<str>> Parser<'a, S> {
-    /// Returns a new parser for the given series of arguments.
-    ///
-    /// The given slice should **not** contain the program name as its first
-    /// element.
-    pub fn new(args: &'a [S], style: ParsingStyle) -> Parser<'a, S> {
-        Parser{
-            args: args.iter(),
-            cur: None,
-            style: style,
-            terminated: false,
+        if !body.starts_with('-') {
+            res.push_str(line);
+            continue;
         }
-    }
 
-    /// Returns the next option or `None` if no options remain.
-    pub fn next_opt(&mut self) -> Option<Opt<'a>> {
-        if let Some(mut cur) = self.cur.take() {
-            if let Some(opt) = cur.next() {
-                self.cur = Some(cur);
-                return Some(Opt::Short(opt));
+        let tag_start = line.len() - body.len();
+        let tag_end = body.find("  ").map_or(line.len(), |n| tag_start + n);
+        let tag = &line[tag_start..tag_end];
+
+        let meta = tag.rfind("--")
+            .filter(|&pos| tag[pos + 2..].starts_with(|c: char| c.is_alphanumeric()))
+            .and_then(|pos| tag[pos..].find(' ').map(|n| tag_start + pos + n));
+
+        match meta {
+            Some(pos) => {
+                res.push_str(&line[..pos]);
+                res.push('=');
+                res.push_str(&line[pos + 1..]);
             }
+            None => res.push_str(line)
         }
+    }
 
-        if self.terminated {
-            return self.args.next().map(|s| Opt::Free(s.as_ref()));
-        }
+    res
+}
 
-        match self.args.next().map(|s| s.as_ref()) {
-            Some(arg @ "-") => {
-                if self.style == ParsingStyle::StopAtFirstFree {
-                    self.terminated = true;
+/// Reads a line answering a `[y/N]` prompt, returning whether it was
+/// `y` or `yes`, in any case
+fn read_confirmation<R: io::BufRead>(input: &mut R) -> io::Result<bool> {
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+
+    let answer = line.trim();
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Returns the file name component of a program path,
+/// or the whole path if it has no file name.
+fn base_name(program: &str) -> &str {
+    std::path::Path::new(program).file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program)
+}
+
+fn command_str<S: AsRef<str>>(program: &str, path: &[S]) -> String {
+    let mut command = program.to_owned();
+
+    for name in path {
+        command.push(' ');
+        command.push_str(name.as_ref());
+    }
+
+    command
+}
+
+/// Implements `Options::parse_args_or_exit_with`, using the original form
+/// of arguments following the program name, `os_args`, if given
+fn parse_or_exit<T: Options>(args: &[String], os_args: Option<&[&OsStr]>,
+        style: ParsingStyle, strategy: &mut dyn ExitStrategy) -> T {
+    let mut args = Cow::Borrowed(args);
+    // Original arguments, once one has been replaced by a correction
+    let mut corrected_os_args: Option<Vec<OsString>> = None;
+    let mut assume_yes = false;
+
+    let e = loop {
+        let os_refs;
+        let cur_os_args = match &corrected_os_args {
+            Some(os_args) => {
+                os_refs = os_args.iter().map(|s| &s[..]).collect::<Vec<_>>();
+                Some(&os_refs[..])
+            }
+            None => os_args
+        };
+
+        match parse_or_help::<T, _>(&args, cur_os_args, style, strategy.help_color(),
+                true, &mut assume_yes) {
+            ParseResult::Parsed(opts) => {
+                if !assume_yes {
+                    for prompt in opts.confirmations() {
+                        if !strategy.confirm(prompt) {
+                            strategy.print_error("aborted");
+                            strategy.exit(1);
+                        }
+                    }
                 }
-                Some(Opt::Free(arg))
+
+                return opts;
             }
-            Some("--") => {
-                self.terminated = true;
-                self.args.next().map(|s| Opt::Free(s.as_ref()))
+            ParseResult::Help(help) | ParseResult::Version(help) => {
+                strategy.print_help(&help);
+                strategy.exit(strategy.help_code());
             }
-            Some(long) if long.starts_with("--") => {
-                match long.find('=') {
-                    Some(pos) => Some(Opt::LongWithArg(
-                        &long[2..pos], &long[pos + 1..])),
-                    None => Some(Opt::Long(&long[2..]))
+            ParseResult::Err(e) => {
+                if let Some((index, corrected)) = e.correction() {
+                    if strategy.accept_suggestion(&format!("{}: {}",
+                            args[0], e.format_with(strategy.error_formatter()))) {
+                        if let Some(os_args) = os_args {
+                            corrected_os_args.get_or_insert_with(
+                                || os_args.iter().map(|&s| s.to_owned()).collect())
+                                [index] = OsString::from(&corrected);
+                        }
+
+                        args.to_mut()[index + 1] = corrected;
+                        continue;
+                    }
                 }
+
+                break e;
             }
-            Some(short) if short.starts_with('-') => {
-                let mut chars = short[1..].chars();
+        }
+    };
 
-                let res = chars.next().map(Opt::Short);
+    let mut msg = error_message(&args[0], &args[1..], &e, strategy.error_color(),
+        strategy.error_formatter());
 
-                self.cur = Some(chars);
-                res
-            }
-            Some(free) => {
-                if self.style == ParsingStyle::StopAtFirstFree {
-                    self.terminated = true;
-                }
-                Some(Opt::Free(free))
+    if let Some(cmds) = e.available_commands() {
+        msg.push_str("\navailable commands: ");
+        msg.push_str(&cmds.join(", "));
+    }
+
+    match T::error_usage() {
+        ErrorUsage::Omit => (),
+        ErrorUsage::Line => {
+            msg.push_str("\nUsage: ");
+            msg.push_str(&command_str(&args[0], e.command_path()));
+            msg.push_str(" [OPTIONS]");
+        }
+        ErrorUsage::Full => {
+            if let Some(help) = command_help::<T, _>(
+                    &args[0], e.command_path(), false, strategy.error_color()) {
+                msg.push_str("\n\n");
+                msg.push_str(&help);
             }
-            None => None
         }
     }
 
-    /// Returns the next argument to an option or `None` if none remain.
-    pub fn next_arg(&mut self) -> Option<&'a str> {
-        if let Some(cur) = self.cur.take() {
-            let arg = cur.as_str();
+    strategy.print_error(&msg);
+    strategy.exit(strategy.error_code(&e));
+}
 
-            if !arg.is_empty() {
-                return Some(arg);
+/// Parses arguments for `parse_args_or_exit` and `parse_args_or_help`,
+/// setting `assume_yes` if the user supplied `--yes`.
+///
+/// If `prompt` is `true`, values of options marked `prompt_secret` which
+/// were not supplied are read from the terminal after parsing.
+fn parse_or_help<T: Options, S: AsRef<str>>(args: &[S], os_args: Option<&[&OsStr]>,
+        style: ParsingStyle, color: bool, prompt: bool, assume_yes: &mut bool)
+        -> ParseResult<T> {
+    let program = args[0].as_ref();
+    let mut parser = Parser::new(&args[1..], style);
+
+    if let Some(os_args) = os_args {
+        parser.set_os_args(os_args);
+    }
+
+    let mut opts = match T::parse(&mut parser) {
+        Ok(opts) => opts,
+        Err(e) => {
+            let e = e.at_position(&parser);
+
+            if let Some(path) = e.help_command_path() {
+                return match command_help::<T, _>(program, path, true, color) {
+                    Some(help) => ParseResult::Help(help),
+                    None => ParseResult::Err(Error::unrecognized_command(&path.join(" ")))
+                };
             }
+
+            // A help or version flag takes precedence over errors
+            // in other arguments. Arguments are scanned, rather than parsed
+            // again, so that no default or environment value is computed twice.
+            let mut parser = Parser::new(&args[1..], style);
+            let mut path = Vec::new();
+
+            return match parser.with_dyn(|parser| T::scan_help(parser, &mut path)) {
+                Some(HelpFlag::HelpAll) =>
+                    ParseResult::Help(wrap_to_terminal(T::usage_all(program))),
+                Some(HelpFlag::Help) => {
+                    let long = parser.long_help_requested();
+                    let help = command_help::<T, _>(program, &path, long, color)
+                        .unwrap_or_else(|| wrap_to_terminal(format_help(program,
+                            T::usage(), T::command_list())));
+
+                    ParseResult::Help(help)
+                }
+                Some(HelpFlag::Version) => ParseResult::Version(version_text::<T>(program)),
+                None => ParseResult::Err(e)
+            };
         }
+    };
+
+    if let Some(path) = parser.help_command_path() {
+        return match command_help::<T, _>(program, path, true, color) {
+            Some(help) => ParseResult::Help(help),
+            None => ParseResult::Err(Error::unrecognized_command(&path.join(" ")))
+        };
+    }
+
+    if opts.help_all_requested() {
+        return ParseResult::Help(wrap_to_terminal(T::usage_all(program)));
+    }
+
+    if opts.help_requested() {
+        let long = parser.long_help_requested();
+        let help = command_help::<T, _>(program, &opts.command_path(), long, color)
+            .unwrap_or_else(|| wrap_to_terminal(format_help(program,
+                opts.self_usage(), opts.self_command_list())));
+
+        return ParseResult::Help(help);
+    }
+
+    if opts.version_requested() {
+        return ParseResult::Version(version_text::<T>(program));
+    }
 
-        self.args.next().map(|s| s.as_ref())
+    if prompt {
+        if let Err(e) = opts.prompt_secrets(&parser) {
+            return ParseResult::Err(e);
+        }
     }
+
+    *assume_yes = parser.assume_yes();
+    ParseResult::Parsed(opts)
 }
 
-impl<'a, S: 'a> Clone for Parser<'a, S> {
-    fn clone(&self) -> Parser<'a, S> {
-        Parser{
-            args: self.args.clone(),
-            cur: self.cur.clone(),
-            style: self.style,
-            terminated: self.terminated,
+/// Returns version information printed for a version flag
+fn version_text<T: Options>(program: &str) -> String {
+    match T::version() {
+        Some(version) => format!("{} {}", program, version),
+        None => program.to_owned()
+    }
+}
+
+/// Returns help text for the command at the given path, as printed
+/// by `parse_args_or_exit`.
+///
+/// If `long` is `true`, long help text is used for options.
+/// If `color` is `true` and the `style` feature is enabled, help text is
+/// styled using ANSI escape sequences.
+/// Help text is wrapped to the width given by `terminal_width`, if any.
+#[cfg_attr(not(feature = "style"), allow(unused_variables))]
+fn command_help<T: Options, S: AsRef<str>>(program: &str, path: &[S], long: bool,
+        color: bool) -> Option<String> {
+    #[cfg(feature = "style")]
+    {
+        if color {
+            let info = T::command_path_markup(path)?;
+            let help = write_help(&command_str(program, path), &info, long, true);
+
+            return Some(style::render(&wrap_to_terminal(help), &AnsiStyle));
         }
     }
+
+    let info = T::command_path_usage(path)?;
+    let help = write_help(&command_str(program, path), &info, long, false);
+
+    Some(wrap_to_terminal(help))
 }
 
-impl<'a> Opt<'a> {
-    #[doc(hidden)]
-    pub fn to_string(&self) -> String {
-        match *self {
-            Opt::Short(ch) => format!("-{}", ch),
-            Opt::Long(s) => format!("--{}", s),
-            Opt::LongWithArg(opt, _) => format!("--{}", opt),
-            Opt::Free(_) => "free".to_owned()
+/// Formats an error message, as printed by `parse_args_or_exit`.
+///
+/// If `color` is `true` and the `style` feature is enabled, the offending
+/// argument is marked beneath the message; otherwise, the message is
+/// prefixed with the program name.
+#[cfg_attr(not(feature = "style"), allow(unused_variables))]
+fn error_message(program: &str, args: &[String], err: &Error, color: bool,
+        formatter: &dyn ErrorFormatter) -> String {
+    #[cfg(feature = "style")]
+    {
+        if color {
+            return format_error_styled(program, args, err, &AnsiStyle, formatter);
         }
     }
+
+    format!("{}: {}", program, err.format_with(formatter))
 }
 
-impl Default for ParsingStyle {
-    /// Returns the default parsing style, `AllOptions`.
-    fn default() -> ParsingStyle {
-        ParsingStyle::AllOptions
+fn wrap_to_terminal(text: String) -> String {
+    match terminal_width() {
+        Some(width) => wrap_help(&text, width),
+        None => text
     }
 }
 
 /// Parses arguments from the command line.
 ///
 /// The first argument (the program name) should be omitted.
-pub fn parse_args<T: Options>(args: &[String], style: ParsingStyle) -> Result<T, Error> {
+pub fn parse_args<T, I>(args: I, style: ParsingStyle) -> Result<T, Error>
+        where T: Options, I: IntoIterator, I::Item: AsRef<str> {
     T::parse_args(args, style)
 }
 
@@ -685,7 +3435,8 @@ pub fn parse_args<T: Options>(args: &[String], style: ParsingStyle) -> Result<T,
 /// [parsing style](enum.ParsingStyle.html).
 ///
 /// The first argument (the program name) should be omitted.
-pub fn parse_args_default<T: Options>(args: &[String]) -> Result<T, Error> {
+pub fn parse_args_default<T, I>(args: I) -> Result<T, Error>
+        where T: Options, I: IntoIterator, I::Item: AsRef<str> {
     T::parse_args_default(args)
 }
 
@@ -698,10 +3449,6 @@ pub fn parse_args_default<T: Options>(args: &[String]) -> Result<T, Error> {
 /// `stderr` and the process will exit with status code `0`.
 ///
 /// Otherwise, the parsed options are returned.
-///
-/// # Panics
-///
-/// If any argument to the process is not valid unicode.
 pub fn parse_args_or_exit<T: Options>(style: ParsingStyle) -> T {
     T::parse_args_or_exit(style)
 }
@@ -716,17 +3463,261 @@ pub fn parse_args_or_exit<T: Options>(style: ParsingStyle) -> T {
 /// `stderr` and the process will exit with status code `0`.
 ///
 /// Otherwise, the parsed options are returned.
+pub fn parse_args_default_or_exit<T: Options>() -> T {
+    T::parse_args_default_or_exit()
+}
+
+/// Pushes the arguments representing a single map value
+fn map_args(args: &mut Vec<String>, opt: &OptionInfo, key: &str, value: &Value)
+        -> Result<(), Error> {
+    let flag = format!("--{}", key);
+
+    match (value, opt.takes_value) {
+        (Value::Bool(b), false) => {
+            if *b {
+                args.push(flag);
+            }
+        }
+        (Value::Count(n), false) => {
+            for _ in 0..*n {
+                args.push(flag.clone());
+            }
+        }
+        (Value::String(s), true) => {
+            args.push(flag);
+            args.push(s.clone());
+        }
+        (Value::Tuple(values), true) => {
+            args.push(flag);
+            args.extend(values.iter().cloned());
+        }
+        (Value::List(values), _) => {
+            for value in values {
+                match value {
+                    Value::List(_) => return Err(map_type_error(&flag, opt.takes_value)),
+                    _ => map_args(args, opt, key, value)?
+                }
+            }
+        }
+        (_, takes_value) => return Err(map_type_error(&flag, takes_value))
+    }
+
+    Ok(())
+}
+
+fn map_type_error(flag: &str, takes_value: bool) -> Error {
+    Error::failed_parse_with_name(flag.to_owned(), if takes_value {
+        "expected a string value".to_owned()
+    } else {
+        "expected a boolean or count value".to_owned()
+    })
+}
+
+/// Checks the options and commands of `T` for misconfiguration.
+///
+/// `derive(Options)` checks each type in isolation; this function inspects
+/// the metadata given by `Options::options_info` and `Options::commands_info`
+/// for the whole command tree and is intended to be called from a test.
+/// Only that metadata is checked. A hand-written implementation which does
+/// not override those methods reports no options or commands, so nothing
+/// is checked for it.
+///
+/// The following problems are reported:
+///
+/// * Option names which are empty or cannot be given on the command line,
+///   e.g. a long name containing `=` or a short name of `-`
+/// * Long or short option names declared more than once within a command
+/// * Required options which also have a default value
+/// * Command names which are empty, begin with `-`, or are declared more
+///   than once within a command
+/// * Positional arguments declared more than once within a command
+///
+/// Whether positional arguments are required is not part of the metadata,
+/// so their order is not checked here; `derive(Options)` rejects a required
+/// `free` field following one which is not required.
 ///
 /// # Panics
 ///
-/// If any argument to the process is not valid unicode.
-pub fn parse_args_default_or_exit<T: Options>() -> T {
-    T::parse_args_default_or_exit()
+/// If any problem is found. The panic message lists every problem found.
+///
+/// # Examples
+///
+/// ```
+/// use gumdrop::Options;
+///
+/// #[derive(Options)]
+/// struct MyOptions {
+///     #[options(help = "print help message")]
+///     help: bool,
+///     #[options(help = "give a value")]
+///     value: Option<String>,
+/// }
+///
+/// gumdrop::assert_valid::<MyOptions>();
+/// ```
+pub fn assert_valid<T: Options>() {
+    let mut problems = Vec::new();
+
+    validate_command("", T::options_info(), T::commands_info(),
+        &T::usage_model(), &mut problems);
+
+    if !problems.is_empty() {
+        panic!("invalid command line interface:\n  {}", problems.join("\n  "));
+    }
+}
+
+fn validate_command(path: &str, options: &[OptionInfo], commands: &[CommandInfo],
+        model: &UsageModel, problems: &mut Vec<String>) {
+    let prefix = if path.is_empty() {
+        String::new()
+    } else {
+        format!("command `{}`: ", path)
+    };
+
+    for (i, opt) in options.iter().enumerate() {
+        if opt.long.is_none() && opt.short.is_none() {
+            problems.push(format!("{}option has neither a long nor a short name", prefix));
+        }
+
+        if let Some(long) = opt.long {
+            if long.is_empty() || long.starts_with('-') ||
+                    long.contains(|ch: char| ch == '=' || ch.is_whitespace()) {
+                problems.push(format!("{}invalid long option name `{}`", prefix, long));
+            }
+
+            if options[..i].iter().any(|o| o.long == Some(long)) {
+                problems.push(format!("{}duplicate option `--{}`", prefix, long));
+            }
+        }
+
+        if let Some(short) = opt.short {
+            if short == '-' || short.is_whitespace() {
+                problems.push(format!("{}invalid short option name `{}`", prefix, short));
+            }
+
+            if options[..i].iter().any(|o| o.short == Some(short)) {
+                problems.push(format!("{}duplicate option `-{}`", prefix, short));
+            }
+        }
+
+        if opt.required && opt.default.is_some() {
+            problems.push(format!("{}required option `{}` has a default value",
+                prefix, option_name(opt)));
+        }
+    }
+
+    let positional = model.sections.iter()
+        .filter(|sec| sec.kind == SectionKind::Positional)
+        .flat_map(|sec| sec.rows)
+        .collect::<Vec<_>>();
+
+    for (i, row) in positional.iter().enumerate() {
+        if positional[..i].iter().any(|r| r.name == row.name) {
+            problems.push(format!("{}duplicate positional argument `{}`", prefix, row.name));
+        }
+    }
+
+    for (i, cmd) in commands.iter().enumerate() {
+        if cmd.name.is_empty() || cmd.name.starts_with('-') {
+            problems.push(format!("{}invalid command name `{}`", prefix, cmd.name));
+        }
+
+        if commands[..i].iter().any(|c| c.name == cmd.name) {
+            problems.push(format!("{}duplicate command `{}`", prefix, cmd.name));
+            continue;
+        }
+
+        let path = if path.is_empty() {
+            cmd.name.to_owned()
+        } else {
+            format!("{} {}", path, cmd.name)
+        };
+
+        validate_command(&path, (cmd.options)(), (cmd.commands)(),
+            &(cmd.usage_model)(), problems);
+    }
+}
+
+fn option_name(opt: &OptionInfo) -> String {
+    match (opt.long, opt.short) {
+        (Some(long), _) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => String::new(),
+    }
+}
+
+/// Formats a number as an English ordinal, e.g. `1st` or `12th`
+struct Ordinal(usize);
+
+impl fmt::Display for Ordinal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = self.0;
+        let suffix = match (n % 10, n % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th"
+        };
+
+        write!(f, "{}{}", n, suffix)
+    }
+}
+
+/// Returns the candidate most similar to `name`, if any is similar enough
+/// to suggest that the user may have meant it.
+fn suggest(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    closest(name, candidates, |cand| edit_distance(name, cand))
+}
+
+/// Returns the possible value most similar to `value`, compared without
+/// regard to case, so that the value is suggested as it is listed
+fn suggest_value(value: &str, possible: &[&'static str]) -> Option<&'static str> {
+    let value = value.to_lowercase();
+
+    closest(&value, possible, |cand| edit_distance(&value, &cand.to_lowercase()))
+}
+
+fn closest<F>(name: &str, candidates: &[&'static str], distance: F) -> Option<&'static str>
+        where F: Fn(&str) -> usize {
+    let len = name.chars().count();
+    let max_dist = (len / 3).max(1);
+
+    candidates.iter()
+        .map(|&cand| (distance(cand), cand))
+        .filter(|&(dist, _)| dist <= max_dist && dist < len)
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, cand)| cand)
+}
+
+/// Returns the Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, &cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+
+            row[j + 1] = if ca == cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Opt, Parser, ParsingStyle};
+    use super::{base_name, edit_distance, help2man_options, pager_command, suggest, suggest_value, Opt, Parser, ParsingStyle};
     use assert_matches::assert_matches;
 
     #[test]
@@ -771,4 +3762,77 @@ mod test {
         assert_matches!(p.next_opt(), Some(Opt::Free("--d")));
         assert_matches!(p.next_opt(), None);
     }
+
+    #[test]
+    fn test_base_name() {
+        assert_eq!(base_name("prog"), "prog");
+        assert_eq!(base_name("target/debug/prog"), "prog");
+        assert_eq!(base_name("/usr/bin/prog"), "prog");
+        assert_eq!(base_name(""), "");
+    }
+
+    #[test]
+    fn test_help2man_options() {
+        let help = "\
+Usage: prog [OPTIONS]
+
+Positional arguments:
+  FILE           input file
+
+Optional arguments:
+  -h, --help     print help message
+  -f, --foo FOO  foo
+  --bar BAR      bar
+  -b BAZ         baz
+  --quux-long-name QUUX
+                 -- quux";
+
+        assert_eq!(help2man_options(help), "\
+Usage: prog [OPTIONS]
+
+Positional arguments:
+  FILE           input file
+
+Optional arguments:
+  -h, --help     print help message
+  -f, --foo=FOO  foo
+  --bar=BAR      bar
+  -b BAZ         baz
+  --quux-long-name=QUUX
+                 -- quux");
+    }
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(pager_command(None),
+            Some(("less".to_owned(), vec!["-FR".to_owned()])));
+        assert_eq!(pager_command(Some("more".to_owned())),
+            Some(("more".to_owned(), vec![])));
+        assert_eq!(pager_command(Some(" less -R  -X ".to_owned())),
+            Some(("less".to_owned(), vec!["-R".to_owned(), "-X".to_owned()])));
+        assert_eq!(pager_command(Some("".to_owned())), None);
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("verbose", "verbose"), 0);
+        assert_eq!(edit_distance("verbos", "verbose"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let names = &["verbose", "version", "help"];
+
+        assert_eq!(suggest("verbos", names), Some("verbose"));
+        assert_eq!(suggest("versoin", names), Some("version"));
+        assert_eq!(suggest("halp", names), Some("help"));
+        assert_eq!(suggest("quiet", names), None);
+        assert_eq!(suggest("x", &["y"]), None);
+
+        let values = &["json", "plain", "YAML"];
+
+        assert_eq!(suggest_value("JSON", values), Some("json"));
+        assert_eq!(suggest_value("Jsn", values), Some("json"));
+        assert_eq!(suggest_value("yml", values), Some("YAML"));
+        assert_eq!(suggest_value("xml", values), None);
+    }
 }
@@ -0,0 +1,387 @@
+//! Formatting of error messages
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::{suggest, suggest_value, Error, Ordinal, Repr};
+
+/// Parts of the message describing an [`Error`](struct.Error.html),
+/// returned by `Error::message`
+///
+/// The `Display` implementation produces the English message which is also
+/// produced by the `Display` implementation of `Error`. An application may
+/// instead produce a translated message from these parts, e.g. using an
+/// [`ErrorFormatter`](trait.ErrorFormatter.html).
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum ErrorMessage<'a> {
+    /// Two options were given which may not be used together
+    ConflictingOptions{
+        /// Name of the option
+        option: &'a str,
+        /// Name of the option with which it conflicts
+        other: &'a str,
+    },
+    /// An error created by `Error::custom`
+    Custom(&'a (dyn StdError + Send + Sync + 'static)),
+    /// An option value failed to parse
+    FailedParse{
+        /// Name of the option
+        option: &'a str,
+        /// Error returned by the parse function
+        err: &'a (dyn StdError + Send + Sync + 'static),
+    },
+    /// A positional argument failed to parse
+    FailedParseFree{
+        /// Name of the positional argument
+        name: &'a str,
+        /// Position of the argument among free arguments, beginning at `1`
+        ordinal: usize,
+        /// The argument value
+        value: &'a str,
+        /// Error returned by the parse function
+        err: &'a (dyn StdError + Send + Sync + 'static),
+    },
+    /// The default value of an option failed to parse
+    FailedParseDefault{
+        /// Name of the option's field
+        option: &'a str,
+        /// The default value
+        value: &'a str,
+        /// Message of the parse error
+        err: &'a str,
+    },
+    /// An option value taken from an environment variable failed to parse
+    FailedParseEnv{
+        /// Name of the environment variable
+        var: &'a str,
+        /// Message of the parse error
+        err: &'a str,
+    },
+    /// Help was requested using a generated `help` command
+    HelpCommand{
+        /// Names of commands for which help was requested, which may be empty
+        path: &'a [String],
+    },
+    /// An option expecting two or more arguments received too few
+    InsufficientArguments{
+        /// Name of the option
+        option: &'a str,
+        /// Number of arguments expected
+        expected: usize,
+        /// Number of arguments found
+        found: usize,
+    },
+    /// Fewer or more free arguments were given than are accepted
+    InvalidFreeCount{
+        /// Minimum number of free arguments
+        min: usize,
+        /// Maximum number of free arguments, if any
+        max: Option<usize>,
+        /// Number of free arguments found
+        found: usize,
+    },
+    /// An option value was not one of its possible values
+    InvalidValue{
+        /// Name of the option
+        option: &'a str,
+        /// The value given
+        value: &'a str,
+        /// Values which the option accepts
+        possible: &'static [&'static str],
+        /// A possible value similar to the value given, if any
+        suggestion: Option<&'static str>,
+    },
+    /// An option expecting an argument received none
+    MissingArgument{
+        /// Name of the option
+        option: &'a str,
+    },
+    /// A command name was expected, but none was given
+    MissingCommand,
+    /// A required option was not given
+    MissingRequired{
+        /// Name of the option
+        option: &'a str,
+    },
+    /// A required command was not given
+    MissingRequiredCommand,
+    /// A required free argument was not given
+    MissingRequiredFree,
+    /// Fewer free arguments were given than are required
+    MissingRequiredFreeCount{
+        /// Number of free arguments expected
+        expected: usize,
+        /// Number of free arguments found
+        found: usize,
+    },
+    /// An option was given without another option which it requires
+    MissingRequirement{
+        /// Name of the option
+        option: &'a str,
+        /// Name of the option which it requires
+        required: &'a str,
+    },
+    /// An option which does not accept an argument received one
+    UnexpectedArgument{
+        /// Name of the option
+        option: &'a str,
+    },
+    /// An option expecting two or more arguments received only one,
+    /// e.g. `--option=value`
+    UnexpectedSingleArgument{
+        /// Name of the option
+        option: &'a str,
+        /// Number of arguments expected
+        expected: usize,
+    },
+    /// A free argument was given, but none are accepted
+    UnexpectedFree{
+        /// The argument
+        arg: &'a str,
+    },
+    /// A command name was not recognized
+    UnrecognizedCommand{
+        /// The command name
+        name: &'a str,
+        /// A command name similar to the name given, if any
+        suggestion: Option<&'static str>,
+    },
+    /// A long option name was not recognized
+    UnrecognizedLongOption{
+        /// The option name, without the leading `--`
+        name: &'a str,
+        /// A long option name similar to the name given, if any
+        suggestion: Option<&'static str>,
+    },
+    /// A short option name was not recognized
+    UnrecognizedShortOption{
+        /// The option character
+        name: char,
+    },
+}
+
+impl Error {
+    /// Returns the parts of the message describing this error.
+    ///
+    /// ```
+    /// use gumdrop::{ErrorMessage, Options};
+    ///
+    /// #[derive(Debug, Options)]
+    /// struct MyOptions {
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let err = MyOptions::parse_args_default(&["--verbos"]).unwrap_err();
+    ///
+    /// match err.message() {
+    ///     ErrorMessage::UnrecognizedLongOption{name, suggestion} => {
+    ///         assert_eq!(name, "verbos");
+    ///         assert_eq!(suggestion, Some("verbose"));
+    ///     }
+    ///     _ => unreachable!()
+    /// }
+    /// ```
+    pub fn message(&self) -> ErrorMessage<'_> {
+        match &self.repr {
+            Repr::ConflictingOptions(option, other) =>
+                ErrorMessage::ConflictingOptions{option, other},
+            Repr::Custom(err) => ErrorMessage::Custom(&**err),
+            Repr::FailedParse(option, err) =>
+                ErrorMessage::FailedParse{option, err: &**err},
+            Repr::FailedParseFree{name, ordinal, value, err} =>
+                ErrorMessage::FailedParseFree{name, ordinal: *ordinal, value, err: &**err},
+            Repr::FailedParseDefault{option, value, err} =>
+                ErrorMessage::FailedParseDefault{option, value, err},
+            Repr::FailedParseEnv{var, err} => ErrorMessage::FailedParseEnv{var, err},
+            Repr::HelpCommand(path) => ErrorMessage::HelpCommand{path},
+            Repr::InsufficientArguments{option, expected, found} =>
+                ErrorMessage::InsufficientArguments{
+                    option, expected: *expected, found: *found},
+            Repr::InvalidFreeCount{min, max, found} =>
+                ErrorMessage::InvalidFreeCount{min: *min, max: *max, found: *found},
+            Repr::InvalidValue{option, value, possible} =>
+                ErrorMessage::InvalidValue{option, value, possible,
+                    suggestion: suggest_value(value, possible)},
+            Repr::MissingArgument(option) => ErrorMessage::MissingArgument{option},
+            Repr::MissingCommand(_) => ErrorMessage::MissingCommand,
+            Repr::MissingRequired(option) => ErrorMessage::MissingRequired{option},
+            Repr::MissingRequiredCommand(_) => ErrorMessage::MissingRequiredCommand,
+            Repr::MissingRequiredFree => ErrorMessage::MissingRequiredFree,
+            Repr::MissingRequiredFreeCount{expected, found} =>
+                ErrorMessage::MissingRequiredFreeCount{expected: *expected, found: *found},
+            Repr::MissingRequirement(option, required) =>
+                ErrorMessage::MissingRequirement{option, required},
+            Repr::UnexpectedArgument(option) => ErrorMessage::UnexpectedArgument{option},
+            Repr::UnexpectedSingleArgument(option, expected) =>
+                ErrorMessage::UnexpectedSingleArgument{option, expected: *expected},
+            Repr::UnexpectedFree(arg) => ErrorMessage::UnexpectedFree{arg},
+            Repr::UnrecognizedCommand(name, cmds) =>
+                ErrorMessage::UnrecognizedCommand{name, suggestion: suggest(name, cmds)},
+            Repr::UnrecognizedLongOption(name, opts) =>
+                ErrorMessage::UnrecognizedLongOption{name, suggestion: suggest(name, opts)},
+            Repr::UnrecognizedShortOption(name) =>
+                ErrorMessage::UnrecognizedShortOption{name: *name},
+        }
+    }
+
+    /// Formats the error message using the given formatter, prefixed with
+    /// the position of the offending argument, if known, as by `{:#}`.
+    ///
+    /// ```
+    /// use gumdrop::{ErrorFormatter, ErrorMessage, Options};
+    ///
+    /// struct German;
+    ///
+    /// impl ErrorFormatter for German {
+    ///     fn message(&self, msg: &ErrorMessage, out: &mut String) {
+    ///         match msg {
+    ///             ErrorMessage::UnrecognizedLongOption{name, ..} =>
+    ///                 out.push_str(&format!("unbekannte Option `--{}`", name)),
+    ///             _ => out.push_str(&msg.to_string())
+    ///         }
+    ///     }
+    ///
+    ///     fn position(&self, index: usize, arg: &str, out: &mut String) {
+    ///         out.push_str(&format!("Argument {} (`{}`): ", index + 1, arg));
+    ///     }
+    /// }
+    ///
+    /// #[derive(Debug, Options)]
+    /// struct MyOptions {
+    ///     verbose: bool,
+    /// }
+    ///
+    /// let err = MyOptions::parse_args_default(&["--frob"]).unwrap_err();
+    ///
+    /// assert_eq!(err.format_with(&German),
+    ///     "Argument 1 (`--frob`): unbekannte Option `--frob`");
+    /// ```
+    pub fn format_with(&self, formatter: &dyn ErrorFormatter) -> String {
+        let mut res = String::new();
+
+        if let Some((index, arg)) = &self.position {
+            formatter.position(*index, arg, &mut res);
+        }
+
+        formatter.message(&self.message(), &mut res);
+        res
+    }
+}
+
+impl fmt::Display for ErrorMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ErrorMessage::*;
+
+        match self {
+            ConflictingOptions{option, other} =>
+                write!(f, "option `{}` cannot be used with `{}`", option, other),
+            Custom(err) => fmt::Display::fmt(err, f),
+            FailedParse{option, err} =>
+                write!(f, "invalid argument to option `{}`: {}", option, err),
+            FailedParseFree{name, ordinal, value, err} =>
+                write!(f, "invalid {} positional argument `{}` for `{}`: {}",
+                    Ordinal(*ordinal), value, name, err),
+            FailedParseDefault{option, value, err} =>
+                write!(f, "invalid default value for `{}` ({:?}): {}", option, value, err),
+            FailedParseEnv{var, err} =>
+                write!(f, "invalid value for environment variable `{}`: {}", var, err),
+            HelpCommand{path: []} => f.write_str("help requested"),
+            HelpCommand{path} => write!(f, "help requested for command `{}`", path.join(" ")),
+            InsufficientArguments{option, expected, found} =>
+                write!(f, "insufficient arguments to option `{}`: expected {}; found {}",
+                    option, expected, found),
+            InvalidFreeCount{min, max, found} => {
+                let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+                match max {
+                    Some(max) if max == min =>
+                        write!(f, "expected {} free argument{}", min, plural(*min))?,
+                    Some(max) if *min == 0 =>
+                        write!(f, "expected at most {} free argument{}", max, plural(*max))?,
+                    Some(max) =>
+                        write!(f, "expected between {} and {} free arguments", min, max)?,
+                    None =>
+                        write!(f, "expected at least {} free argument{}", min, plural(*min))?,
+                }
+
+                write!(f, "; found {}", found)
+            }
+            InvalidValue{option, value, possible, suggestion} => {
+                write!(f, "invalid value `{}` for option `{}`: expected one of: {}",
+                    value, option, possible.join(", "))?;
+
+                if let Some(name) = suggestion {
+                    write!(f, "; did you mean `{}`?", name)?;
+                }
+
+                Ok(())
+            }
+            MissingArgument{option} => write!(f, "missing argument to option `{}`", option),
+            MissingCommand => f.write_str("missing command name"),
+            MissingRequired{option} => write!(f, "missing required option `{}`", option),
+            MissingRequiredCommand => f.write_str("missing required command"),
+            MissingRequiredFree => f.write_str("missing required free argument"),
+            MissingRequiredFreeCount{expected, found} =>
+                write!(f, "missing required free arguments: expected {}; found {}",
+                    expected, found),
+            MissingRequirement{option, required} =>
+                write!(f, "option `{}` requires `{}`", option, required),
+            UnexpectedArgument{option} =>
+                write!(f, "option `{}` does not accept an argument", option),
+            UnexpectedSingleArgument{option, expected} =>
+                write!(f, "option `{}` expects {} arguments; found 1", option, expected),
+            UnexpectedFree{arg} => write!(f, "unexpected free argument `{}`", arg),
+            UnrecognizedCommand{name, suggestion} => {
+                write!(f, "unrecognized command `{}`", name)?;
+
+                if let Some(name) = suggestion {
+                    write!(f, "; did you mean `{}`?", name)?;
+                }
+
+                Ok(())
+            }
+            UnrecognizedLongOption{name, suggestion} => {
+                write!(f, "unrecognized option `--{}`", name)?;
+
+                if let Some(name) = suggestion {
+                    write!(f, "; did you mean `--{}`?", name)?;
+                }
+
+                Ok(())
+            }
+            UnrecognizedShortOption{name} => write!(f, "unrecognized option `-{}`", name),
+        }
+    }
+}
+
+/// Controls how error messages are rendered, e.g. to translate them.
+///
+/// Each method appends its rendered text to `out`. Default implementations
+/// append the English text produced by the `Display` implementation of
+/// `Error`.
+///
+/// A formatter is used by `Error::format_with` and, if returned by
+/// `ExitStrategy::error_formatter`, by `Options::parse_args_or_exit`.
+pub trait ErrorFormatter {
+    /// Renders the message describing an error
+    fn message(&self, msg: &ErrorMessage, out: &mut String) {
+        out.push_str(&msg.to_string());
+    }
+
+    /// Renders the position of the argument at which an error occurred,
+    /// preceding the message.
+    ///
+    /// `index` refers to the arguments given to the parser, which do not
+    /// include the program name.
+    fn position(&self, index: usize, arg: &str, out: &mut String) {
+        out.push_str(&format!("argument {} (`{}`): ", index + 1, arg));
+    }
+}
+
+/// Renders error messages in English, as by the `Display` implementation
+/// of `Error`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultErrorFormatter;
+
+impl ErrorFormatter for DefaultErrorFormatter {}
@@ -0,0 +1,16 @@
+//! Reading option values from the terminal
+
+use crate::Error;
+
+/// Reads a secret value, e.g. a password, from the terminal after writing
+/// the given prompt. Input is not echoed.
+///
+/// This is called by `derive(Options)` implementations of
+/// `Options::prompt_secrets` for options marked `prompt_secret`
+/// which were not given in arguments. `option` names the
+/// option in an error message if the value cannot be read, e.g. when the
+/// process has no controlling terminal.
+pub fn prompt_secret(option: &str, prompt: &str) -> Result<String, Error> {
+    rpassword::prompt_password(prompt).map_err(|e| Error::custom(
+        format!("failed to read value of option `{}` from terminal: {}", option, e)))
+}
@@ -0,0 +1,228 @@
+//! Interactive parsing of commands read line by line
+
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+
+use crate::{error_message, parse_or_help, read_confirmation, DefaultErrorFormatter, Error, Options,
+    ParseResult, ParsingStyle};
+
+/// Splits a line into arguments, in the manner of a POSIX shell.
+///
+/// Arguments are separated by whitespace. Text within single quotes is taken
+/// literally. Within double quotes, a backslash escapes `"` and `\`.
+/// Elsewhere, a backslash escapes any character.
+///
+/// ```
+/// let args = gumdrop::split_line(r#"add -m 'first item' "a \"b\"" c\ d"#).unwrap();
+///
+/// assert_eq!(args, ["add", "-m", "first item", "a \"b\"", "c d"]);
+/// ```
+///
+/// An error is returned if a quote is not closed or the line ends
+/// with a backslash.
+pub fn split_line(line: &str) -> Result<Vec<String>, Error> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut chars = line.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => {
+                in_arg = true;
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => arg.push(ch),
+                        None => return Err(Error::custom("unterminated single quote"))
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ '"') | Some(ch @ '\\') => arg.push(ch),
+                            Some(ch) => {
+                                arg.push('\\');
+                                arg.push(ch);
+                            }
+                            None => return Err(Error::custom("unterminated double quote"))
+                        },
+                        Some(ch) => arg.push(ch),
+                        None => return Err(Error::custom("unterminated double quote"))
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+
+                match chars.next() {
+                    Some(ch) => arg.push(ch),
+                    None => return Err(Error::custom("trailing backslash"))
+                }
+            }
+            ch if ch.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            ch => {
+                in_arg = true;
+                arg.push(ch);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Reads commands from `stdin`, passing each to `handler`,
+/// until end of input is reached or `handler` returns `ControlFlow::Break`.
+///
+/// `prompt` is written to `stdout` before each line is read.
+/// `name` is used in place of the program name in help and error messages.
+///
+/// See [`repl_with`](fn.repl_with.html) for details.
+pub fn repl<T, F>(name: &str, prompt: &str, handler: F) -> io::Result<()>
+        where T: Options, F: FnMut(T) -> ControlFlow<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    repl_with(name, prompt, stdin.lock(), stdout.lock(), handler)
+}
+
+/// Reads commands from `input`, passing each to `handler`,
+/// until end of input is reached or `handler` returns `ControlFlow::Break`.
+///
+/// Each line is split into arguments by [`split_line`](fn.split_line.html)
+/// and parsed using the default [parsing style](enum.ParsingStyle.html).
+/// Blank lines are ignored.
+///
+/// Unlike `parse_args_or_exit`, help text and errors are written to `output`
+/// and reading continues with the next line. If confirmation of an option is
+/// required and `--yes` was not given, the user is asked on `output` and
+/// the answer is read from `input`; the command is skipped if declined.
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use gumdrop::Options;
+///
+/// #[derive(Options)]
+/// enum Command {
+///     #[options(help = "add an item")]
+///     Add(AddOpts),
+///     #[options(help = "stop reading commands")]
+///     Quit(QuitOpts),
+/// }
+///
+/// #[derive(Options)]
+/// struct AddOpts {
+///     #[options(free, required)]
+///     item: String,
+/// }
+///
+/// #[derive(Options)]
+/// struct QuitOpts {}
+///
+/// let mut items = Vec::new();
+/// let mut output = Vec::new();
+///
+/// gumdrop::repl_with("shop", "> ", &b"add eggs\nadd\nquit\nadd milk\n"[..],
+///     &mut output, |cmd| match cmd {
+///         Command::Add(opts) => {
+///             items.push(opts.item);
+///             ControlFlow::Continue(())
+///         }
+///         Command::Quit(_) => ControlFlow::Break(()),
+///     }).unwrap();
+///
+/// assert_eq!(items, ["eggs"]);
+/// ```
+pub fn repl_with<T, R, W, F>(name: &str, prompt: &str, mut input: R, mut output: W,
+        mut handler: F) -> io::Result<()>
+        where T: Options, R: BufRead, W: Write, F: FnMut(T) -> ControlFlow<()> {
+    let mut line = String::new();
+
+    loop {
+        output.write_all(prompt.as_bytes())?;
+        output.flush()?;
+
+        line.clear();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let args = match split_line(&line) {
+            Ok(args) => args,
+            Err(e) => {
+                writeln!(output, "{}: {}", name, e)?;
+                continue;
+            }
+        };
+
+        if args.is_empty() {
+            continue;
+        }
+
+        let mut full_args = Vec::with_capacity(args.len() + 1);
+        full_args.push(name.to_owned());
+        full_args.extend(args);
+
+        let mut assume_yes = false;
+
+        let e = match parse_or_help::<T, _>(&full_args, None,
+                ParsingStyle::default(), false, false, &mut assume_yes) {
+            ParseResult::Parsed(opts) => {
+                if !assume_yes && !confirm_all(&opts, &mut input, &mut output)? {
+                    writeln!(output, "aborted")?;
+                    continue;
+                }
+
+                if handler(opts).is_break() {
+                    return Ok(());
+                }
+
+                continue;
+            }
+            ParseResult::Help(help) | ParseResult::Version(help) => {
+                writeln!(output, "{}", help)?;
+                continue;
+            }
+            ParseResult::Err(e) => e
+        };
+
+        let mut msg = error_message(name, &full_args[1..], &e, false, &DefaultErrorFormatter);
+
+        if let Some(cmds) = e.available_commands() {
+            msg.push_str("\navailable commands: ");
+            msg.push_str(&cmds.join(", "));
+        }
+
+        writeln!(output, "{}", msg)?;
+    }
+}
+
+fn confirm_all<T: Options, R: BufRead, W: Write>(opts: &T, input: &mut R, output: &mut W)
+        -> io::Result<bool> {
+    for prompt in opts.confirmations() {
+        write!(output, "{} [y/N] ", prompt)?;
+        output.flush()?;
+
+        if !read_confirmation(input)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
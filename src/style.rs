@@ -0,0 +1,252 @@
+//! Styling of usage text
+//!
+//! Usage text generated by `gumdrop_derive` with the `style` feature enabled
+//! contains markers surrounding spans of text which may be styled at runtime,
+//! e.g. `"\u{1}hHeading:\u{2}"`. These definitions must agree with those in
+//! `gumdrop_derive`.
+
+#![cfg_attr(not(feature = "style"), allow(dead_code))]
+
+/// Begins a styled span; followed by one of the `STYLE_*` codes below
+pub(crate) const STYLE_START: char = '\u{1}';
+/// Ends a styled span
+pub(crate) const STYLE_END: char = '\u{2}';
+
+const STYLE_HEADING: char = 'h';
+const STYLE_OPTION: char = 'o';
+const STYLE_META: char = 'm';
+const STYLE_DEFAULT: char = 'd';
+const STYLE_POSITIONAL: char = 'p';
+const STYLE_COMMAND: char = 'c';
+const STYLE_STRONG: char = 's';
+const STYLE_EMPHASIS: char = 'e';
+const STYLE_CODE: char = 'l';
+
+/// Returns a heading marked with the heading style code
+pub(crate) fn heading(text: &str) -> String {
+    styled(STYLE_HEADING, text)
+}
+
+fn styled(style: char, text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 3);
+
+    res.push(STYLE_START);
+    res.push(style);
+    res.push_str(text);
+    res.push(STYLE_END);
+    res
+}
+
+/// Returns whether colored output should be written to the given stream.
+///
+/// Color is used only when the stream is a terminal and the `NO_COLOR`
+/// environment variable is unset or empty.
+#[cfg(feature = "style")]
+pub(crate) fn color_enabled<T: std::io::IsTerminal>(stream: &T) -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+
+    !no_color && stream.is_terminal()
+}
+
+/// Controls how parts of usage text are rendered.
+///
+/// Each method receives a span of usage text and appends its rendered form
+/// to `out`. Default implementations append the text unchanged; the methods
+/// for positional argument names, command names, and code spans default
+/// to `option_name`.
+///
+/// Text surrounding these spans is already aligned into columns, so a style
+/// which changes the visible width of a span may cause misaligned output.
+///
+/// # Examples
+///
+/// ```
+/// use gumdrop::{HelpStyle, Options};
+///
+/// struct Shouting;
+///
+/// impl HelpStyle for Shouting {
+///     fn heading(&self, text: &str, out: &mut String) {
+///         out.push_str(&text.to_uppercase());
+///     }
+/// }
+///
+/// #[derive(Options)]
+/// struct MyOptions {
+///     #[options(help = "print help message")]
+///     help: bool,
+/// }
+///
+/// # if cfg!(feature = "strip_help") { return; }
+/// assert!(MyOptions::usage_with_style(&Shouting).starts_with("OPTIONAL ARGUMENTS:"));
+/// ```
+#[cfg(feature = "style")]
+pub trait HelpStyle {
+    /// Renders a section heading, e.g. `Optional arguments:`
+    fn heading(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders the names of an option, e.g. `-f, --foo`
+    fn option_name(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders the meta variable of an option, e.g. `FOO`
+    fn meta(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders the default value of an option, e.g. `(default: 1)`
+    fn default_value(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders the name of a positional argument
+    fn positional_name(&self, text: &str, out: &mut String) {
+        self.option_name(text, out);
+    }
+
+    /// Renders the name of a command
+    fn command_name(&self, text: &str, out: &mut String) {
+        self.option_name(text, out);
+    }
+
+    /// Renders text given strong emphasis in a doc comment, e.g. `**text**`,
+    /// when `markdown = "render"` is given
+    fn strong(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders text given emphasis in a doc comment, e.g. `*text*`,
+    /// when `markdown = "render"` is given
+    fn emphasis(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+
+    /// Renders a code span in a doc comment, e.g. `` `text` ``,
+    /// when `markdown = "render"` is given
+    fn code(&self, text: &str, out: &mut String) {
+        self.option_name(text, out);
+    }
+
+    /// Renders the parts of an error message which draw attention to the
+    /// error, e.g. the `error:` label and the marker beneath a bad argument
+    fn error(&self, text: &str, out: &mut String) {
+        out.push_str(text);
+    }
+}
+
+/// Renders usage text without styling
+#[cfg(feature = "style")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PlainStyle;
+
+/// Renders usage text using ANSI color escape sequences
+///
+/// This style is used by `Options::usage_styled` and `parse_args_or_exit`
+/// when color output is enabled.
+#[cfg(feature = "style")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AnsiStyle;
+
+#[cfg(feature = "style")]
+impl HelpStyle for PlainStyle {}
+
+#[cfg(feature = "style")]
+impl AnsiStyle {
+    fn paint(code: &str, text: &str, out: &mut String) {
+        out.push_str(code);
+        out.push_str(text);
+        out.push_str("\x1b[0m");
+    }
+}
+
+#[cfg(feature = "style")]
+impl HelpStyle for AnsiStyle {
+    fn heading(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[1;33m", text, out);
+    }
+
+    fn option_name(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[32m", text, out);
+    }
+
+    fn meta(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[36m", text, out);
+    }
+
+    fn default_value(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[2m", text, out);
+    }
+
+    fn strong(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[1m", text, out);
+    }
+
+    fn emphasis(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[4m", text, out);
+    }
+
+    fn error(&self, text: &str, out: &mut String) {
+        AnsiStyle::paint("\x1b[1;31m", text, out);
+    }
+}
+
+/// Renders usage text containing style markers using the given style
+#[cfg(feature = "style")]
+pub(crate) fn render(text: &str, style: &dyn HelpStyle) -> String {
+    let mut res = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+
+    while let Some(start) = rest.find(STYLE_START) {
+        res.push_str(&rest[..start]);
+        rest = &rest[start + STYLE_START.len_utf8()..];
+
+        let mut chars = rest.chars();
+        let code = chars.next();
+        rest = chars.as_str();
+
+        let end = rest.find(STYLE_END).unwrap_or(rest.len());
+        let span = &rest[..end];
+
+        match code {
+            Some(STYLE_HEADING) => style.heading(span, &mut res),
+            Some(STYLE_OPTION) => style.option_name(span, &mut res),
+            Some(STYLE_META) => style.meta(span, &mut res),
+            Some(STYLE_DEFAULT) => style.default_value(span, &mut res),
+            Some(STYLE_POSITIONAL) => style.positional_name(span, &mut res),
+            Some(STYLE_COMMAND) => style.command_name(span, &mut res),
+            Some(STYLE_STRONG) => style.strong(span, &mut res),
+            Some(STYLE_EMPHASIS) => style.emphasis(span, &mut res),
+            Some(STYLE_CODE) => style.code(span, &mut res),
+            _ => res.push_str(span)
+        }
+
+        rest = rest.get(end + STYLE_END.len_utf8()..).unwrap_or("");
+    }
+
+    res.push_str(rest);
+    res
+}
+
+#[cfg(all(test, feature = "style"))]
+mod test {
+    use super::{render, AnsiStyle, PlainStyle};
+
+    #[test]
+    fn test_render() {
+        let text = "\u{1}hOptional arguments:\u{2}\n  \u{1}o-f, --foo\u{2} \u{1}mFOO\u{2}  foo";
+
+        assert_eq!(render(text, &PlainStyle), "Optional arguments:\n  -f, --foo FOO  foo");
+        assert_eq!(render(text, &AnsiStyle),
+            "\x1b[1;33mOptional arguments:\x1b[0m\n  \
+             \x1b[32m-f, --foo\x1b[0m \x1b[36mFOO\x1b[0m  foo");
+
+        let text = "\u{1}sall\u{2}, \u{1}eeach\u{2}, \u{1}lcode\u{2}";
+
+        assert_eq!(render(text, &PlainStyle), "all, each, code");
+        assert_eq!(render(text, &AnsiStyle),
+            "\x1b[1mall\x1b[0m, \x1b[4meach\x1b[0m, \x1b[32mcode\x1b[0m");
+    }
+}
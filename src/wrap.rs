@@ -0,0 +1,251 @@
+//! Wrapping of usage text to a given width
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::style::{STYLE_END, STYLE_START};
+
+/// Minimum width available to wrapped text, following its indentation.
+/// Lines which would leave less room than this are not wrapped.
+const MIN_WRAP_WIDTH: usize = 20;
+
+/// Indentation of help text moved to the line following an option or
+/// command name, when too little room remains beside it
+const NEXT_LINE_INDENT: usize = 8;
+
+/// Returns the width to which help text should be wrapped, if any.
+///
+/// If the `COLUMNS` environment variable contains a positive integer,
+/// its value is used. Otherwise, if the `terminal_size` feature is enabled,
+/// the width of the terminal attached to `stdout` or `stderr` is used.
+/// If neither is available, `None` is returned.
+pub fn terminal_width() -> Option<usize> {
+    if let Some(n) = std::env::var("COLUMNS").ok()
+            .and_then(|s| s.trim().parse::<usize>().ok()) {
+        if n > 0 {
+            return Some(n);
+        }
+    }
+
+    #[cfg(feature = "terminal_size")]
+    {
+        if let Some((terminal_size::Width(w), _)) = terminal_size::terminal_size() {
+            return Some(w as usize);
+        }
+    }
+
+    None
+}
+
+/// Wraps each line of help text so that it does not exceed `width` columns.
+///
+/// Width is measured in terminal columns, so that wide characters,
+/// such as those of CJK scripts, occupy two columns.
+///
+/// Continuation lines are indented to the column at which the wrapped text
+/// began; for option and command listings, this is the help text column.
+/// If the help text column leaves too little room, help text is instead
+/// moved to the following line, indented by eight spaces, and wrapped there.
+/// Lines which cannot be wrapped, such as a single long word, are left as is.
+///
+/// # Examples
+///
+/// ```
+/// let usage = "  -f, --foo  set the foo value for this operation";
+///
+/// assert_eq!(gumdrop::wrap_help(usage, 34), "  -f, --foo  set the foo value for\n             this operation");
+/// ```
+pub fn wrap_help(text: &str, width: usize) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut moved = None;
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i != 0 {
+            res.push('\n');
+        }
+
+        moved = wrap_line(&mut res, line, width, moved);
+    }
+
+    res
+}
+
+/// Wraps a single line of help text.
+///
+/// If help text following a name is moved to the next line, the column at
+/// which it began is returned, so that continuation lines of the same help
+/// text, given as `moved`, are moved to the same indentation.
+fn wrap_line(res: &mut String, line: &str, width: usize, moved: Option<usize>)
+        -> Option<usize> {
+    // Blank lines within help text are not indented
+    if line.is_empty() {
+        return moved;
+    }
+
+    let chars = visible_chars(line);
+    let head_len = hanging_indent(&chars);
+    let indent = chars_width(&chars[..head_len]);
+
+    let split = chars.get(head_len).map_or(line.len(), |&(idx, _)| idx);
+    let (head, rest) = line.split_at(split);
+    let name = head.trim_end();
+
+    let continued = moved == Some(indent) && name.is_empty();
+
+    if chars_width(&chars) <= width && !continued {
+        res.push_str(line);
+        return None;
+    }
+
+    if continued || indent + MIN_WRAP_WIDTH > width {
+        if !continued &&
+                (indent <= NEXT_LINE_INDENT || NEXT_LINE_INDENT + MIN_WRAP_WIDTH > width) {
+            res.push_str(line);
+            return None;
+        }
+
+        if !name.is_empty() {
+            res.push_str(name);
+            res.push('\n');
+        }
+
+        res.push_str(&" ".repeat(NEXT_LINE_INDENT));
+        push_words(res, rest, NEXT_LINE_INDENT, width);
+        return Some(indent);
+    }
+
+    res.push_str(head);
+    push_words(res, rest, indent, width);
+    None
+}
+
+/// Appends words of `text` to a line beginning at column `indent`,
+/// beginning a new line, indented to the same column, before any word
+/// which would exceed `width`
+fn push_words(res: &mut String, text: &str, indent: usize, width: usize) {
+    let mut cur_width = indent;
+    let mut line_start = true;
+
+    for word in text.split(' ').filter(|w| !w.is_empty()) {
+        let word_width = chars_width(&visible_chars(word));
+
+        if !line_start && cur_width + 1 + word_width > width {
+            res.push('\n');
+            res.push_str(&" ".repeat(indent));
+            cur_width = indent;
+            line_start = true;
+        }
+
+        if !line_start {
+            res.push(' ');
+            cur_width += 1;
+        }
+
+        res.push_str(word);
+        cur_width += word_width;
+        line_start = false;
+    }
+}
+
+/// Returns the number of characters preceding the text to be wrapped.
+///
+/// Lines in option and command listings begin with two spaces, a name,
+/// and at least two spaces of padding before help text.
+/// Other lines are indented to their leading whitespace.
+fn hanging_indent(chars: &[(usize, char)]) -> usize {
+    let leading = chars.iter().take_while(|&&(_, ch)| ch == ' ').count();
+
+    if leading == 2 {
+        let gap = chars[leading..].windows(2)
+            .position(|w| w[0].1 == ' ' && w[1].1 == ' ');
+
+        if let Some(pos) = gap {
+            let start = leading + pos;
+
+            return start + chars[start..].iter()
+                .take_while(|&&(_, ch)| ch == ' ').count();
+        }
+    }
+
+    leading
+}
+
+fn chars_width(chars: &[(usize, char)]) -> usize {
+    chars.iter().map(|&(_, ch)| ch.width().unwrap_or(0)).sum()
+}
+
+/// Returns the byte index and value of each visible character,
+/// skipping style markers.
+fn visible_chars(text: &str) -> Vec<(usize, char)> {
+    let mut res = Vec::with_capacity(text.len());
+    let mut iter = text.char_indices();
+
+    while let Some((idx, ch)) = iter.next() {
+        match ch {
+            STYLE_START => { iter.next(); }
+            STYLE_END => (),
+            _ => res.push((idx, ch))
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::wrap_help;
+
+    #[test]
+    fn test_wrap_help() {
+        let usage = "\
+Does some things, which are described at length here
+
+Optional arguments:
+  -f, --foo FOO  foo help which is long enough to wrap
+  --a-very-long-option-name
+                 long help which is also long enough to wrap
+  -b, --bar      short help";
+
+        assert_eq!(wrap_help(usage, 40), "\
+Does some things, which are described at
+length here
+
+Optional arguments:
+  -f, --foo FOO  foo help which is long
+                 enough to wrap
+  --a-very-long-option-name
+                 long help which is also
+                 long enough to wrap
+  -b, --bar      short help");
+
+        // Too narrow to wrap following the help column
+        let usage = &"
+  -f, --foo FOO  foo help which is long enough to wrap
+                 and more help
+  --bar          bar"[1..];
+        assert_eq!(wrap_help(usage, 30), &"
+  -f, --foo FOO
+        foo help which is long
+        enough to wrap
+        and more help
+  --bar          bar"[1..]);
+
+        // Too narrow to wrap at all
+        let line = "  -f, --foo FOO  foo help which is long enough to wrap";
+        assert_eq!(wrap_help(line, 27), line);
+    }
+
+    #[test]
+    fn test_wrap_wide() {
+        let usage = "  -f, --foo  \u{6587}\u{5b57} \u{6587}\u{5b57} \u{6587}\u{5b57} \u{6587}\u{5b57} \u{6587}\u{5b57}";
+
+        assert_eq!(wrap_help(usage, 34), "  -f, --foo  \u{6587}\u{5b57} \u{6587}\u{5b57} \u{6587}\u{5b57} \u{6587}\u{5b57}\n             \u{6587}\u{5b57}");
+    }
+
+    #[test]
+    fn test_wrap_markup() {
+        let usage = "  \u{1}o-f, --foo\u{2}  the foo value \u{1}d(default: 1)\u{2}";
+
+        assert_eq!(wrap_help(usage, 35),
+            "  \u{1}o-f, --foo\u{2}  the foo value\n             \u{1}d(default: 1)\u{2}");
+    }
+}
@@ -0,0 +1,124 @@
+// Checks that successful parsing does not allocate, except to store values
+// which are themselves allocated.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use gumdrop::{Options, Parser, ParsingStyle};
+
+struct CountingAlloc;
+
+// Counted per thread, as tests run in parallel
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[derive(Options)]
+struct Opts {
+    help: bool,
+    #[options(count)]
+    verbose: u32,
+    #[options(required)]
+    jobs: u32,
+    #[options(default = "3")]
+    level: u8,
+    pair: Option<(i32, i32)>,
+    #[options(possible_values = "fast,slow")]
+    mode: Option<Mode>,
+    #[options(command)]
+    command: Option<Command>,
+}
+
+#[derive(Options)]
+enum Command {
+    Run(RunOpts),
+}
+
+#[derive(Options)]
+struct RunOpts {
+    #[options(free)]
+    file: Option<u32>,
+    quiet: bool,
+    name: Option<u32>,
+}
+
+enum Mode {
+    Fast,
+    Slow,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mode, String> {
+        match s {
+            "fast" => Ok(Mode::Fast),
+            "slow" => Ok(Mode::Slow),
+            _ => Err(format!("invalid mode `{}`", s))
+        }
+    }
+}
+
+#[test]
+fn test_parse_without_allocation() {
+    let args = ["-vv", "--jobs=4", "--level", "5", "--pair", "1", "2",
+        "--mode", "fast", "run", "-q", "--name", "3", "7"];
+
+    let n = count_allocations(|| {
+        let mut parser = Parser::new(&args, ParsingStyle::default());
+        let opts = Opts::parse(&mut parser).ok().unwrap();
+
+        assert!(!opts.help_requested());
+        assert!(matches!(opts.mode, Some(Mode::Fast)));
+        assert!(!matches!(opts.mode, Some(Mode::Slow)));
+    });
+
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn test_static_errors_without_allocation() {
+    use gumdrop::{Error, ErrorKind};
+
+    const MISSING: Error = Error::missing_required_static("--foo");
+
+    let n = count_allocations(|| {
+        let errors = [
+            Error::conflicting_options_static("--foo", "--bar"),
+            Error::missing_required_static("--foo"),
+            Error::missing_requirement_static("--foo", "--bar"),
+            Error::missing_required_free(),
+            Error::missing_command(),
+            Error::unrecognized_short('x'),
+        ];
+
+        assert_eq!(errors[1].kind(), MISSING.kind());
+        assert_eq!(errors[2].kind(), ErrorKind::MissingRequirement);
+    });
+
+    assert_eq!(n, 0);
+
+    assert_eq!(MISSING.option().as_deref(), Some("--foo"));
+    assert_eq!(Error::missing_requirement_static("--foo", "--bar").to_string(),
+        "option `--foo` requires `--bar`");
+}
@@ -2,9 +2,9 @@ use std::str::FromStr;
 
 use assert_matches::assert_matches;
 
-use gumdrop::Options;
+use gumdrop::{ErrorKind, Options, ParsingStyle};
 
-const EMPTY: &'static [&'static str] = &[];
+const EMPTY: &[&str] = &[];
 
 #[derive(Debug, Options)]
 struct NoOpts { }
@@ -38,6 +38,7 @@ fn test_hygiene() {
     #[allow(dead_code)] struct ToString;
     #[allow(dead_code)] struct Vec;
 
+    #[allow(dead_code)]
     #[derive(Options)]
     struct Opts {
         a: i32,
@@ -52,12 +53,14 @@ fn test_hygiene() {
         cmd: ::std::option::Option<Cmd>,
     }
 
+    #[allow(dead_code)]
     #[derive(Options)]
     enum Cmd {
         Foo(FooOpts),
         Bar(BarOpts),
     }
 
+    #[allow(dead_code)]
     #[derive(Options)]
     struct FooOpts {
         #[options(free)]
@@ -65,6 +68,7 @@ fn test_hygiene() {
         a: i32,
     }
 
+    #[allow(dead_code)]
     #[derive(Options)]
     struct BarOpts {
         #[options(free)]
@@ -109,19 +113,19 @@ fn test_command() {
     }
 
     let opts = Opts::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.command.is_none(), true);
+    assert!(opts.command.is_none());
 
     let opts = Opts::parse_args_default(&["-h"]).unwrap();
-    assert_eq!(opts.help, true);
-    assert_eq!(opts.command.is_none(), true);
+    assert!(opts.help);
+    assert!(opts.command.is_none());
 
     let opts = Opts::parse_args_default(&["-h", "foo", "--foo", "x"]).unwrap();
-    assert_eq!(opts.help, true);
+    assert!(opts.help);
     let cmd = opts.command.unwrap();
     assert_matches!(cmd, Command::Foo(FooOpts{foo: Some(ref foo)}) if foo == "x");
 
     let opts = Opts::parse_args_default(&["--", "foo"]).unwrap();
-    assert_eq!(opts.help, false);
+    assert!(!opts.help);
     let cmd = opts.command.unwrap();
     assert_matches!(cmd, Command::Foo(_));
 
@@ -144,7 +148,7 @@ fn test_command() {
     is_err!(Opts::parse_args_default(&["foo", "-h"]),
         "unrecognized option `-h`");
     is_err!(Opts::parse_args_default(&["baz"]),
-        "unrecognized command `baz`");
+        "unrecognized command `baz`; did you mean `bar`?");
 }
 
 #[test]
@@ -224,72 +228,4424 @@ fn test_nested_command() {
 
     let opts = Main::parse_args_default(&["-h", "alpha", "bar"]).unwrap();
     assert_eq!(opts.self_usage(), Bar::usage());
+    assert_eq!(opts.command_path(), ["alpha", "bar"]);
+
+    let opts = Main::parse_args_default(&["-h", "bravo"]).unwrap();
+    assert_eq!(opts.command_path(), ["bravo"]);
+
+    let opts = Main::parse_args_default(&["-h"]).unwrap();
+    assert_eq!(opts.command_path(), [] as [&str; 0]);
+
+    #[derive(Debug, Options)]
+    enum Outer {
+        Alpha(AlphaCommand),
+    }
+
+    let opts = Outer::parse_args_default(&["alpha", "foo", "-h"]).unwrap();
+    assert_eq!(opts.command_path(), ["alpha", "foo"]);
+    assert_eq!(opts.self_usage(), Foo::usage());
+}
+
+#[test]
+fn test_command_name() {
+    #[derive(Options)]
+    struct Opts {
+        help: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Foo(NoOpts),
+        Bar(NoOpts),
+        #[options(name = "bzzz")]
+        Baz(NoOpts),
+        BoopyDoop(NoOpts),
+    }
+
+
+    let opts = Opts::parse_args_default(&["foo"]).unwrap();
+    assert_matches!(opts.command_name(), Some("foo"));
+
+    let opts = Opts::parse_args_default(&["bar"]).unwrap();
+    assert_matches!(opts.command_name(), Some("bar"));
+
+    let opts = Opts::parse_args_default(&["bzzz"]).unwrap();
+    assert_matches!(opts.command_name(), Some("bzzz"));
+
+    let opts = Opts::parse_args_default(&["boopy-doop"]).unwrap();
+    assert_matches!(opts.command_name(), Some("boopy-doop"));
+}
+
+#[test]
+fn test_command_names() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    struct RequiredOpts {
+        #[options(command, required)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(help_command)]
+    enum Command {
+        Foo(NoOpts),
+        #[options(name = "bzzz")]
+        Baz(NoOpts),
+    }
+
+    assert_eq!(Command::command_names(), ["foo", "bzzz", "help"]);
+    assert_eq!(Opts::command_names(), ["foo", "bzzz", "help"]);
+    assert_eq!(NoOpts::command_names(), [] as [&str; 0]);
+
+    let e = Opts::parse_args_default(&["baz"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.to_string(), "unrecognized command `baz`");
+    assert_eq!(e.available_commands(), Some(&["foo", "bzzz", "help"][..]));
+
+    let e = Command::parse_args_default(EMPTY).map(|_| ()).unwrap_err();
+    assert_eq!(e.to_string(), "missing command name");
+    assert_eq!(e.available_commands(), Some(&["foo", "bzzz", "help"][..]));
+
+    let e = RequiredOpts::parse_args_default(EMPTY).map(|_| ()).unwrap_err();
+    assert_eq!(e.to_string(), "missing required command");
+    assert_eq!(e.available_commands(), Some(&["foo", "bzzz", "help"][..]));
+
+    let e = NoOpts::parse_args_default(&["foo"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.available_commands(), None);
+}
+
+#[test]
+fn test_dispatch() {
+    #[derive(Options)]
+    #[options(dispatch = "Handler")]
+    enum Command {
+        Foo(FooOpts),
+        FooBar(NoOpts),
+        Type(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct FooOpts {
+        #[options(free)]
+        free: Vec<String>,
+    }
+
+    struct Runner {
+        calls: u32,
+    }
+
+    impl Handler for Runner {
+        type Output = String;
+
+        fn foo(&mut self, opts: FooOpts) -> String {
+            self.calls += 1;
+            format!("foo {}", opts.free.join(" "))
+        }
+
+        fn foo_bar(&mut self, _opts: NoOpts) -> String {
+            self.calls += 1;
+            "foo-bar".to_owned()
+        }
+
+        fn r#type(&mut self, _opts: NoOpts) -> String {
+            self.calls += 1;
+            "type".to_owned()
+        }
+    }
+
+    let mut runner = Runner{calls: 0};
+
+    let cmd = Command::parse_args_default(&["foo", "a", "b"]).unwrap();
+    assert_eq!(cmd.dispatch(&mut runner), "foo a b");
+
+    let cmd = Command::parse_args_default(&["foo-bar"]).unwrap();
+    assert_eq!(cmd.dispatch(&mut runner), "foo-bar");
+
+    let cmd = Command::parse_args_default(&["type"]).unwrap();
+    assert_eq!(cmd.dispatch(&mut runner), "type");
+
+    assert_eq!(runner.calls, 3);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_command_usage() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "help me!")]
+        help: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "foo help")]
+        Foo(NoOpts),
+        #[options(help = "bar help")]
+        Bar(NoOpts),
+        #[options(help = "baz help")]
+        #[options(name = "bzzz")]
+        Baz(NoOpts),
+    }
+
+    assert_eq!(Command::usage(), &"
+  foo   foo help
+  bar   bar help
+  bzzz  baz help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::command_list(), Some(Command::usage()));
+    assert_eq!(Opts::command_list(), Some(Command::usage()));
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_help_command() {
+    #[derive(Options)]
+    struct Opts {
+        help: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(help_command)]
+    enum Command {
+        #[options(help = "foo help")]
+        Foo(FooOpts),
+        #[options(help = "bar help")]
+        Bar(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct FooOpts {
+        #[options(help = "alpha help")]
+        alpha: bool,
+
+        #[options(command)]
+        command: Option<FooCommand>,
+    }
+
+    #[derive(Options)]
+    #[options(help_command)]
+    enum FooCommand {
+        #[options(help = "baz help")]
+        Baz(NoOpts),
+    }
+
+    assert_eq!(Command::usage(), &"
+  foo   foo help
+  bar   bar help
+  help  show help for a command"
+        // Skip leading newline
+        [1..]);
+
+    // The `help` command is parsed as a help flag
+    let opts = Opts::parse_args_default(&["help"]).unwrap();
+    assert!(opts.help_requested());
+    assert!(opts.command.is_none());
+
+    #[derive(Options)]
+    struct Required {
+        #[options(required)]
+        jobs: u32,
+        #[options(command, required)]
+        command: Option<Command>,
+    }
+
+    let opts = Required::parse_args_default(&["help", "bar"]).unwrap();
+    assert!(!opts.help_requested());
+    assert!(opts.command.is_none());
+
+    let help_path = |args: &[&str]| {
+        let mut parser = gumdrop::Parser::new(args, ParsingStyle::default());
+        let opts = Opts::parse(&mut parser).unwrap();
+        assert!(opts.help_requested());
+        parser.help_command_path().map(|path| path.join(" "))
+    };
+
+    assert_eq!(help_path(&["help"]).as_deref(), Some(""));
+    assert_eq!(help_path(&["help", "foo", "baz"]).as_deref(), Some("foo baz"));
+    assert_eq!(help_path(&["foo", "help", "baz"]).as_deref(), Some("foo baz"));
+    assert_eq!(help_path(&["foo", "--alpha", "help"]).as_deref(), Some("foo"));
+
+    // Parsed directly, a command enum has no help flag to set
+    let e = Command::parse_args_default(&["help", "foo", "baz"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.help_command_path(), Some(&["foo".to_owned(), "baz".to_owned()][..]));
+    assert_eq!(e.to_string(), "help requested for command `foo baz`");
+
+    let e = Command::parse_args_default(&["help"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.to_string(), "help requested");
+
+    let e = Opts::parse_args_default(&["baz"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.help_command_path(), None);
+
+    assert_eq!(Opts::command_path_usage(EMPTY)
+        .map(|i| (i.usage, i.command_list)),
+        Some((Opts::usage(), Some(Command::usage()))));
+    assert_eq!(Opts::command_path_usage(&["foo"])
+        .map(|i| (i.usage, i.command_list)),
+        Some((FooOpts::usage(), Some(FooCommand::usage()))));
+    assert_eq!(Opts::command_path_usage(&["foo", "baz"])
+        .map(|i| (i.usage, i.command_list)),
+        Some((NoOpts::usage(), None)));
+    assert_eq!(Opts::command_path_usage(&["bar"])
+        .map(|i| (i.usage, i.command_list)),
+        Some((NoOpts::usage(), None)));
+    assert_eq!(Opts::command_path_usage(&["foo", "bar"]), None);
+    assert_eq!(Opts::command_path_usage(&["quux"]), None);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_help_all() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help")]
+        help: bool,
+        #[options(no_short, help = "print help for all commands")]
+        help_all: bool,
+        #[options(required)]
+        foo: u32,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "alpha help")]
+        Alpha(AlphaOpts),
+        #[options(help = "bravo help")]
+        Bravo(BravoOpts),
+    }
+
+    #[derive(Options)]
+    struct AlphaOpts {
+        #[options(help_flag)]
+        halp: bool,
+        #[options(help_all_flag)]
+        halp_all: bool,
+    }
+
+    #[derive(Options)]
+    struct BravoOpts {
+        #[options(help = "charlie help")]
+        charlie: bool,
+    }
+
+    let opts = Opts::parse_args_default(&["--help-all"]).unwrap();
+    assert!(opts.help_all_requested());
+    assert!(!opts.help_requested());
+
+    let opts = Opts::parse_args_default(&["-f", "1", "alpha", "--halp-all"]).unwrap();
+    assert!(opts.help_all_requested());
+
+    let opts = Opts::parse_args_default(&["-f", "1", "alpha", "--halp"]).unwrap();
+    assert!(!opts.help_all_requested());
+
+    assert_eq!(Opts::usage_all("prog"), &"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help     print help
+  --help-all     print help for all commands
+  -f, --foo FOO  (required)
+
+Available commands:
+  alpha  alpha help
+  bravo  bravo help
+
+Usage: prog alpha [OPTIONS]
+
+Optional arguments:
+  -h, --halp
+  -H, --halp-all
+
+Usage: prog bravo [OPTIONS]
+
+Optional arguments:
+  -c, --charlie  charlie help"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_after_help() {
+    /// Does things
+    #[derive(Options)]
+    #[options(after_help)]
+    /// See the manual for more.
+    ///   Report bugs to the tracker.
+    struct Opts {
+        #[options(help = "print help")]
+        help: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(after_help = "Commands may be abbreviated.")]
+    enum Command {
+        #[options(help = "foo help")]
+        Foo(FooOpts),
+    }
+
+    #[derive(Options)]
+    #[options(after_help = "Foo is deprecated.")]
+    struct FooOpts {
+        bar: bool,
+    }
+
+    assert_eq!(Opts::after_help(), Some("See the manual for more.\nReport bugs to the tracker."));
+    assert_eq!(Command::after_help(), Some("Commands may be abbreviated."));
+    assert_eq!(NoOpts::after_help(), None);
+
+    assert_eq!(Opts::usage(), &"
+Does things
+
+Optional arguments:
+  -h, --help  print help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Opts::help_info().format("prog"), &"
+Usage: prog [OPTIONS]
+
+Does things
+
+Optional arguments:
+  -h, --help  print help
+
+Available commands:
+  foo   foo help
+
+See the manual for more.
+Report bugs to the tracker."
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Opts::command_path_usage(&["foo"]).unwrap().format("prog foo"), &"
+Usage: prog foo [OPTIONS]
+
+Optional arguments:
+  -b, --bar
+
+Foo is deprecated."
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::help_info().after_help, Some("Commands may be abbreviated."));
+    assert_eq!(Command::help_info().command_list, Some(Command::usage()));
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_examples() {
+    #[derive(Options)]
+    #[options(example = "prog --foo 1 input.txt", example = "prog -h")]
+    struct Opts {
+        #[options(free, help = "input file")]
+        input: String,
+        #[options(help = "foo help")]
+        foo: u32,
+        #[options(help = "print help")]
+        help: bool,
+    }
+
+    #[derive(Options)]
+    #[options(example = "prog")]
+    struct Empty {
+    }
+
+    assert_eq!(Opts::usage(), &"
+Positional arguments:
+  input          input file
+
+Optional arguments:
+  -f, --foo FOO  foo help
+  -h, --help     print help
+
+Examples:
+  prog --foo 1 input.txt
+  prog -h"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Empty::usage(), "Examples:\n  prog");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_section_headings() {
+    #[derive(Options)]
+    #[options(positional_heading = "Arguments:", optional_heading = "Options:",
+        examples_heading = "Usage examples:", example = "prog foo")]
+    struct Opts {
+        #[options(free)]
+        input: String,
+        foo: bool,
+    }
+
+    #[derive(Options)]
+    struct Main {
+        help: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(command_heading = "Commands:")]
+    enum Command {
+        Foo(NoOpts),
+    }
+
+    #[derive(Options)]
+    #[options(command_heading = "Subcommands:")]
+    struct Override {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Arguments:
+  input
+
+Options:
+  -f, --foo
+
+Usage examples:
+  prog foo"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Main::help_info().format("prog"), &"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help
+
+Commands:
+  foo"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::help_info().command_heading, Some("Commands:"));
+    assert_eq!(Override::help_info().command_heading, Some("Subcommands:"));
+    assert_eq!(NoOpts::help_info().command_heading, None);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_option_headings() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help")]
+        help: bool,
+        #[options(heading = "Network options", help = "host name")]
+        host: String,
+        #[options(heading = "Output options:", help = "be verbose")]
+        verbose: bool,
+        #[options(heading = "Network options", help = "port number")]
+        port: u16,
+        #[options(help = "config file")]
+        config: String,
+    }
+
+    #[derive(Options)]
+    struct AllGrouped {
+        #[options(heading = "Misc")]
+        foo: bool,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -h, --help           print help
+  -c, --config CONFIG  config file
+
+Network options:
+  -H, --host HOST      host name
+  -p, --port PORT      port number
+
+Output options:
+  -v, --verbose        be verbose"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(AllGrouped::usage(), "Misc:\n  -f, --foo");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_display_order() {
+    #[derive(Options)]
+    struct Opts {
+        alpha: bool,
+        #[options(display_order = 2)]
+        bravo: bool,
+        charlie: bool,
+        #[options(display_order = 1)]
+        delta: bool,
+    }
+
+    #[derive(Options)]
+    #[options(sort = "alpha")]
+    struct Sorted {
+        zulu: bool,
+        #[options(no_long)]
+        yankee: bool,
+        xray: bool,
+        #[options(display_order = 0)]
+        whiskey: bool,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -d, --delta
+  -b, --bravo
+  -a, --alpha
+  -c, --charlie"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Sorted::usage(), &"
+Optional arguments:
+  -w, --whiskey
+  -x, --xray
+  -y
+  -z, --zulu"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_column_width() {
+    #[derive(Options)]
+    #[options(help_column_width = 20)]
+    struct Wide {
+        #[options(help = "foo help")]
+        foo: bool,
+    }
+
+    #[derive(Options)]
+    #[options(help_max_width = 14)]
+    struct Narrow {
+        #[options(help = "foo help")]
+        foo: bool,
+        #[options(help = "long help")]
+        long_option: bool,
+    }
+
+    #[derive(Options)]
+    #[options(help_column_width = 12)]
+    enum Command {
+        #[options(help = "foo help")]
+        Foo(NoOpts),
+    }
+
+    assert_eq!(Wide::usage(), &"
+Optional arguments:
+  -f, --foo         foo help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Narrow::usage(), &"
+Optional arguments:
+  -f, --foo  foo help
+  -l, --long-option
+             long help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::usage(), "  foo       foo help");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_unicode_width() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(meta = "\u{6587}\u{4ef6}", help = "file")]
+        file: String,
+        #[options(help = "verbose")]
+        verbose: bool,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(name = "\u{6587}\u{4ef6}\u{540d}\u{79f0}", help = "name help")]
+        Name(NoOpts),
+        #[options(help = "foo help")]
+        Foo(NoOpts),
+    }
+
+    // Each CJK character occupies two columns
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -f, --file \u{6587}\u{4ef6}  file
+  -v, --verbose    verbose"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::usage(), &"
+  \u{6587}\u{4ef6}\u{540d}\u{79f0}  name help
+  foo       foo help"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_long_help() {
+    use gumdrop::{Parser, ParsingStyle};
+
+    #[derive(Options)]
+    struct Opts {
+        /// print help
+        help: bool,
+        /// Sets the foo value.
+        ///
+        /// The foo value is used
+        /// for all operations.
+        foo: u32,
+        #[options(help = "bar help", long_help = "bar help, at length")]
+        bar: bool,
+        /// Not repeated in long help
+        baz: bool,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -h, --help     print help
+  -f, --foo FOO  Sets the foo value.
+  -b, --bar      bar help
+  -B, --baz      Not repeated in long help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Opts::long_usage(), &"
+Optional arguments:
+  -h, --help     print help
+  -f, --foo FOO  Sets the foo value.
+                 The foo value is used for all operations.
+  -b, --bar      bar help, at length
+  -B, --baz      Not repeated in long help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(NoOpts::long_usage(), NoOpts::usage());
+
+    let mut parser = Parser::new(&["-h"], ParsingStyle::default());
+    let opts = Opts::parse(&mut parser).unwrap();
+    assert!(opts.help);
+    assert!(!parser.long_help_requested());
+
+    let mut parser = Parser::new(&["--help"], ParsingStyle::default());
+    let opts = Opts::parse(&mut parser).unwrap();
+    assert!(opts.help);
+    assert!(parser.long_help_requested());
+
+    let info = Opts::help_info();
+    assert!(info.format("prog").ends_with(Opts::usage()));
+    assert!(info.format_long("prog").ends_with(Opts::long_usage()));
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_verbatim_doc_comment() {
+    /// Formats things:
+    ///   - one
+    ///   - two
+    #[derive(Options)]
+    #[options(verbatim_doc_comment)]
+    struct Opts {
+        /// Output format
+        ///
+        ///   NAME   DESCRIPTION
+        ///   json   JSON output
+        ///   text   plain text
+        #[options(verbatim_doc_comment)]
+        format: String,
+        /// Output format
+        ///
+        ///   NAME   DESCRIPTION
+        ///   json   JSON output
+        output: String,
+    }
+
+    /// Formats things:
+    ///   - one
+    #[derive(Options)]
+    struct Trimmed {
+    }
+
+    assert_eq!(Opts::usage(), &"
+Formats things:
+  - one
+  - two
+
+Optional arguments:
+  -f, --format FORMAT  Output format
+  -o, --output OUTPUT  Output format"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Opts::long_usage(), &"
+Formats things:
+  - one
+  - two
+
+Optional arguments:
+  -f, --format FORMAT  Output format
+
+                         NAME   DESCRIPTION
+                         json   JSON output
+                         text   plain text
+  -o, --output OUTPUT  Output format
+                       NAME   DESCRIPTION json   JSON output"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Trimmed::usage(), "Formats things:\n- one");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_help_expr() {
+    #[derive(Options)]
+    #[options(help = concat!("About ", env!("CARGO_PKG_NAME")))]
+    struct Opts {
+        #[options(help = stringify!(foo help))]
+        foo: bool,
+        #[options(help = "bar help")]
+        bar: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = concat!("baz", " help"))]
+        Baz(NoOpts),
+    }
+
+    assert_eq!(Opts::usage(), &"
+About gumdrop
+
+Optional arguments:
+  -f, --foo  foo help
+  -b, --bar  bar help"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Command::usage(), "  baz   baz help");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_env() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "foo value", env = "GUMDROP_TEST_ENV_FOO")]
+        foo: Option<u32>,
+        #[options(required, env = "GUMDROP_TEST_ENV_BAR")]
+        bar: String,
+        #[options(env = "GUMDROP_TEST_ENV_BAZ")]
+        baz: Vec<i32>,
+    }
+
+    std::env::set_var("GUMDROP_TEST_ENV_FOO", "1");
+    std::env::set_var("GUMDROP_TEST_ENV_BAR", "bar");
+    std::env::remove_var("GUMDROP_TEST_ENV_BAZ");
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.foo, Some(1));
+    assert_eq!(opts.bar, "bar");
+    assert!(opts.baz.is_empty());
+
+    std::env::set_var("GUMDROP_TEST_ENV_BAZ", "3");
+
+    let opts = Opts::parse_args_default(&["-f", "2", "-b", "x", "--baz", "4"]).unwrap();
+    assert_eq!(opts.foo, Some(2));
+    assert_eq!(opts.bar, "x");
+    assert_eq!(opts.baz, [4]);
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.baz, [3]);
+
+    std::env::set_var("GUMDROP_TEST_ENV_FOO", "x");
+    is_err!(Opts::parse_args_default(EMPTY),
+        "invalid value for environment variable `GUMDROP_TEST_ENV_FOO`: \
+         invalid digit found in string");
+
+    std::env::remove_var("GUMDROP_TEST_ENV_FOO");
+    std::env::remove_var("GUMDROP_TEST_ENV_BAR");
+    is_err!(Opts::parse_args_default(EMPTY),
+        "missing required option `-b, --bar`");
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo value (env: GUMDROP_TEST_ENV_FOO)
+  -b, --bar BAR  (required) (env: GUMDROP_TEST_ENV_BAR)
+  -B, --baz BAZ  (env: GUMDROP_TEST_ENV_BAZ)"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_required_marker() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(free, required, help = "input file")]
+        input: String,
+        #[options(free, required)]
+        output: String,
+        #[options(required, help = "foo help")]
+        foo: i32,
+        #[options(required, default = "1")]
+        bar: i32,
+        baz: bool,
+    }
+
+    #[derive(Options)]
+    #[options(required_marker = "*")]
+    struct Marked {
+        #[options(required, help = "foo help")]
+        foo: i32,
+        bar: bool,
+    }
+
+    #[derive(Options)]
+    #[options(required_marker = "")]
+    struct Unmarked {
+        #[options(required, help = "foo help")]
+        foo: i32,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Positional arguments:
+  input          input file (required)
+  output         (required)
+
+Optional arguments:
+  -f, --foo FOO  foo help (required)
+  -b, --bar BAR  (required) (default: 1)
+  -B, --baz"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Marked::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo help *
+  -b, --bar"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Unmarked::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo help"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_show_default() {
+    #[derive(Options)]
+    #[options(default_format = "[default: {}]")]
+    struct Opts {
+        #[options(help = "foo help", default = "1")]
+        foo: u32,
+        #[options(default = "2", show_default = false)]
+        bar: u32,
+        #[options(default = "3", default_expr_display = "three")]
+        baz: u32,
+    }
+
+    #[derive(Options)]
+    #[options(show_default = false)]
+    struct Hidden {
+        #[options(help = "foo help", default = "1")]
+        foo: u32,
+        #[options(default = "2", show_default = true)]
+        bar: u32,
+    }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!((opts.foo, opts.bar, opts.baz), (1, 2, 3));
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo help [default: 1]
+  -b, --bar BAR
+  -B, --baz BAZ  [default: three]"
+        // Skip leading newline
+        [1..]);
+
+    assert_eq!(Hidden::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo help
+  -b, --bar BAR  (default: 2)"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_hide_default() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "temp dir", default = "/tmp/foo", hide_default)]
+        temp: String,
+        #[options(help = "bar help", default = "1")]
+        bar: u32,
+    }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.temp, "/tmp/foo");
+    assert_eq!(opts.bar, 1);
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -t, --temp TEMP  temp dir
+  -b, --bar BAR    bar help (default: 1)"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_relations() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "json output", conflicts_with = "plain")]
+        json: bool,
+        #[options(help = "plain output")]
+        plain: bool,
+        #[options(no_short, requires = "user")]
+        password: Option<String>,
+        user: Option<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["--json", "-u", "me", "--password", "x"]).unwrap();
+    assert!(opts.json);
+    assert_eq!(opts.password.as_deref(), Some("x"));
+
+    is_err!(Opts::parse_args_default(&["--json", "-p"]),
+        "option `-j, --json` cannot be used with `-p, --plain`");
+    is_err!(Opts::parse_args_default(&["--password", "x"]),
+        "option `--password` requires `-u, --user`");
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -j, --json           json output [conflicts with --plain]
+  -p, --plain          plain output [conflicts with --json]
+  --password PASSWORD  [requires --user]
+  -u, --user USER"
+        // Skip leading newline
+        [1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_possible_values() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "output format", possible_values = "json, plain,yaml")]
+        format: Option<String>,
+        #[options(possible_values = "1, 2")]
+        level: u32,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -f, --format FORMAT  output format [possible values: json, plain, yaml]
+  -l, --level LEVEL    [possible values: 1, 2]"
+        // Skip leading newline
+        [1..]);
+}
+
+#[test]
+fn test_did_you_mean() {
+    #[derive(Options)]
+    struct Opts {
+        verbose: bool,
+        #[options(no_short)]
+        version: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        Install(NoOpts),
+        Remove(NoOpts),
+    }
+
+    is_err!(Opts::parse_args_default(&["--verbos"]),
+        "unrecognized option `--verbos`; did you mean `--verbose`?");
+    is_err!(Opts::parse_args_default(&["--versoin=1"]),
+        "unrecognized option `--versoin`; did you mean `--version`?");
+    is_err!(Opts::parse_args_default(&["--quiet"]),
+        "unrecognized option `--quiet`");
+    is_err!(Opts::parse_args_default(&["-x"]),
+        "unrecognized option `-x`");
+    is_err!(Opts::parse_args_default(&["instal"]),
+        "unrecognized command `instal`; did you mean `install`?");
+    is_err!(Opts::parse_args_default(&["update"]),
+        "unrecognized command `update`");
+}
+
+#[test]
+fn test_invalid_value() {
+    #[derive(Debug, PartialEq)]
+    enum Format {
+        Json,
+        Plain,
+    }
+
+    impl std::str::FromStr for Format {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Format, String> {
+            match s {
+                "json" => Ok(Format::Json),
+                "plain" => Ok(Format::Plain),
+                _ => Err("bad format".to_owned())
+            }
+        }
+    }
+
+    #[derive(Options)]
+    struct Opts {
+        #[options(possible_values = "json, plain")]
+        format: Option<Format>,
+        #[options(no_short, possible_values = "json, plain")]
+        formats: Vec<Format>,
+    }
+
+    let opts = Opts::parse_args_default(&["-f", "json", "--formats=plain"]).unwrap();
+    assert_eq!(opts.format, Some(Format::Json));
+    assert_eq!(opts.formats, [Format::Plain]);
+
+    is_err!(Opts::parse_args_default(&["-f", "jsn"]),
+        "invalid value `jsn` for option `-f`: expected one of: json, plain; \
+         did you mean `json`?");
+    is_err!(Opts::parse_args_default(&["--formats=xml"]),
+        "invalid value `xml` for option `--formats`: expected one of: json, plain");
+
+    let e = Opts::parse_args_default(&["-f", "x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.possible_values(), Some(&["json", "plain"][..]));
+}
+
+#[test]
+fn test_error_kind() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(required)]
+        foo: i32,
+        bar: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        Baz(NoOpts),
+    }
+
+    let e = Opts::parse_args_default(EMPTY).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::MissingRequired);
+    assert_eq!(e.option().as_deref(), Some("--foo"));
+
+    let e = Opts::parse_args_default(&["-f", "x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::FailedParse);
+    assert_eq!(e.option().as_deref(), Some("-f"));
+
+    let e = Opts::parse_args_default(&["-x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnrecognizedOption);
+    assert_eq!(e.option().as_deref(), Some("-x"));
+
+    let e = Opts::parse_args_default(&["--bar=1"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedArgument);
+    assert_eq!(e.option().as_deref(), Some("--bar"));
+
+    let e = Opts::parse_args_default(&["-f", "1", "quux"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnrecognizedCommand);
+    assert_eq!(e.command(), Some("quux"));
+    assert_eq!(e.option(), None);
+}
+
+#[test]
+fn test_error_position() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(required)]
+        foo: i32,
+        bar: bool,
+    }
+
+    let e = Opts::parse_args_default(&["-f", "1", "--frob"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.position(), Some(2));
+    assert_eq!(e.argument(), Some("--frob"));
+    assert_eq!(format!("{:#}", e), "argument 3 (`--frob`): unrecognized option `--frob`");
+    assert_eq!(e.to_string(), "unrecognized option `--frob`");
+
+    let e = Opts::parse_args_default(&["-b", "-f", "x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.position(), Some(2));
+    assert_eq!(e.argument(), Some("x"));
+
+    let e = Opts::parse_args_default(&["-bx"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.position(), Some(0));
+    assert_eq!(e.argument(), Some("-bx"));
+
+    let e = Opts::parse_args_default(&["-b"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.position(), None);
+    assert_eq!(format!("{:#}", e), "missing required option `-f, --foo`");
+}
+
+#[test]
+fn test_error_source() {
+    use std::error::Error as _;
+    use std::num::ParseIntError;
+
+    #[derive(Options)]
+    struct Opts {
+        foo: i32,
+        #[options(parse(try_from_str = "parse_bar"))]
+        bar: i32,
+    }
+
+    fn parse_bar(_: &str) -> Result<i32, String> {
+        Err("bad bar".to_owned())
+    }
+
+    let e = Opts::parse_args_default(&["-f", "x"]).map(|_| ()).unwrap_err();
+    let source = e.source().unwrap();
+    assert!(source.downcast_ref::<ParseIntError>().is_some());
+    assert_eq!(source.to_string(), "invalid digit found in string");
+
+    let e = Opts::parse_args_default(&["-b", "x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.to_string(), "invalid argument to option `-b`: bad bar");
+    assert_eq!(e.source().unwrap().to_string(), "bad bar");
+
+    let e = Opts::parse_args_default(&["-x"]).map(|_| ()).unwrap_err();
+    assert!(e.source().is_none());
+}
+
+#[test]
+fn test_custom_error() {
+    #[derive(Debug, PartialEq)]
+    struct RangeError(i32);
+
+    impl std::fmt::Display for RangeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{} is out of range", self.0)
+        }
+    }
+
+    impl std::error::Error for RangeError {}
+
+    #[derive(Options)]
+    struct Opts {
+        #[options(parse(try_from_str = "parse_level"))]
+        level: i32,
+        #[options(parse(try_from_str = "parse_custom"))]
+        custom: i32,
+    }
+
+    fn parse_level(s: &str) -> Result<i32, RangeError> {
+        match s.parse() {
+            Ok(n) if n < 10 => Ok(n),
+            Ok(n) => Err(RangeError(n)),
+            Err(_) => Err(RangeError(-1)),
+        }
+    }
+
+    fn parse_custom(s: &str) -> Result<i32, gumdrop::Error> {
+        parse_level(s).map_err(gumdrop::Error::custom)
+    }
+
+    let e = Opts::parse_args_default(&["-l", "12"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::FailedParse);
+    assert_eq!(e.downcast_ref::<RangeError>(), Some(&RangeError(12)));
+    assert_eq!(e.to_string(), "invalid argument to option `-l`: 12 is out of range");
+
+    let e = Opts::parse_args_default(&["-c", "13"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.downcast_ref::<RangeError>(), Some(&RangeError(13)));
+    assert_eq!(e.to_string(), "invalid argument to option `-c`: 13 is out of range");
+
+    let e = gumdrop::Error::custom(RangeError(14));
+    assert_eq!(e.kind(), ErrorKind::Custom);
+    assert_eq!(e.downcast_ref::<RangeError>(), Some(&RangeError(14)));
+    assert_eq!(e.to_string(), "14 is out of range");
+
+    let e = gumdrop::Error::custom("something went wrong");
+    assert_eq!(e.to_string(), "something went wrong");
+    assert!(e.downcast_ref::<RangeError>().is_none());
+}
+
+#[test]
+fn test_all_errors() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(required)]
+        foo: i32,
+        bar: i32,
+        baz: bool,
+
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Sub(SubOpts),
+    }
+
+    #[derive(Debug, Options)]
+    struct SubOpts {
+        quux: i32,
+    }
+
+    let errors = Opts::parse_args_all_errors(&["-b", "x", "--frob", "-z", "-b", "2"],
+        ParsingStyle::default()).unwrap_err();
+
+    assert_eq!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+        "invalid argument to option `-b`: invalid digit found in string",
+        "unrecognized option `--frob`",
+        "unrecognized option `-z`",
+        "missing required option `-f, --foo`",
+    ]);
+    assert_eq!(errors.iter().map(|e| e.position()).collect::<Vec<_>>(),
+        [Some(1), Some(2), Some(3), None]);
+
+    let errors = Opts::parse_args_all_errors(&["-f", "1", "sub", "-q", "x", "-y"],
+        ParsingStyle::default()).unwrap_err();
+
+    assert_eq!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+        "invalid argument to option `-q`: invalid digit found in string",
+        "unrecognized option `-y`",
+    ]);
+
+    let errors = Opts::parse_args_all_errors(&["--frob", "nope", "-b", "x"],
+        ParsingStyle::default()).unwrap_err();
+
+    assert_eq!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+        "unrecognized option `--frob`",
+        "unrecognized command `nope`",
+        "missing required option `-f, --foo`",
+    ]);
+
+    let opts = Opts::parse_args_all_errors(&["-f", "1", "-b", "2"],
+        ParsingStyle::default()).unwrap();
+    assert_eq!((opts.foo, opts.bar), (1, 2));
+}
+
+#[test]
+fn test_error_usage() {
+    #[derive(Options)]
+    #[options(error_usage = "full")]
+    struct Opts {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(error_usage = "line")]
+    enum Command {
+        Remote(RemoteOpts),
+    }
+
+    #[derive(Options)]
+    struct RemoteOpts {
+        #[options(command)]
+        command: Option<RemoteCommand>,
+    }
+
+    #[derive(Options)]
+    enum RemoteCommand {
+        Add(NoOpts),
+    }
+
+    assert_eq!(Opts::error_usage(), gumdrop::ErrorUsage::Full);
+    assert_eq!(Command::error_usage(), gumdrop::ErrorUsage::Line);
+    assert_eq!(RemoteOpts::error_usage(), gumdrop::ErrorUsage::Omit);
+
+    let e = Opts::parse_args_default(&["remote", "add", "-x"]).map(|_| ()).unwrap_err();
+    assert_eq!(e.command_path(), ["remote", "add"]);
+
+    let e = Opts::parse_args_default(&["-x"]).map(|_| ()).unwrap_err();
+    assert!(e.command_path().is_empty());
+}
+
+#[cfg(feature = "style")]
+#[test]
+fn test_error_style() {
+    use gumdrop::{format_error_with_style, HelpStyle, PlainStyle};
+
+    struct Brackets;
+
+    impl HelpStyle for Brackets {
+        fn error(&self, text: &str, out: &mut String) {
+            out.push('[');
+            out.push_str(text);
+            out.push(']');
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        foo: i32,
+    }
+
+    let args = &["-f", "1", "--frob"];
+    let e = Opts::parse_args_default(args).unwrap_err();
+
+    assert_eq!(format_error_with_style("prog", args, &e, &PlainStyle), &"
+error: unrecognized option `--frob`
+  prog -f 1 --frob
+            ^^^^^^"[1..]);
+
+    assert_eq!(format_error_with_style("prog", args, &e, &Brackets), &"
+[error:] unrecognized option `--frob`
+  prog -f 1 [--frob]
+            [^^^^^^]"[1..]);
+
+    let args = &["--foo", "x"];
+    let e = Opts::parse_args_default(args).unwrap_err();
+
+    assert_eq!(format_error_with_style("prog", args, &e, &PlainStyle), &"
+error: invalid argument to option `--foo`: invalid digit found in string
+  prog --foo x
+             ^"[1..]);
+
+    let e = Opts::parse_args_default(&["-f"]).unwrap_err();
+
+    assert_eq!(format_error_with_style("prog", &["-f"], &e, &PlainStyle), &"
+error: missing argument to option `-f`
+  prog -f
+       ^^"[1..]);
+
+    let e = gumdrop::Error::custom("no position");
+
+    assert_eq!(format_error_with_style("prog", &["-f"], &e, &PlainStyle),
+        "error: no position");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_exit_strategy() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use gumdrop::ExitStrategy;
+
+    #[derive(Default)]
+    struct Capture {
+        help: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    impl ExitStrategy for Capture {
+        fn print_help(&mut self, text: &str) {
+            self.help.push(text.to_owned());
+        }
+
+        fn print_error(&mut self, text: &str) {
+            self.errors.push(text.to_owned());
+        }
+
+        fn help_color(&self) -> bool { false }
+        fn error_color(&self) -> bool { false }
+
+        fn error_code(&self, _err: &gumdrop::Error) -> i32 { 64 }
+
+        fn exit(&mut self, code: i32) -> ! {
+            std::panic::panic_any(code)
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "foo")]
+        foo: i32,
+    }
+
+    fn run(args: &[&str]) -> (Result<Opts, i32>, Capture) {
+        let args = args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        let mut cap = Capture::default();
+
+        let res = catch_unwind(AssertUnwindSafe(||
+            Opts::parse_args_or_exit_with(&args, ParsingStyle::default(), &mut cap)));
+
+        (res.map_err(|e| *e.downcast::<i32>().unwrap()), cap)
+    }
+
+    let (opts, cap) = run(&["prog", "-f", "1"]);
+    assert_eq!(opts.unwrap().foo, 1);
+    assert!(cap.help.is_empty() && cap.errors.is_empty());
+
+    let (opts, cap) = run(&["prog", "--frob"]);
+    assert_eq!(opts.unwrap_err(), 64);
+    assert_eq!(cap.errors, ["prog: argument 1 (`--frob`): unrecognized option `--frob`"]);
+
+    let (opts, cap) = run(&["prog", "-h"]);
+    assert_eq!(opts.unwrap_err(), 0);
+    assert_eq!(cap.help, [&"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help     print help message
+  -f, --foo FOO  foo"[1..]]);
+}
+
+#[test]
+fn test_exit_codes() {
+    use gumdrop::{Error, ExitStrategy, StandardExit};
+
+    let unrecognized = Error::unrecognized_command("foo");
+    let env = Error::failed_parse_env("FOO", "bad".to_owned());
+    let missing = Error::missing_required("--foo");
+
+    let exit = StandardExit::new();
+    assert_eq!(exit.error_code(&unrecognized), 2);
+    assert_eq!(exit.error_code(&env), 2);
+    assert_eq!(exit.help_code(), 0);
+
+    let exit = StandardExit::new().sysexits();
+    assert_eq!(exit.error_code(&unrecognized), 64);
+    assert_eq!(exit.error_code(&env), 78);
+
+    let exit = StandardExit::new().sysexits()
+        .exit_code(ErrorKind::MissingRequired, 3)
+        .exit_code(ErrorKind::FailedParseEnv, 4);
+    assert_eq!(exit.error_code(&unrecognized), 64);
+    assert_eq!(exit.error_code(&missing), 3);
+    assert_eq!(exit.error_code(&env), 4);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_parse_args_or_help() {
+    use gumdrop::ParseResult;
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "foo")]
+        foo: i32,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(help_command)]
+    enum Command {
+        #[options(help = "bar things")]
+        Bar(NoOpts),
+    }
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "-f", "1"], ParsingStyle::default()),
+        ParseResult::Parsed(Opts{foo: 1, ..}));
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "-h"], ParsingStyle::default()),
+        ParseResult::Help(ref help) if help == &"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help     print help message
+  -f, --foo FOO  foo
+
+Available commands:
+  bar   bar things
+  help  show help for a command"[1..]);
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "help", "bar"], ParsingStyle::default()),
+        ParseResult::Help(ref help) if help == "Usage: prog bar [OPTIONS]");
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "help", "baz"], ParsingStyle::default()),
+        ParseResult::Err(ref e) if e.kind() == ErrorKind::UnrecognizedCommand);
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "--frob"], ParsingStyle::default()),
+        ParseResult::Err(ref e) if e.to_string() == "unrecognized option `--frob`");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_print_help() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "bar things")]
+        Bar(BarOpts),
+    }
+
+    #[derive(Options)]
+    struct BarOpts {
+        #[options(help = "baz")]
+        baz: bool,
+    }
+
+    let mut out = Vec::new();
+    Opts::print_usage("prog", &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), &"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help  print help message
+
+Available commands:
+  bar   bar things
+"[1..]);
+
+    let opts = Opts::parse_args_default(&["bar"]).unwrap();
+    let mut out = Vec::new();
+    opts.print_help("prog", &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), &"
+Usage: prog bar [OPTIONS]
+
+Optional arguments:
+  -b, --baz  baz
+"[1..]);
+
+    assert_eq!(opts.self_usage_with("app"), &"
+Usage: app bar [OPTIONS]
+
+Optional arguments:
+  -b, --baz  baz"[1..]);
+    assert_eq!(Opts::usage_with("app"), &"
+Usage: app [OPTIONS]
+
+Optional arguments:
+  -h, --help  print help message
+
+Available commands:
+  bar   bar things"[1..]);
+    assert_eq!(Command::usage_with("app"), &"
+Usage: app [OPTIONS]
+
+Available commands:
+  bar   bar things"[1..]);
+    assert_eq!(BarOpts::usage_with("app bar"), &"
+Usage: app bar [OPTIONS]
+
+Optional arguments:
+  -b, --baz  baz"[1..]);
+}
+
+#[test]
+fn test_program_name() {
+    #[derive(Options)]
+    #[options(program_name = "prog")]
+    struct Opts {
+        foo: bool,
+    }
+
+    #[derive(Options)]
+    #[options(program_name = "cmd")]
+    enum Command {
+        Foo(NoOpts),
+    }
+
+    assert_eq!(Opts::program_name(), Some("prog"));
+    assert_eq!(Command::program_name(), Some("cmd"));
+    assert_eq!(NoOpts::program_name(), None);
+}
+
+#[test]
+fn test_version_flag() {
+    use gumdrop::ParseResult;
+
+    #[derive(Debug, Options)]
+    #[options(version = "1.2.3")]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "print version")]
+        version: bool,
+        #[options(required)]
+        foo: i32,
+    }
+
+    #[derive(Debug, Options)]
+    struct Crate {
+        #[options(version_flag)]
+        ver: bool,
+    }
+
+    #[derive(Debug, Options)]
+    struct NotFlag {
+        version: Option<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["--version"]).unwrap();
+    assert!(opts.version_requested());
+    assert!(!opts.help_requested());
+    assert_eq!(Opts::version(), Some("1.2.3"));
+
+    assert_matches!(Opts::parse_args_or_help(&["prog", "-v"], ParsingStyle::default()),
+        ParseResult::Version(ref v) if v == "prog 1.2.3");
+    assert_matches!(Opts::parse_args_or_help(&["prog", "-v", "-h"], ParsingStyle::default()),
+        ParseResult::Help(_));
+
+    assert_matches!(Crate::parse_args_or_help(&["prog", "--ver"], ParsingStyle::default()),
+        ParseResult::Version(ref v) if v == &format!("prog {}", env!("CARGO_PKG_VERSION")));
+
+    let opts = NotFlag::parse_args_default(&["--version", "x"]).unwrap();
+    assert!(!opts.version_requested());
+    assert_eq!(NotFlag::version(), None);
+}
+
+#[test]
+fn test_from_iter() {
+    #[derive(Options)]
+    struct Opts {
+        foo: i32,
+        #[options(free)]
+        free: Vec<String>,
+    }
+
+    let opts = Opts::from_iter(&["prog", "-f", "1", "bar"]);
+    assert_eq!(opts.foo, 1);
+    assert_eq!(opts.free, ["bar"]);
+
+    let opts = Opts::from_iter(vec!["prog".to_owned()]);
+    assert_eq!(opts.foo, 0);
+    assert!(opts.free.is_empty());
+}
+
+#[test]
+fn test_parse_iter() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        foo: i32,
+        #[options(free)]
+        free: Vec<String>,
+    }
+
+    let opts = Opts::parse_args_default(["-f", "1", "a"]).unwrap();
+    assert_eq!(opts.foo, 1);
+    assert_eq!(opts.free, ["a"]);
+
+    let args = vec!["-f".to_owned(), "2".to_owned()];
+    let opts = Opts::parse_args_default(&args).unwrap();
+    assert_eq!(opts.foo, 2);
+
+    let opts = Opts::parse_args_default(args).unwrap();
+    assert_eq!(opts.foo, 2);
+
+    let opts = Opts::parse_args("x -f 3 y".split(' '), ParsingStyle::AllOptions).unwrap();
+    assert_eq!(opts.foo, 3);
+    assert_eq!(opts.free, ["x", "y"]);
+
+    let opts: Opts = gumdrop::parse_args_default(std::iter::once("-f4")).unwrap();
+    assert_eq!(opts.foo, 4);
+
+    let e = Opts::parse_args_default("-f 1 --bar".split(' ')).unwrap_err();
+    assert_eq!(e.position(), Some(2));
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_options_info() {
+    use gumdrop::OptionInfo;
+
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(required, meta = "N", help = "number of things")]
+        number: u32,
+        #[options(no_short, default = "x")]
+        name: String,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        Foo(NoOpts),
+    }
+
+    assert_eq!(Opts::options_info(), &[
+        OptionInfo{
+            long: Some("help"),
+            short: Some('h'),
+            meta: None,
+            help: Some("print help message"),
+            required: false,
+            takes_value: false,
+            default: None,
+        },
+        OptionInfo{
+            long: Some("number"),
+            short: Some('n'),
+            meta: Some("N"),
+            help: Some("number of things"),
+            required: true,
+            takes_value: true,
+            default: None,
+        },
+        OptionInfo{
+            long: Some("name"),
+            short: None,
+            meta: Some("NAME"),
+            help: None,
+            required: false,
+            takes_value: true,
+            default: Some("x"),
+        },
+    ][..]);
+
+    assert!(Command::options_info().is_empty());
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_commands_info() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    #[options(help_command)]
+    enum Command {
+        #[options(help = "foo things")]
+        Foo(FooOpts),
+        #[options(name = "bar-baz")]
+        Bar(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct FooOpts {
+        quux: bool,
+        #[options(command)]
+        command: Option<FooCommand>,
+    }
+
+    #[derive(Options)]
+    enum FooCommand {
+        Alpha(NoOpts),
+    }
+
+    let info = Opts::commands_info();
+    assert_eq!(info.len(), 2);
+    assert_eq!(Command::commands_info().len(), 2);
+
+    assert_eq!(info[0].name, "foo");
+    assert_eq!(info[0].help, Some("foo things"));
+    assert_eq!((info[0].options)().iter().map(|o| o.long).collect::<Vec<_>>(),
+        [Some("quux")]);
+
+    let sub = (info[0].commands)();
+    assert_eq!(sub.iter().map(|c| c.name).collect::<Vec<_>>(), ["alpha"]);
+    assert!((sub[0].commands)().is_empty());
+
+    assert_eq!(info[1].name, "bar-baz");
+    assert_eq!(info[1].help, None);
+    assert!((info[1].options)().is_empty());
+
+    assert!(NoOpts::commands_info().is_empty());
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_cli_spec() {
+    use gumdrop::CliSpec;
+
+    #[derive(Options)]
+    struct Opts {
+        foo: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "bar things")]
+        Bar(BarOpts<i32>),
+    }
+
+    #[derive(Options)]
+    struct BarOpts<T: std::str::FromStr + Default>
+            where T::Err: std::error::Error + Send + Sync + 'static {
+        baz: T,
+    }
+
+    let spec: CliSpec = Opts::cli_spec();
+
+    assert_eq!(spec.options, Opts::options_info());
+    assert_eq!(spec.options[0].long, Some("foo"));
+    assert_eq!(spec.commands.len(), 1);
+    assert_eq!(spec.commands[0].name, "bar");
+    assert_eq!(spec.commands[0].help, Some("bar things"));
+    assert_eq!((spec.commands[0].options)(), BarOpts::<i32>::options_info());
+    assert_eq!((spec.commands[0].options)()[0].long, Some("baz"));
+    assert!((spec.commands[0].commands)().is_empty());
+
+    let spec = Command::cli_spec();
+    assert!(spec.options.is_empty());
+    assert_eq!(spec.commands[0].name, "bar");
+
+    // A command type implemented without `derive(Options)`
+    struct Manual;
+
+    impl Options for Manual {
+        fn parse<S: AsRef<str>>(_: &mut gumdrop::Parser<S>) -> Result<Manual, gumdrop::Error> {
+            Ok(Manual)
+        }
+
+        fn parse_command<S: AsRef<str>>(_: &str, _: &mut gumdrop::Parser<S>)
+                -> Result<Manual, gumdrop::Error> {
+            Ok(Manual)
+        }
+
+        fn command(&self) -> Option<&dyn Options> { None }
+        fn usage() -> &'static str { "" }
+        fn self_usage(&self) -> &'static str { "" }
+        fn command_usage(_: &str) -> Option<&'static str> { None }
+        fn command_list() -> Option<&'static str> { None }
+        fn self_command_list(&self) -> Option<&'static str> { None }
+    }
+
+    #[derive(Options)]
+    struct ManualOpts {
+        quiet: bool,
+        #[options(command)]
+        command: Option<Manual>,
+    }
+
+    assert_eq!(ManualOpts::cli_spec().options[0].long, Some("quiet"));
+    assert!(ManualOpts::cli_spec().commands.is_empty());
+    assert!(Manual::cli_spec().options.is_empty());
+}
+
+#[test]
+fn test_generic_parse_error() {
+    use std::str::FromStr;
+
+    #[derive(Debug, Default)]
+    struct Level(u32);
+
+    impl FromStr for Level {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Level, String> {
+            s.parse().map(Level).map_err(|_| format!("bad level: {}", s))
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts<T: FromStr + Default> where T::Err: std::fmt::Display {
+        level: T,
+        jobs: u32,
+    }
+
+    let opts = Opts::<Level>::parse_args_default(&["-l", "3"]).unwrap();
+    assert_eq!(opts.level.0, 3);
+
+    let e = Opts::<Level>::parse_args_default(&["-l", "x"]).unwrap_err();
+    assert_eq!(e.to_string(), "invalid argument to option `-l`: bad level: x");
+
+    let e = Opts::<i32>::parse_args_default(&["-l", "x"]).unwrap_err();
+    assert_eq!(e.to_string(),
+        "invalid argument to option `-l`: invalid digit found in string");
+    assert!(e.downcast_ref::<std::num::ParseIntError>().is_none());
+
+    // Fields not involving a type parameter retain their parse error
+    let e = Opts::<Level>::parse_args_default(&["-j", "x"]).unwrap_err();
+    assert!(e.downcast_ref::<std::num::ParseIntError>().is_some());
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_usage_model() {
+    use gumdrop::{SectionKind, UsageRow};
+
+    /// Frobnicates things
+    #[derive(Options)]
+    #[options(example = "prog -v foo")]
+    struct Opts {
+        #[options(help = "input file")]
+        file: Option<String>,
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "be verbose")]
+        verbose: bool,
+        #[options(required, meta = "N", help = "number of jobs\nrun at once")]
+        jobs: u32,
+        #[options(default = "x", heading = "Naming")]
+        name: String,
+        #[options(long = "a-very-long-option-name", help = "long")]
+        long_name: bool,
+        #[options(free, required, help = "things")]
+        things: Vec<String>,
+    }
+
+    #[derive(Options)]
+    #[options(help_command)]
+    enum Command {
+        #[options(help = "foo things")]
+        Foo(NoOpts),
+        Bar(NoOpts),
+    }
+
+    let model = Opts::usage_model();
+
+    assert_eq!(model.description, Some("Frobnicates things"));
+    assert_eq!(model.sections.iter().map(|s| (s.kind, s.heading)).collect::<Vec<_>>(), [
+        (SectionKind::Positional, "Positional arguments:"),
+        (SectionKind::Optional, "Optional arguments:"),
+        (SectionKind::Optional, "Naming:"),
+        (SectionKind::Examples, "Examples:"),
+    ]);
+    assert_eq!(model.sections[1].rows[3], UsageRow{
+        name: "-j, --jobs",
+        meta: Some("N"),
+        help: Some("number of jobs\nrun at once"),
+        notes: &["(required)"],
+    });
+    assert_eq!(model.render(), Opts::usage());
+
+    let model = Command::usage_model();
+
+    assert_eq!(model.sections.len(), 1);
+    assert_eq!(model.sections[0].kind, SectionKind::Commands);
+    assert_eq!(model.sections[0].rows.iter().map(|r| r.name).collect::<Vec<_>>(),
+        ["foo", "bar", "help"]);
+    assert_eq!(model.render(), format!("Available commands:\n{}", Command::usage()));
+
+    assert_eq!(NoOpts::usage_model().render(), NoOpts::usage());
+}
+
+#[cfg(all(feature = "clap", not(feature = "strip_help")))]
+#[test]
+fn test_clap_command() {
+    use gumdrop::clap_command;
+
+    /// Frobnicates things
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(required, meta = "N", help = "number of jobs")]
+        jobs: u32,
+        #[options(free, help = "input file")]
+        file: Option<String>,
+    }
+
+    #[derive(Options)]
+    struct CmdOpts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "make things")]
+        Make(MakeOpts),
+    }
+
+    #[derive(Options)]
+    struct MakeOpts {
+        #[options(default = "all", help = "target to make")]
+        target: String,
+    }
+
+    let cmd = clap_command::<Opts>("prog");
+    cmd.clone().debug_assert();
+
+    assert_eq!(cmd.get_about().map(|s| s.to_string()), Some("Frobnicates things".to_owned()));
+
+    let jobs = cmd.get_arguments().find(|a| a.get_id() == "jobs").unwrap();
+    assert_eq!(jobs.get_long(), Some("jobs"));
+    assert_eq!(jobs.get_short(), Some('j'));
+    assert!(jobs.is_required_set());
+
+    let file = cmd.get_positionals().next().unwrap();
+    assert_eq!(file.get_id(), "file");
+    assert_eq!(file.get_index(), Some(1));
+
+    let matches = cmd.try_get_matches_from(["prog", "-h", "-j", "2", "foo"]).unwrap();
+    assert!(matches.get_flag("help"));
+    assert_eq!(matches.get_one::<String>("jobs").map(|s| &s[..]), Some("2"));
+
+    let cmd = clap_command::<CmdOpts>("prog");
+    cmd.clone().debug_assert();
+
+    let make = cmd.find_subcommand("make").unwrap();
+    assert_eq!(make.get_about().map(|s| s.to_string()), Some("make things".to_owned()));
+
+    let target = make.get_arguments().next().unwrap();
+    assert_eq!(target.get_default_values(), ["all"]);
+}
+
+#[test]
+fn test_to_args() {
+    use gumdrop::ToArgs;
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(to_args)]
+    struct Opts {
+        #[options(free)]
+        free: Vec<String>,
+        foo: bool,
+        #[options(count)]
+        verbose: u32,
+        #[options(no_long)]
+        bar: Option<i32>,
+        pair: Option<(i32, i32)>,
+        #[options(multi = "push")]
+        name: Vec<String>,
+    }
+
+    let opts = Opts{
+        free: vec!["-x".to_owned(), "y".to_owned()],
+        foo: true,
+        verbose: 2,
+        bar: Some(-1),
+        pair: Some((1, 2)),
+        name: vec!["a".to_owned(), "b".to_owned()],
+    };
+
+    let args = opts.to_args();
+
+    assert_eq!(args, ["--foo", "--verbose", "--verbose", "-b", "-1",
+        "--pair", "1", "2", "--name", "a", "--name", "b", "--", "-x", "y"]);
+    assert_eq!(Opts::parse_args_default(&args).unwrap(), opts);
+
+    assert_eq!(Opts::parse_args_default(EMPTY).unwrap().to_args(), EMPTY);
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(to_args)]
+    struct Main {
+        quiet: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(to_args)]
+    enum Command {
+        Foo(Opts),
+        BarBaz(NoOpts),
+    }
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(to_args)]
+    struct NoOpts {}
+
+    let opts = Main{
+        quiet: true,
+        command: Some(Command::Foo(Opts::parse_args_default(&["--foo"]).unwrap())),
+    };
+
+    assert_eq!(opts.to_args(), ["--quiet", "foo", "--foo"]);
+    assert_eq!(Main::parse_args_default(opts.to_args()).unwrap(), opts);
+
+    let opts = Main{ quiet: false, command: Some(Command::BarBaz(NoOpts{})) };
+
+    assert_eq!(opts.to_args(), ["bar-baz"]);
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn test_arbitrary() {
+    use gumdrop::arbitrary::{Arbitrary, Unstructured};
+    use gumdrop::ToArgs;
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(arbitrary)]
+    struct Main {
+        #[options(free)]
+        free: Vec<String>,
+        foo: bool,
+        #[options(count)]
+        verbose: u8,
+        bar: Option<i64>,
+        name: String,
+        #[options(multi = "push")]
+        item: Vec<u16>,
+    }
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(arbitrary)]
+    struct Outer {
+        quiet: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(arbitrary)]
+    enum Command {
+        Main(Main),
+        Other(Other),
+    }
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(arbitrary)]
+    struct Other {
+        #[options(no_long)]
+        x: Option<u32>,
+    }
+
+    let data: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+
+    for start in 0..64 {
+        let mut u = Unstructured::new(&data[start * 32..]);
+
+        let opts = Outer::arbitrary(&mut u).unwrap();
+        let args = opts.to_args();
+
+        assert_eq!(Outer::parse_args_default(&args).unwrap(), opts, "args: {:?}", args);
+    }
+}
+
+#[test]
+fn test_assert_valid() {
+    #[derive(Options)]
+    struct Opts {
+        help: bool,
+        #[options(short = "v")]
+        version: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        Foo(FooOpts),
+        Bar(FooOpts),
+    }
+
+    #[derive(Options)]
+    struct FooOpts {
+        #[options(free)]
+        free: Vec<String>,
+        verbose: bool,
+    }
+
+    gumdrop::assert_valid::<Opts>();
+
+    struct Bad;
+
+    impl Options for Bad {
+        fn parse<S: AsRef<str>>(_: &mut gumdrop::Parser<S>) -> Result<Bad, gumdrop::Error> {
+            Ok(Bad)
+        }
+
+        fn parse_command<S: AsRef<str>>(name: &str, _: &mut gumdrop::Parser<S>)
+                -> Result<Bad, gumdrop::Error> {
+            Err(gumdrop::Error::unrecognized_command(name))
+        }
+
+        fn command(&self) -> Option<&dyn Options> { None }
+        fn usage() -> &'static str { "" }
+        fn self_usage(&self) -> &'static str { "" }
+        fn command_usage(_: &str) -> Option<&'static str> { None }
+        fn command_list() -> Option<&'static str> { None }
+        fn self_command_list(&self) -> Option<&'static str> { None }
+
+        fn options_info() -> &'static [gumdrop::OptionInfo] {
+            const INFO: &[gumdrop::OptionInfo] = &[
+                gumdrop::OptionInfo{ long: Some("foo"), short: Some('f'), ..EMPTY_INFO },
+                gumdrop::OptionInfo{ long: Some("bar"), short: Some('f'), ..EMPTY_INFO },
+                gumdrop::OptionInfo{ long: Some("a=b"), required: true,
+                    default: Some("1"), ..EMPTY_INFO },
+            ];
+            INFO
+        }
+
+        fn commands_info() -> &'static [gumdrop::CommandInfo] {
+            <Command as Options>::commands_info()
+        }
+    }
+
+    const EMPTY_INFO: gumdrop::OptionInfo = gumdrop::OptionInfo{
+        long: None, short: None, meta: None, help: None,
+        required: false, takes_value: false, default: None,
+    };
+
+    let err = std::panic::catch_unwind(gumdrop::assert_valid::<Bad>).unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+
+    assert_eq!(msg, &"
+invalid command line interface:
+  duplicate option `-f`
+  invalid long option name `a=b`
+  required option `--a=b` has a default value"[1..]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_config_template() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "a flag\nover two lines")]
+        foo: bool,
+        #[options(no_long)]
+        bar: bool,
+        #[options(required, help = "the name")]
+        name: String,
+        #[options(default = "a \"b\"")]
+        quoted: String,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "make stuff")]
+        Make(MakeOpts),
+        Run(RunOpts),
+    }
+
+    #[derive(Options)]
+    struct MakeOpts {
+        #[options(help = "number of jobs", default = "1")]
+        jobs: u32,
+        #[options(command)]
+        command: Option<SubCommand>,
+    }
+
+    #[derive(Options)]
+    enum SubCommand {
+        All(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct RunOpts {
+        #[options(meta = "DIR")]
+        dir: Option<String>,
+    }
+
+    assert_eq!(gumdrop::config_template::<Opts>(), &"
+# a flag
+# over two lines
+#foo = false
+
+# the name
+# (required)
+#name = \"NAME\"
+
+#quoted = \"a \\\"b\\\"\"
+
+# make stuff
+#[make]
+
+# number of jobs
+#jobs = 1
+
+#[make.all]
+
+#[run]
+
+#dir = \"DIR\"
+"[1..]);
+}
+
+#[test]
+fn test_to_map() {
+    use std::collections::BTreeMap;
+    use gumdrop::{ToArgs, Value};
+
+    #[derive(Debug, PartialEq, Options)]
+    #[options(to_args)]
+    struct Opts {
+        #[options(free)]
+        free: Vec<String>,
+        foo: bool,
+        #[options(count)]
+        verbose: u8,
+        #[options(no_long)]
+        bar: Option<i32>,
+        name: Option<String>,
+        pair: (i32, i32),
+        #[options(multi = "push")]
+        item: Vec<u32>,
+    }
+
+    fn s(s: &str) -> Value { Value::String(s.to_owned()) }
+
+    let opts = Opts::parse_args_default(
+        &["-f", "-vv", "-b", "1", "--pair", "2", "3", "-i", "4", "-i", "5", "x"]).unwrap();
+    let map = opts.to_map();
+
+    let mut expected = BTreeMap::new();
+    expected.insert("foo".to_owned(), Value::Bool(true));
+    expected.insert("verbose".to_owned(), Value::Count(2));
+    expected.insert("pair".to_owned(), Value::Tuple(vec!["2".to_owned(), "3".to_owned()]));
+    expected.insert("item".to_owned(), Value::List(vec![s("4"), s("5")]));
+
+    assert_eq!(map, expected);
+
+    let parsed = Opts::from_map(&map).unwrap();
+
+    assert_eq!(parsed, Opts{ free: vec![], bar: None, ..opts });
+
+    let mut map = BTreeMap::new();
+    map.insert("name".to_owned(), s("a b"));
+    map.insert("pair".to_owned(), Value::Tuple(vec!["1".to_owned(), "-2".to_owned()]));
+
+    let opts = Opts::from_map(&map).unwrap();
+    assert_eq!(opts.name.as_deref(), Some("a b"));
+    assert_eq!(opts.pair, (1, -2));
+
+    let mut map = BTreeMap::new();
+    map.insert("nope".to_owned(), Value::Bool(true));
+    assert_eq!(Opts::from_map(&map).unwrap_err().to_string(),
+        "unrecognized option `--nope`");
+
+    let mut map = BTreeMap::new();
+    map.insert("foo".to_owned(), s("yes"));
+    assert_eq!(Opts::from_map(&map).unwrap_err().to_string(),
+        "invalid argument to option `--foo`: expected a boolean or count value");
+
+    let mut map = BTreeMap::new();
+    map.insert("name".to_owned(), Value::Bool(true));
+    assert_eq!(Opts::from_map(&map).unwrap_err().to_string(),
+        "invalid argument to option `--name`: expected a string value");
+}
+
+#[test]
+fn test_parse_observer() {
+    use gumdrop::{ParseEvent, Parser};
+
+    #[derive(Options)]
+    struct Opts {
+        foo: bool,
+        bar: Option<String>,
+        pair: Option<(i32, i32)>,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        Run(RunOpts),
+    }
+
+    #[derive(Options)]
+    struct RunOpts {
+        #[options(free)]
+        free: Vec<String>,
+        quiet: bool,
+    }
+
+    let args = ["-f", "--bar=x", "--pair", "1", "2", "run", "-q", "a", "b"];
+    let mut events = Vec::new();
+
+    let mut parser = Parser::new(&args, ParsingStyle::default());
+    parser.set_observer(|event| events.push(event.to_string()));
+
+    Opts::parse(&mut parser).unwrap();
+    drop(parser);
+
+    assert_eq!(events, ["option -f", "option --bar", "value x",
+        "option --pair", "value 1", "value 2", "command run",
+        "option -q", "free a", "free b"]);
+
+    // Unrecognized options are not reported
+    let args = ["-f", "--baz"];
+    let mut events = Vec::new();
+
+    let mut parser = Parser::new(&args, ParsingStyle::default());
+    parser.set_observer(|event| {
+        if let ParseEvent::Option(opt) = event {
+            events.push(opt.to_string());
+        }
+    });
+
+    assert!(Opts::parse(&mut parser).is_err());
+    drop(parser);
+
+    assert_eq!(events, ["-f"]);
+}
+
+#[cfg(feature = "strip_help")]
+#[test]
+fn test_strip_help() {
+    /// Does things
+    #[derive(Options)]
+    #[options(after_help = "more text", example = "prog -f")]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "the foo")]
+        foo: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "make stuff")]
+        Make(NoOpts),
+    }
+
+    assert_eq!(Opts::usage(), "");
+    assert_eq!(Opts::after_help(), None);
+    assert_eq!(Opts::command_list(), Some(""));
+    assert_eq!(Opts::options_info()[1].help, None);
+    assert_eq!(Opts::commands_info()[0].help, None);
+    assert_eq!(Opts::usage_model().description, None);
+
+    let opts = Opts::parse_args_default(&["-f", "make"]).unwrap();
+    assert!(opts.foo);
+    assert_eq!(opts.command_name(), Some("make"));
+}
+
+#[test]
+fn test_parse_dyn() {
+    use gumdrop::{DynArg, Parser};
+
+    #[derive(Debug, PartialEq, Options)]
+    struct Opts {
+        alpha: bool,
+        beta: bool,
+        output: Option<String>,
+    }
+
+    // A type implemented without `derive(Options)`
+    #[derive(Debug, PartialEq)]
+    struct Manual(String);
+
+    impl Options for Manual {
+        fn parse<S: AsRef<str>>(parser: &mut Parser<S>) -> Result<Manual, gumdrop::Error> {
+            let arg = parser.next_arg().ok_or_else(gumdrop::Error::missing_command)?;
+            Manual::parse_command(arg, parser)
+        }
+
+        fn parse_command<S: AsRef<str>>(name: &str, parser: &mut Parser<S>)
+                -> Result<Manual, gumdrop::Error> {
+            let arg = parser.next_arg().unwrap_or("");
+            Ok(Manual(format!("{} {}", name, arg)))
+        }
+
+        fn command(&self) -> Option<&dyn Options> { None }
+        fn usage() -> &'static str { "" }
+        fn self_usage(&self) -> &'static str { "" }
+        fn command_usage(_: &str) -> Option<&'static str> { None }
+        fn command_list() -> Option<&'static str> { None }
+        fn self_command_list(&self) -> Option<&'static str> { None }
+    }
+
+    let expected = Opts{
+        alpha: true,
+        beta: true,
+        output: Some("file".to_owned()),
+    };
+
+    let args = ["-abofile"];
+    assert_eq!(Opts::parse_args_default(&args).unwrap(), expected);
+
+    let args = args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(Opts::parse_args_default(&args).unwrap(), expected);
+
+    // State is shared with the parser given to `with_dyn`
+    let args = ["-a", "-b", "x"];
+    let mut parser = Parser::new(&args, ParsingStyle::default());
+
+    let opt = parser.with_dyn(|p: &mut Parser<DynArg>| {
+        assert_eq!(p.next_opt(), Some(gumdrop::Opt::Short('a')));
+        p.next_opt().map(|opt| opt.to_string())
+    });
+
+    assert_eq!(opt.as_deref(), Some("-b"));
+    assert_eq!(parser.position(), Some(1));
+    assert_eq!(parser.next_arg(), Some("x"));
+
+    // The default `parse_dyn` calls `parse`
+    let args = ["run", "fast"];
+    let mut parser = Parser::new(&args, ParsingStyle::default());
+
+    assert_eq!(parser.with_dyn(Manual::parse_dyn).unwrap(), Manual("run fast".to_owned()));
+
+    let err = Opts::parse_args_default(&["-a", "-z"]).unwrap_err();
+    assert_eq!(err.position(), Some(1));
+}
+
+#[test]
+fn test_help_despite_errors() {
+    use gumdrop::ParseResult;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "print version")]
+        version: bool,
+        #[options(required, help = "a number")]
+        number: i32,
+        #[options(no_short, default_fn = "level")]
+        level: u32,
+        #[options(no_short)]
+        name: Option<String>,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Run(RunOpts),
+    }
+
+    static LEVELS: AtomicUsize = AtomicUsize::new(0);
+
+    fn level() -> u32 {
+        LEVELS.fetch_add(1, Ordering::SeqCst);
+        1
+    }
+
+    #[derive(Debug, Options)]
+    #[options(no_short)]
+    struct RunOpts {
+        help: bool,
+        count: u32,
+    }
+
+    let res = Opts::parse_args_or_help(&["prog", "--help", "--number", "notanumber"],
+        ParsingStyle::default());
+    assert_matches!(res, ParseResult::Help(ref help) if help.starts_with("Usage: prog"));
+
+    let res = Opts::parse_args_or_help(&["prog", "--bogus", "-h"], ParsingStyle::default());
+    assert_matches!(res, ParseResult::Help(_));
+
+    let res = Opts::parse_args_or_help(&["prog", "--number", "x", "--version"],
+        ParsingStyle::default());
+    assert_matches!(res, ParseResult::Version(_));
+
+    let res = Opts::parse_args_or_help(&["prog", "-n", "1", "run", "--count", "x", "--help"],
+        ParsingStyle::default());
+    assert_matches!(res, ParseResult::Help(ref help) if help.starts_with("Usage: prog run"));
+
+    // The first error is returned when no help is requested
+    let res = Opts::parse_args_or_help(&["prog", "--number", "x", "--bogus"],
+        ParsingStyle::default());
+    assert_matches!(res, ParseResult::Err(ref e) if e.kind() == ErrorKind::FailedParse);
+
+    // Arguments are scanned for help flags without parsing them again
+    LEVELS.store(0, Ordering::SeqCst);
+    let res = Opts::parse_args_or_help(&["prog", "--number", "x"], ParsingStyle::default());
+    assert_matches!(res, ParseResult::Err(_));
+    assert_eq!(LEVELS.load(Ordering::SeqCst), 1);
+
+    // The value of an option is not taken as a flag
+    let res = Opts::parse_args_or_help(&["prog", "--name", "--help", "--number", "x"],
+        ParsingStyle::default());
+    assert_matches!(res, ParseResult::Err(ref e) if e.kind() == ErrorKind::FailedParse);
+
+    // Other parsing methods are unaffected
+    assert!(Opts::parse_args_default(&["--help", "--number", "x"]).is_err());
+}
+
+#[test]
+fn test_strictness() {
+    #[derive(Options)]
+    #[options(lenient_unknown_options, lenient_extra_free)]
+    struct Opts {
+        foo: bool,
+        #[options(free)]
+        file: Option<String>,
+    }
+
+    let opts = Opts::parse_args_default(
+        &["--bar=1", "-x", "a", "b", "--foo", "c"]).unwrap();
+    assert!(opts.foo);
+    assert_eq!(opts.file, Some("a".to_owned()));
+
+    #[derive(Options)]
+    #[options(lenient_extra_free)]
+    struct Opts2 {
+        foo: bool,
+    }
+
+    let opts = Opts2::parse_args_default(&["a", "--foo", "b"]).unwrap();
+    assert!(opts.foo);
+    is_err!(Opts2::parse_args_default(&["--bar"]),
+        "unrecognized option `--bar`");
+
+    #[derive(Options)]
+    #[options(auto_short = false, auto_help = false)]
+    struct Opts3 {
+        foo: bool,
+        help: bool,
+    }
+
+    let opts = Opts3::parse_args_default(&["--help"]).unwrap();
+    assert!(opts.help);
+    assert!(!opts.help_requested());
+    is_err!(Opts3::parse_args_default(&["-f"]), "unrecognized option `-f`");
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_strictness_usage() {
+    #[derive(Options)]
+    #[options(lenient_unknown_options, lenient_extra_free)]
+    struct Opts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(help = "foo option")]
+        foo: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "run a thing")]
+        Run(NoOpts),
+    }
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -h, --help  print help message
+  -f, --foo   foo option"[1..]);
+    assert_eq!(Opts::command_list(), Some("  run   run a thing"));
+}
+
+#[test]
+fn test_rename_all() {
+    #[derive(Options)]
+    #[options(rename_all = "snake_case")]
+    struct Snake {
+        dry_run: bool,
+        #[options(long = "out-file")]
+        output_file: Option<String>,
+    }
+
+    let opts = Snake::parse_args_default(&["--dry_run", "--out-file", "x"]).unwrap();
+    assert!(opts.dry_run);
+    assert_eq!(opts.output_file, Some("x".to_owned()));
+    is_err!(Snake::parse_args_default(&["--dry-run"]),
+        "unrecognized option `--dry-run`; did you mean `--dry_run`?");
+
+    #[derive(Options)]
+    #[options(rename_all = "SCREAMING-KEBAB-CASE")]
+    struct ScreamingKebab {
+        dry_run: bool,
+    }
+
+    let opts = ScreamingKebab::parse_args_default(&["--DRY-RUN"]).unwrap();
+    assert!(opts.dry_run);
+
+    #[derive(Options)]
+    #[options(rename_all = "SCREAMING_SNAKE_CASE")]
+    struct ScreamingSnake {
+        dry_run: bool,
+    }
+
+    let opts = ScreamingSnake::parse_args_default(&["--DRY_RUN"]).unwrap();
+    assert!(opts.dry_run);
+
+    #[derive(Options)]
+    #[options(rename_all = "lowercase")]
+    struct Lower {
+        dry_run: bool,
+    }
+
+    let opts = Lower::parse_args_default(&["--dryrun"]).unwrap();
+    assert!(opts.dry_run);
+}
+
+#[test]
+fn test_rename_all_commands() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(rename_all = "lowercase")]
+    enum Command {
+        FooBar(NoOpts),
+        #[options(name = "baz-quux")]
+        BazQuux(NoOpts),
+    }
+
+    let opts = Opts::parse_args_default(&["foobar"]).unwrap();
+    assert_matches!(opts.command, Some(Command::FooBar(_)));
+    let opts = Opts::parse_args_default(&["baz-quux"]).unwrap();
+    assert_matches!(opts.command, Some(Command::BazQuux(_)));
+
+    #[derive(Debug, Options)]
+    #[options(rename_all = "verbatim")]
+    enum Verbatim {
+        FooBar(NoOpts),
+    }
+
+    assert_matches!(Verbatim::parse_args_default(&["FooBar"]), Ok(Verbatim::FooBar(_)));
+
+    #[derive(Debug, Options)]
+    #[options(rename_all = "snake_case")]
+    enum Snake {
+        FooBar(NoOpts),
+    }
+
+    assert_matches!(Snake::parse_args_default(&["foo_bar"]), Ok(Snake::FooBar(_)));
+}
+
+#[test]
+fn test_default_literal() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(default = 3)]
+        jobs: u32,
+        #[options(default = true)]
+        color: bool,
+        #[options(default = 1.5)]
+        scale: f64,
+        #[options(default = 'x')]
+        sep: char,
+    }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.jobs, 3);
+    assert!(opts.color);
+    assert_eq!(opts.scale, 1.5);
+    assert_eq!(opts.sep, 'x');
+
+    let opts = Opts::parse_args_default(&["-j", "4", "-s", "2"]).unwrap();
+    assert_eq!(opts.jobs, 4);
+    assert_eq!(opts.scale, 2.0);
+
+    let info = Opts::options_info();
+    assert_eq!(info[0].default, Some("3"));
+    assert_eq!(info[2].default, Some("1.5"));
+    assert_eq!(info[3].default, Some("x"));
+}
+
+#[test]
+fn test_default_fn() {
+    fn default_jobs() -> u32 { 4 }
+
+    fn default_name() -> String { "foo".to_owned() }
+
+    #[derive(Options)]
+    struct Opts {
+        #[options(default_fn = "default_jobs", default_expr_display = "number of CPUs")]
+        jobs: u32,
+        #[options(default_fn = "default_name")]
+        name: String,
+    }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.jobs, 4);
+    assert_eq!(opts.name, "foo");
+
+    let opts = Opts::parse_args_default(&["-j", "2", "-n", "bar"]).unwrap();
+    assert_eq!(opts.jobs, 2);
+    assert_eq!(opts.name, "bar");
+
+    let info = Opts::options_info();
+    assert_eq!(info[0].default, Some("number of CPUs"));
+    assert_eq!(info[1].default, None);
+}
+
+#[test]
+fn test_base() {
+    #[derive(Options)]
+    #[options(base = "Opts::from_config()")]
+    struct Opts {
+        verbose: bool,
+        jobs: u32,
+        name: Option<String>,
+        #[options(default = "1")]
+        level: u32,
+        #[options(free)]
+        files: Vec<String>,
+    }
+
+    impl Opts {
+        fn from_config() -> Opts {
+            Opts{
+                verbose: true,
+                jobs: 8,
+                name: Some("config".to_owned()),
+                level: 5,
+                files: vec!["a".to_owned()],
+            }
+        }
+    }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.jobs, 8);
+    assert_eq!(opts.name, Some("config".to_owned()));
+    assert_eq!(opts.level, 1);
+    assert_eq!(opts.files, ["a"]);
+
+    let opts = Opts::parse_args_default(&["-j", "2", "b"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.jobs, 2);
+    assert_eq!(opts.name, Some("config".to_owned()));
+    assert_eq!(opts.files, ["a", "b"]);
+}
+
+#[test]
+fn test_parse_with_parser() {
+    use gumdrop::{DynArg, Error, Opt, Parser};
+
+    // Collects `KEY=VALUE` pairs until an argument `;`
+    fn parse_pairs<'a>(opt: Opt<'a>, parser: &mut Parser<'a, DynArg>)
+            -> Result<Vec<(String, String)>, Error> {
+        let mut pairs = Vec::new();
+        let mut arg = parser.next_value(opt)?;
+
+        while arg != ";" {
+            let (key, value) = arg.split_once('=')
+                .ok_or_else(|| Error::failed_parse(opt, format!("invalid pair `{}`", arg)))?;
+            pairs.push((key.to_owned(), value.to_owned()));
+
+            arg = parser.next_arg().ok_or_else(|| Error::missing_argument(opt))?;
+        }
+
+        Ok(pairs)
+    }
+
+    #[derive(Options)]
+    struct Opts {
+        #[options(parse(with_parser = "parse_pairs"))]
+        vars: Option<Vec<(String, String)>>,
+        #[options(free)]
+        free: Vec<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["-v", "a=1", "b=2", ";", "x"]).unwrap();
+    assert_eq!(opts.vars, Some(vec![
+        ("a".to_owned(), "1".to_owned()),
+        ("b".to_owned(), "2".to_owned()),
+    ]));
+    assert_eq!(opts.free, ["x"]);
+
+    let opts = Opts::parse_args_default(&["--vars=a=1", ";"]).unwrap();
+    assert_eq!(opts.vars, Some(vec![("a".to_owned(), "1".to_owned())]));
+
+    is_err!(Opts::parse_args_default(&["-v", "a=1"]),
+        "missing argument to option `-v`");
+    is_err!(Opts::parse_args_default(&["-v", "a", ";"]),
+        "invalid argument to option `-v`: invalid pair `a`");
+}
+
+#[test]
+fn test_parse_contextual() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(possible_values = "bytes,kib")]
+        unit: Option<String>,
+        #[options(parse(contextual = "parse_size"))]
+        size: Vec<u64>,
+    }
+
+    fn parse_size(s: &str, opts: &Opts) -> Result<u64, std::num::ParseIntError> {
+        let n: u64 = s.parse()?;
+
+        match opts.unit.as_deref() {
+            Some("kib") => Ok(n * 1024),
+            _ => Ok(n)
+        }
+    }
+
+    let opts = Opts::parse_args_default(&["-s", "1", "-u", "kib", "-s", "2"]).unwrap();
+    assert_eq!(opts.size, [1, 2048]);
+
+    is_err!(Opts::parse_args_default(&["-s", "x"]),
+        |e| e.starts_with("invalid argument to option `-s`: "));
+}
+
+#[test]
+fn test_free_index() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(free, index = 3)]
+        rest: Vec<String>,
+        #[options(free, index = 2)]
+        second: Option<String>,
+        #[options(free, index = 1)]
+        first: Option<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["a", "b", "c", "d"]).unwrap();
+    assert_eq!(opts.first, Some("a".to_owned()));
+    assert_eq!(opts.second, Some("b".to_owned()));
+    assert_eq!(opts.rest, ["c", "d"]);
+}
+
+#[test]
+fn test_free_tuple() {
+    #[derive(Options)]
+    #[options(to_args)]
+    struct Opts {
+        verbose: bool,
+        #[options(free, required)]
+        range: (u32, u32),
+        #[options(free)]
+        point: Option<[i32; 2]>,
+    }
+
+    let opts = Opts::parse_args_default(&["1", "2", "-v", "3", "4"]).unwrap();
+    assert_eq!(opts.range, (1, 2));
+    assert_eq!(opts.point, Some([3, 4]));
+    assert!(opts.verbose);
+    assert_eq!(gumdrop::ToArgs::to_args(&opts), ["--verbose", "--", "1", "2", "3", "4"]);
+
+    let opts = Opts::parse_args_default(&["1", "2"]).unwrap();
+    assert_eq!(opts.point, None);
+
+    let err = Opts::parse_args_default(EMPTY).map(|_| ()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingRequiredFree);
+    assert_eq!(err.to_string(), "missing required free arguments: expected 2; found 0");
+
+    is_err!(Opts::parse_args_default(&["1"]),
+        "missing required free arguments: expected 2; found 1");
+    is_err!(Opts::parse_args_default(&["1", "2", "3"]),
+        "missing required free arguments: expected 2; found 1");
+
+    #[derive(Options)]
+    struct Pairs {
+        #[options(free)]
+        pairs: Vec<(String, u32)>,
+    }
+
+    let opts = Pairs::parse_args_default(&["a", "1", "b", "2"]).unwrap();
+    assert_eq!(opts.pairs, [("a".to_owned(), 1), ("b".to_owned(), 2)]);
+}
+
+#[test]
+fn test_free_variadic_not_last() {
+    #[derive(Options)]
+    struct Opts {
+        force: bool,
+        #[options(free, required)]
+        sources: Vec<String>,
+        #[options(free, required)]
+        dest: String,
+    }
+
+    let opts = Opts::parse_args_default(&["a", "-f", "b", "c"]).unwrap();
+    assert_eq!(opts.sources, ["a", "b"]);
+    assert_eq!(opts.dest, "c");
+    assert!(opts.force);
+
+    let opts = Opts::parse_args_default(&["a", "b"]).unwrap();
+    assert_eq!(opts.sources, ["a"]);
+    assert_eq!(opts.dest, "b");
+
+    is_err!(Opts::parse_args_default(&["a"]), "missing required free argument");
+    is_err!(Opts::parse_args_default(EMPTY), "missing required free argument");
+
+    #[derive(Options)]
+    struct Opts2 {
+        #[options(free)]
+        first: Option<u32>,
+        #[options(free)]
+        middle: Vec<u32>,
+        #[options(free)]
+        last: Option<u32>,
+    }
+
+    let opts = Opts2::parse_args_default(&["1", "2", "3", "4"]).unwrap();
+    assert_eq!(opts.first, Some(1));
+    assert_eq!(opts.middle, [2, 3]);
+    assert_eq!(opts.last, Some(4));
+
+    let opts = Opts2::parse_args_default(&["1", "2"]).unwrap();
+    assert_eq!(opts.first, Some(1));
+    assert_eq!(opts.middle, [0; 0]);
+    assert_eq!(opts.last, Some(2));
+
+    is_err!(Opts2::parse_args_default(&["1", "x", "3"]),
+        |e| e.starts_with("invalid 2nd positional argument `x` for `middle`: "));
+}
+
+#[test]
+fn test_free_count() {
+    #[derive(Options)]
+    #[options(min_free = 1, max_free = 3)]
+    struct Opts {
+        help: bool,
+        #[options(free)]
+        files: Vec<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["a", "b"]).unwrap();
+    assert_eq!(opts.files, ["a", "b"]);
+
+    let err = Opts::parse_args_default(EMPTY).map(|_| ()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidFreeCount);
+    assert_eq!(err.to_string(), "expected between 1 and 3 free arguments; found 0");
+
+    is_err!(Opts::parse_args_default(&["a", "b", "c", "d"]),
+        "expected between 1 and 3 free arguments; found 4");
+    assert!(Opts::parse_args_default(&["-h"]).unwrap().help);
+
+    #[derive(Options)]
+    #[options(min_free = 2)]
+    struct Min {
+        #[options(free)]
+        files: Vec<String>,
+    }
+
+    is_err!(Min::parse_args_default(&["a"]),
+        "expected at least 2 free arguments; found 1");
+
+    #[derive(Options)]
+    #[options(max_free = 1)]
+    struct Max {
+        #[options(free)]
+        files: Vec<String>,
+    }
+
+    is_err!(Max::parse_args_default(&["a", "b"]),
+        "expected at most 1 free argument; found 2");
+
+    #[derive(Options)]
+    #[options(min_free = 2, max_free = 2)]
+    struct Exact {
+        #[options(free)]
+        files: Vec<String>,
+    }
+
+    is_err!(Exact::parse_args_default(&["a"]),
+        "expected 2 free arguments; found 1");
+}
+
+#[test]
+fn test_named_free() {
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct Opts {
+        verbose: bool,
+        #[options(free, required, long = "input", help = "input file")]
+        input: String,
+        #[options(free, short = "o")]
+        output: Option<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["a", "b"]).unwrap();
+    assert_eq!(opts.input, "a");
+    assert_eq!(opts.output.as_deref(), Some("b"));
+
+    let opts = Opts::parse_args_default(&["--input", "a", "-o", "b"]).unwrap();
+    assert_eq!(opts.input, "a");
+    assert_eq!(opts.output.as_deref(), Some("b"));
+
+    let opts = Opts::parse_args_default(&["-v", "--input=a"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.input, "a");
+    assert_eq!(opts.output, None);
+
+    // Given as an option, the field's position is skipped
+    let opts = Opts::parse_args_default(&["--input", "a", "x"]).unwrap();
+    assert_eq!(opts.input, "a");
+    assert_eq!(opts.output.as_deref(), Some("x"));
+
+    let opts = Opts::parse_args_default(&["-o", "b", "x"]).unwrap();
+    assert_eq!(opts.input, "x");
+    assert_eq!(opts.output.as_deref(), Some("b"));
+
+    is_err!(Opts::parse_args_default(&["--input", "a", "x", "y"]),
+        "unexpected free argument `y`");
+    is_err!(Opts::parse_args_default(&["x", "--input", "a"]),
+        "option `--input` cannot be used with `input`");
+
+    // A field following a `Vec<T>` field is filled once all are read
+    #[derive(Debug, Options)]
+    struct Copy {
+        #[options(free)]
+        sources: Vec<String>,
+        #[options(free, long = "dest")]
+        dest: Option<String>,
+    }
+
+    let opts = Copy::parse_args_default(&["a", "b", "c"]).unwrap();
+    assert_eq!(opts.sources, ["a", "b"]);
+    assert_eq!(opts.dest.as_deref(), Some("c"));
+
+    let opts = Copy::parse_args_default(&["a", "b", "--dest", "c"]).unwrap();
+    assert_eq!(opts.sources, ["a", "b"]);
+    assert_eq!(opts.dest.as_deref(), Some("c"));
+
+    is_err!(Opts::parse_args_default(EMPTY),
+        "missing required free argument");
+    is_err!(Opts::parse_args_default(&["-o", "b"]),
+        "missing required free argument");
+
+    let opts = Opts::parse_args_default(&["-o", "b", "--input", "a"]).unwrap();
+    assert_eq!(gumdrop::ToArgs::to_args(&opts), ["--", "a", "b"]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_named_free_usage() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(free, long = "input", meta = "FILE", help = "input file")]
+        input: Option<String>,
+    }
+
+    assert_eq!(Opts::usage(), &"
+Positional arguments:
+  input             input file
+
+Optional arguments:
+  -i, --input FILE  input file"[1..]);
+}
+
+#[test]
+fn test_free_map() {
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct Opts {
+        #[options(free, fall_through)]
+        vars: BTreeMap<String, String>,
+        #[options(free)]
+        targets: Vec<String>,
+    }
+
+    let opts = Opts::parse_args_default(&["CC=gcc", "N=", "all", "install"]).unwrap();
+    assert_eq!(opts.vars.len(), 2);
+    assert_eq!(opts.vars["CC"], "gcc");
+    assert_eq!(opts.vars["N"], "");
+    assert_eq!(opts.targets, ["all", "install"]);
+    assert_eq!(gumdrop::ToArgs::to_args(&opts),
+        ["--", "CC=gcc", "N=", "all", "install"]);
+
+    // Arguments following the first target are not assignments
+    let opts = Opts::parse_args_default(&["all", "CC=gcc"]).unwrap();
+    assert!(opts.vars.is_empty());
+    assert_eq!(opts.targets, ["all", "CC=gcc"]);
+
+    #[derive(Debug, Options)]
+    struct Opts2 {
+        #[options(free, required)]
+        limits: HashMap<String, u32>,
+    }
+
+    let opts = Opts2::parse_args_default(&["a=1", "b=2=3"]);
+    is_err!(opts, "invalid 2nd positional argument `b=2=3` for `limits`: \
+        invalid digit found in string");
+
+    let opts = Opts2::parse_args_default(&["a=1", "b=2"]).unwrap();
+    assert_eq!(opts.limits["a"], 1);
+    assert_eq!(opts.limits["b"], 2);
+
+    is_err!(Opts2::parse_args_default(&["a=1", "b"]),
+        "invalid 2nd positional argument `b` for `limits`: expected `KEY=VALUE`");
+    is_err!(Opts2::parse_args_default(EMPTY),
+        "missing required free argument");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_free_os_string() {
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(free)]
+        first: Option<OsString>,
+        #[options(free)]
+        files: Vec<PathBuf>,
+        name: Option<String>,
+    }
+
+    let bad = OsStr::from_bytes(b"b\xffc");
+
+    let opts = Opts::parse_args_os(
+        [OsStr::new("a"), bad, OsStr::new("--name"), bad, bad],
+        ParsingStyle::default()).unwrap();
+    assert_eq!(opts.first.as_deref(), Some(OsStr::new("a")));
+    assert_eq!(opts.files, [PathBuf::from(bad), PathBuf::from(bad)]);
+    assert_eq!(opts.name.as_deref(), Some("b\u{fffd}c"));
+
+    // Arguments given as strings are parsed as before
+    let opts = Opts::parse_args_default(&["a", "b"]).unwrap();
+    assert_eq!(opts.first.as_deref(), Some(OsStr::new("a")));
+    assert_eq!(opts.files, [PathBuf::from("b")]);
+}
+
+#[test]
+fn test_free_with_command() {
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct Opts {
+        verbose: bool,
+        #[options(free)]
+        project: Option<String>,
+        #[options(free)]
+        target: Option<String>,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    enum Command {
+        Build(BuildOpts),
+        Clean(BuildOpts),
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct BuildOpts {
+        release: bool,
+    }
+
+    let opts = Opts::parse_args_default(&["proj", "x86", "build", "-r"]).unwrap();
+    assert_eq!(opts.project.as_deref(), Some("proj"));
+    assert_eq!(opts.target.as_deref(), Some("x86"));
+    assert_matches!(opts.command, Some(Command::Build(BuildOpts{release: true})));
+    assert_eq!(gumdrop::ToArgs::to_args(&opts), ["proj", "x86", "build", "--release"]);
+
+    // A command name ends free arguments
+    let opts = Opts::parse_args_default(&["-v", "proj", "clean"]).unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.project.as_deref(), Some("proj"));
+    assert_eq!(opts.target, None);
+    assert_matches!(opts.command, Some(Command::Clean(_)));
+
+    let opts = Opts::parse_args_default(&["build"]).unwrap();
+    assert_eq!(opts.project, None);
+    assert_matches!(opts.command, Some(Command::Build(_)));
+
+    // Once free fields are filled, an argument must name a command
+    is_err!(Opts::parse_args_default(&["a", "b", "c"]),
+        "unrecognized command `c`");
+
+    #[derive(Debug, Options)]
+    struct Opts2 {
+        #[options(free, required)]
+        project: String,
+        #[options(command, required)]
+        command: Option<Command>,
+    }
+
+    let opts = Opts2::parse_args_default(&["proj", "clean"]).unwrap();
+    assert_eq!(opts.project, "proj");
+
+    is_err!(Opts2::parse_args_default(&["clean"]),
+        "missing required free argument");
+    is_err!(Opts2::parse_args_default(&["proj"]),
+        "missing required command");
+}
+
+#[cfg(feature = "prompt")]
+#[test]
+fn test_prompt_secret() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        help: bool,
+        user: Option<String>,
+        #[options(prompt_secret)]
+        password: String,
+        #[options(prompt_secret = "PIN: ")]
+        pin: Option<u32>,
+    }
+
+    // Values given in arguments are not read from the terminal
+    let opts = Opts::parse_args_default(&["-u", "me", "-p", "secret", "--pin", "1234"]).unwrap();
+    assert_eq!(opts.user.as_deref(), Some("me"));
+    assert_eq!(opts.password, "secret");
+    assert_eq!(opts.pin, Some(1234));
+
+    // Nor are they read when help is requested
+    let opts = Opts::parse_args_default(&["-h"]).unwrap();
+    assert!(opts.help);
+    assert_eq!(opts.password, "");
+
+    // Parsing never reads from the terminal; values not given are recorded
+    // by the parser, to be read by `prompt_secrets`
+    let args = ["-u", "me", "--pin", "1234"];
+    let mut parser = gumdrop::Parser::new(&args, ParsingStyle::default());
+    let opts = Opts::parse(&mut parser).unwrap();
+    assert_eq!(opts.password, "");
+    assert!(parser.prompt_requested(concat!(module_path!(), "::Opts.password")));
+    assert!(!parser.prompt_requested(concat!(module_path!(), "::Opts.pin")));
+
+    let mut opts = Opts::parse_args_default(&["-p", "secret"]).unwrap();
+    assert_eq!(opts.pin, None);
+
+    // Nothing is read when no value was recorded as missing
+    let parser = gumdrop::Parser::new(EMPTY, ParsingStyle::default());
+    opts.prompt_secrets(&parser).unwrap();
+    assert_eq!(opts.pin, None);
+}
+
+#[test]
+fn test_confirm() {
+    use gumdrop::ExitStrategy;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[derive(Default)]
+    struct Answer {
+        yes: bool,
+        prompts: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    impl ExitStrategy for Answer {
+        fn print_error(&mut self, text: &str) {
+            self.errors.push(text.to_owned());
+        }
+
+        fn confirm(&mut self, prompt: &str) -> bool {
+            self.prompts.push(prompt.to_owned());
+            self.yes
+        }
+
+        fn exit(&mut self, code: i32) -> ! {
+            std::panic::panic_any(code)
+        }
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct Opts {
+        verbose: bool,
+        #[options(confirm = "Really delete everything?")]
+        delete_all: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    enum Command {
+        Purge(PurgeOpts),
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct PurgeOpts {
+        #[options(confirm = "Purge the cache?")]
+        cache: bool,
+    }
+
+    fn run(args: &[&str], yes: bool) -> (Result<Opts, i32>, Answer) {
+        let args = args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        let mut answer = Answer{yes, ..Answer::default()};
+
+        let res = catch_unwind(AssertUnwindSafe(||
+            Opts::parse_args_or_exit_with(&args, ParsingStyle::default(), &mut answer)));
+
+        (res.map_err(|e| *e.downcast::<i32>().unwrap()), answer)
+    }
+
+    let (opts, answer) = run(&["prog", "-v"], false);
+    assert!(opts.unwrap().verbose);
+    assert!(answer.prompts.is_empty());
+
+    let (opts, answer) = run(&["prog", "--delete-all", "purge", "-c"], true);
+    let opts = opts.unwrap();
+    assert!(opts.delete_all);
+    assert_eq!(opts.confirmations(), ["Really delete everything?", "Purge the cache?"]);
+    assert_eq!(answer.prompts, ["Really delete everything?", "Purge the cache?"]);
+    assert_eq!(gumdrop::ToArgs::to_args(&opts), ["--delete-all", "purge", "--cache"]);
+
+    let (opts, answer) = run(&["prog", "-d"], false);
+    assert_eq!(opts.unwrap_err(), 1);
+    assert_eq!(answer.prompts, ["Really delete everything?"]);
+    assert_eq!(answer.errors, ["aborted"]);
+
+    let (opts, answer) = run(&["prog", "--yes", "-d"], false);
+    assert!(opts.unwrap().delete_all);
+    assert!(answer.prompts.is_empty());
+
+    // A command having `confirm` options has its own `--yes`
+    let (opts, answer) = run(&["prog", "purge", "-c", "--yes"], false);
+    assert_matches!(opts.unwrap().command, Some(Command::Purge(PurgeOpts{cache: true})));
+    assert!(answer.prompts.is_empty());
+
+    #[cfg(not(feature = "strip_help"))]
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -v, --verbose
+  -d, --delete-all
+  --yes             assume yes in answer to confirmation prompts"[1..]);
+}
+
+#[test]
+fn test_split_line() {
+    assert_eq!(gumdrop::split_line("").unwrap(), EMPTY);
+    assert_eq!(gumdrop::split_line("  a  b\tc ").unwrap(), ["a", "b", "c"]);
+    assert_eq!(gumdrop::split_line("'a b' \"c \\\" d\" e\\ f ''").unwrap(),
+        ["a b", "c \" d", "e f", ""]);
+    assert_eq!(gumdrop::split_line("'\\n' \"\\n\"").unwrap(), ["\\n", "\\n"]);
+    assert_eq!(gumdrop::split_line("a'b'\"c\"").unwrap(), ["abc"]);
+
+    is_err!(gumdrop::split_line("a 'b"), "unterminated single quote");
+    is_err!(gumdrop::split_line("a \"b"), "unterminated double quote");
+    is_err!(gumdrop::split_line("a\\"), "trailing backslash");
+}
+
+#[test]
+fn test_repl() {
+    use std::ops::ControlFlow;
+
+    #[derive(Debug, Options)]
+    enum Command {
+        #[options(help = "add an item")]
+        Add(AddOpts),
+        #[options(help = "remove all items")]
+        Clear(ClearOpts),
+        #[options(help = "stop reading commands")]
+        Quit(QuitOpts),
+    }
+
+    #[derive(Debug, Options)]
+    struct AddOpts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(free, required)]
+        item: String,
+    }
+
+    #[derive(Debug, Options)]
+    struct ClearOpts {
+        #[options(confirm = "remove all items?")]
+        force: bool,
+    }
+
+    #[derive(Debug, Options)]
+    struct QuitOpts {}
+
+    let input = "\n\
+        add 'brown eggs'\n\
+        add\n\
+        frob\n\
+        add 'x\n\
+        clear --force\n\
+        no\n\
+        clear --force --yes\n\
+        add --help\n\
+        quit\n\
+        add milk\n";
+
+    let mut items = Vec::new();
+    let mut output = Vec::new();
+
+    gumdrop::repl_with("shop", "> ", input.as_bytes(), &mut output, |cmd| match cmd {
+        Command::Add(opts) => {
+            items.push(opts.item);
+            ControlFlow::Continue(())
+        }
+        Command::Clear(_) => {
+            items.clear();
+            ControlFlow::Continue(())
+        }
+        Command::Quit(_) => ControlFlow::Break(()),
+    }).unwrap();
+
+    assert_eq!(items, EMPTY);
+
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("shop: missing required free argument"), "{}", output);
+    assert!(output.contains("shop: argument 1 (`frob`): unrecognized command `frob`"),
+        "{}", output);
+    assert!(output.contains("available commands: add, clear, quit"), "{}", output);
+    assert!(output.contains("shop: unterminated single quote"), "{}", output);
+    assert!(output.contains("remove all items? [y/N] aborted"), "{}", output);
+    #[cfg(not(feature = "strip_help"))]
+    assert!(output.contains("Usage: shop add [OPTIONS]"), "{}", output);
+    assert!(output.ends_with("> "), "{}", output);
+    assert_eq!(output.matches("> ").count(), 9);
+}
+
+#[test]
+fn test_accept_suggestion() {
+    use gumdrop::ExitStrategy;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[derive(Default)]
+    struct Answer {
+        yes: bool,
+        prompts: Vec<String>,
+    }
+
+    impl ExitStrategy for Answer {
+        fn print_error(&mut self, _text: &str) {}
+
+        fn accept_suggestion(&mut self, prompt: &str) -> bool {
+            self.prompts.push(prompt.to_owned());
+            self.yes
+        }
+
+        fn exit(&mut self, code: i32) -> ! {
+            std::panic::panic_any(code)
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Format {
+        Json,
+        Plain,
+    }
+
+    impl std::str::FromStr for Format {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Format, String> {
+            match s {
+                "json" => Ok(Format::Json),
+                "plain" => Ok(Format::Plain),
+                _ => Err("bad format".to_owned())
+            }
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        verbose: bool,
+        #[options(no_short)]
+        version: Option<u32>,
+        #[options(possible_values = "json, plain")]
+        format: Option<Format>,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Install(NoOpts),
+    }
+
+    fn run(args: &[&str], yes: bool) -> (Result<Opts, i32>, Answer) {
+        let args = args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        let mut answer = Answer{yes, ..Answer::default()};
+
+        let res = catch_unwind(AssertUnwindSafe(||
+            Opts::parse_args_or_exit_with(&args, ParsingStyle::default(), &mut answer)));
+
+        (res.map_err(|e| *e.downcast::<i32>().unwrap()), answer)
+    }
+
+    let e = Opts::parse_args_default(&["-v", "--versoin=2"]).unwrap_err();
+    assert_eq!(e.correction(), Some((1, "--version=2".to_owned())));
+    let e = Opts::parse_args_default(&["-fjsn"]).unwrap_err();
+    assert_eq!(e.correction(), Some((0, "-fjson".to_owned())));
+    let e = Opts::parse_args_default(&["--format", "plan"]).unwrap_err();
+    assert_eq!(e.correction(), Some((1, "plain".to_owned())));
+    let e = Opts::parse_args_default(&["instal"]).unwrap_err();
+    assert_eq!(e.correction(), Some((0, "install".to_owned())));
+    let e = Opts::parse_args_default(&["--quiet"]).unwrap_err();
+    assert_eq!(e.correction(), None);
+
+    let (opts, answer) = run(&["prog", "--verbos", "-f", "plan", "instal"], true);
+    let opts = opts.unwrap();
+    assert!(opts.verbose);
+    assert_eq!(opts.format, Some(Format::Plain));
+    assert_matches!(opts.command, Some(Command::Install(_)));
+    assert_eq!(answer.prompts, [
+        "prog: argument 1 (`--verbos`): unrecognized option `--verbos`; \
+            did you mean `--verbose`?",
+        "prog: argument 3 (`plan`): invalid value `plan` for option `-f`: \
+            expected one of: json, plain; did you mean `plain`?",
+        "prog: argument 4 (`instal`): unrecognized command `instal`; \
+            did you mean `install`?",
+    ]);
+
+    let (opts, answer) = run(&["prog", "--verbos"], false);
+    assert_eq!(opts.unwrap_err(), 2);
+    assert_eq!(answer.prompts.len(), 1);
+
+    let (opts, answer) = run(&["prog", "--quiet"], true);
+    assert_eq!(opts.unwrap_err(), 2);
+    assert!(answer.prompts.is_empty());
+}
+
+#[test]
+fn test_command_ignore_case() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(free)]
+        file: Option<String>,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(ignore_case, help_command)]
+    enum Command {
+        #[options(help = "install a package")]
+        Install(InstallOpts),
+        #[options(help = "show status")]
+        ShowStatus(NoOpts),
+    }
+
+    #[derive(Debug, Options)]
+    struct InstallOpts {
+        #[options(free)]
+        package: String,
+    }
+
+    for name in &["install", "INSTALL", "Install"] {
+        let opts = Opts::parse_args_default(&[name, "foo"]).unwrap();
+        assert!(opts.file.is_none());
+        assert_matches!(opts.command, Some(Command::Install(InstallOpts{ref package}))
+            if package == "foo");
+    }
+
+    let opts = Opts::parse_args_default(&["Show-Status"]).unwrap();
+    assert_matches!(opts.command, Some(Command::ShowStatus(_)));
+
+    let opts = Opts::parse_args_default(&["a.txt", "install", "foo"]).unwrap();
+    assert_eq!(opts.file.as_deref(), Some("a.txt"));
+    assert_matches!(opts.command, Some(Command::Install(_)));
+
+    assert_eq!(Command::find_command("INSTALL"), Some("install"));
+    assert_eq!(Command::find_command("HELP"), Some("help"));
+    assert_eq!(Opts::find_command("iNsTaLl"), Some("install"));
+    assert_eq!(Command::find_command("instal"), None);
+    assert_eq!(NoOpts::find_command("install"), None);
+
+    is_err!(Command::parse_args_default(&["instal"]),
+        "unrecognized command `instal`; did you mean `install`?");
+    assert_matches!(Command::parse_args_default(&["INSTALL", "a", "b"]),
+        Err(ref e) if e.command_path() == ["install"]);
+
+    #[cfg(not(feature = "strip_help"))]
+    assert_eq!(Command::command_usage("Install"), Some(InstallOpts::usage()));
+    #[cfg(not(feature = "strip_help"))]
+    assert!(Command::command_path_usage(&["SHOW-STATUS"]).is_some());
+    assert_matches!(Command::parse_args_default(&["HELP", "install"]),
+        Err(ref e) if e.help_command_path() == Some(&["install".to_owned()][..]));
+}
+
+#[test]
+fn test_command_fallback() {
+    use gumdrop::{DynArg, Error, Parser};
+
+    #[derive(Debug, Options)]
+    #[options(fallback = "implicit_run")]
+    enum Command {
+        Run(RunOpts),
+        List(NoOpts),
+    }
+
+    #[derive(Debug, Options)]
+    struct RunOpts {
+        verbose: bool,
+        #[options(free)]
+        args: Vec<String>,
+    }
+
+    fn implicit_run(name: &str, parser: &mut Parser<DynArg>) -> Result<Command, Error> {
+        if name.starts_with('_') {
+            return Err(Error::unrecognized_command(name));
+        }
+
+        let mut opts = RunOpts::parse_dyn(parser)?;
+        opts.args.insert(0, name.to_owned());
+        Ok(Command::Run(opts))
+    }
+
+    let cmd = Command::parse_args_default(&["run", "a", "b"]).unwrap();
+    assert_matches!(cmd, Command::Run(RunOpts{verbose: false, ref args}) if args == &["a", "b"]);
+
+    let cmd = Command::parse_args_default(&["list"]).unwrap();
+    assert_matches!(cmd, Command::List(_));
+
+    let cmd = Command::parse_args_default(&["build", "-v", "x"]).unwrap();
+    assert_matches!(cmd, Command::Run(RunOpts{verbose: true, ref args})
+        if args == &["build", "x"]);
+
+    is_err!(Command::parse_args_default(&["_build"]), "unrecognized command `_build`");
+    is_err!(Command::parse_args_default(&["build", "-x"]), "unrecognized option `-x`");
+}
+
+#[test]
+fn test_command_catch_all() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        verbose: bool,
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args, dispatch = "Handler")]
+    enum Command {
+        #[options(help = "build the project")]
+        Build(BuildOpts),
+        #[options(catch_all)]
+        Other(String, Vec<String>),
+    }
+
+    #[derive(Debug, Options)]
+    #[options(to_args)]
+    struct BuildOpts {}
+
+    struct Names;
+
+    impl Handler for Names {
+        type Output = String;
+
+        fn build(&mut self, _opts: BuildOpts) -> String { "build".to_owned() }
+
+        fn other(&mut self, name: String, args: Vec<String>) -> String {
+            format!("{} {}", name, args.join(" "))
+        }
+    }
+
+    let opts = Opts::parse_args_default(&["-v", "build"]).unwrap();
+    assert_matches!(opts.command, Some(Command::Build(_)));
+
+    let opts = Opts::parse_args_default(&["-v", "deploy", "--fast", "x", "--", "-y"]).unwrap();
+    assert!(opts.verbose);
+    let cmd = opts.command.unwrap();
+    assert_matches!(cmd, Command::Other(ref name, ref args)
+        if name == "deploy" && args == &["--fast", "x", "--", "-y"]);
+    assert!(!cmd.help_requested());
+    assert_eq!(cmd.command_name(), None);
+    assert!(cmd.command_path().is_empty());
+    assert_eq!(gumdrop::ToArgs::to_args(&cmd), ["deploy", "--fast", "x", "--", "-y"]);
+    assert_eq!(cmd.dispatch(&mut Names), "deploy --fast x -- -y");
+
+    assert_eq!(Command::command_names(), ["build"]);
+    is_err!(Command::parse_args_default(&["build", "x"]), "unexpected free argument `x`");
+
+    #[cfg(not(feature = "strip_help"))]
+    assert_eq!(Command::usage(), "  build  build the project");
+}
+
+#[test]
+fn test_command_visitor() {
+    use gumdrop::CommandVisitor;
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Remote(RemoteOpts),
+        #[options(catch_all)]
+        Other(String, Vec<String>),
+    }
+
+    #[derive(Debug, Options)]
+    struct RemoteOpts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(command)]
+        command: Option<RemoteCommand>,
+    }
+
+    #[derive(Debug, Options)]
+    enum RemoteCommand {
+        Add(NoOpts),
+        Remove(NoOpts),
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl CommandVisitor for Log {
+        fn visit_command(&mut self, name: &'static str, opts: &dyn Options) {
+            self.0.push(format!("{}{}", name, if opts.help_requested() { "?" } else { "" }));
+        }
+
+        fn visit_unknown(&mut self, name: &str, args: &[String]) {
+            self.0.push(format!("unknown {} {:?}", name, args));
+        }
+    }
+
+    fn visit(args: &[&str]) -> Vec<String> {
+        let opts = Opts::parse_args_default(args).unwrap();
+        let mut log = Log::default();
+        opts.accept(&mut log);
+        log.0
+    }
+
+    assert_eq!(visit(&[]), EMPTY);
+    assert_eq!(visit(&["remote"]), ["remote"]);
+    assert_eq!(visit(&["remote", "-h", "remove"]), ["remote?", "remove"]);
+    assert_eq!(visit(&["frob", "-x"]), [r#"unknown frob ["-x"]"#]);
+
+    let mut log = Log::default();
+    NoOpts{}.accept(&mut log);
+    assert_eq!(log.0, EMPTY);
 }
 
 #[test]
-fn test_command_name() {
+fn test_command_mut() {
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Debug, Options)]
+    enum Command {
+        Remote(RemoteOpts),
+        #[options(catch_all)]
+        Other(String, Vec<String>),
+    }
+
+    #[derive(Debug, Options)]
+    struct RemoteOpts {
+        #[options(command)]
+        command: Option<RemoteCommand>,
+    }
+
+    #[derive(Debug, Options)]
+    enum RemoteCommand {
+        Add(NoOpts),
+    }
+
+    fn addr(opts: &dyn Options) -> *const () {
+        opts as *const dyn Options as *const ()
+    }
+
+    let mut opts = Opts::parse_args_default(&["remote", "add"]).unwrap();
+    let cmd = addr(opts.command().unwrap());
+    assert_eq!(addr(opts.command_mut().unwrap()), cmd);
+    let sub = addr(opts.command().unwrap().command().unwrap());
+    assert_eq!(addr(opts.command_mut().unwrap().command_mut().unwrap()), sub);
+    assert!(opts.command_mut().unwrap().command_mut().unwrap().command_mut().is_none());
+
+    let mut opts = Opts::parse_args_default(&["frob"]).unwrap();
+    assert!(opts.command_mut().unwrap().command_mut().is_none());
+
+    let mut opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert!(opts.command_mut().is_none());
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_help_for() {
     #[derive(Options)]
+    #[options(program_name = "prog")]
     struct Opts {
+        #[options(help = "print help message")]
         help: bool,
-
         #[options(command)]
         command: Option<Command>,
     }
 
-    #[derive(Debug, Options)]
+    #[derive(Options)]
     enum Command {
-        Foo(NoOpts),
-        Bar(NoOpts),
-        #[options(name = "bzzz")]
-        Baz(NoOpts),
-        BoopyDoop(NoOpts),
+        #[options(help = "alpha things")]
+        Alpha(AlphaOpts),
     }
 
+    #[derive(Options)]
+    struct AlphaOpts {
+        #[options(help = "be quick")]
+        quick: bool,
+        #[options(command)]
+        command: Option<AlphaCommand>,
+    }
 
-    let opts = Opts::parse_args_default(&["foo"]).unwrap();
-    assert_matches!(opts.command_name(), Some("foo"));
+    #[derive(Options)]
+    enum AlphaCommand {
+        #[options(help = "foo things")]
+        Foo(FooOpts),
+    }
 
-    let opts = Opts::parse_args_default(&["bar"]).unwrap();
-    assert_matches!(opts.command_name(), Some("bar"));
+    #[derive(Options)]
+    struct FooOpts {
+        #[options(free, help = "target")]
+        target: String,
+    }
 
-    let opts = Opts::parse_args_default(&["bzzz"]).unwrap();
-    assert_matches!(opts.command_name(), Some("bzzz"));
+    assert_eq!(Opts::help_for(&["alpha", "foo"]).unwrap(), &"
+Usage: prog alpha foo [OPTIONS]
 
-    let opts = Opts::parse_args_default(&["boopy-doop"]).unwrap();
-    assert_matches!(opts.command_name(), Some("boopy-doop"));
+Positional arguments:
+  target  target"[1..]);
+
+    assert_eq!(Opts::help_for(&["alpha"]).unwrap(), &"
+Usage: prog alpha [OPTIONS]
+
+Optional arguments:
+  -q, --quick  be quick
+
+Available commands:
+  foo   foo things"[1..]);
+
+    assert_eq!(Opts::help_for(EMPTY).unwrap(), &"
+Usage: prog [OPTIONS]
+
+Optional arguments:
+  -h, --help  print help message
+
+Available commands:
+  alpha  alpha things"[1..]);
+
+    assert!(Opts::help_for(&["beta"]).is_none());
+    assert!(Opts::help_for(&["alpha", "foo", "bar"]).is_none());
 }
 
+#[cfg(not(feature = "strip_help"))]
 #[test]
-fn test_command_usage() {
+fn test_next_line_help() {
     #[derive(Options)]
+    #[options(next_line_help)]
     struct Opts {
-        #[options(help = "help me!")]
+        #[options(free, help = "input file")]
+        input: String,
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(no_short, meta = "DIRECTORY", help = "write output to a directory\nwhich is created if necessary")]
+        output_directory: Option<String>,
+        #[options(no_short, default = "3")]
+        level: u32,
+        quiet: bool,
+    }
+
+    #[derive(Options)]
+    #[options(next_line_help, help_column_width = 6)]
+    enum Command {
+        #[options(help = "build things")]
+        Build(NoOpts),
+        Clean(NoOpts),
+    }
+
+    assert_eq!(Opts::usage(), &"
+Positional arguments:
+  input
+        input file
+
+Optional arguments:
+  -h, --help
+        print help message
+  --output-directory DIRECTORY
+        write output to a directory
+        which is created if necessary
+  --level LEVEL
+        (default: 3)
+  -q, --quiet"[1..]);
+
+    assert_eq!(Command::usage(), &"
+  build
+      build things
+  clean"[1..]);
+
+    assert_eq!(Opts::usage_model().render(), Opts::usage());
+    assert_eq!(Command::usage_model().render(),
+        format!("Available commands:\n{}", Command::usage()));
+
+    let wrapped = gumdrop::wrap_help(Opts::usage(), 32);
+    assert!(wrapped.contains("\n        write output to a\n        directory\n"), "{}", wrapped);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_localize() {
+    fn lookup(text: &str) -> Option<String> {
+        match text {
+            "Positional arguments:" => Some("Arguments positionnels :".to_owned()),
+            "Optional arguments:" => Some("Arguments optionnels :".to_owned()),
+            "input file" => Some("fichier d'entrée".to_owned()),
+            "print help message" => Some("afficher l'aide".to_owned()),
+            "LEVEL" => Some("NIVEAU".to_owned()),
+            "build the project" => Some("compiler le projet".to_owned()),
+            _ => None
+        }
+    }
+
+    #[derive(Options)]
+    #[options(localize = "lookup")]
+    struct Opts {
+        #[options(free, help = "input file")]
+        input: String,
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(meta = "LEVEL", help = "optimization level")]
+        opt: u32,
+    }
+
+    #[derive(Options)]
+    #[options(localize = "lookup")]
+    enum Command {
+        #[options(help = "build the project")]
+        Build(NoOpts),
+        #[options(help = "run the project")]
+        Run(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct NoOpts { }
+
+    assert_eq!(Opts::localized_usage(), &"
+Arguments positionnels :
+  input            fichier d'entrée
+
+Arguments optionnels :
+  -h, --help       afficher l'aide
+  -o, --opt NIVEAU optimization level"[1..]);
+
+    assert_eq!(Command::localized_usage(), &"
+  build  compiler le projet
+  run    run the project"[1..]);
+
+    assert_eq!(NoOpts::localized_usage(), NoOpts::usage());
+}
+
+#[test]
+fn test_error_formatter() {
+    use gumdrop::{ErrorFormatter, ErrorMessage, ExitStrategy};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    struct French;
+
+    impl ErrorFormatter for French {
+        fn message(&self, msg: &ErrorMessage, out: &mut String) {
+            match msg {
+                ErrorMessage::UnrecognizedLongOption{name, suggestion: Some(s)} =>
+                    out.push_str(&format!("option inconnue `--{}` ; vouliez-vous dire `--{}` ?",
+                        name, s)),
+                ErrorMessage::MissingArgument{option} =>
+                    out.push_str(&format!("argument manquant pour `{}`", option)),
+                _ => out.push_str(&msg.to_string())
+            }
+        }
+
+        fn position(&self, index: usize, arg: &str, out: &mut String) {
+            out.push_str(&format!("argument {} (`{}`) : ", index + 1, arg));
+        }
+    }
+
+    #[derive(Default)]
+    struct Capture {
+        errors: Vec<String>,
+    }
+
+    impl ExitStrategy for Capture {
+        fn print_error(&mut self, text: &str) {
+            self.errors.push(text.to_owned());
+        }
+
+        fn error_color(&self) -> bool { false }
+
+        fn error_formatter(&self) -> &dyn ErrorFormatter { &French }
+
+        fn exit(&mut self, code: i32) -> ! {
+            std::panic::panic_any(code)
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        verbose: bool,
+        file: Option<String>,
+        #[options(free)]
+        free: Vec<u32>,
+    }
+
+    let e = Opts::parse_args_default(&["--verbos"]).unwrap_err();
+    assert_matches!(e.message(),
+        ErrorMessage::UnrecognizedLongOption{name: "verbos", suggestion: Some("verbose")});
+    assert_eq!(e.message().to_string(), e.to_string());
+    assert_eq!(e.format_with(&gumdrop::DefaultErrorFormatter), format!("{:#}", e));
+    assert_eq!(e.format_with(&French),
+        "argument 1 (`--verbos`) : option inconnue `--verbos` ; vouliez-vous dire `--verbose` ?");
+
+    let e = Opts::parse_args_default(&["x"]).unwrap_err();
+    assert_matches!(e.message(),
+        ErrorMessage::FailedParseFree{name: "free", ordinal: 1, value: "x", ..});
+
+    let e = Opts::parse_args_default(&["-f"]).unwrap_err();
+    assert_eq!(e.format_with(&French), "argument 1 (`-f`) : argument manquant pour `-f`");
+
+    let args = ["prog".to_owned(), "--file".to_owned()];
+    let mut capture = Capture::default();
+    let res = catch_unwind(AssertUnwindSafe(||
+        Opts::parse_args_or_exit_with(&args, ParsingStyle::default(), &mut capture)));
+
+    assert_eq!(*res.unwrap_err().downcast::<i32>().unwrap(), 2);
+    assert_eq!(capture.errors[0].lines().next(),
+        Some("prog: argument 1 (`--file`) : argument manquant pour `--file`"));
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_command_sort() {
+    #[derive(Options)]
+    #[options(sort = "alpha")]
+    enum Alpha {
+        #[options(help = "remove a package")]
+        Remove(NoOpts),
+        #[options(help = "add a package")]
+        Add(NoOpts),
+        #[options(help = "list packages")]
+        List(NoOpts),
+    }
+
+    #[derive(Options)]
+    #[options(help_command, sort = "alpha", pin = "help, version")]
+    enum Pinned {
+        #[options(help = "remove a package")]
+        Remove(NoOpts),
+        #[options(help = "show version")]
+        Version(NoOpts),
+        #[options(help = "add a package")]
+        Add(NoOpts),
+    }
+
+    #[derive(Options)]
+    #[options(pin = "list")]
+    enum Declared {
+        Remove(NoOpts),
+        Add(NoOpts),
+        List(NoOpts),
+    }
+
+    assert_eq!(Alpha::usage(), &"
+  add     add a package
+  list    list packages
+  remove  remove a package"[1..]);
+
+    assert_eq!(Pinned::usage(), &"
+  help     show help for a command
+  version  show version
+  add      add a package
+  remove   remove a package"[1..]);
+
+    assert_eq!(Declared::usage(), "  list\n  remove\n  add");
+
+    assert_eq!(Alpha::command_names(), ["remove", "add", "list"]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_markdown() {
+    /// Fetches **remote** changes.
+    ///
+    /// See [the manual](https://example.com/fetch) or <https://example.com>.
+    #[derive(Options)]
+    #[options(markdown = "strip")]
+    struct Strip {
+        /// Use the `--depth` of *each* remote, or \*none\*
+        depth: Option<u32>,
+        /// Keep snake_case names and 2 * 3 * 4 unchanged
+        keep: bool,
+        #[options(help = "explicit *help* is unchanged")]
         help: bool,
+    }
+
+    #[derive(Options)]
+    #[options(markdown = "render")]
+    struct Render {
+        /// Use **all** remotes, `origin` by _default_
+        all: bool,
+    }
+
+    #[derive(Options)]
+    #[options(markdown = "render")]
+    enum Command {
+        /// Fetch **remote** changes
+        Fetch(NoOpts),
+    }
 
+    #[derive(Options)]
+    struct Verbatim {
+        /// Use the `--depth` of *each* remote
+        depth: Option<u32>,
+    }
+
+    assert_eq!(Strip::usage(), &"
+Fetches remote changes.
+
+See the manual (https://example.com/fetch) or https://example.com.
+
+Optional arguments:
+  -d, --depth DEPTH  Use the --depth of each remote, or *none*
+  -k, --keep         Keep snake_case names and 2 * 3 * 4 unchanged
+  -h, --help         explicit *help* is unchanged"[1..]);
+
+    assert_eq!(Render::usage(), &"
+Optional arguments:
+  -a, --all  Use all remotes, origin by default"[1..]);
+    assert_eq!(Render::options_info()[0].help,
+        Some("Use all remotes, origin by default"));
+    assert_eq!(Render::usage_model().render(), Render::usage());
+    assert_eq!(Command::usage(), "  fetch  Fetch remote changes");
+
+    assert!(Verbatim::usage().ends_with("Use the `--depth` of *each* remote"));
+
+    #[cfg(feature = "style")]
+    {
+        assert_eq!(Render::usage_markup(), "\
+            \u{1}hOptional arguments:\u{2}\n  \
+            \u{1}o-a, --all\u{2}  Use \u{1}sall\u{2} remotes, \
+            \u{1}lorigin\u{2} by \u{1}edefault\u{2}");
+        assert_eq!(Command::usage_markup(), "  \u{1}cfetch\u{2}  Fetch \u{1}sremote\u{2} changes");
+    }
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_command_preview() {
+    #[derive(Options)]
+    struct Main {
+        #[options(help = "print help message")]
+        help: bool,
         #[options(command)]
         command: Option<Command>,
     }
 
     #[derive(Options)]
+    #[options(preview, help_command)]
     enum Command {
-        #[options(help = "foo help")]
-        Foo(NoOpts),
-        #[options(help = "bar help")]
-        Bar(NoOpts),
-        #[options(help = "baz help")]
-        #[options(name = "bzzz")]
-        Baz(NoOpts),
+        #[options(help = "fetch changes")]
+        Fetch(FetchOpts),
+        #[options(help = "push changes")]
+        Push(PushOpts),
+        #[options(help = "show status")]
+        Status(NoOpts),
+    }
+
+    #[derive(Options)]
+    struct FetchOpts {
+        #[options(help = "print help message")]
+        help: bool,
+        #[options(preview, help = "fetch all remotes")]
+        all: bool,
+        #[options(preview, meta = "N", help = "limit fetching to N commits")]
+        depth: Option<u32>,
+    }
+
+    #[derive(Options)]
+    struct PushOpts {
+        #[options(preview, help = "force updates")]
+        force: bool,
+        #[options(help = "push tags")]
+        tags: bool,
     }
 
+    assert_eq!(FetchOpts::preview(), &"
+  -a, --all      fetch all remotes
+  -d, --depth N  limit fetching to N commits"[1..]);
+    assert_eq!(PushOpts::preview(), "  -f, --force  force updates");
+    assert_eq!(NoOpts::preview(), "");
+
+    let preview = &"
+Available commands:
+  fetch   fetch changes
+      -a, --all      fetch all remotes
+      -d, --depth N  limit fetching to N commits
+  push    push changes
+      -f, --force  force updates
+  status  show status
+  help    show help for a command"[1..];
+
+    assert!(Command::help_info().format("prog").ends_with(preview));
+    assert!(Main::help_info().format("prog").ends_with(preview));
+    assert_eq!(Main::usage_with("prog").matches("--force").count(), 1);
+
+    #[cfg(feature = "style")]
+    assert_eq!(gumdrop::format_help_with_style::<Main, &str>("prog", &[], &gumdrop::PlainStyle),
+        Some(Main::help_info().format("prog")));
+
+    // Usage text and the help text of commands are unchanged
     assert_eq!(Command::usage(), &"
-  foo   foo help
-  bar   bar help
-  bzzz  baz help"
-        // Skip leading newline
-        [1..]);
+  fetch   fetch changes
+  push    push changes
+  status  show status
+  help    show help for a command"[1..]);
+    assert_eq!(Main::help_for(&["push"]).map(|s| s.contains("--tags")), Some(true));
+}
 
-    assert_eq!(Command::command_list(), Some(Command::usage()));
-    assert_eq!(Opts::command_list(), Some(Command::usage()));
+#[test]
+fn test_value_ignore_case() {
+    #[derive(Debug, PartialEq)]
+    enum Format {
+        Json,
+        Plain,
+    }
+
+    impl std::str::FromStr for Format {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Format, String> {
+            match s {
+                "json" => Ok(Format::Json),
+                "plain" => Ok(Format::Plain),
+                _ => Err("bad format".to_owned())
+            }
+        }
+    }
+
+    #[derive(Debug, Options)]
+    struct Opts {
+        #[options(ignore_case, possible_values = "json, plain", help = "output format")]
+        format: Option<Format>,
+        #[options(ignore_case, possible_values = "json, plain", no_short)]
+        formats: Vec<Format>,
+        #[options(possible_values = "json, plain")]
+        strict: Option<Format>,
+    }
+
+    let opts = Opts::parse_args_default(&["--format", "JSON"]).unwrap();
+    assert_eq!(opts.format, Some(Format::Json));
+    let opts = Opts::parse_args_default(&["-fPlain"]).unwrap();
+    assert_eq!(opts.format, Some(Format::Plain));
+    let opts = Opts::parse_args_default(&["--format=json"]).unwrap();
+    assert_eq!(opts.format, Some(Format::Json));
+    let opts = Opts::parse_args_default(&["--formats", "Json", "--formats", "PLAIN"]).unwrap();
+    assert_eq!(opts.formats, [Format::Json, Format::Plain]);
+
+    is_err!(Opts::parse_args_default(&["--format", "JSN"]),
+        "invalid value `JSN` for option `--format`: expected one of: json, plain; \
+            did you mean `json`?");
+
+    // Without `ignore_case`, the value is suggested as listed
+    let e = Opts::parse_args_default(&["--strict", "PLAIN"]).unwrap_err();
+    assert_eq!(e.to_string(), "invalid value `PLAIN` for option `--strict`: \
+        expected one of: json, plain; did you mean `plain`?");
+    assert_eq!(e.correction(), Some((1, "plain".to_owned())));
+
+    #[cfg(not(feature = "strip_help"))]
+    assert!(Opts::usage().contains("output format [possible values: json, plain]"));
 }
 
 #[test]
@@ -300,10 +4656,10 @@ fn test_opt_bool() {
     }
 
     let opts = Opts::parse_args_default(&["--switch"]).unwrap();
-    assert_eq!(opts.switch, true);
+    assert!(opts.switch);
 
     let opts = Opts::parse_args_default(&["-s"]).unwrap();
-    assert_eq!(opts.switch, true);
+    assert!(opts.switch);
 
     is_err!(Opts::parse_args_default(&["--switch=x"]),
         "option `--switch` does not accept an argument");
@@ -435,7 +4791,7 @@ fn test_opt_long() {
     }
 
     let opts = Opts::parse_args_default(&["--thing"]).unwrap();
-    assert_eq!(opts.foo, true);
+    assert!(opts.foo);
 
     is_err!(Opts::parse_args_default(&["-f"]),
         "unrecognized option `-f`");
@@ -452,7 +4808,7 @@ fn test_opt_short() {
     }
 
     let opts = Opts::parse_args_default(&["-x"]).unwrap();
-    assert_eq!(opts.foo, true);
+    assert!(opts.foo);
 
     is_err!(Opts::parse_args_default(&["-f"]),
         "unrecognized option `-f`");
@@ -475,12 +4831,12 @@ fn test_opt_short_override() {
     }
 
     let opts = Opts::parse_args_default(&["-o"]).unwrap();
-    assert_eq!(opts.option_0, false);
-    assert_eq!(opts.option_1, true);
+    assert!(!opts.option_0);
+    assert!(opts.option_1);
 
     let opts = Opts::parse_args_default(&["-O"]).unwrap();
-    assert_eq!(opts.option_0, true);
-    assert_eq!(opts.option_1, false);
+    assert!(opts.option_0);
+    assert!(!opts.option_1);
 }
 
 #[test]
@@ -518,6 +4874,7 @@ fn test_typed_free() {
     assert_eq!(opts.free, [1, 2, 3]);
 }
 
+#[cfg(not(feature = "strip_help"))]
 #[test]
 fn test_multi_free() {
     #[derive(Options)]
@@ -586,6 +4943,7 @@ Positional arguments:
     assert_eq!(opts.rest, vec!["4".to_owned(), "five".to_owned(), "VI".to_owned()]);
 }
 
+#[cfg(not(feature = "strip_help"))]
 #[test]
 fn test_usage() {
     #[derive(Options)]
@@ -671,6 +5029,52 @@ Optional arguments:
         [1..]);
 }
 
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_usage_styled() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(free, help = "free help")]
+        free: Vec<String>,
+        #[options(help = "foo help", default = "1")]
+        foo: u32,
+    }
+
+    #[cfg_attr(not(feature = "style"), allow(dead_code))]
+    #[derive(Options)]
+    struct Main {
+        #[options(command)]
+        command: Option<Command>,
+    }
+
+    #[derive(Options)]
+    enum Command {
+        #[options(help = "bar help")]
+        Bar(NoOpts),
+    }
+
+    // Output of tests is not a terminal, so no styling is applied
+    assert_eq!(Opts::usage_styled(), Opts::usage());
+    assert_eq!(Command::usage_styled(), Command::usage());
+
+    #[cfg(feature = "style")]
+    {
+        assert_eq!(Opts::usage_markup(), "\
+            \u{1}hPositional arguments:\u{2}\n  \
+            \u{1}pfree\u{2}           free help\n\
+            \n\
+            \u{1}hOptional arguments:\u{2}\n  \
+            \u{1}o-f, --foo\u{2} \u{1}mFOO\u{2}  foo help \u{1}d(default: 1)\u{2}");
+        assert_eq!(Command::usage_markup(), "  \u{1}cbar\u{2}   bar help");
+        assert_eq!(Main::command_path_markup(EMPTY)
+            .map(|i| (i.usage, i.command_list)),
+            Some((Main::usage_markup(), Some(Command::usage_markup()))));
+        assert_eq!(Main::command_path_markup(&["bar"])
+            .map(|i| (i.usage, i.command_list)),
+            Some((NoOpts::usage_markup(), None)));
+    }
+}
+
 #[test]
 fn test_help_flag() {
     #[derive(Options)]
@@ -679,10 +5083,10 @@ fn test_help_flag() {
     }
 
     let opts = Opts::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 
     let opts = Opts::parse_args_default(&["--help"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 }
 
 #[test]
@@ -694,7 +5098,7 @@ fn test_no_help_flag() {
     }
 
     let opts = Opts::parse_args_default(&["--help"]).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 }
 
 #[test]
@@ -710,16 +5114,16 @@ fn test_many_help_flags() {
     }
 
     let opts = Opts::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 
     let opts = Opts::parse_args_default(&["--help"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts::parse_args_default(&["--halp"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts::parse_args_default(&["--help-please"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 }
 
 #[test]
@@ -761,25 +5165,25 @@ fn test_help_flag_command() {
     }
 
     let opts = Opts::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 
     let opts = Opts::parse_args_default(&["-h"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts::parse_args_default(&["foo", "-h"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts::parse_args_default(&["bar", "-h"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts::parse_args_default(&["baz", "-h"]).unwrap();
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help_requested());
 
     let opts = Opts2::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 
     let opts = Opts3::parse_args_default(EMPTY).unwrap();
-    assert_eq!(opts.help_requested(), false);
+    assert!(!opts.help_requested());
 }
 
 #[test]
@@ -803,14 +5207,14 @@ fn test_type_attrs() {
         "unrecognized option `-h`");
 
     let opts = Opts::parse_args_default(&["--help"]).unwrap();
-    assert_eq!(opts.help, true);
-    assert_eq!(opts.help_requested(), false);
+    assert!(opts.help);
+    assert!(!opts.help_requested());
 
     let opts = Opts::parse_args_default(&["--foo"]).unwrap();
-    assert_eq!(opts.foo, true);
+    assert!(opts.foo);
 
     let opts = Opts::parse_args_default(&["-b"]).unwrap();
-    assert_eq!(opts.bar, true);
+    assert!(opts.bar);
 
     #[derive(Options)]
     #[options(no_short)]
@@ -824,11 +5228,11 @@ fn test_type_attrs() {
         "unrecognized option `-f`");
 
     let opts = Opts2::parse_args_default(&["--foo", "-b"]).unwrap();
-    assert_eq!(opts.foo, true);
-    assert_eq!(opts.bar, true);
+    assert!(opts.foo);
+    assert!(opts.bar);
 
     let opts = Opts2::parse_args_default(&["--bar"]).unwrap();
-    assert_eq!(opts.bar, true);
+    assert!(opts.bar);
 
     #[derive(Options)]
     #[options(no_long)]
@@ -842,11 +5246,11 @@ fn test_type_attrs() {
         "unrecognized option `--foo`");
 
     let opts = Opts3::parse_args_default(&["--bar"]).unwrap();
-    assert_eq!(opts.bar, true);
+    assert!(opts.bar);
 
     let opts = Opts3::parse_args_default(&["-f", "-b"]).unwrap();
-    assert_eq!(opts.foo, true);
-    assert_eq!(opts.bar, true);
+    assert!(opts.foo);
+    assert!(opts.bar);
 
     #[derive(Options)]
     #[options(no_help_flag)]
@@ -856,8 +5260,8 @@ fn test_type_attrs() {
     }
 
     let opts = Opts4::parse_args_default(&["-h"]).unwrap();
-    assert_eq!(opts.help, true);
-    assert_eq!(opts.help_requested(), true);
+    assert!(opts.help);
+    assert!(opts.help_requested());
 
     #[derive(Options)]
     #[options(required)]
@@ -909,12 +5313,31 @@ fn test_required() {
     }
 
     is_err!(Opts::parse_args_default(EMPTY),
-        "missing required option `--foo`");
+        "missing required option `-f, --foo`");
     is_err!(Opts2::parse_args_default(EMPTY),
         "missing required command");
     is_err!(Opts3::parse_args_default(EMPTY),
         "missing required free argument");
 
+    #[derive(Options)]
+    struct Forms {
+        #[options(required, no_short)]
+        long: i32,
+        #[options(required, no_long, short = "s")]
+        short: i32,
+    }
+
+    is_err!(Forms::parse_args_default(&["-s", "1"]),
+        "missing required option `--long`");
+    is_err!(Forms::parse_args_default(&["--long", "1"]),
+        "missing required option `-s`");
+
+    // Errors arising from an argument name the option as given
+    is_err!(Opts::parse_args_default(&["--foo", "x"]),
+        "invalid argument to option `--foo`: invalid digit found in string");
+    is_err!(Opts::parse_args_default(&["-f", "x"]),
+        "invalid argument to option `-f`: invalid digit found in string");
+
     let opts = Opts::parse_args_default(&["-f", "1"]).unwrap();
     assert_eq!(opts.foo, 1);
     let opts = Opts::parse_args_default(&["-f1"]).unwrap();
@@ -950,10 +5373,10 @@ fn test_required_help() {
     }
 
     let opts = Opts::parse_args_default(&["-h"]).unwrap();
-    assert_eq!(opts.help, true);
+    assert!(opts.help);
 
     let opts = Opts2::parse_args_default(&["--secondary-help"]).unwrap();
-    assert_eq!(opts.secondary_help, true);
+    assert!(opts.secondary_help);
 }
 
 #[test]
@@ -982,7 +5405,7 @@ fn test_parse() {
     fn parse_foo(s: &str) -> Foo { Foo(s.to_owned()) }
     fn parse_bar(s: &str) -> Result<Bar, <u32 as FromStr>::Err> { s.parse().map(Bar) }
 
-    impl<'a> From<&'a str> for Baz {
+    impl From<&str> for Baz {
         fn from(s: &str) -> Baz {
             Baz(s.to_owned())
         }
@@ -1145,6 +5568,7 @@ fn test_no_multi() {
         "unexpected free argument `error`");
 }
 
+#[cfg(not(feature = "strip_help"))]
 #[test]
 fn test_doc_help() {
     /// type-level help comment
@@ -1188,6 +5612,7 @@ Optional arguments:
         [1..]);
 }
 
+#[cfg(not(feature = "strip_help"))]
 #[test]
 fn test_doc_help_multiline() {
     /// type-level help comment
@@ -1210,7 +5635,7 @@ Optional arguments:
 
 #[test]
 fn test_failed_parse_free() {
-    #[derive(Options)]
+    #[derive(Debug, Options)]
     struct Opts {
         #[options(free)]
         foo: u32,
@@ -1225,26 +5650,73 @@ fn test_failed_parse_free() {
     }
 
     is_err!(Opts::parse_args_default(&["x"]),
-        |e| e.starts_with("invalid argument to option `foo`: "));
+        |e| e.starts_with("invalid 1st positional argument `x` for `foo`: "));
 
     is_err!(Opts::parse_args_default(&["0", "x"]),
-        |e| e.starts_with("invalid argument to option `bar`: "));
+        |e| e.starts_with("invalid 2nd positional argument `x` for `bar`: "));
 
     is_err!(Opts::parse_args_default(&["0", "0", "x"]),
-        |e| e.starts_with("invalid argument to option `baz`: "));
+        |e| e.starts_with("invalid 3rd positional argument `x` for `baz`: "));
+
+    is_err!(Opts::parse_args_default(&["0", "0", "1", "2", "3", "4", "5", "6", "7", "8", "x"]),
+        "invalid 11th positional argument `x` for `baz`: invalid digit found in string");
+
+    let e = Opts::parse_args_default(&["0", "y"]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::FailedParse);
+    assert_eq!(e.option().as_deref(), Some("bar"));
+    assert!(std::error::Error::source(&e).is_some());
+    assert_eq!(format!("{:#}", e),
+        "argument 2 (`y`): invalid 2nd positional argument `y` for `bar`: \
+         invalid digit found in string");
 }
 
-#[cfg(feature = "default_expr")]
 #[test]
 fn test_default_expr() {
     #[derive(Options)]
     struct Opts {
         #[options(default_expr = "foo()")]
         foo: u32,
+        #[options(default_expr = foo() + 1)]
+        bar: u32,
+        #[options(help = "baz help", default_expr = vec![foo(), 2])]
+        baz: Vec<u32>,
     }
 
     fn foo() -> u32 { 123 }
 
     let opts = Opts::parse_args_default(EMPTY).unwrap();
     assert_eq!(opts.foo, foo());
+    assert_eq!(opts.bar, 124);
+    assert_eq!(opts.baz, [123, 2]);
+}
+
+#[cfg(not(feature = "strip_help"))]
+#[test]
+fn test_default_expr_display() {
+    #[derive(Options)]
+    struct Opts {
+        #[options(help = "foo help", default_expr = "foo()",
+            default_expr_display = "123")]
+        foo: u32,
+        #[options(default_expr = "foo() + 1", show_default = true)]
+        bar: u32,
+        #[options(default_expr = "foo() + 2")]
+        baz: u32,
+        #[options(default_expr = foo() * 2, show_default = true)]
+        qux: u32,
+    }
+
+    fn foo() -> u32 { 123 }
+
+    let opts = Opts::parse_args_default(EMPTY).unwrap();
+    assert_eq!(opts.foo, 123);
+
+    assert_eq!(Opts::usage(), &"
+Optional arguments:
+  -f, --foo FOO  foo help (default: 123)
+  -b, --bar BAR  (default: foo() + 1)
+  -B, --baz BAZ
+  -q, --qux QUX  (default: foo() * 2)"
+        // Skip leading newline
+        [1..]);
 }